@@ -0,0 +1,399 @@
+use common::comm::ahrs::{Accelerometer, Altitude, Quaternion};
+
+// m/s² in a G, as the accelerometer reports in units of G
+const STANDARD_GRAVITY: f64 = 9.80665;
+
+// constants of the international standard atmosphere's barometric formula,
+// valid through the troposphere
+const ISA_SCALE_HEIGHT: f64 = 44_330.77;
+const ISA_EXPONENT: f64 = 0.190_263;
+
+/// The default standard deviation of the barometric altitude, in meters.
+pub const DEFAULT_BARO_NOISE: f64 = 0.5;
+
+/// The default standard deviation of the vertical acceleration, in m/s². This
+/// also absorbs accelerometer bias and attitude error, so is set well above
+/// the sensor's own noise.
+pub const DEFAULT_ACCEL_NOISE: f64 = 0.5;
+
+/// Estimates altitude above the pad and vertical velocity with a Kalman filter
+/// that integrates the vertical acceleration and corrects towards the altitude
+/// measured by the barometer.
+pub struct AltitudeEstimator {
+  // none until armed, in which case there is nothing to measure from
+  reference: Option<f64>,
+  pressure_altitude: f64,
+
+  // the state is [altitude, vertical velocity] with covariance p
+  x: [f64; 2],
+  p: [[f64; 2]; 2],
+
+  baro_variance: f64,
+  accel_variance: f64,
+}
+
+impl Default for AltitudeEstimator {
+  fn default() -> Self {
+    AltitudeEstimator::new(DEFAULT_BARO_NOISE, DEFAULT_ACCEL_NOISE)
+  }
+}
+
+impl AltitudeEstimator {
+  /// Creates an unarmed estimator with the given standard deviations of the
+  /// barometric altitude (m) and vertical acceleration (m/s²).
+  pub fn new(baro_noise: f64, accel_noise: f64) -> Self {
+    AltitudeEstimator {
+      reference: None,
+      pressure_altitude: 0.0,
+      x: [0.0; 2],
+      p: [[0.0; 2]; 2],
+      baro_variance: baro_noise * baro_noise,
+      accel_variance: accel_noise * accel_noise,
+    }
+  }
+
+  /// Captures the given pressure as the pad reference and restarts the
+  /// estimate from rest at zero altitude. Returns false, leaving the
+  /// estimator unchanged, if the pressure isn't a plausible reading.
+  pub fn arm(&mut self, pressure: f64) -> bool {
+    if !is_valid_pressure(pressure) {
+      return false;
+    }
+
+    self.reference = Some(pressure);
+    self.pressure_altitude = 0.0;
+    self.x = [0.0; 2];
+
+    // the vehicle is known to be sitting on the pad
+    self.p = [[self.baro_variance, 0.0], [0.0, 0.0]];
+
+    true
+  }
+
+  /// Discards the pad reference, so nothing is estimated until rearmed.
+  pub fn disarm(&mut self) {
+    self.reference = None;
+  }
+
+  /// Whether a pad reference has been captured.
+  pub fn is_armed(&self) -> bool {
+    self.reference.is_some()
+  }
+
+  /// Advances the estimate by `dt` seconds with the latest acceleration, in G,
+  /// rotated to vertical by the attitude, and corrects it with the barometer
+  /// if `pressure` is a valid reading.
+  pub fn update(
+    &mut self,
+    attitude: Quaternion,
+    accelerometer: Accelerometer,
+    pressure: Option<f64>,
+    dt: f64,
+  ) -> Altitude {
+    if self.reference.is_none() {
+      return self.altitude();
+    }
+
+    if dt > 0.0 {
+      if let Some(accel) = vertical_acceleration(attitude, accelerometer) {
+        self.predict(accel, dt);
+      }
+    }
+
+    if let Some(pressure) = pressure.filter(|p| is_valid_pressure(*p)) {
+      self.correct(pressure);
+    }
+
+    self.altitude()
+  }
+
+  /// The latest estimate.
+  pub fn altitude(&self) -> Altitude {
+    let Some(reference) = self.reference else {
+      return Altitude::default();
+    };
+
+    Altitude {
+      armed: true,
+      reference_pressure: reference,
+      pressure_altitude: self.pressure_altitude,
+      altitude: self.x[0],
+      vertical_velocity: self.x[1],
+    }
+  }
+
+  // propagates the state with the vertical acceleration as the control input
+  fn predict(&mut self, accel: f64, dt: f64) {
+    let [h, v] = self.x;
+    self.x = [h + v * dt + 0.5 * accel * dt * dt, v + accel * dt];
+
+    // P = F P Fᵀ + Q, with F = [[1, dt], [0, 1]]
+    let [[p00, p01], [p10, p11]] = self.p;
+    let p00 = p00 + dt * (p10 + p01) + dt * dt * p11;
+    let p01 = p01 + dt * p11;
+    let p10 = p10 + dt * p11;
+
+    // process noise of a white acceleration held over the step
+    let q = self.accel_variance;
+    let dt2 = dt * dt;
+
+    self.p = [
+      [p00 + q * dt2 * dt2 / 4.0, p01 + q * dt2 * dt / 2.0],
+      [p10 + q * dt2 * dt / 2.0, p11 + q * dt2],
+    ];
+  }
+
+  // corrects the state with the barometric altitude, which observes the
+  // altitude directly
+  fn correct(&mut self, pressure: f64) {
+    let Some(reference) = self.reference else {
+      return;
+    };
+
+    self.pressure_altitude = pressure_altitude(pressure, reference);
+
+    let [[p00, p01], [p10, p11]] = self.p;
+    let innovation = self.pressure_altitude - self.x[0];
+    let s = p00 + self.baro_variance;
+    let gain = [p00 / s, p10 / s];
+
+    self.x[0] += gain[0] * innovation;
+    self.x[1] += gain[1] * innovation;
+
+    self.p = [
+      [(1.0 - gain[0]) * p00, (1.0 - gain[0]) * p01],
+      [p10 - gain[1] * p00, p11 - gain[1] * p01],
+    ];
+  }
+}
+
+/// The altitude above the point where the pressure was `reference`, from the
+/// international standard atmosphere. Both pressures must share a unit.
+pub fn pressure_altitude(pressure: f64, reference: f64) -> f64 {
+  ISA_SCALE_HEIGHT * (1.0 - (pressure / reference).powf(ISA_EXPONENT))
+}
+
+/// The acceleration along the vertical, in m/s² and positive up, with gravity
+/// removed. None if the reading is unusable.
+fn vertical_acceleration(
+  attitude: Quaternion,
+  accelerometer: Accelerometer,
+) -> Option<f64> {
+  let Quaternion { w, x, y, z } = attitude;
+  let a = [accelerometer.x, accelerometer.y, accelerometer.z];
+
+  if !a.iter().all(|c| c.is_finite()) {
+    return None;
+  }
+
+  // the vertical row of the body to level rotation
+  let up = 2.0 * (x * z - w * y) * a[0]
+    + 2.0 * (y * z + w * x) * a[1]
+    + (1.0 - 2.0 * (x * x + y * y)) * a[2];
+
+  // at rest the accelerometer reads 1 G upwards
+  Some((up - 1.0) * STANDARD_GRAVITY)
+}
+
+fn is_valid_pressure(pressure: f64) -> bool {
+  pressure.is_finite() && pressure > 0.0
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use common::comm::ahrs::Vector;
+
+  const DT: f64 = 0.01;
+
+  // standard sea level pressure, in bar
+  const PAD_PRESSURE: f64 = 1.01325;
+
+  const LEVEL: Quaternion = Quaternion {
+    w: 1.0,
+    x: 0.0,
+    y: 0.0,
+    z: 0.0,
+  };
+
+  fn accel(z: f64) -> Vector {
+    Vector { x: 0.0, y: 0.0, z }
+  }
+
+  // the inverse of pressure_altitude, to synthesize a barometer trace
+  fn pressure_at(altitude: f64) -> f64 {
+    PAD_PRESSURE * (1.0 - altitude / ISA_SCALE_HEIGHT).powf(1.0 / ISA_EXPONENT)
+  }
+
+  // a small deterministic generator of roughly gaussian noise, so the tests
+  // don't need a source of randomness
+  struct Noise(u64);
+
+  impl Noise {
+    fn next(&mut self, deviation: f64) -> f64 {
+      // the sum of uniform samples has a variance of count / 12
+      let sum: f64 = (0..12)
+        .map(|_| {
+          self.0 = self
+            .0
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+          (self.0 >> 11) as f64 / (1u64 << 53) as f64
+        })
+        .sum();
+
+      (sum - 6.0) * deviation
+    }
+  }
+
+  fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+    assert!(
+      (actual - expected).abs() <= tolerance,
+      "expected {expected} ± {tolerance}, got {actual}"
+    );
+  }
+
+  #[test]
+  fn pressure_altitude_matches_the_standard_atmosphere() {
+    assert_close(pressure_altitude(PAD_PRESSURE, PAD_PRESSURE), 0.0, 1e-9);
+
+    // 898.76 hPa is 1000 m in the standard atmosphere
+    assert_close(pressure_altitude(0.89876, PAD_PRESSURE), 1000.0, 0.5);
+
+    // relative to a pad that is itself above sea level
+    let pad = pressure_at(300.0);
+    assert_close(pressure_altitude(pressure_at(400.0), pad), 100.0, 1.0);
+  }
+
+  #[test]
+  fn reports_nothing_until_armed() {
+    let mut estimator = AltitudeEstimator::default();
+
+    let altitude =
+      estimator.update(LEVEL, accel(2.0), Some(pressure_at(50.0)), DT);
+    assert_eq!(altitude, Altitude::default());
+
+    assert!(!estimator.arm(0.0));
+    assert!(!estimator.arm(f64::NAN));
+    assert!(!estimator.is_armed());
+
+    assert!(estimator.arm(PAD_PRESSURE));
+    assert!(estimator.is_armed());
+
+    estimator.disarm();
+    assert_eq!(estimator.altitude(), Altitude::default());
+  }
+
+  #[test]
+  fn stays_on_the_pad_through_noise() {
+    let mut estimator = AltitudeEstimator::default();
+    let mut noise = Noise(1);
+    estimator.arm(PAD_PRESSURE);
+
+    let mut worst_altitude: f64 = 0.0;
+    let mut worst_pressure_altitude: f64 = 0.0;
+
+    for _ in 0..2000 {
+      let pressure = pressure_at(noise.next(DEFAULT_BARO_NOISE));
+      let altitude = estimator.update(
+        LEVEL,
+        accel(1.0 + noise.next(0.01)),
+        Some(pressure),
+        DT,
+      );
+
+      worst_altitude = worst_altitude.max(altitude.altitude.abs());
+      worst_pressure_altitude =
+        worst_pressure_altitude.max(altitude.pressure_altitude.abs());
+      assert_close(altitude.vertical_velocity, 0.0, 0.5);
+    }
+
+    assert!(worst_altitude < 0.5, "worst altitude was {worst_altitude}");
+    assert!(
+      worst_altitude < worst_pressure_altitude / 2.0,
+      "filter did not smooth the barometer"
+    );
+  }
+
+  #[test]
+  fn tracks_a_boost_and_coast() {
+    let mut estimator = AltitudeEstimator::default();
+    let mut noise = Noise(2);
+    estimator.arm(PAD_PRESSURE);
+
+    let (mut altitude, mut velocity) = (0.0, 0.0);
+
+    // 3 s at 5 G of net thrust, then coast upwards for 5 s
+    for step in 0..800 {
+      let net = if step < 300 { 5.0 } else { -1.0 };
+
+      velocity += net * STANDARD_GRAVITY * DT;
+      altitude += velocity * DT;
+
+      let estimate = estimator.update(
+        LEVEL,
+        accel(net + 1.0),
+        Some(pressure_at(altitude + noise.next(DEFAULT_BARO_NOISE))),
+        DT,
+      );
+
+      assert_close(estimate.altitude, altitude, 2.0);
+      assert_close(estimate.vertical_velocity, velocity, 2.0);
+    }
+
+    assert!(altitude > 500.0);
+  }
+
+  #[test]
+  fn rotates_acceleration_to_vertical() {
+    // pitched 90 degrees, so the body's X axis points up
+    let half = std::f64::consts::FRAC_1_SQRT_2;
+    let pitched = Quaternion {
+      w: half,
+      x: 0.0,
+      y: -half,
+      z: 0.0,
+    };
+
+    let reading = Vector {
+      x: 3.0,
+      y: 0.0,
+      z: 0.0,
+    };
+
+    let accel = vertical_acceleration(pitched, reading).unwrap();
+    assert_close(accel, 2.0 * STANDARD_GRAVITY, 1e-9);
+  }
+
+  #[test]
+  fn coasts_through_barometer_dropouts() {
+    let mut estimator = AltitudeEstimator::default();
+    estimator.arm(PAD_PRESSURE);
+
+    let (mut altitude, mut velocity) = (0.0, 0.0);
+
+    // accelerate up to 10 m/s and hold it, with the barometer throughout
+    for step in 0..1000 {
+      let net = if step < 500 { 2.0 } else { 0.0 };
+
+      velocity += net * DT;
+      altitude += velocity * DT;
+
+      let reading = accel(1.0 + net / STANDARD_GRAVITY);
+      estimator.update(LEVEL, reading, Some(pressure_at(altitude)), DT);
+    }
+
+    let before = estimator.altitude();
+    assert_close(before.vertical_velocity, 10.0, 0.5);
+
+    // then lose the barometer for a second, or have it read garbage
+    for step in 0..100 {
+      let pressure = (step % 2 == 0).then_some(f64::NAN);
+      estimator.update(LEVEL, accel(1.0), pressure, DT);
+    }
+
+    let after = estimator.altitude();
+    assert_close(after.altitude, before.altitude + 10.0, 1.0);
+    assert_eq!(after.pressure_altitude, before.pressure_altitude);
+  }
+}
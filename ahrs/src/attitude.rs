@@ -0,0 +1,462 @@
+use common::comm::ahrs::{
+  Accelerometer,
+  Attitude,
+  EulerAngles,
+  Gyroscope,
+  Magnetometer,
+  Quaternion,
+  Vector,
+};
+
+/// The default gain of the accelerometer and magnetometer correction, in
+/// rad/s. Higher values converge faster but let more vibration through.
+pub const DEFAULT_BETA: f64 = 0.1;
+
+/// Estimates attitude with a Madgwick gradient-descent filter, integrating the
+/// gyroscope and correcting its drift towards gravity and, when available, the
+/// magnetic field.
+pub struct AttitudeEstimator {
+  // none until the first sample, which sets the initial attitude directly
+  quaternion: Option<[f64; 4]>,
+  beta: f64,
+}
+
+impl Default for AttitudeEstimator {
+  fn default() -> Self {
+    AttitudeEstimator::new(DEFAULT_BETA)
+  }
+}
+
+impl AttitudeEstimator {
+  /// Creates an estimator with the given correction gain.
+  pub fn new(beta: f64) -> Self {
+    AttitudeEstimator {
+      quaternion: None,
+      beta,
+    }
+  }
+
+  /// Advances the estimate by `dt` seconds. A magnetometer reading of all
+  /// zeros is treated as missing, leaving yaw to the gyroscope alone.
+  pub fn update(
+    &mut self,
+    gyroscope: Gyroscope,
+    accelerometer: Accelerometer,
+    magnetometer: Magnetometer,
+    dt: f64,
+  ) -> Attitude {
+    let accel = to_array(accelerometer);
+    let mag = to_array(magnetometer);
+    let mag = (is_finite_nonzero(&mag)).then_some(mag);
+
+    let q = *self
+      .quaternion
+      .get_or_insert_with(|| initial_attitude(accel, mag));
+
+    let gyro = to_array(gyroscope).map(f64::to_radians);
+    let [q0, q1, q2, q3] = q;
+
+    // rate of change of the quaternion from the gyroscope, q ⊗ (0, ω) / 2
+    let mut q_dot = [
+      0.5 * (-q1 * gyro[0] - q2 * gyro[1] - q3 * gyro[2]),
+      0.5 * (q0 * gyro[0] + q2 * gyro[2] - q3 * gyro[1]),
+      0.5 * (q0 * gyro[1] - q1 * gyro[2] + q3 * gyro[0]),
+      0.5 * (q0 * gyro[2] + q1 * gyro[1] - q2 * gyro[0]),
+    ];
+
+    // without a valid accelerometer reading there is no reference to correct
+    // against, so the gyroscope is integrated alone
+    if is_finite_nonzero(&accel) {
+      let mut gradient = gravity_gradient(q, normalize(accel));
+
+      if let Some(mag) = mag {
+        let magnetic = magnetic_gradient(q, normalize(mag));
+
+        for (g, m) in gradient.iter_mut().zip(magnetic) {
+          *g += m;
+        }
+      }
+
+      let norm = magnitude(&gradient);
+
+      if norm > 0.0 {
+        for (dot, g) in q_dot.iter_mut().zip(gradient) {
+          *dot -= self.beta * g / norm;
+        }
+      }
+    }
+
+    let mut next = q;
+
+    if dt.is_finite() && dt > 0.0 {
+      for (component, dot) in next.iter_mut().zip(q_dot) {
+        *component += dot * dt;
+      }
+    }
+
+    let next = normalize(next);
+
+    // a bad sample must not poison every estimate after it
+    if next.iter().all(|c| c.is_finite()) {
+      self.quaternion = Some(next);
+    }
+
+    let [w, x, y, z] = self.quaternion.unwrap_or(q);
+
+    Attitude {
+      quaternion: Quaternion { w, x, y, z },
+      euler: euler_angles([w, x, y, z]),
+      angular_rate: gyroscope,
+    }
+  }
+}
+
+/// The gradient of the error between measured gravity and gravity predicted
+/// by the attitude, J_gᵀ f_g in Madgwick's paper.
+fn gravity_gradient(q: [f64; 4], a: [f64; 3]) -> [f64; 4] {
+  let [q0, q1, q2, q3] = q;
+
+  let f = [
+    2.0 * (q1 * q3 - q0 * q2) - a[0],
+    2.0 * (q0 * q1 + q2 * q3) - a[1],
+    2.0 * (0.5 - q1 * q1 - q2 * q2) - a[2],
+  ];
+
+  let j = [
+    [-2.0 * q2, 2.0 * q3, -2.0 * q0, 2.0 * q1],
+    [2.0 * q1, 2.0 * q0, 2.0 * q3, 2.0 * q2],
+    [0.0, -4.0 * q1, -4.0 * q2, 0.0],
+  ];
+
+  transpose_multiply(j, f)
+}
+
+/// The gradient of the error between the measured magnetic field and the field
+/// predicted by the attitude, J_bᵀ f_b in Madgwick's paper. The reference field
+/// is the measurement rotated into the level frame with its horizontal part
+/// pointed north, so that only heading is corrected.
+fn magnetic_gradient(q: [f64; 4], m: [f64; 3]) -> [f64; 4] {
+  let [q0, q1, q2, q3] = q;
+
+  let h = rotate(q, m);
+  let bx = (h[0] * h[0] + h[1] * h[1]).sqrt();
+  let bz = h[2];
+
+  let f = [
+    2.0 * bx * (0.5 - q2 * q2 - q3 * q3) + 2.0 * bz * (q1 * q3 - q0 * q2)
+      - m[0],
+    2.0 * bx * (q1 * q2 - q0 * q3) + 2.0 * bz * (q0 * q1 + q2 * q3) - m[1],
+    2.0 * bx * (q0 * q2 + q1 * q3) + 2.0 * bz * (0.5 - q1 * q1 - q2 * q2)
+      - m[2],
+  ];
+
+  let j = [
+    [
+      -2.0 * bz * q2,
+      2.0 * bz * q3,
+      -4.0 * bx * q2 - 2.0 * bz * q0,
+      -4.0 * bx * q3 + 2.0 * bz * q1,
+    ],
+    [
+      -2.0 * bx * q3 + 2.0 * bz * q1,
+      2.0 * bx * q2 + 2.0 * bz * q0,
+      2.0 * bx * q1 + 2.0 * bz * q3,
+      -2.0 * bx * q0 + 2.0 * bz * q2,
+    ],
+    [
+      2.0 * bx * q2,
+      2.0 * bx * q3 - 4.0 * bz * q1,
+      2.0 * bx * q0 - 4.0 * bz * q2,
+      2.0 * bx * q1,
+    ],
+  ];
+
+  transpose_multiply(j, f)
+}
+
+/// Computes the attitude directly from a single accelerometer and (optional)
+/// magnetometer reading, so the filter doesn't have to converge from level.
+fn initial_attitude(accel: [f64; 3], mag: Option<[f64; 3]>) -> [f64; 4] {
+  if !is_finite_nonzero(&accel) {
+    return [1.0, 0.0, 0.0, 0.0];
+  }
+
+  let roll = accel[1].atan2(accel[2]);
+  let pitch = (-accel[0]).atan2(accel[1].hypot(accel[2]));
+
+  let yaw = mag.map_or(0.0, |m| {
+    // tilt-compensate the field before taking its heading
+    let (sin_roll, cos_roll) = roll.sin_cos();
+    let (sin_pitch, cos_pitch) = pitch.sin_cos();
+
+    let north = m[0] * cos_pitch
+      + m[1] * sin_roll * sin_pitch
+      + m[2] * cos_roll * sin_pitch;
+    let west = m[1] * cos_roll - m[2] * sin_roll;
+
+    (-west).atan2(north)
+  });
+
+  from_euler(roll, pitch, yaw)
+}
+
+fn from_euler(roll: f64, pitch: f64, yaw: f64) -> [f64; 4] {
+  let (sr, cr) = (roll / 2.0).sin_cos();
+  let (sp, cp) = (pitch / 2.0).sin_cos();
+  let (sy, cy) = (yaw / 2.0).sin_cos();
+
+  [
+    cr * cp * cy + sr * sp * sy,
+    sr * cp * cy - cr * sp * sy,
+    cr * sp * cy + sr * cp * sy,
+    cr * cp * sy - sr * sp * cy,
+  ]
+}
+
+fn euler_angles(q: [f64; 4]) -> EulerAngles {
+  let [w, x, y, z] = q;
+
+  EulerAngles {
+    roll: (2.0 * (w * x + y * z))
+      .atan2(1.0 - 2.0 * (x * x + y * y))
+      .to_degrees(),
+    pitch: (2.0 * (w * y - z * x)).clamp(-1.0, 1.0).asin().to_degrees(),
+    yaw: (2.0 * (w * z + x * y))
+      .atan2(1.0 - 2.0 * (y * y + z * z))
+      .to_degrees(),
+  }
+}
+
+/// Rotates a body-frame vector into the level frame.
+fn rotate(q: [f64; 4], v: [f64; 3]) -> [f64; 3] {
+  let [w, x, y, z] = q;
+
+  [
+    (1.0 - 2.0 * (y * y + z * z)) * v[0]
+      + 2.0 * (x * y - w * z) * v[1]
+      + 2.0 * (x * z + w * y) * v[2],
+    2.0 * (x * y + w * z) * v[0]
+      + (1.0 - 2.0 * (x * x + z * z)) * v[1]
+      + 2.0 * (y * z - w * x) * v[2],
+    2.0 * (x * z - w * y) * v[0]
+      + 2.0 * (y * z + w * x) * v[1]
+      + (1.0 - 2.0 * (x * x + y * y)) * v[2],
+  ]
+}
+
+fn transpose_multiply(j: [[f64; 4]; 3], f: [f64; 3]) -> [f64; 4] {
+  let mut result = [0.0; 4];
+
+  for (row, error) in j.iter().zip(f) {
+    for (r, element) in result.iter_mut().zip(row) {
+      *r += element * error;
+    }
+  }
+
+  result
+}
+
+fn to_array(vector: Vector) -> [f64; 3] {
+  [vector.x, vector.y, vector.z]
+}
+
+fn magnitude(v: &[f64]) -> f64 {
+  v.iter().map(|c| c * c).sum::<f64>().sqrt()
+}
+
+fn normalize<const N: usize>(v: [f64; N]) -> [f64; N] {
+  let norm = magnitude(&v);
+  v.map(|c| c / norm)
+}
+
+fn is_finite_nonzero(v: &[f64]) -> bool {
+  v.iter().all(|c| c.is_finite()) && v.iter().any(|c| *c != 0.0)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const DT: f64 = 0.01;
+
+  // a northern hemisphere field in gauss, pointing north and down
+  const EARTH_FIELD: [f64; 3] = [0.22, 0.0, -0.4];
+
+  const GRAVITY: [f64; 3] = [0.0, 0.0, 1.0];
+
+  fn vector(v: [f64; 3]) -> Vector {
+    Vector {
+      x: v[0],
+      y: v[1],
+      z: v[2],
+    }
+  }
+
+  // what a body with the given attitude (in degrees) measures for a vector
+  // fixed in the level frame
+  fn measure(attitude: [f64; 3], level: [f64; 3]) -> Vector {
+    let q = from_euler(
+      attitude[0].to_radians(),
+      attitude[1].to_radians(),
+      attitude[2].to_radians(),
+    );
+    let conjugate = [q[0], -q[1], -q[2], -q[3]];
+
+    vector(rotate(conjugate, level))
+  }
+
+  fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+    assert!(
+      (actual - expected).abs() <= tolerance,
+      "expected {expected} ± {tolerance}, got {actual}"
+    );
+  }
+
+  #[test]
+  fn stationary_and_level_stays_level() {
+    let mut estimator = AttitudeEstimator::default();
+    let mut attitude = Attitude::default();
+
+    for _ in 0..1000 {
+      attitude = estimator.update(
+        vector([0.0; 3]),
+        vector(GRAVITY),
+        vector([0.0; 3]),
+        DT,
+      );
+    }
+
+    assert_close(attitude.euler.roll, 0.0, 1e-6);
+    assert_close(attitude.euler.pitch, 0.0, 1e-6);
+    assert_close(attitude.euler.yaw, 0.0, 1e-6);
+    assert_close(attitude.quaternion.w, 1.0, 1e-9);
+  }
+
+  #[test]
+  fn first_sample_sets_tilt_and_heading() {
+    let truth = [20.0, 10.0, -120.0];
+    let mut estimator = AttitudeEstimator::default();
+
+    let attitude = estimator.update(
+      vector([0.0; 3]),
+      measure(truth, GRAVITY),
+      measure(truth, EARTH_FIELD),
+      DT,
+    );
+
+    assert_close(attitude.euler.roll, truth[0], 0.5);
+    assert_close(attitude.euler.pitch, truth[1], 0.5);
+    assert_close(attitude.euler.yaw, truth[2], 0.5);
+  }
+
+  #[test]
+  fn converges_to_a_static_tilt() {
+    let truth = [30.0, -20.0, 0.0];
+    let mut estimator = AttitudeEstimator::default();
+
+    // start level, then hold the tilt for 30 s
+    estimator.update(vector([0.0; 3]), vector(GRAVITY), vector([0.0; 3]), DT);
+
+    let mut attitude = Attitude::default();
+
+    for _ in 0..3000 {
+      attitude = estimator.update(
+        vector([0.0; 3]),
+        measure(truth, GRAVITY),
+        vector([0.0; 3]),
+        DT,
+      );
+    }
+
+    assert_close(attitude.euler.roll, truth[0], 0.5);
+    assert_close(attitude.euler.pitch, truth[1], 0.5);
+  }
+
+  #[test]
+  fn integrates_yaw_rate_without_magnetometer() {
+    let mut estimator = AttitudeEstimator::default();
+    let mut attitude = Attitude::default();
+
+    // 45 deg/s for 2 s
+    for _ in 0..200 {
+      attitude = estimator.update(
+        vector([0.0, 0.0, 45.0]),
+        vector(GRAVITY),
+        vector([0.0; 3]),
+        DT,
+      );
+    }
+
+    assert_close(attitude.euler.yaw, 90.0, 0.5);
+    assert_close(attitude.euler.roll, 0.0, 0.1);
+    assert_close(attitude.euler.pitch, 0.0, 0.1);
+    assert_close(attitude.angular_rate.z, 45.0, 1e-9);
+  }
+
+  #[test]
+  fn tracks_a_roll_maneuver() {
+    let mut estimator = AttitudeEstimator::default();
+    let mut attitude = Attitude::default();
+
+    // 30 deg/s for 1 s, with gravity following the true attitude
+    for step in 1..=100 {
+      let roll = 30.0 * step as f64 * DT;
+
+      attitude = estimator.update(
+        vector([30.0, 0.0, 0.0]),
+        measure([roll, 0.0, 0.0], GRAVITY),
+        vector([0.0; 3]),
+        DT,
+      );
+    }
+
+    assert_close(attitude.euler.roll, 30.0, 1.0);
+    assert_close(attitude.euler.pitch, 0.0, 0.1);
+    assert_close(attitude.euler.yaw, 0.0, 0.1);
+  }
+
+  #[test]
+  fn magnetometer_corrects_heading() {
+    let mut estimator = AttitudeEstimator::default();
+
+    // start facing north, then hold a heading of 60 degrees for 30 s
+    estimator.update(
+      vector([0.0; 3]),
+      vector(GRAVITY),
+      measure([0.0; 3], EARTH_FIELD),
+      DT,
+    );
+
+    let mut attitude = Attitude::default();
+
+    for _ in 0..3000 {
+      attitude = estimator.update(
+        vector([0.0; 3]),
+        vector(GRAVITY),
+        measure([0.0, 0.0, 60.0], EARTH_FIELD),
+        DT,
+      );
+    }
+
+    assert_close(attitude.euler.yaw, 60.0, 0.5);
+    assert_close(attitude.euler.roll, 0.0, 0.1);
+    assert_close(attitude.euler.pitch, 0.0, 0.1);
+  }
+
+  #[test]
+  fn ignores_invalid_samples() {
+    let mut estimator = AttitudeEstimator::default();
+
+    estimator.update(vector([0.0; 3]), vector(GRAVITY), vector([0.0; 3]), DT);
+
+    let attitude = estimator.update(
+      vector([f64::NAN; 3]),
+      vector(GRAVITY),
+      vector([0.0; 3]),
+      DT,
+    );
+
+    assert!(attitude.quaternion.w.is_finite());
+    assert_close(attitude.euler.roll, 0.0, 1e-6);
+  }
+}
@@ -0,0 +1,44 @@
+use crate::pins::{get_pin, BAR_CS, CAMERA_ENABLE, MAG_CS};
+use common::comm::{
+  ahrs::Command,
+  gpio::{
+    PinMode::Output,
+    PinValue::{High, Low},
+  },
+};
+
+pub fn init_gpio() {
+  disable_camera();
+
+  // the IMU chip select is owned by its driver, but the barometer and
+  // magnetometer share the bus and must be held inactive (active low)
+  for location in [BAR_CS, MAG_CS] {
+    let mut chip_select = get_pin(location);
+    chip_select.mode(Output);
+    chip_select.digital_write(High);
+  }
+}
+
+pub fn enable_camera() {
+  let mut pin = get_pin(CAMERA_ENABLE);
+  pin.mode(Output);
+  pin.digital_write(High);
+}
+
+pub fn disable_camera() {
+  let mut pin = get_pin(CAMERA_ENABLE);
+  pin.mode(Output);
+  pin.digital_write(Low);
+}
+
+pub fn execute(command: Command) {
+  match command {
+    Command::CameraEnable(true) => enable_camera(),
+    Command::CameraEnable(false) => disable_camera(),
+
+    // handled by the main loop, which owns the sensors and estimators
+    Command::ArmAltimeter(_)
+    | Command::PersistGyroBias
+    | Command::CalibrateMagnetometer(_) => {}
+  }
+}
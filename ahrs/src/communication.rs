@@ -0,0 +1,31 @@
+use common::comm::{ahrs::DataPoint, flight::DataMessage};
+use jeflog::warn;
+use std::{
+  borrow::Cow,
+  net::{SocketAddr, UdpSocket},
+};
+
+pub const AHRS_ID: &str = "ahrs-01";
+
+pub fn send_data(
+  socket: &UdpSocket,
+  address: &SocketAddr,
+  datapoints: Vec<DataPoint>,
+) {
+  // create a buffer to store the data to send in
+  let mut buffer: [u8; 2048] = [0; 2048];
+
+  // get the data and store it in the buffer
+  let data = DataMessage::Ahrs(AHRS_ID.to_string(), Cow::Owned(datapoints));
+  let serialized = match postcard::to_slice(&data, &mut buffer) {
+    Ok(slice) => slice,
+    Err(e) => {
+      warn!("Could not serialize buffer ({e}), continuing...");
+      return;
+    }
+  };
+
+  if let Some(e) = socket.send_to(serialized, address).err() {
+    warn!("Could not send data ({e}), continuing...");
+  }
+}
@@ -0,0 +1,103 @@
+use common::comm::ahrs::{Magnetometer, Vector};
+use jeflog::{fail, pass, warn};
+use lis3mdl::calibration::Calibration;
+use std::{fs, io, path::Path};
+
+/// Where the hard and soft iron calibration is kept across restarts.
+const CALIBRATION_PATH: &str = "/var/lib/ahrs/magnetometer";
+
+/// The most samples kept while recording, ten minutes at the send rate, so a
+/// recording that is never finished can't grow without bound.
+const MAX_RECORDED_SAMPLES: usize = 60_000;
+
+/// Corrects the magnetometer for hard and soft iron, and records raw samples
+/// to fit a new correction to while the board is turned through every
+/// orientation.
+pub struct MagnetometerCalibrator {
+  calibration: Calibration,
+  recording: Option<Vec<[f64; 3]>>,
+}
+
+impl MagnetometerCalibrator {
+  /// Loads the saved calibration, or applies none if there isn't one.
+  pub fn restore() -> Self {
+    let calibration = match load(Path::new(CALIBRATION_PATH)) {
+      Ok(calibration) => {
+        pass!("Restored magnetometer calibration: {calibration}");
+        calibration
+      }
+      Err(error) => {
+        warn!("Could not restore magnetometer calibration ({error}).");
+        Calibration::default()
+      }
+    };
+
+    MagnetometerCalibrator {
+      calibration,
+      recording: None,
+    }
+  }
+
+  /// Starts recording raw samples, discarding any earlier recording.
+  pub fn start_recording(&mut self) {
+    self.recording = Some(Vec::new());
+  }
+
+  /// Fits a calibration to the recorded samples, then applies and saves it.
+  /// A recording which can't be fit leaves the old calibration in place.
+  pub fn finish_recording(&mut self) {
+    let Some(samples) = self.recording.take() else {
+      warn!("Magnetometer calibration finished without being started.");
+      return;
+    };
+
+    match Calibration::fit(&samples) {
+      Ok(calibration) => {
+        pass!(
+          "Fit magnetometer calibration to {} samples: {calibration}",
+          samples.len()
+        );
+        self.calibration = calibration;
+
+        if let Err(error) = self.save() {
+          fail!("Failed to save magnetometer calibration: {error}");
+        }
+      }
+      Err(error) => {
+        fail!("Failed to fit magnetometer calibration ({error}).");
+      }
+    }
+  }
+
+  /// Corrects a raw sample in gauss, recording it first if a recording is in
+  /// progress.
+  pub fn correct(&mut self, raw: [f64; 3]) -> Magnetometer {
+    if let Some(recording) = &mut self.recording {
+      if recording.len() < MAX_RECORDED_SAMPLES {
+        recording.push(raw);
+      }
+    }
+
+    let [x, y, z] = self.calibration.apply(raw);
+    Vector { x, y, z }
+  }
+
+  fn save(&self) -> io::Result<()> {
+    let path = Path::new(CALIBRATION_PATH);
+    let temporary = path.with_extension("tmp");
+
+    if let Some(parent) = path.parent() {
+      fs::create_dir_all(parent)?;
+    }
+
+    // write then rename so a power loss mid-write can't corrupt the file
+    fs::write(&temporary, format!("{}\n", self.calibration))?;
+    fs::rename(&temporary, path)
+  }
+}
+
+fn load(path: &Path) -> io::Result<Calibration> {
+  fs::read_to_string(path)?
+    .parse()
+    .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
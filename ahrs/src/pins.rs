@@ -0,0 +1,22 @@
+use common::comm::gpio::{Gpio, Pin};
+use std::sync::LazyLock;
+
+pub static GPIO_CONTROLLERS: LazyLock<Vec<Gpio>> =
+  LazyLock::new(open_controllers);
+
+// controller = floor(GPIO#/32)
+// pin = remainder
+pub const IMU_CS: [usize; 2] = [0, 11];
+pub const IMU_DATA_READY: [usize; 2] = [2, 17];
+pub const IMU_NRESET: [usize; 2] = [2, 25];
+pub const BAR_CS: [usize; 2] = [2, 24];
+pub const MAG_CS: [usize; 2] = [1, 14];
+pub const CAMERA_ENABLE: [usize; 2] = [1, 12];
+
+pub fn open_controllers() -> Vec<Gpio> {
+  (0..=3).map(Gpio::open_controller).collect()
+}
+
+pub fn get_pin(location: [usize; 2]) -> Pin {
+  GPIO_CONTROLLERS[location[0]].get_pin(location[1])
+}
@@ -0,0 +1,172 @@
+use crate::pins::{
+  get_pin,
+  BAR_CS,
+  IMU_CS,
+  IMU_DATA_READY,
+  IMU_NRESET,
+  MAG_CS,
+};
+use common::comm::ahrs::{self, Gyroscope, Imu, ImuHealth, Vector};
+use imu::{bit_mappings::DriverResult, AdisIMUDriver};
+use jeflog::{fail, pass, warn};
+use lis3mdl::Magnetometer;
+use ms5611::Barometer;
+use spidev::Spidev;
+use std::io;
+
+const SPI_PATH: &str = "/dev/spidev0.0";
+
+// the IMU samples at 2000 SPS internally, so this gives about 222 Hz
+const IMU_DECIMATION_RATE: u16 = 8;
+
+// about two seconds of samples at the decimated rate
+const GYRO_BIAS_SAMPLES: usize = 444;
+
+// m/s² in a G, as the driver reports acceleration in m/s²
+const STANDARD_GRAVITY: f64 = 9.80665;
+
+/// Opens, resets and validates the ADIS IMU.
+pub fn init_imu() -> DriverResult<AdisIMUDriver> {
+  let spi = Spidev::open(SPI_PATH)?;
+
+  let mut driver = AdisIMUDriver::initialize(
+    spi,
+    get_pin(IMU_DATA_READY),
+    get_pin(IMU_NRESET),
+    get_pin(IMU_CS),
+  )?;
+
+  driver.write_dec_rate(IMU_DECIMATION_RATE)?;
+
+  if !driver.validate() {
+    fail!("IMU product ID did not match, is it connected?");
+    return Err(
+      io::Error::new(io::ErrorKind::NotFound, "IMU product ID mismatch").into(),
+    );
+  }
+
+  pass!("Initialized IMU with decimation rate {IMU_DECIMATION_RATE}.");
+  Ok(driver)
+}
+
+/// Estimates the gyroscope bias while the board sits still at startup and
+/// corrects for it on the IMU, returning the correction in deg/s. If the board
+/// is moving the correction already on the IMU, which may be one persisted to
+/// its flash, is kept instead.
+pub fn calibrate_gyro(driver: &mut AdisIMUDriver) -> Gyroscope {
+  let bias = match driver.calibrate_gyro_bias(GYRO_BIAS_SAMPLES) {
+    Ok(bias) => {
+      pass!("Calibrated gyroscope bias: {bias}");
+      Ok(bias)
+    }
+    Err(e) => {
+      warn!("Failed to calibrate the gyroscope ({e}), keeping its bias.");
+      driver.read_bias()
+    }
+  };
+
+  match bias {
+    Ok(bias) => {
+      let gyro = bias.get_gyro_float();
+
+      Vector {
+        x: gyro[0] as f64,
+        y: gyro[1] as f64,
+        z: gyro[2] as f64,
+      }
+    }
+    Err(e) => {
+      fail!("Failed to read the gyroscope bias ({e}).");
+      Vector::default()
+    }
+  }
+}
+
+/// Reads the latest angular rate and acceleration from the IMU. A read that
+/// fails its checksum or flags an IMU error is returned as an error.
+pub fn read_imu(driver: &mut AdisIMUDriver) -> DriverResult<Imu> {
+  let (_, data) = driver.burst_read_gyro_32()?;
+  let gyro = data.get_gyro_float();
+  let accel = data.get_accel_float();
+
+  Ok(Imu {
+    accelerometer: Vector {
+      x: accel[0] as f64 / STANDARD_GRAVITY,
+      y: accel[1] as f64 / STANDARD_GRAVITY,
+      z: accel[2] as f64 / STANDARD_GRAVITY,
+    },
+    gyroscope: Vector {
+      x: gyro[0] as f64,
+      y: gyro[1] as f64,
+      z: gyro[2] as f64,
+    },
+  })
+}
+
+/// Reports how many reads of the IMU have succeeded or failed.
+pub fn imu_health(driver: &AdisIMUDriver) -> ImuHealth {
+  let health = driver.health();
+
+  ImuHealth {
+    good_samples: health.good_samples,
+    checksum_failures: health.checksum_failures,
+    overruns: health.overruns,
+    sensor_failures: health.sensor_failures,
+    other_failures: health.other_failures,
+  }
+}
+
+/// Opens the magnetometer, which shares the IMU's bus. Attitude can be
+/// estimated without it, so a failure is reported rather than retried.
+pub fn init_magnetometer() -> Option<Magnetometer> {
+  match Magnetometer::new(SPI_PATH, get_pin(MAG_CS)) {
+    Ok(magnetometer) => {
+      pass!("Initialized magnetometer.");
+      Some(magnetometer)
+    }
+    Err(e) => {
+      fail!("Failed to initialize the magnetometer ({e}), continuing without.");
+      None
+    }
+  }
+}
+
+/// Reads the field in gauss if the magnetometer has a new sample.
+pub fn read_magnetometer(
+  magnetometer: &mut Magnetometer,
+) -> io::Result<Option<[f64; 3]>> {
+  if !magnetometer.data_ready()? {
+    return Ok(None);
+  }
+
+  magnetometer.read_field().map(Some)
+}
+
+/// Opens the barometer, which shares the IMU's bus. Without it the altimeter
+/// cannot be armed, but attitude is still estimated, so a failure is reported
+/// rather than retried.
+pub fn init_barometer() -> Option<Barometer> {
+  match Barometer::new(SPI_PATH, get_pin(BAR_CS)) {
+    Ok(barometer) => {
+      pass!("Initialized barometer.");
+      Some(barometer)
+    }
+    Err(e) => {
+      fail!("Failed to initialize the barometer ({e}), continuing without.");
+      None
+    }
+  }
+}
+
+/// Reads the pressure and temperature if the barometer has finished a new
+/// pair of conversions, starting the next one either way.
+pub fn read_barometer(
+  barometer: &mut Barometer,
+) -> io::Result<Option<ahrs::Barometer>> {
+  let reading = barometer.poll()?;
+
+  Ok(reading.map(|reading| ahrs::Barometer {
+    temperature: reading.temperature,
+    pressure: reading.pressure,
+  }))
+}
@@ -0,0 +1,316 @@
+use crate::{
+  altitude::AltitudeEstimator,
+  attitude::AttitudeEstimator,
+  command::{execute, init_gpio},
+  communication::{send_data, AHRS_ID},
+  magnetometer::MagnetometerCalibrator,
+  sensors::{
+    calibrate_gyro,
+    imu_health,
+    init_barometer,
+    init_imu,
+    init_magnetometer,
+    read_barometer,
+    read_imu,
+    read_magnetometer,
+  },
+};
+use common::comm::{
+  ahrs::{Ahrs, Command, DataPoint, Gyroscope},
+  discovery::{
+    check_heartbeat,
+    establish_flight_computer_connection,
+    receive_command,
+  },
+  flight::BoardKind,
+};
+use imu::AdisIMUDriver;
+use jeflog::{fail, pass, warn};
+use lis3mdl::Magnetometer;
+use ms5611::Barometer;
+use std::{
+  net::{SocketAddr, UdpSocket},
+  thread,
+  time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+// how often a datapoint is sent to the flight computer
+const SEND_PERIOD: Duration = Duration::from_millis(10);
+
+// how long to wait before trying to bring up a missing IMU again
+const IMU_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+// boxed so that each transition moves a pointer rather than the sensors and
+// estimators
+pub enum State {
+  Init,
+  Connect(Box<ConnectData>),
+  MainLoop(Box<MainLoopData>),
+  Abort(Box<AbortData>),
+}
+
+pub struct ConnectData {
+  driver: AdisIMUDriver,
+  gyro_bias: Gyroscope,
+  magnetometer: Option<Magnetometer>,
+  barometer: Option<Barometer>,
+  mag_calibrator: MagnetometerCalibrator,
+  attitude: AttitudeEstimator,
+  altitude: AltitudeEstimator,
+  // the flight computer last connected to, if comms with it were lost
+  fc_address: Option<SocketAddr>,
+}
+
+pub struct MainLoopData {
+  driver: AdisIMUDriver,
+  magnetometer: Option<Magnetometer>,
+  barometer: Option<Barometer>,
+  mag_calibrator: MagnetometerCalibrator,
+  attitude: AttitudeEstimator,
+  altitude: AltitudeEstimator,
+  last_sample: Option<Instant>,
+  my_data_socket: UdpSocket,
+  my_command_socket: UdpSocket,
+  fc_address: SocketAddr,
+  then: Instant,
+  next_send: Instant,
+  state: Ahrs,
+}
+
+pub struct AbortData {
+  driver: AdisIMUDriver,
+  gyro_bias: Gyroscope,
+  magnetometer: Option<Magnetometer>,
+  barometer: Option<Barometer>,
+  mag_calibrator: MagnetometerCalibrator,
+  attitude: AttitudeEstimator,
+  altitude: AltitudeEstimator,
+  fc_address: SocketAddr,
+}
+
+impl State {
+  pub fn next(self) -> Self {
+    match self {
+      State::Init => init(),
+
+      State::Connect(data) => connect(data),
+
+      State::MainLoop(data) => main_loop(data),
+
+      State::Abort(data) => abort(data),
+    }
+  }
+}
+
+fn init() -> State {
+  init_gpio();
+
+  match init_imu() {
+    Ok(mut driver) => State::Connect(Box::new(ConnectData {
+      gyro_bias: calibrate_gyro(&mut driver),
+      driver,
+      magnetometer: init_magnetometer(),
+      barometer: init_barometer(),
+      mag_calibrator: MagnetometerCalibrator::restore(),
+      attitude: AttitudeEstimator::default(),
+      altitude: AltitudeEstimator::default(),
+      fc_address: None,
+    })),
+    Err(e) => {
+      fail!("Failed to initialize the IMU ({e}), retrying...");
+      thread::sleep(IMU_RETRY_DELAY);
+      State::Init
+    }
+  }
+}
+
+fn connect(data: Box<ConnectData>) -> State {
+  let (data_socket, command_socket, fc_address) =
+    establish_flight_computer_connection(
+      AHRS_ID,
+      BoardKind::Ahrs,
+      data.fc_address,
+    );
+
+  State::MainLoop(Box::new(MainLoopData {
+    driver: data.driver,
+    magnetometer: data.magnetometer,
+    barometer: data.barometer,
+    mag_calibrator: data.mag_calibrator,
+    attitude: data.attitude,
+    altitude: data.altitude,
+    last_sample: None,
+    my_command_socket: command_socket,
+    my_data_socket: data_socket,
+    fc_address,
+    then: Instant::now(),
+    next_send: Instant::now(),
+    state: Ahrs {
+      gyro_bias: data.gyro_bias,
+      ..Ahrs::default()
+    },
+  }))
+}
+
+fn main_loop(mut data: Box<MainLoopData>) -> State {
+  match receive_command(&data.my_command_socket) {
+    Some(Command::ArmAltimeter(true)) => {
+      let pressure = data.state.barometer.pressure;
+
+      if data.altitude.arm(pressure) {
+        pass!("Armed the altimeter with a pad pressure of {pressure} bar.");
+      } else {
+        fail!("Cannot arm the altimeter without a valid barometer reading.");
+      }
+    }
+    Some(Command::ArmAltimeter(false)) => {
+      pass!("Disarmed the altimeter.");
+      data.altitude.disarm();
+    }
+    Some(Command::PersistGyroBias) => match data.driver.flash_update() {
+      Ok(()) => {
+        pass!("Persisted the gyroscope bias to the IMU's flash.");
+      }
+      Err(e) => {
+        fail!("Failed to persist the gyroscope bias ({e}).");
+      }
+    },
+    Some(Command::CalibrateMagnetometer(true)) => {
+      pass!("Recording the magnetometer, turn the board through every axis.");
+      data.mag_calibrator.start_recording();
+    }
+    Some(Command::CalibrateMagnetometer(false)) => {
+      data.mag_calibrator.finish_recording();
+    }
+    Some(command) => {
+      pass!("Executing command: {command}");
+      execute(command);
+    }
+    None => {}
+  }
+
+  let (updated_time, abort_status) =
+    check_heartbeat(&data.my_data_socket, data.then);
+  data.then = updated_time;
+
+  if abort_status {
+    return State::Abort(Box::new(AbortData {
+      driver: data.driver,
+      gyro_bias: data.state.gyro_bias,
+      magnetometer: data.magnetometer,
+      barometer: data.barometer,
+      mag_calibrator: data.mag_calibrator,
+      attitude: data.attitude,
+      altitude: data.altitude,
+      fc_address: data.fc_address,
+    }));
+  }
+
+  let now = Instant::now();
+
+  if now < data.next_send {
+    // the heartbeat limit is far longer than the send period, so sleeping
+    // until the next send can't miss it
+    thread::sleep(data.next_send - now);
+    return State::MainLoop(data);
+  }
+
+  // skip any sends that were missed rather than bursting to catch up
+  data.next_send += SEND_PERIOD;
+  if data.next_send < now {
+    data.next_send = now + SEND_PERIOD;
+  }
+
+  // the magnetometer samples slower than the loop, so it is only read when it
+  // has something new
+  if let Some(magnetometer) = &mut data.magnetometer {
+    match read_magnetometer(magnetometer) {
+      Ok(Some(field)) => {
+        data.state.magnetometer = data.mag_calibrator.correct(field);
+      }
+      Ok(None) => {}
+      Err(e) => {
+        warn!("Failed to read the magnetometer ({e}).");
+      }
+    }
+  }
+
+  // likewise the barometer alternates between converting temperature and
+  // pressure, so only every other read gives a new reading
+  let mut pressure = None;
+
+  if let Some(barometer) = &mut data.barometer {
+    match read_barometer(barometer) {
+      Ok(Some(reading)) => {
+        data.state.barometer = reading;
+        pressure = Some(reading.pressure);
+      }
+      Ok(None) => {}
+      Err(e) => {
+        warn!("Failed to read the barometer ({e}).");
+      }
+    }
+  }
+
+  // a failed read keeps the last good reading rather than sending garbage
+  match read_imu(&mut data.driver) {
+    Ok(imu) => {
+      let dt = data
+        .last_sample
+        .map_or(0.0, |last| (now - last).as_secs_f64());
+      data.last_sample = Some(now);
+
+      data.state.imu = imu;
+      data.state.attitude = data.attitude.update(
+        imu.gyroscope,
+        imu.accelerometer,
+        data.state.magnetometer,
+        dt,
+      );
+
+      // the barometer only corrects the estimate with a fresh reading, and
+      // between them it is left to the accelerometer alone
+      data.state.altitude = data.altitude.update(
+        data.state.attitude.quaternion,
+        imu.accelerometer,
+        pressure,
+        dt,
+      );
+    }
+    Err(e) => {
+      warn!("Dropped a bad IMU sample ({e}).");
+    }
+  }
+
+  data.state.imu_health = imu_health(&data.driver);
+
+  let datapoint = DataPoint {
+    state: data.state,
+    timestamp: SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|duration| duration.as_secs_f64())
+      .unwrap_or(0.0),
+  };
+
+  send_data(&data.my_data_socket, &data.fc_address, vec![datapoint]);
+
+  State::MainLoop(data)
+}
+
+fn abort(data: Box<AbortData>) -> State {
+  fail!("Lost contact with the flight computer, safing and reconnecting.");
+  init_gpio();
+
+  // identify to the same flight computer again, which ignores this board
+  // until it does
+  State::Connect(Box::new(ConnectData {
+    driver: data.driver,
+    gyro_bias: data.gyro_bias,
+    magnetometer: data.magnetometer,
+    barometer: data.barometer,
+    mag_calibrator: data.mag_calibrator,
+    attitude: data.attitude,
+    altitude: data.altitude,
+    fc_address: Some(data.fc_address),
+  }))
+}
@@ -1,5 +1,6 @@
 use std::time::{Instant, Duration};
-use common::comm::{bms::{Bms, DataPoint}, gpio::PinValue::Low, ADCKind::{SamAnd5V, VBatUmbCharge}};
+use common::comm::{bms::{Bms, DataPoint}, gpio::PinValue::Low};
+use crate::board;
 use ads114s06::ADC;
 use std::f64::NAN;
 
@@ -14,11 +15,7 @@ pub fn init_adcs(adcs: &mut Vec<ADC>) {
     print!("]\n");
     
     // positive input channel initial mux
-    if adc.kind == VBatUmbCharge {
-      adc.set_positive_input_channel(0);
-    } else {
-      adc.set_positive_input_channel(2);
-    }
+    adc.set_positive_input_channel(first_input(adc));
 
     // negative channel input mux (does not change)
     adc.set_negative_input_channel_to_aincom();
@@ -68,29 +65,50 @@ pub fn start_adcs(adcs: &mut Vec<ADC>) {
   }
 }
 
-pub fn reset_adcs(adcs: &mut Vec<ADC>) {
-  for adc in adcs.iter_mut() {
-    adc.spi_stop_conversion(); // stop collecting data
+/// The input an ADC is muxed to before its first conversion.
+fn first_input(adc: &ADC) -> u8 {
+  board::inputs(adc.kind).first().map_or(0, |input| input.ain)
+}
 
-    // reset back to first channel for when data collection resumes
-    if adc.kind == VBatUmbCharge {
-      adc.set_positive_input_channel(0);
-    } else {
-      adc.set_positive_input_channel(2);
-    }
+/// Stores a converted measurement under the name the board definition uses.
+fn store(bms: &mut Bms, measurement: &str, value: f64) {
+  match measurement {
+    "battery_bus.voltage" => bms.battery_bus.voltage = value,
+    "battery_bus.current" => bms.battery_bus.current = value,
+    "umbilical_bus.voltage" => bms.umbilical_bus.voltage = value,
+    "umbilical_bus.current" => bms.umbilical_bus.current = value,
+    "sam_power_bus.voltage" => bms.sam_power_bus.voltage = value,
+    "sam_power_bus.current" => bms.sam_power_bus.current = value,
+    "five_volt_rail.voltage" => bms.five_volt_rail.voltage = value,
+    "five_volt_rail.current" => bms.five_volt_rail.current = value,
+    "charger" => bms.charger = value,
+    "e_stop" => bms.e_stop = value,
+    "rbf_tag" => bms.rbf_tag = value,
+    // every measurement is checked against board::MEASUREMENTS on load
+    _ => unreachable!("unknown measurement {measurement}"),
   }
 }
 
 pub fn poll_adcs(adcs: &mut Vec<ADC>) -> DataPoint {
-  let mut bms_data = Bms::default();
-  for channel in 0..6 {
+  // the e-stop and RBF tag sense lines are optional, and say nothing on a
+  // revision which doesn't wire them
+  let mut bms_data = Bms {
+    e_stop: f64::NAN,
+    rbf_tag: f64::NAN,
+    ..Bms::default()
+  };
+  let rounds = adcs
+    .iter()
+    .map(|adc| board::inputs(adc.kind).len())
+    .max()
+    .unwrap_or(0);
+
+  for round in 0..rounds {
     for (i, adc) in adcs.iter_mut().enumerate() {
-      let reached_max_vbat_umb_charge =
-        adc.kind == VBatUmbCharge && channel > 4;
-      let reached_max_sam_and_5v = adc.kind == SamAnd5V && channel < 2;
-      if reached_max_vbat_umb_charge || reached_max_sam_and_5v {
+      let inputs = board::inputs(adc.kind);
+      let Some(input) = inputs.get(round) else {
         continue;
-      }
+      };
 
       // poll for data ready
       let time = Instant::now();
@@ -116,51 +134,16 @@ pub fn poll_adcs(adcs: &mut Vec<ADC>) -> DataPoint {
         },
 
         Err(e) => {
-          eprintln!("Err reading data on ADC {} channel {}: {:#?}", i, channel, e);
+          eprintln!("Err reading data on ADC {} AIN{}: {:#?}", i, input.ain, e);
           NAN
         }
       };
 
-      match adc.kind {
-        VBatUmbCharge => {
-          if channel == 0 {
-            bms_data.battery_bus.current = data * 2.0;
-          } else if channel == 1 {
-            bms_data.battery_bus.voltage = data * 22.5;
-          } else if channel == 2 {
-            bms_data.umbilical_bus.current = data * 2.0;
-          } else if channel == 3 {
-            bms_data.umbilical_bus.voltage = data * 22.5;
-          } else if channel == 4 {
-            // charger current sense
-            bms_data.charger = (data - 0.25) / 0.15;
-          }
-
-          // muxing logic
-          adc.set_positive_input_channel((channel + 1) % 5);
-        },
+      store(&mut bms_data, &input.measurement, input.convert(data));
 
-        SamAnd5V => {
-          if channel == 2 {
-            bms_data.sam_power_bus.current = data * 2.0;
-          } else if channel == 3 {
-            bms_data.sam_power_bus.voltage = data * 22.5;
-          } else if channel == 4 {
-            bms_data.five_volt_rail.voltage = data * 22.5;
-          } else if channel == 5 {
-            bms_data.five_volt_rail.current = data * 2.0;
-          }
-
-          // muxing logic
-          if channel == 5 {
-            adc.set_positive_input_channel(0);
-          } else {
-            adc.set_positive_input_channel(channel + 1);
-          }
-        },
-
-        _ => panic!("Imposter ADC among us!")
-      }
+      // muxing logic
+      let next = &inputs[(round + 1) % inputs.len()];
+      adc.set_positive_input_channel(next.ain);
     }
   }
 
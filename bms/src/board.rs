@@ -0,0 +1,126 @@
+use crate::command::GPIO_CONTROLLERS;
+use common::comm::{
+  board::{BoardDefinition, BoardDefinitionError, GpioPin, InputWiring},
+  gpio::Pin,
+  ADCKind,
+};
+use std::sync::OnceLock;
+
+/// Where an updated set of board definitions may be placed on the BMS without
+/// rebuilding the software.
+const DEFINITIONS_PATH: &str = "/etc/bms/boards.json";
+
+/// The board definitions the BMS software was built with.
+const DEFAULT_DEFINITIONS: &str = include_str!("../boards.json");
+
+/// How many GPIO controllers the AM335x has.
+const GPIO_CONTROLLER_COUNT: usize = 4;
+
+/// Every named output the BMS software drives.
+const REQUIRED_OUTPUTS: [&str; 7] = [
+  "battery_enable",
+  "sam_enable",
+  "charge_enable",
+  "estop_reset",
+  "reco_1",
+  "reco_2",
+  "reco_3",
+];
+
+/// Every ADC the BMS software polls.
+const REQUIRED_ADCS: [ADCKind; 2] = [ADCKind::VBatUmbCharge, ADCKind::SamAnd5V];
+
+/// Every measurement an ADC input may be wired to, as stored by
+/// `adc::poll_adcs`.
+///
+/// The e-stop and RBF tag sense lines are optional. `bms-rev1` leaves them out
+/// until the AIN each is on is confirmed against its schematic, so the e-stop
+/// state reads as unknown there and no e-stop events are sent.
+pub const MEASUREMENTS: [&str; 11] = [
+  "battery_bus.voltage",
+  "battery_bus.current",
+  "umbilical_bus.voltage",
+  "umbilical_bus.current",
+  "sam_power_bus.voltage",
+  "sam_power_bus.current",
+  "five_volt_rail.voltage",
+  "five_volt_rail.current",
+  "charger",
+  "e_stop",
+  "rbf_tag",
+];
+
+/// Every measurement the protections, state of charge and charger rely on.
+const REQUIRED_MEASUREMENTS: [&str; 9] = [
+  "battery_bus.voltage",
+  "battery_bus.current",
+  "umbilical_bus.voltage",
+  "umbilical_bus.current",
+  "sam_power_bus.voltage",
+  "sam_power_bus.current",
+  "five_volt_rail.voltage",
+  "five_volt_rail.current",
+  "charger",
+];
+
+static BOARD: OnceLock<BoardDefinition> = OnceLock::new();
+
+/// Loads the definition of the board this is running on. This must succeed
+/// before anything else touches the GPIO.
+pub fn load() -> Result<&'static BoardDefinition, BoardDefinitionError> {
+  if let Some(board) = BOARD.get() {
+    return Ok(board);
+  }
+
+  let board = BoardDefinition::load(DEFINITIONS_PATH, DEFAULT_DEFINITIONS)?;
+  board.validate(GPIO_CONTROLLER_COUNT)?;
+  board.require_outputs(&REQUIRED_OUTPUTS)?;
+  board.require_measurements(&MEASUREMENTS, &REQUIRED_MEASUREMENTS)?;
+
+  for kind in REQUIRED_ADCS {
+    if board.adc(kind).and_then(|adc| adc.data_ready).is_none() {
+      return Err(BoardDefinitionError::Incomplete {
+        revision: board.revision.clone(),
+        missing: format!("ADC {kind:?} with a DRDY line"),
+      });
+    }
+  }
+
+  Ok(BOARD.get_or_init(|| board))
+}
+
+/// Gets the definition of the board this is running on.
+///
+/// Panics if the definition has not yet been loaded with `load`.
+pub fn definition() -> &'static BoardDefinition {
+  BOARD.get().expect("board definition was not loaded")
+}
+
+/// Gets what each input of an ADC measures, in the order they are polled.
+pub fn inputs(kind: ADCKind) -> &'static [InputWiring] {
+  // every required ADC is checked for when the definition is loaded
+  &definition()
+    .adc(kind)
+    .expect("ADC not in board definition")
+    .inputs
+}
+
+/// Determines if any ADC input is wired to the given measurement.
+pub fn senses(measurement: &str) -> bool {
+  definition()
+    .adcs
+    .iter()
+    .flat_map(|adc| &adc.inputs)
+    .any(|input| input.measurement == measurement)
+}
+
+/// Gets a pin as described by the board definition.
+pub fn pin(pin: GpioPin) -> Pin {
+  GPIO_CONTROLLERS[pin.controller].get_pin(pin.pin)
+}
+
+/// Gets a named output from the board definition.
+pub fn output(name: &str) -> Pin {
+  // every required output is checked for when the definition is loaded
+  pin(definition().output(name).expect("output not in board definition"))
+}
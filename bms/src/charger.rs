@@ -0,0 +1,398 @@
+use crate::command::{charger_enabled, disable_charger, enable_charger};
+use common::comm::bms::{Bms, ChargeFault, ChargeStatus, ChargerState};
+use jeflog::{fail, pass, warn};
+use std::time::{Duration, Instant};
+
+/// Below this battery voltage the pack is too deeply discharged to charge.
+const MIN_START_VOLTAGE: f64 = 15.0;
+
+/// Above this battery voltage a charge is not started at all.
+const MAX_START_VOLTAGE: f64 = 24.6;
+
+/// Below this battery voltage the pack is charged at a reduced current.
+const PRECHARGE_VOLTAGE: f64 = 18.0;
+
+/// Above this battery voltage the charger is holding the charge voltage.
+const CONSTANT_VOLTAGE: f64 = 24.9;
+
+/// If the battery voltage ever rises above this, charging stops immediately.
+const MAX_VOLTAGE: f64 = 25.4;
+
+/// Once holding the charge voltage, the charge terminates when the charger
+/// current falls below this.
+const TAPER_CURRENT: f64 = 0.2;
+
+/// How long the taper current must be held before terminating, so that noise
+/// on the current sense doesn't end the charge early.
+const TAPER_TIME: Duration = Duration::from_secs(10);
+
+/// The longest precharge may take to bring the battery up.
+const MAX_PRECHARGE_TIME: Duration = Duration::from_secs(30 * 60);
+
+/// The longest a whole charge may take.
+const MAX_CHARGE_TIME: Duration = Duration::from_secs(4 * 60 * 60);
+
+/// Controls the charger, moving through precharge, constant current and
+/// constant voltage based on the battery voltage, and terminating on a taper
+/// current, a timeout, or a voltage limit.
+///
+/// The only control the BMS has over the charger is its enable line. The
+/// charger regulates its own current and voltage, so the phases are what the
+/// battery voltage says it should be doing, and are used to pick which limits
+/// apply. The BMS has no pack temperature sense, so only voltage limits apply.
+///
+/// The transitions themselves never touch the GPIO. `update` drives the enable
+/// line from whether a charge is running once they have been made.
+#[derive(Default)]
+pub struct Charger {
+  state: ChargerState,
+  requested: bool,
+  // none unless a charge is running
+  started: Option<Instant>,
+  // how long the most recent charge ran, once it has ended
+  elapsed: f64,
+  precharge_started: Option<Instant>,
+  tapered_since: Option<Instant>,
+  last_update: Option<Instant>,
+  charged_ah: f64,
+}
+
+impl Charger {
+  /// Requests a charge to start. It only starts on the next update, once the
+  /// battery voltage is known to be within limits.
+  pub fn start(&mut self) {
+    self.requested = true;
+  }
+
+  /// Stops charging and returns to idle, clearing any charge fault.
+  pub fn stop(&mut self) {
+    self.halt(Instant::now());
+    disable_charger();
+  }
+
+  /// Advances the controller using the latest readings, filling in the charge
+  /// status of `state`.
+  pub fn update(&mut self, state: &mut Bms) {
+    let now = Instant::now();
+    let was_charging = self.is_charging();
+
+    self.advance(state, now);
+
+    match (was_charging, self.is_charging()) {
+      (false, true) => enable_charger(),
+      (true, false) => disable_charger(),
+      _ => {}
+    }
+
+    state.charge = self.status(charger_enabled(), now);
+  }
+
+  // returns to idle as `stop` does, leaving the enable line to the caller
+  fn halt(&mut self, now: Instant) {
+    if self.is_charging() {
+      pass!("Charge stopped after {:.3} Ah.", self.charged_ah);
+    }
+
+    self.requested = false;
+    self.end(ChargerState::Idle, now);
+  }
+
+  // makes whatever transitions the readings in `state`, taken at `now`, call
+  // for
+  fn advance(&mut self, state: &Bms, now: Instant) {
+    let voltage = state.battery_bus.voltage;
+    let current = state.charger;
+
+    if self.requested {
+      self.requested = false;
+      self.begin(voltage, now);
+    }
+
+    if self.is_charging() {
+      let dt = self
+        .last_update
+        .map_or(0.0, |last| (now - last).as_secs_f64());
+
+      if current.is_finite() {
+        self.charged_ah += current.max(0.0) * dt / 3600.0;
+      }
+
+      self.step(state, now);
+    }
+
+    self.last_update = Some(now);
+  }
+
+  fn status(&self, enabled: bool, now: Instant) -> ChargeStatus {
+    ChargeStatus {
+      state: self.state,
+      enabled,
+      elapsed: self
+        .started
+        .map_or(self.elapsed, |started| (now - started).as_secs_f64()),
+      charged_ah: self.charged_ah,
+    }
+  }
+
+  fn begin(&mut self, voltage: f64, now: Instant) {
+    if self.is_charging() {
+      return;
+    }
+
+    if !voltage.is_finite() || voltage > MAX_START_VOLTAGE {
+      warn!("Refusing to charge with the battery at {voltage:.2} V.");
+      self.state = ChargerState::Idle;
+      return;
+    }
+
+    self.started = Some(now);
+    self.precharge_started = None;
+    self.tapered_since = None;
+    self.charged_ah = 0.0;
+
+    if voltage < MIN_START_VOLTAGE {
+      self.fault(ChargeFault::Undervoltage, voltage, now);
+      return;
+    }
+
+    pass!("Starting charge with the battery at {voltage:.2} V.");
+    self.state = ChargerState::Precharge;
+  }
+
+  fn step(&mut self, state: &Bms, now: Instant) {
+    let voltage = state.battery_bus.voltage;
+    let current = state.charger;
+
+    if state.faults.charger_overcurrent {
+      self.fault(ChargeFault::Protection, voltage, now);
+      return;
+    }
+
+    // a failed reading says nothing about how the charge is going
+    if !voltage.is_finite() || !current.is_finite() {
+      return;
+    }
+
+    if voltage > MAX_VOLTAGE {
+      self.fault(ChargeFault::Overvoltage, voltage, now);
+      return;
+    }
+
+    let started = *self.started.get_or_insert(now);
+
+    if now - started > MAX_CHARGE_TIME {
+      self.fault(ChargeFault::Timeout, voltage, now);
+      return;
+    }
+
+    self.state = match self.state {
+      ChargerState::Precharge if voltage < PRECHARGE_VOLTAGE => {
+        let since = *self.precharge_started.get_or_insert(now);
+
+        if now - since > MAX_PRECHARGE_TIME {
+          self.fault(ChargeFault::PrechargeTimeout, voltage, now);
+          return;
+        }
+
+        ChargerState::Precharge
+      }
+      ChargerState::Precharge | ChargerState::ConstantCurrent
+        if voltage < CONSTANT_VOLTAGE =>
+      {
+        ChargerState::ConstantCurrent
+      }
+      ChargerState::Precharge
+      | ChargerState::ConstantCurrent
+      | ChargerState::ConstantVoltage => {
+        if current >= TAPER_CURRENT {
+          self.tapered_since = None;
+          ChargerState::ConstantVoltage
+        } else if now - *self.tapered_since.get_or_insert(now) < TAPER_TIME {
+          ChargerState::ConstantVoltage
+        } else {
+          pass!(
+            "Charge done at {voltage:.2} V after {:.3} Ah.",
+            self.charged_ah
+          );
+          self.end(ChargerState::Done, now);
+          return;
+        }
+      }
+      other => other,
+    };
+  }
+
+  fn fault(&mut self, fault: ChargeFault, voltage: f64, now: Instant) {
+    fail!("Charge stopped on {fault:?} with the battery at {voltage:.2} V.");
+    self.end(ChargerState::Fault(fault), now);
+  }
+
+  // leaves the charge in `state`, keeping how long it ran for the status
+  fn end(&mut self, state: ChargerState, now: Instant) {
+    if let Some(started) = self.started.take() {
+      self.elapsed = (now - started).as_secs_f64();
+    }
+
+    self.state = state;
+  }
+
+  fn is_charging(&self) -> bool {
+    matches!(
+      self.state,
+      ChargerState::Precharge
+        | ChargerState::ConstantCurrent
+        | ChargerState::ConstantVoltage
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SECOND: Duration = Duration::from_secs(1);
+
+  fn reading(voltage: f64, current: f64) -> Bms {
+    let mut state = Bms::default();
+    state.battery_bus.voltage = voltage;
+    state.charger = current;
+    state
+  }
+
+  // a charger asked to start with the battery at `voltage`
+  fn started(voltage: f64, now: Instant) -> Charger {
+    let mut charger = Charger::default();
+    charger.start();
+    charger.advance(&reading(voltage, 1.0), now);
+    charger
+  }
+
+  #[test]
+  fn refuses_to_start_outside_the_voltage_limits() {
+    let now = Instant::now();
+
+    for voltage in [MAX_START_VOLTAGE + 0.1, f64::NAN] {
+      let charger = started(voltage, now);
+      assert_eq!(charger.state, ChargerState::Idle);
+      assert!(!charger.is_charging());
+    }
+
+    let charger = started(MIN_START_VOLTAGE - 0.1, now);
+    assert_eq!(
+      charger.state,
+      ChargerState::Fault(ChargeFault::Undervoltage)
+    );
+    assert!(!charger.is_charging());
+  }
+
+  #[test]
+  fn moves_through_each_phase_and_terminates_on_taper() {
+    let start = Instant::now();
+    let mut charger = started(16.0, start);
+    assert_eq!(charger.state, ChargerState::Precharge);
+
+    charger.advance(&reading(20.0, 1.0), start + SECOND);
+    assert_eq!(charger.state, ChargerState::ConstantCurrent);
+
+    charger.advance(&reading(25.0, 1.0), start + 2 * SECOND);
+    assert_eq!(charger.state, ChargerState::ConstantVoltage);
+
+    // tapered, but not yet for long enough
+    let tapered = start + 3 * SECOND;
+    charger.advance(&reading(25.0, 0.1), tapered);
+    charger.advance(&reading(25.0, 0.1), tapered + TAPER_TIME - SECOND);
+    assert_eq!(charger.state, ChargerState::ConstantVoltage);
+
+    charger.advance(&reading(25.0, 0.1), tapered + TAPER_TIME);
+    assert_eq!(charger.state, ChargerState::Done);
+    assert!(!charger.is_charging());
+    assert!(charger.charged_ah > 0.0);
+  }
+
+  #[test]
+  fn a_current_spike_restarts_the_taper_time() {
+    let start = Instant::now();
+    let mut charger = started(24.0, start);
+
+    charger.advance(&reading(25.0, 0.1), start);
+    charger.advance(&reading(25.0, 0.5), start + 5 * SECOND);
+    charger.advance(&reading(25.0, 0.1), start + 6 * SECOND);
+    charger.advance(&reading(25.0, 0.1), start + TAPER_TIME + SECOND);
+    assert_eq!(charger.state, ChargerState::ConstantVoltage);
+
+    charger.advance(&reading(25.0, 0.1), start + TAPER_TIME + 6 * SECOND);
+    assert_eq!(charger.state, ChargerState::Done);
+  }
+
+  #[test]
+  fn faults_when_precharge_takes_too_long() {
+    let start = Instant::now();
+    let mut charger = started(16.0, start);
+
+    charger.advance(&reading(16.0, 1.0), start + MAX_PRECHARGE_TIME);
+    assert_eq!(charger.state, ChargerState::Precharge);
+
+    charger.advance(&reading(16.0, 1.0), start + MAX_PRECHARGE_TIME + SECOND);
+    assert_eq!(
+      charger.state,
+      ChargerState::Fault(ChargeFault::PrechargeTimeout)
+    );
+  }
+
+  #[test]
+  fn faults_when_the_charge_takes_too_long() {
+    let start = Instant::now();
+    let mut charger = started(20.0, start);
+
+    charger.advance(&reading(20.0, 1.0), start + MAX_CHARGE_TIME);
+    assert_eq!(charger.state, ChargerState::ConstantCurrent);
+
+    charger.advance(&reading(20.0, 1.0), start + MAX_CHARGE_TIME + SECOND);
+    assert_eq!(charger.state, ChargerState::Fault(ChargeFault::Timeout));
+  }
+
+  #[test]
+  fn faults_on_overvoltage_while_charging() {
+    let start = Instant::now();
+    let mut charger = started(24.0, start);
+
+    charger.advance(&reading(MAX_VOLTAGE + 0.1, 1.0), start + SECOND);
+    assert_eq!(charger.state, ChargerState::Fault(ChargeFault::Overvoltage));
+  }
+
+  #[test]
+  fn faults_when_the_charger_protection_trips() {
+    let start = Instant::now();
+    let mut charger = started(20.0, start);
+
+    let mut state = reading(20.0, 5.0);
+    state.faults.charger_overcurrent = true;
+    charger.advance(&state, start + SECOND);
+
+    assert_eq!(charger.state, ChargerState::Fault(ChargeFault::Protection));
+  }
+
+  #[test]
+  fn unread_measurements_change_nothing() {
+    let start = Instant::now();
+    let mut charger = started(20.0, start);
+
+    charger.advance(&reading(f64::NAN, f64::NAN), start + SECOND);
+    assert_eq!(charger.state, ChargerState::ConstantCurrent);
+  }
+
+  #[test]
+  fn halting_returns_to_idle_and_keeps_the_elapsed_time() {
+    let start = Instant::now();
+    let mut charger = started(20.0, start);
+
+    charger.advance(&reading(20.0, 1.0), start + SECOND);
+    charger.halt(start + 2 * SECOND);
+
+    assert_eq!(charger.state, ChargerState::Idle);
+
+    let status = charger.status(false, start + 10 * SECOND);
+    assert_eq!(status.elapsed, 2.0);
+    assert!(!status.enabled);
+  }
+}
@@ -1,3 +1,4 @@
+use crate::board;
 use common::comm::gpio::{
   Gpio,
   Pin,
@@ -34,60 +35,60 @@ pub fn init_gpio() {
 }
 
 pub fn get_cs_mappings() -> HashMap<ADCKind, Pin> {
-  let mut vbat_umb_charge_chip_select: Pin = GPIO_CONTROLLERS[0].get_pin(30);
-  vbat_umb_charge_chip_select.mode(Output);
-  let mut sam_and_5v_chip_select: Pin = GPIO_CONTROLLERS[0].get_pin(31);
-  sam_and_5v_chip_select.mode(Output);
-
-  HashMap::from([
-    (ADCKind::VBatUmbCharge, vbat_umb_charge_chip_select),
-    (ADCKind::SamAnd5V, sam_and_5v_chip_select),
-  ])
+  board::definition()
+    .adcs
+    .iter()
+    .map(|adc| {
+      let mut chip_select = board::pin(adc.chip_select);
+      chip_select.mode(Output);
+      (adc.kind, chip_select)
+    })
+    .collect()
 }
 
 pub fn enable_battery_power() {
-  // P8 GPIO 36 Pin 69
-  let mut pin = GPIO_CONTROLLERS[1].get_pin(4);
+  let mut pin = board::output("battery_enable");
   pin.mode(Output);
   pin.digital_write(High);
 }
 
 pub fn disable_battery_power() {
-  // P8 GPIO 36 Pin 69
-  let mut pin = GPIO_CONTROLLERS[1].get_pin(4);
+  let mut pin = board::output("battery_enable");
   pin.mode(Output);
   pin.digital_write(Low);
 }
 
 pub fn enable_sam_power() {
-  // P8 GPIO 22 Pin 65
-  let mut pin = GPIO_CONTROLLERS[0].get_pin(22);
+  let mut pin = board::output("sam_enable");
   pin.mode(Output);
   pin.digital_write(High);
 }
 
 pub fn disable_sam_power() {
-  // P8 GPIO 22 Pin 65
-  let mut pin = GPIO_CONTROLLERS[0].get_pin(22);
+  let mut pin = board::output("sam_enable");
   pin.mode(Output);
   pin.digital_write(Low);
 }
 
 pub fn enable_charger() {
-  let mut pin = GPIO_CONTROLLERS[2].get_pin(25);
+  let mut pin = board::output("charge_enable");
   pin.mode(Output);
   pin.digital_write(High);
 }
 
 pub fn disable_charger() {
-  let mut pin = GPIO_CONTROLLERS[2].get_pin(25);
+  let mut pin = board::output("charge_enable");
   pin.mode(Output);
   pin.digital_write(Low);
 }
 
+pub fn charger_enabled() -> bool {
+  board::output("charge_enable").output_value() == High
+}
+
 // The delays are made from the BMS hardware team for safing the system
 pub fn estop_init() {
-  let mut pin = GPIO_CONTROLLERS[2].get_pin(1);
+  let mut pin = board::output("estop_reset");
   pin.mode(Output);
   pin.digital_write(High);
   thread::sleep(Duration::from_millis(5));
@@ -99,8 +100,7 @@ pub fn estop_init() {
 // need to confirm that pin actually needs to be toggled and for how long
 // is estop_init all that is necessary?
 pub fn estop_reset() {
-  // P8 GPIO 65 Pin 64
-  let mut pin = GPIO_CONTROLLERS[2].get_pin(1);
+  let mut pin = board::output("estop_reset");
   pin.mode(Output);
   pin.digital_write(High);
   thread::sleep(Duration::from_millis(5));
@@ -109,46 +109,25 @@ pub fn estop_reset() {
 
 // not a command that can be currently sent from FC
 pub fn set_estop_low() {
-  let mut pin = GPIO_CONTROLLERS[2].get_pin(1);
+  let mut pin = board::output("estop_reset");
   pin.mode(Output);
   pin.digital_write(Low);
 }
 
 // not a command that can be currently sent from FC
 pub fn reco_enable(channel: u32) {
-  match channel {
-    1 => {
-      // P8 GPIO 68 Pin 56
-      let mut pin = GPIO_CONTROLLERS[2].get_pin(4);
-      pin.mode(Output);
-      pin.digital_write(High);
-    }
-    2 => {
-      // P8 GPIO 67 Pin 54
-      let mut pin = GPIO_CONTROLLERS[2].get_pin(3);
-      pin.mode(Output);
-      pin.digital_write(High);
-    }
-    3 => {
-      // P8 GPIO 66 Pin 53
-      let mut pin = GPIO_CONTROLLERS[2].get_pin(2);
-      pin.mode(Output);
-      pin.digital_write(High);
-    }
-    _ => println!("Error"),
+  if !(1..=3).contains(&channel) {
+    println!("Error");
+    return;
   }
+
+  let mut pin = board::output(&format!("reco_{channel}"));
+  pin.mode(Output);
+  pin.digital_write(High);
 }
 
 pub fn execute(command: Command) {
   match command {
-    Command::Charge(x) => {
-      if x {
-        enable_charger();
-      } else {
-        disable_charger();
-      }
-    }
-
     Command::BatteryLoadSwitch(x) => {
       if x {
         enable_battery_power();
@@ -169,5 +148,15 @@ pub fn execute(command: Command) {
       // explore what actually needs to happen here
       estop_init();
     }
+
+    // handled by the monitor, which owns the ADCs, protections and charge
+    // controller
+    Command::Charge(_)
+    | Command::SelfTest
+    | Command::SetProtectionLimit(_)
+    | Command::ClearFaults => {}
+
+    // handled by the link to the flight computer, which resends events
+    Command::AcknowledgeEStop(_) => {}
   }
 }
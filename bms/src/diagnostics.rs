@@ -0,0 +1,98 @@
+use crate::board;
+use ads114s06::ADC;
+use common::comm::{
+  bms::Bms,
+  diagnostics::{
+    AdcDiagnostics,
+    OutputDiagnostics,
+    RailDiagnostics,
+    SelfTestReport,
+  },
+  gpio::PinValue::{High, Low},
+};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// register addresses on the ADS114S06
+const ID_REGISTER: usize = 0x00;
+
+// conversions run at 4000 SPS, so DRDY should never take this long
+const DRDY_TIMEOUT: Duration = Duration::from_millis(10);
+
+// how far a rail may stray from nominal before it is flagged
+const RAIL_TOLERANCE: f64 = 0.1;
+
+/// Reads the ID and status registers and DRDY health of every ADC, checks the
+/// latest rail measurements, and reads back every load switch without changing
+/// its state.
+pub fn self_test(adcs: &mut [ADC], latest: &Bms) -> SelfTestReport {
+  let mut report = SelfTestReport {
+    timestamp: SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|duration| duration.as_secs_f64())
+      .unwrap_or(0.0),
+    adcs: Vec::with_capacity(adcs.len()),
+    rails: Vec::new(),
+    outputs: Vec::new(),
+  };
+
+  for adc in adcs.iter_mut() {
+    let start = Instant::now();
+    let mut data_ready = false;
+
+    while Instant::now() - start < DRDY_TIMEOUT {
+      if adc.check_drdy() == Low {
+        data_ready = true;
+        break;
+      }
+    }
+
+    let id = adc.spi_read_reg(ID_REGISTER).ok();
+    let status = adc.get_status_reg().ok();
+
+    report.adcs.push(AdcDiagnostics {
+      name: format!("{:?}", adc.kind),
+      // an ID of all zeros or all ones means nothing answered on the bus
+      id: id.filter(|id| *id != 0x00 && *id != 0xFF),
+      status,
+      data_ready: Some(data_ready),
+      // counted by the driver since startup, the same as on the SAM
+      spi_errors: adc.spi_errors,
+    });
+  }
+
+  let rails = [
+    ("five_volt_rail", latest.five_volt_rail.voltage, Some(5.0)),
+    ("battery_bus", latest.battery_bus.voltage, None),
+    ("umbilical_bus", latest.umbilical_bus.voltage, None),
+    ("sam_power_bus", latest.sam_power_bus.voltage, None),
+  ];
+
+  for (name, voltage, nominal) in rails {
+    let in_range = match nominal {
+      Some(nominal) => (voltage - nominal).abs() <= nominal * RAIL_TOLERANCE,
+      None => voltage.is_finite(),
+    };
+
+    report.rails.push(RailDiagnostics {
+      name: name.to_string(),
+      voltage,
+      nominal,
+      in_range,
+    });
+  }
+
+  let outputs =
+    ["battery_enable", "sam_enable", "charge_enable", "estop_reset"];
+
+  for name in outputs {
+    let pin = board::output(name);
+
+    report.outputs.push(OutputDiagnostics {
+      name: name.to_string(),
+      commanded: pin.output_value() == High,
+      measured: pin.digital_read() == High,
+    });
+  }
+
+  report
+}
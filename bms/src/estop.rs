@@ -0,0 +1,411 @@
+use common::comm::bms::{Bms, EStopEvent, EStopEventKind, EStopPath};
+use jeflog::{fail, pass, warn};
+use std::{
+  collections::VecDeque,
+  time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// Below this voltage the e-stop loop is considered open.
+const E_STOP_THRESHOLD: f64 = 5.0;
+
+/// Above this voltage the remove-before-flight tag is considered inserted.
+const RBF_TAG_THRESHOLD: f64 = 5.0;
+
+/// How long a sense line must hold a new state before it is believed.
+const DEBOUNCE: Duration = Duration::from_millis(50);
+
+/// How long after a commanded reset the e-stop loop closing is attributed to
+/// that command rather than to the loop itself.
+const COMMANDED_RESET_WINDOW: Duration = Duration::from_secs(1);
+
+/// How long to wait for the flight computer to acknowledge an event before
+/// sending it again.
+const RESEND_PERIOD: Duration = Duration::from_millis(100);
+
+/// The most events held until they are acknowledged. The oldest are dropped
+/// first, since the latest states are in every datapoint anyway.
+const MAX_UNACKNOWLEDGED: usize = 64;
+
+/// Debounces a single digital state read from an analog sense line.
+struct Debounced {
+  // none until the first reading
+  state: Option<bool>,
+  candidate: Option<(bool, Instant)>,
+}
+
+impl Debounced {
+  const fn new() -> Self {
+    Debounced {
+      state: None,
+      candidate: None,
+    }
+  }
+
+  /// Feeds a new raw reading, returning the new debounced state if it changed.
+  /// The first reading is taken as-is and never reported as a change.
+  fn update(&mut self, raw: bool, now: Instant) -> Option<bool> {
+    let Some(state) = self.state else {
+      self.state = Some(raw);
+      return None;
+    };
+
+    if raw == state {
+      self.candidate = None;
+      return None;
+    }
+
+    let since = match self.candidate {
+      Some((candidate, since)) if candidate == raw => since,
+      _ => {
+        self.candidate = Some((raw, now));
+        now
+      }
+    };
+
+    if now - since < DEBOUNCE {
+      return None;
+    }
+
+    self.state = Some(raw);
+    self.candidate = None;
+    Some(raw)
+  }
+
+  fn state(&self) -> Option<bool> {
+    self.state
+  }
+}
+
+/// Watches the e-stop and remove-before-flight sense lines, reporting their
+/// debounced states and every change of either.
+///
+/// Only lines wired in the board definition (as the `e_stop` and `rbf_tag`
+/// measurements) are watched. A line that isn't reads as NaN, so its state is
+/// reported as unknown and it sends no events.
+pub struct EStopMonitor {
+  e_stop: Debounced,
+  rbf_tag: Debounced,
+  reset_commanded_at: Option<Instant>,
+  sequence: u32,
+}
+
+impl Default for EStopMonitor {
+  fn default() -> Self {
+    EStopMonitor {
+      e_stop: Debounced::new(),
+      rbf_tag: Debounced::new(),
+      reset_commanded_at: None,
+      sequence: 0,
+    }
+  }
+}
+
+impl EStopMonitor {
+  /// Notes that the flight computer commanded an e-stop reset at `now`, so
+  /// that the loop closing shortly after is attributed to it.
+  pub fn reset_commanded(&mut self, now: Instant) {
+    self.reset_commanded_at = Some(now);
+  }
+
+  /// Updates the debounced states from the raw voltages in `state`, read at
+  /// `now`, filling in its logical states and returning any events that
+  /// occurred.
+  pub fn update(&mut self, state: &mut Bms, now: Instant) -> Vec<EStopEvent> {
+    let mut events = Vec::new();
+
+    // a reading that failed says nothing about the state of the line
+    if state.e_stop.is_finite() {
+      let open = state.e_stop < E_STOP_THRESHOLD;
+
+      if let Some(open) = self.e_stop.update(open, now) {
+        let (kind, path) = if open {
+          (EStopEventKind::Tripped, EStopPath::Loop)
+        } else if self
+          .reset_commanded_at
+          .is_some_and(|at| now - at <= COMMANDED_RESET_WINDOW)
+        {
+          (EStopEventKind::Reset, EStopPath::Command)
+        } else {
+          (EStopEventKind::Reset, EStopPath::Loop)
+        };
+
+        events.push(self.event(kind, path, state));
+      }
+    }
+
+    if state.rbf_tag.is_finite() {
+      let inserted = state.rbf_tag > RBF_TAG_THRESHOLD;
+
+      if let Some(inserted) = self.rbf_tag.update(inserted, now) {
+        let kind = if inserted {
+          EStopEventKind::Inserted
+        } else {
+          EStopEventKind::Removed
+        };
+
+        events.push(self.event(kind, EStopPath::RbfTag, state));
+      }
+    }
+
+    state.e_stop_tripped = self.e_stop.state();
+    state.rbf_tag_inserted = self.rbf_tag.state();
+
+    for event in &events {
+      match event.kind {
+        EStopEventKind::Tripped => {
+          fail!(
+            "E-stop tripped by {:?} at {:.3}.",
+            event.path,
+            event.timestamp
+          );
+        }
+        EStopEventKind::Reset => {
+          pass!(
+            "E-stop reset by {:?} at {:.3}.",
+            event.path,
+            event.timestamp
+          );
+        }
+        EStopEventKind::Inserted => {
+          pass!("RBF tag inserted at {:.3}.", event.timestamp);
+        }
+        EStopEventKind::Removed => {
+          warn!("RBF tag removed at {:.3}.", event.timestamp);
+        }
+      }
+    }
+
+    events
+  }
+
+  fn event(
+    &mut self,
+    kind: EStopEventKind,
+    path: EStopPath,
+    state: &Bms,
+  ) -> EStopEvent {
+    self.sequence = self.sequence.wrapping_add(1);
+
+    EStopEvent {
+      sequence: self.sequence,
+      kind,
+      path,
+      e_stop: state.e_stop,
+      rbf_tag: state.rbf_tag,
+      timestamp: SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs_f64())
+        .unwrap_or(0.0),
+    }
+  }
+}
+
+/// Holds e-stop events until the flight computer acknowledges them. Only the
+/// oldest is sent at a time, again every `RESEND_PERIOD` until acknowledged,
+/// so that the flight computer sees every event once and in order even when
+/// a datagram is dropped or comms are lost.
+#[derive(Default)]
+pub struct EStopRelay {
+  unacknowledged: VecDeque<EStopEvent>,
+  last_sent: Option<Instant>,
+}
+
+impl EStopRelay {
+  /// Queues an event to be sent.
+  pub fn push(&mut self, event: EStopEvent) {
+    if self.unacknowledged.len() == MAX_UNACKNOWLEDGED {
+      warn!("Too many unacknowledged e-stop events, dropping the oldest.");
+      self.unacknowledged.pop_front();
+      self.last_sent = None;
+    }
+
+    self.unacknowledged.push_back(event);
+  }
+
+  /// Stops sending the event with the given sequence number.
+  pub fn acknowledge(&mut self, sequence: u32) {
+    let front = self.unacknowledged.front();
+
+    if front.is_some_and(|event| event.sequence == sequence) {
+      self.unacknowledged.pop_front();
+      self.last_sent = None;
+    }
+  }
+
+  /// Gets the event which is due to be sent, if one is.
+  pub fn due(&mut self) -> Option<EStopEvent> {
+    let event = *self.unacknowledged.front()?;
+    let now = Instant::now();
+
+    let recently_sent = self.last_sent.map(|last| now - last);
+
+    if recently_sent.is_some_and(|elapsed| elapsed < RESEND_PERIOD) {
+      return None;
+    }
+
+    self.last_sent = Some(now);
+    Some(event)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn event(sequence: u32) -> EStopEvent {
+    EStopEvent {
+      sequence,
+      kind: EStopEventKind::Tripped,
+      path: EStopPath::Loop,
+      e_stop: 0.0,
+      rbf_tag: 0.0,
+      timestamp: 0.0,
+    }
+  }
+
+  #[test]
+  fn relay_resends_until_acknowledged() {
+    let mut relay = EStopRelay::default();
+    relay.push(event(1));
+    relay.push(event(2));
+
+    assert_eq!(relay.due().map(|event| event.sequence), Some(1));
+    assert_eq!(relay.due(), None);
+
+    // an acknowledgement of anything but the oldest changes nothing
+    relay.acknowledge(2);
+    relay.last_sent = Some(Instant::now() - RESEND_PERIOD);
+    assert_eq!(relay.due().map(|event| event.sequence), Some(1));
+
+    relay.acknowledge(1);
+    assert_eq!(relay.due().map(|event| event.sequence), Some(2));
+
+    relay.acknowledge(2);
+    assert_eq!(relay.due(), None);
+  }
+
+  #[test]
+  fn relay_drops_the_oldest_when_full() {
+    let mut relay = EStopRelay::default();
+
+    for sequence in 0..=MAX_UNACKNOWLEDGED as u32 {
+      relay.push(event(sequence));
+    }
+
+    assert_eq!(relay.unacknowledged.len(), MAX_UNACKNOWLEDGED);
+    assert_eq!(relay.due().map(|event| event.sequence), Some(1));
+  }
+
+  // feeds a reading until it has been held for the debounce time, returning
+  // the events it caused
+  fn settle(
+    monitor: &mut EStopMonitor,
+    state: &mut Bms,
+    now: Instant,
+  ) -> Vec<EStopEvent> {
+    assert!(monitor.update(state, now).is_empty());
+    monitor.update(state, now + DEBOUNCE)
+  }
+
+  fn sense(e_stop: f64, rbf_tag: f64) -> Bms {
+    Bms {
+      e_stop,
+      rbf_tag,
+      ..Bms::default()
+    }
+  }
+
+  #[test]
+  fn unwired_lines_are_unknown() {
+    let mut monitor = EStopMonitor::default();
+    let mut state = sense(f64::NAN, f64::NAN);
+
+    assert!(monitor.update(&mut state, Instant::now()).is_empty());
+    assert_eq!(state.e_stop_tripped, None);
+    assert_eq!(state.rbf_tag_inserted, None);
+  }
+
+  #[test]
+  fn e_stop_loop_trips_and_resets() {
+    let mut monitor = EStopMonitor::default();
+    let mut state = sense(24.0, f64::NAN);
+    let start = Instant::now();
+
+    // the first reading is the starting state, not a change
+    assert!(monitor.update(&mut state, start).is_empty());
+    assert_eq!(state.e_stop_tripped, Some(false));
+
+    // a blip shorter than the debounce time is ignored
+    state.e_stop = 0.0;
+    monitor.update(&mut state, start);
+    state.e_stop = 24.0;
+    assert!(monitor.update(&mut state, start + DEBOUNCE).is_empty());
+
+    state.e_stop = 0.0;
+    let events = settle(&mut monitor, &mut state, start + 2 * DEBOUNCE);
+
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].kind, EStopEventKind::Tripped);
+    assert_eq!(events[0].path, EStopPath::Loop);
+    assert_eq!(state.e_stop_tripped, Some(true));
+
+    // closed again without a command, so the loop reset it
+    state.e_stop = 24.0;
+    let events = settle(&mut monitor, &mut state, start + 4 * DEBOUNCE);
+
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].kind, EStopEventKind::Reset);
+    assert_eq!(events[0].path, EStopPath::Loop);
+    assert_eq!(events[0].sequence, 2);
+    assert_eq!(state.e_stop_tripped, Some(false));
+  }
+
+  #[test]
+  fn e_stop_reset_is_attributed_to_a_recent_command() {
+    let mut monitor = EStopMonitor::default();
+    let mut state = sense(0.0, f64::NAN);
+    let start = Instant::now();
+
+    monitor.update(&mut state, start);
+    assert_eq!(state.e_stop_tripped, Some(true));
+
+    monitor.reset_commanded(start);
+    state.e_stop = 24.0;
+    let events = settle(&mut monitor, &mut state, start + DEBOUNCE);
+
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].kind, EStopEventKind::Reset);
+    assert_eq!(events[0].path, EStopPath::Command);
+
+    // a command too long before the loop closes isn't what closed it
+    state.e_stop = 0.0;
+    settle(&mut monitor, &mut state, start + 3 * DEBOUNCE);
+
+    let late = start + COMMANDED_RESET_WINDOW + 6 * DEBOUNCE;
+    monitor.reset_commanded(start + 4 * DEBOUNCE);
+    state.e_stop = 24.0;
+    let events = settle(&mut monitor, &mut state, late);
+
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].path, EStopPath::Loop);
+  }
+
+  #[test]
+  fn rbf_tag_has_its_own_events() {
+    let mut monitor = EStopMonitor::default();
+    let mut state = sense(f64::NAN, 0.0);
+    let start = Instant::now();
+
+    assert!(monitor.update(&mut state, start).is_empty());
+
+    state.rbf_tag = 10.0;
+    let events = settle(&mut monitor, &mut state, start + DEBOUNCE);
+
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].kind, EStopEventKind::Inserted);
+    assert_eq!(events[0].path, EStopPath::RbfTag);
+    assert_eq!(events[0].sequence, 1);
+    assert_eq!(state.rbf_tag_inserted, Some(true));
+    assert_eq!(state.e_stop_tripped, None);
+  }
+}
@@ -1,9 +1,31 @@
 pub mod adc;
+pub mod board;
+pub mod charger;
 pub mod command;
 pub mod communication;
+pub mod diagnostics;
+pub mod estop;
+pub mod monitor;
+pub mod protection;
+pub mod soc;
 pub mod state;
 
+use jeflog::{fail, pass};
+use std::process;
+
 fn main() {
+  // nothing may be driven until we know which revision we are running on, so
+  // an unknown board exits before touching any GPIO
+  match board::load() {
+    Ok(board) => {
+      pass!("Loaded board definition for {}.", board.revision);
+    }
+    Err(error) => {
+      fail!("Could not identify this board: {error}. Exiting safely.");
+      process::exit(1);
+    }
+  }
+
   let mut state = state::State::Init;
 
   loop {
@@ -0,0 +1,253 @@
+use crate::{
+  adc::{init_adcs, poll_adcs, start_adcs},
+  board,
+  charger::Charger,
+  command::{execute, init_gpio},
+  diagnostics::self_test,
+  estop::EStopMonitor,
+  protection::{self, Protections},
+  soc::SocEstimator,
+};
+use ads114s06::ADC;
+use common::comm::{
+  bms::{Command, DataPoint, EStopEvent},
+  diagnostics::SelfTestReport,
+  ADCKind::{SamAnd5V, VBatUmbCharge},
+};
+use jeflog::{fail, pass, warn};
+use std::{
+  mem,
+  sync::{
+    mpsc::{self, Receiver, Sender},
+    Arc,
+    Condvar,
+    Mutex,
+  },
+  thread::{self, JoinHandle},
+  time::{Duration, Instant},
+};
+
+/// The most e-stop events held for the link while it looks for the flight
+/// computer, past which the oldest are dropped.
+const MAX_PENDING_EVENTS: usize = 64;
+
+/// What the link to the flight computer may ask of the monitor.
+pub enum Request {
+  /// Handles a command sent by the flight computer.
+  Command(Command),
+
+  /// Puts every output in its safe state, as when comms are lost.
+  Safe,
+}
+
+/// Everything the monitor has produced since it was last collected from.
+#[derive(Default)]
+pub struct Outbox {
+  /// The latest datapoint, if one was taken since the last collection.
+  pub datapoint: Option<DataPoint>,
+
+  /// Every e-stop event since the last collection, oldest first.
+  pub events: Vec<EStopEvent>,
+
+  /// Every self-test report since the last collection.
+  pub reports: Vec<SelfTestReport>,
+}
+
+/// A handle to the thread which samples the ADCs and enforces the
+/// protections, state of charge and charge controller. It runs regardless of
+/// whether the flight computer is connected, so that the battery is protected
+/// while looking for it or after comms are lost.
+pub struct Monitor {
+  requests: Sender<Request>,
+  outbox: Arc<(Mutex<Outbox>, Condvar)>,
+  thread: JoinHandle<()>,
+}
+
+impl Monitor {
+  /// Sets up the ADCs and starts monitoring them.
+  pub fn spawn() -> Self {
+    let (requests, receiver) = mpsc::channel();
+    let outbox = Arc::new((Mutex::new(Outbox::default()), Condvar::new()));
+    let shared = outbox.clone();
+
+    let thread = thread::spawn(move || run(receiver, &shared));
+
+    Monitor {
+      requests,
+      outbox,
+      thread,
+    }
+  }
+
+  /// Passes a request on to the monitor, which handles it before its next
+  /// reading.
+  pub fn request(&self, request: Request) {
+    if self.requests.send(request).is_err() {
+      fail!("Monitor is no longer running, dropping request.");
+    }
+  }
+
+  /// Waits up to `timeout` for a new datapoint, returning everything produced
+  /// since the last collection.
+  ///
+  /// Panics if the monitor has stopped, since nothing is protecting the
+  /// battery at that point.
+  pub fn collect(&self, timeout: Duration) -> Outbox {
+    assert!(!self.thread.is_finished(), "BMS monitor stopped");
+
+    let (outbox, fresh) = &*self.outbox;
+    let outbox = outbox.lock().unwrap();
+    let (mut outbox, _) = fresh
+      .wait_timeout_while(outbox, timeout, |outbox| outbox.datapoint.is_none())
+      .unwrap();
+
+    mem::take(&mut *outbox)
+  }
+}
+
+// the state only touched by the monitor thread
+struct Monitored {
+  adcs: Vec<ADC>,
+  protections: Protections,
+  soc: SocEstimator,
+  estop: EStopMonitor,
+  charger: Charger,
+}
+
+fn run(requests: Receiver<Request>, outbox: &(Mutex<Outbox>, Condvar)) {
+  let mut adcs: Vec<ADC> = [VBatUmbCharge, SamAnd5V]
+    .into_iter()
+    .map(|kind| {
+      // both are checked for when the board definition is loaded
+      let wiring = board::definition().adc(kind).unwrap();
+
+      ADC::new(
+        "/dev/spidev0.0",
+        board::pin(wiring.data_ready.unwrap()),
+        Some(board::pin(wiring.chip_select)),
+        kind,
+      )
+      .unwrap_or_else(|e| panic!("Failed to initialize {kind:?} ADC: {e:?}"))
+    })
+    .collect();
+
+  init_adcs(&mut adcs);
+
+  // tell the ADCs to start collecting data
+  start_adcs(&mut adcs);
+
+  // the sense lines are optional, as not every revision has them wired
+  for measurement in ["e_stop", "rbf_tag"] {
+    if !board::senses(measurement) {
+      warn!(
+        "The {} definition doesn't wire '{measurement}', so its state is \
+        unknown and no events are sent for it.",
+        board::definition().revision
+      );
+    }
+  }
+
+  let mut monitored = Monitored {
+    adcs,
+    protections: Protections::restore(),
+    soc: SocEstimator::restore(),
+    estop: EStopMonitor::default(),
+    charger: Charger::default(),
+  };
+
+  loop {
+    let mut self_test_requested = false;
+
+    for request in requests.try_iter() {
+      match request {
+        Request::Command(command) => {
+          self_test_requested |= monitored.handle(command);
+        }
+        Request::Safe => monitored.safe(),
+      }
+    }
+
+    let mut datapoint = poll_adcs(&mut monitored.adcs);
+    let now = Instant::now();
+
+    for action in monitored.protections.check(&datapoint.state, now) {
+      protection::act(action);
+    }
+
+    datapoint.state.faults = monitored.protections.faults();
+    monitored.soc.update(&mut datapoint.state);
+    monitored.charger.update(&mut datapoint.state);
+    let events = monitored.estop.update(&mut datapoint.state, now);
+
+    // the self-test needs the ADCs and a fresh reading, so it is run after
+    // polling rather than alongside the other commands
+    let report = self_test_requested.then(|| {
+      let report = self_test(&mut monitored.adcs, &datapoint.state);
+
+      if report.passed() {
+        pass!("Self-test passed.");
+      } else {
+        warn!("Self-test found faults: {report:#?}");
+      }
+
+      report
+    });
+
+    let (shared, fresh) = outbox;
+    let mut shared = shared.lock().unwrap();
+    shared.datapoint = Some(datapoint);
+    shared.events.extend(events);
+    shared.reports.extend(report);
+
+    let overflow = shared.events.len().saturating_sub(MAX_PENDING_EVENTS);
+    shared.events.drain(..overflow);
+
+    fresh.notify_all();
+  }
+}
+
+impl Monitored {
+  // handles a command from the flight computer, returning whether it asked
+  // for a self-test
+  fn handle(&mut self, command: Command) -> bool {
+    match command {
+      Command::SelfTest => return true,
+      Command::SetProtectionLimit(limit) => {
+        pass!("Setting protection limit: {limit:?}");
+        self.protections.set_limit(limit);
+      }
+      Command::Charge(false) => {
+        pass!("Stopping charge.");
+        self.charger.stop();
+      }
+      command @ Command::Charge(true) if self.protections.permits(&command) => {
+        pass!("Starting charge.");
+        self.charger.start();
+      }
+      Command::ClearFaults => {
+        pass!("Clearing latched faults.");
+        self.protections.clear_faults();
+      }
+      command if self.protections.permits(&command) => {
+        pass!("Executing command...");
+
+        if command == Command::ResetEstop {
+          self.estop.reset_commanded(Instant::now());
+        }
+
+        execute(command);
+      }
+      _ => {}
+    }
+
+    false
+  }
+
+  fn safe(&mut self) {
+    self.charger.stop();
+
+    // the ADC driver drives each chip select as it needs it, so turning them
+    // all off here doesn't disturb polling
+    init_gpio();
+  }
+}
@@ -0,0 +1,392 @@
+use crate::command::{disable_battery_power, disable_charger, disable_sam_power};
+use common::comm::bms::{
+  Bms,
+  Command,
+  Faults,
+  Protection,
+  ProtectionAction,
+  ProtectionLimit,
+};
+use jeflog::{fail, pass, warn};
+use std::{
+  collections::HashMap,
+  fs,
+  io,
+  path::Path,
+  time::{Duration, Instant},
+};
+
+/// Where limits set by the flight computer are kept across restarts.
+const LIMITS_PATH: &str = "/var/lib/bms/limits";
+
+/// The limits the BMS starts with before any are set by the flight computer.
+pub const DEFAULT_LIMITS: [ProtectionLimit; 5] = [
+  ProtectionLimit {
+    protection: Protection::BatteryUndervoltage,
+    enabled: true,
+    threshold: 20.0,
+    debounce_ms: 500,
+    action: ProtectionAction::OpenBatteryLoadSwitch,
+  },
+  ProtectionLimit {
+    protection: Protection::BatteryOvercurrent,
+    enabled: true,
+    threshold: 10.0,
+    debounce_ms: 100,
+    action: ProtectionAction::OpenBatteryLoadSwitch,
+  },
+  ProtectionLimit {
+    protection: Protection::UmbilicalOvercurrent,
+    enabled: true,
+    threshold: 10.0,
+    debounce_ms: 100,
+    action: ProtectionAction::LatchFault,
+  },
+  ProtectionLimit {
+    protection: Protection::SamOvercurrent,
+    enabled: true,
+    threshold: 8.0,
+    debounce_ms: 100,
+    action: ProtectionAction::OpenSamLoadSwitch,
+  },
+  ProtectionLimit {
+    protection: Protection::ChargerOvercurrent,
+    enabled: true,
+    threshold: 3.0,
+    debounce_ms: 200,
+    action: ProtectionAction::DisableCharger,
+  },
+];
+
+/// Checks every protection rule against the latest readings, acting on and
+/// latching any that trip.
+pub struct Protections {
+  limits: HashMap<Protection, ProtectionLimit>,
+
+  // when each currently-violated limit was first seen violated
+  violated_since: HashMap<Protection, Instant>,
+
+  faults: Faults,
+}
+
+impl Default for Protections {
+  fn default() -> Self {
+    Protections {
+      limits: DEFAULT_LIMITS
+        .into_iter()
+        .map(|limit| (limit.protection, limit))
+        .collect(),
+      violated_since: HashMap::new(),
+      faults: Faults::default(),
+    }
+  }
+}
+
+impl Protections {
+  /// Restores the limits last set by the flight computer from disk, using the
+  /// defaults for any that were never set.
+  pub fn restore() -> Self {
+    let mut protections = Protections::default();
+
+    match load(Path::new(LIMITS_PATH)) {
+      Ok(limits) => {
+        pass!("Restored {} protection limits.", limits.len());
+
+        for limit in limits {
+          protections.limits.insert(limit.protection, limit);
+        }
+      }
+      Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+      Err(error) => {
+        warn!("Could not restore protection limits ({error}), using defaults.");
+      }
+    }
+
+    protections
+  }
+
+  /// Replaces the limit of a single protection, persisting it so that it
+  /// survives a restart.
+  pub fn set_limit(&mut self, limit: ProtectionLimit) {
+    self.violated_since.remove(&limit.protection);
+    self.limits.insert(limit.protection, limit);
+
+    if let Err(error) = self.save() {
+      warn!("Could not persist protection limits ({error}).");
+    }
+  }
+
+  /// Clears every latched fault. Anything still violated trips again once its
+  /// debounce time passes.
+  pub fn clear_faults(&mut self) {
+    self.faults = Faults::default();
+    self.violated_since.clear();
+  }
+
+  /// Checks every limit against the given state, read at `now`, returning the
+  /// actions of any which tripped for the caller to take.
+  ///
+  /// A tripped limit is latched, so its action is returned once until the
+  /// faults are cleared.
+  pub fn check(&mut self, state: &Bms, now: Instant) -> Vec<ProtectionAction> {
+    let mut actions = Vec::new();
+
+    for limit in self.limits.values() {
+      let value = limit.protection.measure(state);
+
+      if !limit.enabled || !limit.protection.violated_by(value, limit.threshold)
+      {
+        self.violated_since.remove(&limit.protection);
+        continue;
+      }
+
+      let since = *self.violated_since.entry(limit.protection).or_insert(now);
+      let debounce = Duration::from_millis(limit.debounce_ms as u64);
+
+      if now - since < debounce || self.faults.get(limit.protection) {
+        continue;
+      }
+
+      fail!(
+        "{:?} tripped at {value:.3} (limit {}), taking action {:?}.",
+        limit.protection,
+        limit.threshold,
+        limit.action
+      );
+
+      actions.push(limit.action);
+      self.faults.set(limit.protection, true);
+    }
+
+    actions
+  }
+
+  /// The faults latched since they were last cleared, to be reported.
+  pub fn faults(&self) -> Faults {
+    self.faults
+  }
+
+  /// Determines if a command may be executed given the latched faults. A load
+  /// switch or charger opened by a protection stays open until cleared.
+  pub fn permits(&self, command: &Command) -> bool {
+    let blocked_by = match command {
+      Command::BatteryLoadSwitch(true) => {
+        ProtectionAction::OpenBatteryLoadSwitch
+      }
+      Command::SamLoadSwitch(true) => ProtectionAction::OpenSamLoadSwitch,
+      Command::Charge(true) => ProtectionAction::DisableCharger,
+      _ => return true,
+    };
+
+    let blocking = self.limits.values().find(|limit| {
+      limit.action == blocked_by && self.faults.get(limit.protection)
+    });
+
+    if let Some(limit) = blocking {
+      warn!(
+        "Refusing '{command}' while {:?} is latched. Clear faults first.",
+        limit.protection
+      );
+      return false;
+    }
+
+    true
+  }
+
+  fn save(&self) -> io::Result<()> {
+    let limits: Vec<ProtectionLimit> = self.limits.values().copied().collect();
+    let contents = postcard::to_allocvec(&limits)
+      .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+
+    let path = Path::new(LIMITS_PATH);
+    let temporary = path.with_extension("tmp");
+
+    if let Some(parent) = path.parent() {
+      fs::create_dir_all(parent)?;
+    }
+
+    // write then rename so a power loss mid-write can't corrupt the limits
+    fs::write(&temporary, contents)?;
+    fs::rename(&temporary, path)
+  }
+}
+
+fn load(path: &Path) -> io::Result<Vec<ProtectionLimit>> {
+  let contents = fs::read(path)?;
+
+  postcard::from_bytes(&contents)
+    .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+/// Takes the action of a tripped protection.
+pub fn act(action: ProtectionAction) {
+  match action {
+    ProtectionAction::OpenBatteryLoadSwitch => disable_battery_power(),
+    ProtectionAction::OpenSamLoadSwitch => disable_sam_power(),
+    ProtectionAction::DisableCharger => disable_charger(),
+    ProtectionAction::LatchFault => {}
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // the default limits, with the battery overcurrent limit replaced
+  fn protections(enabled: bool, action: ProtectionAction) -> Protections {
+    let mut protections = Protections::default();
+
+    protections.limits.insert(
+      Protection::BatteryOvercurrent,
+      ProtectionLimit {
+        protection: Protection::BatteryOvercurrent,
+        enabled,
+        threshold: 10.0,
+        debounce_ms: 100,
+        action,
+      },
+    );
+
+    protections
+  }
+
+  // a reading which violates nothing but, if told to, battery overcurrent
+  fn reading(battery_current: f64) -> Bms {
+    let mut state = Bms::default();
+    state.battery_bus.voltage = 24.0;
+    state.battery_bus.current = battery_current;
+    state
+  }
+
+  #[test]
+  fn trips_once_violated_for_the_debounce_time() {
+    let mut protections =
+      protections(true, ProtectionAction::OpenBatteryLoadSwitch);
+    let start = Instant::now();
+    let at = |ms| start + Duration::from_millis(ms);
+
+    assert!(protections.check(&reading(12.0), at(0)).is_empty());
+    assert!(protections.check(&reading(12.0), at(99)).is_empty());
+    assert!(!protections.faults().battery_overcurrent);
+
+    assert_eq!(
+      protections.check(&reading(12.0), at(100)),
+      [ProtectionAction::OpenBatteryLoadSwitch]
+    );
+    assert!(protections.faults().battery_overcurrent);
+  }
+
+  #[test]
+  fn a_break_in_the_violation_restarts_the_debounce() {
+    let mut protections =
+      protections(true, ProtectionAction::OpenBatteryLoadSwitch);
+    let start = Instant::now();
+    let at = |ms| start + Duration::from_millis(ms);
+
+    protections.check(&reading(12.0), at(0));
+    protections.check(&reading(5.0), at(50));
+    protections.check(&reading(12.0), at(60));
+
+    assert!(protections.check(&reading(12.0), at(150)).is_empty());
+    assert_eq!(protections.check(&reading(12.0), at(160)).len(), 1);
+  }
+
+  #[test]
+  fn never_trips_when_disabled_or_unread() {
+    let mut disabled =
+      protections(false, ProtectionAction::OpenBatteryLoadSwitch);
+    let mut unread = protections(true, ProtectionAction::OpenBatteryLoadSwitch);
+    let start = Instant::now();
+    let at = |ms| start + Duration::from_millis(ms);
+
+    for elapsed in [0, 100, 1000] {
+      let now = at(elapsed);
+      assert!(disabled.check(&reading(12.0), now).is_empty());
+      assert!(unread.check(&reading(f64::NAN), now).is_empty());
+    }
+
+    assert_eq!(disabled.faults(), Faults::default());
+    assert_eq!(unread.faults(), Faults::default());
+  }
+
+  #[test]
+  fn latches_until_cleared() {
+    let mut protections =
+      protections(true, ProtectionAction::OpenBatteryLoadSwitch);
+    let start = Instant::now();
+    let at = |ms| start + Duration::from_millis(ms);
+
+    protections.check(&reading(12.0), at(0));
+    assert_eq!(protections.check(&reading(12.0), at(100)).len(), 1);
+
+    // the action is taken once, and the fault outlasts the violation
+    assert!(protections.check(&reading(12.0), at(500)).is_empty());
+    assert!(protections.check(&reading(5.0), at(600)).is_empty());
+    assert!(protections.faults().battery_overcurrent);
+
+    protections.check(&reading(12.0), at(700));
+    assert!(protections.check(&reading(12.0), at(900)).is_empty());
+    assert!(protections.faults().battery_overcurrent);
+  }
+
+  #[test]
+  fn clearing_faults_restarts_the_debounce() {
+    let mut protections =
+      protections(true, ProtectionAction::OpenBatteryLoadSwitch);
+    let start = Instant::now();
+    let at = |ms| start + Duration::from_millis(ms);
+
+    protections.check(&reading(12.0), at(0));
+    protections.check(&reading(12.0), at(100));
+
+    protections.clear_faults();
+    assert_eq!(protections.faults(), Faults::default());
+
+    // still violated, so it trips again, but only after a full debounce
+    assert!(protections.check(&reading(12.0), at(200)).is_empty());
+    assert!(protections.check(&reading(12.0), at(299)).is_empty());
+    assert_eq!(
+      protections.check(&reading(12.0), at(300)),
+      [ProtectionAction::OpenBatteryLoadSwitch]
+    );
+  }
+
+  #[test]
+  fn latched_faults_block_reclosing_what_they_opened() {
+    let mut protections =
+      protections(true, ProtectionAction::OpenBatteryLoadSwitch);
+    let start = Instant::now();
+    let at = |ms| start + Duration::from_millis(ms);
+
+    assert!(protections.permits(&Command::BatteryLoadSwitch(true)));
+
+    protections.check(&reading(12.0), at(0));
+    protections.check(&reading(12.0), at(100));
+
+    assert!(!protections.permits(&Command::BatteryLoadSwitch(true)));
+
+    // opening it, and anything the fault didn't open, is still permitted
+    assert!(protections.permits(&Command::BatteryLoadSwitch(false)));
+    assert!(protections.permits(&Command::SamLoadSwitch(true)));
+    assert!(protections.permits(&Command::Charge(true)));
+
+    protections.clear_faults();
+    assert!(protections.permits(&Command::BatteryLoadSwitch(true)));
+  }
+
+  #[test]
+  fn latch_only_faults_block_nothing() {
+    let mut protections = protections(true, ProtectionAction::LatchFault);
+    let start = Instant::now();
+    let at = |ms| start + Duration::from_millis(ms);
+
+    protections.check(&reading(12.0), at(0));
+    assert_eq!(
+      protections.check(&reading(12.0), at(100)),
+      [ProtectionAction::LatchFault]
+    );
+
+    assert!(protections.faults().battery_overcurrent);
+    assert!(protections.permits(&Command::BatteryLoadSwitch(true)));
+  }
+}
@@ -0,0 +1,331 @@
+use common::comm::bms::Bms;
+use jeflog::{pass, warn};
+use std::{
+  fs,
+  io,
+  path::Path,
+  time::{Duration, Instant},
+};
+
+/// Where the estimator state is kept across restarts.
+const STATE_PATH: &str = "/var/lib/bms/soc";
+
+/// How often the estimator state is written to disk.
+const SAVE_PERIOD: Duration = Duration::from_secs(10);
+
+/// The rated capacity of the pack, in amp-hours.
+const PACK_CAPACITY_AH: f64 = 10.0;
+
+/// The number of cells in series in the pack.
+const CELLS_IN_SERIES: f64 = 6.0;
+
+/// Open-circuit voltage of a single cell against state of charge for our
+/// Li-ion chemistry, sorted by state of charge.
+const CELL_OCV_CURVE: [(f64, f64); 12] = [
+  (0.00, 3.00),
+  (0.05, 3.30),
+  (0.10, 3.45),
+  (0.20, 3.55),
+  (0.30, 3.62),
+  (0.40, 3.68),
+  (0.50, 3.74),
+  (0.60, 3.80),
+  (0.70, 3.88),
+  (0.80, 3.97),
+  (0.90, 4.07),
+  (1.00, 4.20),
+];
+
+/// Below this current (in either direction) the pack is considered at rest.
+const REST_CURRENT: f64 = 0.1;
+
+/// How long the pack must be at rest before its voltage is trusted as its
+/// open-circuit voltage.
+const REST_TIME: Duration = Duration::from_secs(60);
+
+/// The time constant with which a rested pack's state of charge is pulled
+/// towards the value read from the OCV curve.
+const OCV_CORRECTION_TIME_CONSTANT: f64 = 120.0;
+
+/// The time constant of the current average used for time remaining.
+const CURRENT_AVERAGE_TIME_CONSTANT: f64 = 30.0;
+
+/// Below this average discharge current, no time remaining is reported.
+const MIN_DISCHARGE_CURRENT: f64 = 0.05;
+
+/// Estimates the state of charge of the battery by counting the charge that
+/// flows through the battery bus, correcting against the open-circuit voltage
+/// whenever the pack has been at rest long enough for it to be meaningful.
+pub struct SocEstimator {
+  // none until the first reading if nothing was persisted
+  state_of_charge: Option<f64>,
+  consumed_ah: f64,
+  average_current: f64,
+  last_update: Option<Instant>,
+  resting_since: Option<Instant>,
+  last_save: Instant,
+}
+
+impl SocEstimator {
+  /// Restores the estimator from disk, or starts fresh if nothing was saved.
+  pub fn restore() -> Self {
+    let (state_of_charge, consumed_ah) = match load(Path::new(STATE_PATH)) {
+      Ok((state_of_charge, consumed_ah)) => {
+        pass!(
+          "Restored state of charge {:.1}% ({consumed_ah:.3} Ah consumed).",
+          state_of_charge * 100.0
+        );
+        (Some(state_of_charge), consumed_ah)
+      }
+      Err(error) => {
+        warn!("Could not restore state of charge ({error}), using OCV.");
+        (None, 0.0)
+      }
+    };
+
+    SocEstimator::new(state_of_charge, consumed_ah)
+  }
+
+  fn new(state_of_charge: Option<f64>, consumed_ah: f64) -> Self {
+    SocEstimator {
+      state_of_charge,
+      consumed_ah,
+      average_current: 0.0,
+      last_update: None,
+      resting_since: None,
+      last_save: Instant::now(),
+    }
+  }
+
+  /// Updates the estimate with the latest reading, filling in the state of
+  /// charge, consumed amp-hours and time remaining of `state`.
+  ///
+  /// This is called for every reading the monitor takes, whether or not the
+  /// flight computer is connected, so that no charge goes uncounted.
+  pub fn update(&mut self, state: &mut Bms) {
+    let now = Instant::now();
+    self.integrate(state, now);
+
+    if now - self.last_save >= SAVE_PERIOD {
+      self.last_save = now;
+
+      if let Err(error) = self.save() {
+        warn!("Could not persist state of charge ({error}).");
+      }
+    }
+
+    self.report(state);
+  }
+
+  // counts the charge since the last reading, which was taken at `now`
+  fn integrate(&mut self, state: &Bms, now: Instant) {
+    let current = state.battery_bus.current;
+    let voltage = state.battery_bus.voltage;
+
+    if !current.is_finite() || !voltage.is_finite() {
+      return;
+    }
+
+    let dt = self
+      .last_update
+      .map_or(0.0, |last| (now - last).as_secs_f64());
+    self.last_update = Some(now);
+
+    let ocv_soc = soc_from_ocv(voltage);
+    let soc = self.state_of_charge.get_or_insert(ocv_soc);
+
+    // coulomb counting, where a positive current discharges the pack
+    let charge_ah = current * dt / 3600.0;
+    *soc = (*soc - charge_ah / PACK_CAPACITY_AH).clamp(0.0, 1.0);
+    self.consumed_ah = (self.consumed_ah + charge_ah).max(0.0);
+
+    // once rested, the terminal voltage approaches the open-circuit voltage,
+    // so slowly pull the estimate towards it to cancel out integration drift
+    if current.abs() < REST_CURRENT {
+      let since = *self.resting_since.get_or_insert(now);
+
+      if now - since >= REST_TIME {
+        let alpha = dt / (OCV_CORRECTION_TIME_CONSTANT + dt);
+        *soc += alpha * (ocv_soc - *soc);
+      }
+    } else {
+      self.resting_since = None;
+    }
+
+    // a full pack has nothing consumed from it
+    if *soc >= 1.0 {
+      self.consumed_ah = 0.0;
+    }
+
+    let alpha = dt / (CURRENT_AVERAGE_TIME_CONSTANT + dt);
+    self.average_current += alpha * (current - self.average_current);
+  }
+
+  fn report(&self, state: &mut Bms) {
+    let soc = self.state_of_charge.unwrap_or(f64::NAN);
+
+    state.state_of_charge = soc;
+    state.consumed_ah = self.consumed_ah;
+    state.time_remaining = (self.average_current > MIN_DISCHARGE_CURRENT)
+      .then(|| soc * PACK_CAPACITY_AH / self.average_current * 3600.0);
+  }
+
+  fn save(&self) -> io::Result<()> {
+    let Some(state_of_charge) = self.state_of_charge else {
+      return Ok(());
+    };
+
+    let path = Path::new(STATE_PATH);
+    let temporary = path.with_extension("tmp");
+
+    if let Some(parent) = path.parent() {
+      fs::create_dir_all(parent)?;
+    }
+
+    // write then rename so a power loss mid-write can't corrupt the state
+    let contents = format!("{state_of_charge} {}\n", self.consumed_ah);
+    fs::write(&temporary, contents)?;
+    fs::rename(&temporary, path)
+  }
+}
+
+fn load(path: &Path) -> io::Result<(f64, f64)> {
+  let contents = fs::read_to_string(path)?;
+  let mut fields = contents.split_whitespace().map(str::parse::<f64>);
+
+  match (fields.next(), fields.next()) {
+    (Some(Ok(state_of_charge)), Some(Ok(consumed_ah)))
+      if (0.0..=1.0).contains(&state_of_charge) && consumed_ah >= 0.0 =>
+    {
+      Ok((state_of_charge, consumed_ah))
+    }
+    _ => Err(io::Error::new(
+      io::ErrorKind::InvalidData,
+      "malformed state of charge file",
+    )),
+  }
+}
+
+/// Looks up the state of charge of a rested pack from its voltage by linearly
+/// interpolating the OCV curve.
+fn soc_from_ocv(pack_voltage: f64) -> f64 {
+  let cell_voltage = pack_voltage / CELLS_IN_SERIES;
+  let (first_soc, first_voltage) = CELL_OCV_CURVE[0];
+
+  if cell_voltage <= first_voltage {
+    return first_soc;
+  }
+
+  for window in CELL_OCV_CURVE.windows(2) {
+    let (low_soc, low_voltage) = window[0];
+    let (high_soc, high_voltage) = window[1];
+
+    if cell_voltage <= high_voltage {
+      let fraction =
+        (cell_voltage - low_voltage) / (high_voltage - low_voltage);
+      return low_soc + fraction * (high_soc - low_soc);
+    }
+  }
+
+  1.0
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SECOND: Duration = Duration::from_secs(1);
+
+  fn reading(voltage: f64, current: f64) -> Bms {
+    let mut state = Bms::default();
+    state.battery_bus.voltage = voltage;
+    state.battery_bus.current = current;
+    state
+  }
+
+  fn pack(cell_voltage: f64) -> f64 {
+    cell_voltage * CELLS_IN_SERIES
+  }
+
+  #[test]
+  fn ocv_clamps_outside_the_curve() {
+    assert_eq!(soc_from_ocv(pack(2.5)), 0.0);
+    assert_eq!(soc_from_ocv(pack(4.5)), 1.0);
+  }
+
+  #[test]
+  fn ocv_interpolates_between_points() {
+    for (soc, cell_voltage) in CELL_OCV_CURVE {
+      assert!((soc_from_ocv(pack(cell_voltage)) - soc).abs() < 1e-9);
+    }
+
+    assert!((soc_from_ocv(pack(3.71)) - 0.45).abs() < 1e-9);
+  }
+
+  #[test]
+  fn first_reading_starts_from_ocv() {
+    let mut estimator = SocEstimator::new(None, 0.0);
+    estimator.integrate(&reading(pack(3.74), 2.0), Instant::now());
+
+    assert!((estimator.state_of_charge.unwrap() - 0.5).abs() < 1e-9);
+    assert_eq!(estimator.consumed_ah, 0.0);
+  }
+
+  #[test]
+  fn discharge_is_counted() {
+    let mut estimator = SocEstimator::new(Some(1.0), 0.0);
+    let start = Instant::now();
+
+    estimator.integrate(&reading(pack(4.0), 5.0), start);
+    estimator.integrate(&reading(pack(4.0), 5.0), start + 36 * SECOND);
+
+    // 5 A for 36 s is 0.05 Ah of a 10 Ah pack
+    assert!((estimator.consumed_ah - 0.05).abs() < 1e-9);
+    assert!((estimator.state_of_charge.unwrap() - 0.995).abs() < 1e-9);
+    assert!(estimator.average_current > 0.0);
+  }
+
+  #[test]
+  fn charge_is_counted_until_full() {
+    let mut estimator = SocEstimator::new(Some(0.999), 0.01);
+    let start = Instant::now();
+
+    estimator.integrate(&reading(pack(4.1), -5.0), start);
+    estimator.integrate(&reading(pack(4.1), -5.0), start + 36 * SECOND);
+
+    assert_eq!(estimator.state_of_charge, Some(1.0));
+    assert_eq!(estimator.consumed_ah, 0.0);
+  }
+
+  #[test]
+  fn failed_readings_are_skipped() {
+    let mut estimator = SocEstimator::new(Some(0.5), 0.0);
+    let start = Instant::now();
+
+    estimator.integrate(&reading(pack(3.74), 5.0), start);
+    estimator.integrate(&reading(f64::NAN, 5.0), start + 36 * SECOND);
+
+    assert_eq!(estimator.last_update, Some(start));
+    assert_eq!(estimator.consumed_ah, 0.0);
+  }
+
+  #[test]
+  fn rested_pack_is_pulled_towards_ocv() {
+    let mut estimator = SocEstimator::new(Some(0.8), 0.0);
+    let start = Instant::now();
+    let rested = start + REST_TIME;
+
+    // the voltage says the pack is half full, but it has not rested yet
+    estimator.integrate(&reading(pack(3.74), 0.0), start);
+    estimator.integrate(&reading(pack(3.74), 0.0), start + SECOND);
+    assert_eq!(estimator.state_of_charge, Some(0.8));
+
+    estimator.integrate(&reading(pack(3.74), 0.0), rested);
+    let soc = estimator.state_of_charge.unwrap();
+    assert!(soc < 0.8 && soc > 0.5);
+
+    // a load ends the rest, so the estimate is left alone again
+    estimator.integrate(&reading(pack(3.74), 1.0), rested + SECOND);
+    assert_eq!(estimator.resting_since, None);
+  }
+}
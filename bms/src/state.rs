@@ -1,21 +1,28 @@
-use crate::adc::{init_adcs, start_adcs, reset_adcs, poll_adcs};
 use crate::{
-  command::{init_gpio, GPIO_CONTROLLERS},
-  communication::{
-    check_and_execute,
+  command::init_gpio,
+  communication::{send_data, send_diagnostics, send_estop_event, BMS_ID},
+  estop::EStopRelay,
+  monitor::{Monitor, Request},
+};
+use common::comm::{
+  bms::Command,
+  discovery::{
     check_heartbeat,
     establish_flight_computer_connection,
-    send_data,
+    receive_command,
   },
+  flight::BoardKind,
 };
-use ads114s06::ADC;
-use common::comm::ADCKind::{SamAnd5V, VBatUmbCharge};
 use jeflog::fail;
 use std::{
   net::{SocketAddr, UdpSocket},
-  time::Instant
+  time::{Duration, Instant},
 };
 
+// how long to wait for the monitor to take a reading before checking for
+// commands and heartbeats again
+const READING_TIMEOUT: Duration = Duration::from_millis(10);
+
 pub enum State {
   Init,
   Connect(ConnectData),
@@ -24,11 +31,15 @@ pub enum State {
 }
 
 pub struct ConnectData {
-  adcs: Vec<ADC>,
+  monitor: Monitor,
+  estop_events: EStopRelay,
+  // the flight computer last connected to, if comms with it were lost
+  fc_address: Option<SocketAddr>,
 }
 
 pub struct MainLoopData {
-  adcs: Vec<ADC>,
+  monitor: Monitor,
+  estop_events: EStopRelay,
   my_data_socket: UdpSocket,
   my_command_socket: UdpSocket,
   fc_address: SocketAddr,
@@ -36,7 +47,9 @@ pub struct MainLoopData {
 }
 
 pub struct AbortData {
-  adcs: Vec<ADC>,
+  monitor: Monitor,
+  estop_events: EStopRelay,
+  fc_address: SocketAddr,
 }
 
 impl State {
@@ -56,39 +69,26 @@ impl State {
 fn init() -> State {
   init_gpio();
 
-  // VBatUmbCharge
-  let adc1: ADC = ADC::new(
-    "/dev/spidev0.0",
-    GPIO_CONTROLLERS[1].get_pin(28),
-    Some(GPIO_CONTROLLERS[0].get_pin(30)),
-    VBatUmbCharge,
-  )
-  .expect("Failed to initialize VBatUmbCharge ADC");
-
-  // SamAnd5V
-  let adc2: ADC = ADC::new(
-    "/dev/spidev0.0",
-    GPIO_CONTROLLERS[1].get_pin(18),
-    Some(GPIO_CONTROLLERS[0].get_pin(31)),
-    SamAnd5V,
-  )
-  .expect("Failed to initialize the SamAnd5V ADC");
-
-  let mut adcs: Vec<ADC> = vec![adc1, adc2];
-  init_adcs(&mut adcs);
-
-  State::Connect(ConnectData { adcs })
+  // the protections run from here on, whether or not the flight computer is
+  // ever found
+  State::Connect(ConnectData {
+    monitor: Monitor::spawn(),
+    estop_events: EStopRelay::default(),
+    fc_address: None,
+  })
 }
 
-fn connect(mut data: ConnectData) -> State {
+fn connect(data: ConnectData) -> State {
   let (data_socket, command_socket, fc_address) =
-    establish_flight_computer_connection();
-
-  // tell the ADCs to start collecting data
-  start_adcs(&mut data.adcs);
+    establish_flight_computer_connection(
+      BMS_ID,
+      BoardKind::Bms,
+      data.fc_address,
+    );
 
   State::MainLoop(MainLoopData {
-    adcs: data.adcs,
+    monitor: data.monitor,
+    estop_events: data.estop_events,
     my_command_socket: command_socket,
     my_data_socket: data_socket,
     fc_address,
@@ -97,29 +97,56 @@ fn connect(mut data: ConnectData) -> State {
 }
 
 fn main_loop(mut data: MainLoopData) -> State {
-  check_and_execute(&data.my_command_socket);
+  match receive_command(&data.my_command_socket) {
+    Some(Command::AcknowledgeEStop(sequence)) => {
+      data.estop_events.acknowledge(sequence);
+    }
+    Some(command) => data.monitor.request(Request::Command(command)),
+    None => {}
+  }
+
   let (updated_time, abort_status) =
     check_heartbeat(&data.my_data_socket, data.then);
   data.then = updated_time;
 
   if abort_status {
-    return State::Abort(AbortData { adcs: data.adcs });
+    return State::Abort(AbortData {
+      monitor: data.monitor,
+      estop_events: data.estop_events,
+      fc_address: data.fc_address,
+    });
+  }
+
+  let outbox = data.monitor.collect(READING_TIMEOUT);
+
+  for event in outbox.events {
+    data.estop_events.push(event);
   }
 
-  let datapoint = poll_adcs(&mut data.adcs);
-  send_data(&data.my_data_socket, &data.fc_address, datapoint);
+  if let Some(event) = data.estop_events.due() {
+    send_estop_event(&data.my_data_socket, &data.fc_address, event);
+  }
+
+  if let Some(datapoint) = outbox.datapoint {
+    send_data(&data.my_data_socket, &data.fc_address, datapoint);
+  }
+
+  for report in outbox.reports {
+    send_diagnostics(&data.my_data_socket, &data.fc_address, report);
+  }
 
   State::MainLoop(data)
 }
 
-fn abort(mut data: AbortData) -> State {
+fn abort(data: AbortData) -> State {
   fail!("Aborting goodbye!");
-  init_gpio();
-  /* init_gpio turns off all chip selects but reset_adcs makes use of them
-  again. However with the ADC driver that reset_adcs uses, each chip select
-  will be turned off after the ADC is done being communicated with. init_gpio
-  needs to turn off all chip selects at the start so its mainly code reuse
-   */
-  reset_adcs(&mut data.adcs); // reset ADC pin muxing and stop collecting data
-  State::Connect(ConnectData { adcs: data.adcs })
+  data.monitor.request(Request::Safe);
+
+  // identify to the same flight computer again, which ignores this board
+  // until it does
+  State::Connect(ConnectData {
+    monitor: data.monitor,
+    estop_events: data.estop_events,
+    fc_address: Some(data.fc_address),
+  })
 }
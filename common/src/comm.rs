@@ -2,7 +2,7 @@ use ahrs::Ahrs;
 use bms::Bms;
 use postcard::experimental::max_size::MaxSize;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fmt};
+use std::{collections::HashMap, fmt, net::SocketAddr, time::Duration};
 
 #[cfg(feature = "rusqlite")]
 use rusqlite::{
@@ -22,6 +22,18 @@ pub mod flight;
 /// Deals with all communication regarding AHRS (i forgot the acronym)
 pub mod ahrs;
 
+/// Deals with board self-tests and the diagnostics they report.
+pub mod diagnostics;
+
+/// Describes the hardware revisions of each board as data.
+pub mod board;
+
+/// Deals with the operating modes of the vehicle and what each permits.
+pub mod mode;
+
+/// Deals with data boards locating the flight computer on the network.
+pub mod discovery;
+
 mod gui;
 pub use gui::*;
 
@@ -39,6 +51,8 @@ impl fmt::Display for sam::Unit {
         Self::Kelvin => "K",
         Self::Pounds => "lbf",
         Self::Volts => "V",
+        Self::Meters => "m",
+        Self::MetersPerSecond => "m/s",
       }
     )
   }
@@ -84,6 +98,11 @@ pub struct VehicleState {
 
   /// Holds the latest readings of all sensors on the vehicle.
   pub sensor_readings: HashMap<String, Measurement>,
+
+  /// The operating mode of the vehicle.
+  pub mode: mode::VehicleMode,
+  /// How long the control server has been gone, while it is.
+  pub server_loss: Option<ServerLossTimer>,
 }
 
 impl VehicleState {
@@ -204,7 +223,62 @@ pub struct Sequence {
   pub script: String,
 }
 
-/// A trigger with a
+/// How a run of a sequence on the flight computer ended.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SequenceOutcome {
+  /// The script ran to completion.
+  Finished,
+
+  /// The script raised an exception, or could not be started.
+  Raised {
+    /// The exception, as Python would print it.
+    error: String,
+
+    /// The formatted traceback of the exception, if it has one.
+    traceback: Option<String>,
+  },
+
+  /// The sequence was stopped or aborted before it could finish.
+  Cancelled,
+}
+
+/// Something which happened while a sequence was running.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SequenceEventKind {
+  /// The sequence started running.
+  Started,
+
+  /// The sequence printed a line, given without its newline.
+  Output(String),
+
+  /// The sequence stopped running, for the given reason.
+  Ended(SequenceOutcome),
+}
+
+/// An event in a run of a sequence, reported by the flight computer to the
+/// control server.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct SequenceEvent {
+  /// The name of the sequence.
+  pub name: String,
+
+  /// What happened.
+  pub kind: SequenceEventKind,
+
+  /// The UNIX timestamp of the event on the flight computer.
+  pub timestamp: f64,
+}
+
+/// A trigger with a condition which runs a script once it starts holding.
+///
+/// A trigger fires on the rising edge of its condition, so the script runs
+/// once rather than for as long as the condition holds. It may not fire again
+/// until it re-arms, which is once its condition stops holding or, if it has
+/// one, once its reset condition holds. A trigger starts out disarmed, so one
+/// set, or restored after a reboot, while its condition already holds waits
+/// for it to re-arm unless it sets `fire_if_holding`.
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct Trigger {
   /// The unique, human-readable name which identifies the trigger.
@@ -218,8 +292,424 @@ pub struct Trigger {
 
   /// Whether or not the trigger is active
   pub active: bool,
+
+  /// The condition which must hold for the trigger to re-arm after firing,
+  /// written in Python. This gives the trigger hysteresis, as with a condition
+  /// of `PT1 > 500 * psi` and a reset condition of `PT1 < 450 * psi`.
+  #[serde(default)]
+  pub reset: Option<String>,
+
+  /// How long, in milliseconds, the condition must hold continuously before
+  /// the trigger fires.
+  #[serde(default)]
+  pub hold_ms: u32,
+
+  /// The least time, in milliseconds, between the trigger firing and it firing
+  /// again, even if it has re-armed.
+  #[serde(default)]
+  pub cooldown_ms: u32,
+
+  /// Whether the trigger starts out armed, and so fires as soon as it is set
+  /// if its condition already holds.
+  #[serde(default)]
+  pub fire_if_holding: bool,
+
+  /// How often, in milliseconds, the condition is checked. `None` uses the
+  /// default rate of the flight computer.
+  ///
+  /// This is only a best effort. Every trigger is checked on one thread which
+  /// also runs the script of whichever fires, so no condition is checked at
+  /// all until that script finishes.
+  #[serde(default)]
+  pub poll_ms: Option<u32>,
+}
+
+/// Something which happened to a trigger on the flight computer.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TriggerEventKind {
+  /// The trigger fired, and its script is about to run.
+  Fired,
+
+  /// The condition or reset condition of the trigger could not be compiled or
+  /// evaluated. Only the first of consecutive failures is reported.
+  Failed(String),
+
+  /// The trigger was evaluated successfully after failing.
+  Recovered,
+}
+
+/// An event of a trigger, reported by the flight computer to the control
+/// server.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct TriggerEvent {
+  /// The name of the trigger.
+  pub name: String,
+
+  /// What happened.
+  pub kind: TriggerEventKind,
+
+  /// The UNIX timestamp of the event on the flight computer.
+  pub timestamp: f64,
+}
+
+/// What the flight computer does once it loses communications with a board.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Criticality {
+  /// The whole vehicle is aborted.
+  Abort,
+
+  /// Only the valves on the board are commanded to their unpowered state.
+  Safe,
+
+  /// The loss is only reported.
+  Warn,
+}
+
+#[cfg(feature = "rusqlite")]
+impl ToSql for Criticality {
+  fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+    // see the ChannelType ToSql comment for details
+    let mut json = serde_json::to_string(&self)
+      .expect("failed to serialize Criticality into JSON");
+
+    json.pop();
+    json.remove(0);
+
+    Ok(ToSqlOutput::Owned(rusqlite::types::Value::Text(json)))
+  }
+}
+
+#[cfg(feature = "rusqlite")]
+impl FromSql for Criticality {
+  fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+    if let ValueRef::Text(text) = value {
+      // see the ChannelType ToSql comment for details
+      let mut json = vec![b'"'];
+      json.extend_from_slice(text);
+      json.push(b'"');
+
+      let criticality = serde_json::from_slice(&json)
+        .map_err(|error| FromSqlError::Other(Box::new(error)))?;
+
+      Ok(criticality)
+    } else {
+      Err(FromSqlError::InvalidType)
+    }
+  }
+}
+
+/// What the flight computer does when a board it has lost is heard from
+/// again.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reconnect {
+  /// The board is taken back into service once it identifies itself again,
+  /// being sent heartbeats and having its data ingested as before. Until then,
+  /// anything else it sends is ignored.
+  Resume,
+
+  /// The board stays lost, with its data ignored, until its policy is changed
+  /// or the flight computer restarts.
+  Latch,
+}
+
+#[cfg(feature = "rusqlite")]
+impl ToSql for Reconnect {
+  fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+    // see the ChannelType ToSql comment for details
+    let mut json = serde_json::to_string(&self)
+      .expect("failed to serialize Reconnect into JSON");
+
+    json.pop();
+    json.remove(0);
+
+    Ok(ToSqlOutput::Owned(rusqlite::types::Value::Text(json)))
+  }
+}
+
+#[cfg(feature = "rusqlite")]
+impl FromSql for Reconnect {
+  fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+    if let ValueRef::Text(text) = value {
+      // see the ChannelType ToSql comment for details
+      let mut json = vec![b'"'];
+      json.extend_from_slice(text);
+      json.push(b'"');
+
+      let reconnect = serde_json::from_slice(&json)
+        .map_err(|error| FromSqlError::Other(Box::new(error)))?;
+
+      Ok(reconnect)
+    } else {
+      Err(FromSqlError::InvalidType)
+    }
+  }
 }
 
+/// How the flight computer decides that it has lost communications with a
+/// board, and what it does about it.
+///
+/// A board which has no policy of its own is lost after 100 ms of silence,
+/// which aborts the vehicle.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct CommsPolicy {
+  /// The ID of the board the policy applies to.
+  pub board_id: flight::BoardId,
+
+  /// How long, in milliseconds, the board may go without being heard from
+  /// before it has missed a window.
+  pub timeout_ms: u32,
+
+  /// How many consecutive windows the board may miss before it is lost, so
+  /// that a board is lost after `timeout_ms * (grace_count + 1)` of silence.
+  pub grace_count: u32,
+
+  /// What is done once the board is lost.
+  pub criticality: Criticality,
+
+  /// What is done if the board is heard from after being lost.
+  pub reconnect: Reconnect,
+}
+
+/// A change in the communications with a board.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BoardCommsEventKind {
+  /// The board missed more windows than its policy allows, and its loss was
+  /// handled as given.
+  Lost(Criticality),
+
+  /// The board was heard from after being lost, and taken back into service.
+  Recovered,
+}
+
+/// A loss or recovery of communications with a board, reported by the flight
+/// computer to the control server.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct BoardCommsEvent {
+  /// The ID of the board.
+  pub board_id: flight::BoardId,
+
+  /// What happened.
+  pub kind: BoardCommsEventKind,
+
+  /// The UNIX timestamp of the event on the flight computer.
+  pub timestamp: f64,
+}
+
+/// What the flight computer does once it has been without the control server
+/// for longer than its server loss policy allows.
+#[derive(
+  Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum ServerLossAction {
+  /// Running sequences and triggers carry on as if the server were there.
+  #[default]
+  Continue,
+
+  /// Running sequences are paused at their next device access until the
+  /// server reconnects.
+  Hold,
+
+  /// The vehicle is aborted.
+  Abort,
+
+  /// Every running sequence is stopped and the safing sequence is run.
+  Safe,
+}
+
+#[cfg(feature = "rusqlite")]
+impl ToSql for ServerLossAction {
+  fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+    // see the ChannelType ToSql comment for details
+    let mut json = serde_json::to_string(&self)
+      .expect("failed to serialize ServerLossAction into JSON");
+
+    json.pop();
+    json.remove(0);
+
+    Ok(ToSqlOutput::Owned(rusqlite::types::Value::Text(json)))
+  }
+}
+
+#[cfg(feature = "rusqlite")]
+impl FromSql for ServerLossAction {
+  fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+    if let ValueRef::Text(text) = value {
+      // see the ChannelType ToSql comment for details
+      let mut json = vec![b'"'];
+      json.extend_from_slice(text);
+      json.push(b'"');
+
+      let action = serde_json::from_slice(&json)
+        .map_err(|error| FromSqlError::Other(Box::new(error)))?;
+
+      Ok(action)
+    } else {
+      Err(FromSqlError::InvalidType)
+    }
+  }
+}
+
+/// What the flight computer does when its connection to the control server
+/// drops.
+///
+/// Without a policy, the flight computer continues as if nothing happened.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct ServerLossPolicy {
+  /// What is done once the server has been gone for `timeout_ms`.
+  pub action: ServerLossAction,
+
+  /// How long, in milliseconds, the server may be gone before the action is
+  /// taken.
+  pub timeout_ms: u32,
+
+  /// The sequence run by the `Safe` action.
+  pub safing_sequence: Option<Sequence>,
+}
+
+/// How long the flight computer has been without the control server, as
+/// shown in telemetry while the server is gone.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ServerLossTimer {
+  /// The UNIX timestamp at which the server was lost.
+  pub lost_at: f64,
+
+  /// How many seconds the server has been gone.
+  pub elapsed_s: f64,
+
+  /// How many seconds are left before the action is taken, being zero once it
+  /// has been and `None` if the action is to continue.
+  pub remaining_s: Option<f64>,
+
+  /// What is done once the timer runs out.
+  pub action: ServerLossAction,
+
+  /// Whether the action has been taken.
+  pub acted: bool,
+}
+
+/// A loss of the control server which has ended, reported by the flight
+/// computer once the server reconnects.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ServerLossEvent {
+  /// The UNIX timestamp at which the server was lost.
+  pub lost_at: f64,
+
+  /// The UNIX timestamp at which the server reconnected.
+  pub regained_at: f64,
+
+  /// What the policy was to do once the timer ran out.
+  pub action: ServerLossAction,
+
+  /// Whether the action was taken before the server reconnected.
+  pub acted: bool,
+}
+
+/// Everything the control server configures on the flight computer.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Configuration {
+  /// The mappings of every sensor and valve.
+  pub mappings: Vec<NodeMapping>,
+
+  /// Every trigger, active or not.
+  pub triggers: Vec<Trigger>,
+
+  /// The sequence run on an abort, if one is set.
+  pub abort_sequence: Option<Sequence>,
+
+  /// The comms policy of every board which doesn't use the default.
+  pub comms_policies: Vec<CommsPolicy>,
+
+  /// What is done if the connection to the control server drops.
+  pub server_loss_policy: ServerLossPolicy,
+}
+
+impl Configuration {
+  /// Computes a digest of the configuration, by which the control server can
+  /// check that the flight computer holds what it should.
+  ///
+  /// Only what the flight computer acts on is included, so inactive triggers
+  /// are left out, and the order of mappings and triggers doesn't matter.
+  pub fn digest(&self) -> u64 {
+    let mut mappings = self.mappings.iter().collect::<Vec<_>>();
+    mappings.sort_by(|a, b| a.text_id.cmp(&b.text_id));
+
+    let mut triggers = self
+      .triggers
+      .iter()
+      .filter(|trigger| trigger.active)
+      .collect::<Vec<_>>();
+
+    triggers.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let abort_sequence = &self.abort_sequence;
+
+    let mut comms_policies = self.comms_policies.iter().collect::<Vec<_>>();
+    comms_policies.sort_by(|a, b| a.board_id.cmp(&b.board_id));
+
+    // serializing these types can't fail
+    let bytes = postcard::to_allocvec(&(
+      mappings,
+      triggers,
+      abort_sequence,
+      comms_policies,
+      &self.server_loss_policy,
+    ))
+    .expect("failed to serialize configuration");
+
+    // 64-bit FNV-1a, which unlike the standard library hasher is guaranteed to
+    // give the same digest on both computers
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+      (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+  }
+}
+
+/// Where the configuration the flight computer is running with came from.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigurationSource {
+  /// Nothing was restored at startup, and nothing has been received since.
+  Empty,
+
+  /// Restored from disk at startup and not since changed by the control
+  /// server.
+  Restored {
+    /// The UNIX timestamp at which the configuration was saved.
+    saved_at: f64,
+  },
+
+  /// Changed by the control server since startup, and saved to disk.
+  Fresh,
+}
+
+/// The configuration held by the flight computer, as it reports it to the
+/// control server.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ConfigurationStatus {
+  /// Where the configuration came from.
+  pub source: ConfigurationSource,
+
+  /// The digest of the configuration, as from `Configuration::digest`.
+  pub digest: u64,
+
+  /// Whether the configuration is saved to disk, so that the flight computer
+  /// restarts with it. If the last save failed, `source` still describes what
+  /// was saved before, which is what a restart would bring back.
+  pub persisted: bool,
+}
+
+/// How often the control server sends `FlightControlMessage::Heartbeat`.
+pub const SERVER_HEARTBEAT_PERIOD: Duration = Duration::from_millis(250);
+
+/// How long the flight computer goes without hearing from the control server
+/// before it considers the server lost.
+pub const SERVER_HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(1);
+
 /// A message sent from the control server to the flight computer.
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub enum FlightControlMessage {
@@ -246,10 +736,73 @@ pub enum FlightControlMessage {
 
   /// Instructs the flight computer to run an immediate abort.
   Abort,
+
+  /// Instructs the flight computer to have the board with the given ID run a
+  /// self-test and report its diagnostics.
+  SelfTest(flight::BoardId),
+
+  /// Requests that the flight computer change the mode of the vehicle. The
+  /// outcome is reported back with `FlightStatusMessage::ModeChange`.
+  SetMode {
+    /// The mode requested.
+    mode: mode::VehicleMode,
+
+    /// Who asked for the change, recorded along with its outcome.
+    requested_by: String,
+  },
+
+  /// Replaces the mappings, triggers, abort sequence, comms policies and
+  /// server loss policy all at once, as sent on connecting. The new
+  /// configuration is acknowledged with `FlightStatusMessage::Configuration`.
+  Configuration(Configuration),
+
+  /// Sets the comms policy of a board, replacing any it had before.
+  CommsPolicy(CommsPolicy),
+
+  /// Sets every address the vehicle state is streamed to, being the server
+  /// and its standby if it has one. Until this is received after connecting,
+  /// the vehicle state is streamed to port 7201 of the server.
+  TelemetryTargets(Vec<SocketAddr>),
+
+  /// Sent every `SERVER_HEARTBEAT_PERIOD` so that the flight computer notices
+  /// a server which has stopped without closing the connection, such as one
+  /// which lost power or its network.
+  Heartbeat,
+}
+
+/// A message sent from the flight computer to the control server.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum FlightStatusMessage {
+  /// A self-test report relayed from the board with the given ID.
+  Diagnostics(flight::BoardId, diagnostics::SelfTestReport),
+
+  /// An e-stop trip or reset relayed from the BMS with the given ID.
+  EStop(flight::BoardId, bms::EStopEvent),
+
+  /// A change of vehicle mode, or a refused request for one.
+  ModeChange(mode::ModeChange),
+
+  /// An event in a run of a sequence, including those run by triggers.
+  Sequence(SequenceEvent),
+
+  /// A trigger firing, or failing to be evaluated.
+  Trigger(TriggerEvent),
+
+  /// The configuration held by the flight computer, sent on connecting and
+  /// whenever it changes.
+  Configuration(ConfigurationStatus),
+
+  /// A board being lost, or recovered after being lost.
+  BoardComms(BoardCommsEvent),
+
+  /// A loss of the control server, sent once it has reconnected.
+  ServerLoss(ServerLossEvent),
 }
 
 // Kind of ADC
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[derive(
+  Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize,
+)]
 pub enum ADCKind {
   CurrentLoopPt,
   VValve,
@@ -263,3 +816,82 @@ pub enum ADCKind {
   VBatUmbCharge, // just for bms
   SamAnd5V,      // just for bms
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn mapping(text_id: &str, channel: u32) -> NodeMapping {
+    NodeMapping {
+      text_id: text_id.to_owned(),
+      board_id: "sam-01".to_owned(),
+      sensor_type: SensorType::Valve,
+      channel,
+      computer: Computer::Flight,
+      max: None,
+      min: None,
+      calibrated_offset: 0.0,
+      powered_threshold: None,
+      normally_closed: Some(true),
+    }
+  }
+
+  fn trigger(name: &str, active: bool) -> Trigger {
+    Trigger {
+      name: name.to_owned(),
+      condition: "False".to_owned(),
+      script: "pass".to_owned(),
+      active,
+      reset: None,
+      hold_ms: 0,
+      cooldown_ms: 0,
+      fire_if_holding: false,
+      poll_ms: None,
+    }
+  }
+
+  fn configuration() -> Configuration {
+    Configuration {
+      mappings: vec![mapping("vent", 1), mapping("main", 2)],
+      triggers: vec![trigger("overpressure", true), trigger("leak", true)],
+      abort_sequence: None,
+      comms_policies: Vec::new(),
+      server_loss_policy: ServerLossPolicy::default(),
+    }
+  }
+
+  #[test]
+  fn digest_ignores_order() {
+    let mut reordered = configuration();
+    reordered.mappings.reverse();
+    reordered.triggers.reverse();
+
+    assert_eq!(configuration().digest(), reordered.digest());
+  }
+
+  #[test]
+  fn digest_ignores_inactive_triggers() {
+    let mut with_inactive = configuration();
+    with_inactive.triggers.push(trigger("disabled", false));
+
+    assert_eq!(configuration().digest(), with_inactive.digest());
+
+    // but deactivating one which was active changes what flight acts on
+    with_inactive.triggers[0].active = false;
+    assert_ne!(configuration().digest(), with_inactive.digest());
+  }
+
+  #[test]
+  fn digest_covers_what_flight_acts_on() {
+    let mut remapped = configuration();
+    remapped.mappings[0].channel = 3;
+    assert_ne!(configuration().digest(), remapped.digest());
+
+    let mut with_abort = configuration();
+    with_abort.abort_sequence = Some(Sequence {
+      name: "abort".to_owned(),
+      script: "vent.open()".to_owned(),
+    });
+    assert_ne!(configuration().digest(), with_abort.digest());
+  }
+}
@@ -1,37 +1,75 @@
-use super::{bms::Rail, flight::Ingestible, VehicleState};
+use super::{
+  bms::Rail,
+  flight::Ingestible,
+  sam::Unit,
+  Measurement,
+  VehicleState,
+};
 use postcard::experimental::max_size::MaxSize;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
 type Celsius = f64;
 type Bar = f64;
+type Meters = f64;
+type MetersPerSecond = f64;
+
+/// The name under which the filtered altitude is exposed to sequences.
+pub const ALTITUDE_SENSOR: &str = "altitude";
+
+/// The name under which the filtered vertical velocity is exposed to
+/// sequences.
+pub const VERTICAL_VELOCITY_SENSOR: &str = "vertical_velocity";
 
 /// Represents a vector
 #[derive(
   Deserialize, Serialize, Clone, Copy, MaxSize, Debug, PartialEq, Default,
 )]
 pub struct Vector {
-  x: f64,
-  y: f64,
-  z: f64,
+  /// The X component.
+  pub x: f64,
+  /// The Y component.
+  pub y: f64,
+  /// The Z component.
+  pub z: f64,
 }
 
 /// in units of Gs
-type Accelerometer = Vector;
+pub type Accelerometer = Vector;
 
 /// in units of degrees/second
-type Gyroscope = Vector;
+pub type Gyroscope = Vector;
 
 /// in units of Gauss
-type Magnetometer = Vector;
+pub type Magnetometer = Vector;
 
 /// Represents the state of the IMU
 #[derive(
   Deserialize, Serialize, Clone, Copy, MaxSize, Debug, PartialEq, Default,
 )]
 pub struct Imu {
-  accelerometer: Accelerometer,
-  gyroscope: Gyroscope,
+  /// The latest acceleration.
+  pub accelerometer: Accelerometer,
+  /// The latest angular rate.
+  pub gyroscope: Gyroscope,
+}
+
+/// Counts of IMU reads by outcome since the AHRS board started. Only good
+/// samples are used, the rest are dropped.
+#[derive(
+  Deserialize, Serialize, Clone, Copy, MaxSize, Debug, PartialEq, Default,
+)]
+pub struct ImuHealth {
+  /// Reads that passed every check.
+  pub good_samples: u32,
+  /// Reads corrupted in transfer, caught by the burst checksum.
+  pub checksum_failures: u32,
+  /// Reads flagging that the IMU overran its data path.
+  pub overruns: u32,
+  /// Reads flagging a failed gyroscope or accelerometer.
+  pub sensor_failures: u32,
+  /// Reads that failed in any other way.
+  pub other_failures: u32,
 }
 
 /// Represents the state of the Barometer
@@ -39,8 +77,80 @@ pub struct Imu {
   Deserialize, Serialize, Clone, Copy, MaxSize, Debug, PartialEq, Default,
 )]
 pub struct Barometer {
-  temperature: Celsius,
-  pressure: Bar,
+  /// The temperature measured by the barometer.
+  pub temperature: Celsius,
+  /// The static pressure.
+  pub pressure: Bar,
+}
+
+/// An orientation as a unit quaternion, rotating the body frame into the
+/// local level frame (x north, z up).
+#[derive(Deserialize, Serialize, Clone, Copy, MaxSize, Debug, PartialEq)]
+pub struct Quaternion {
+  /// The scalar part.
+  pub w: f64,
+  /// The X component of the vector part.
+  pub x: f64,
+  /// The Y component of the vector part.
+  pub y: f64,
+  /// The Z component of the vector part.
+  pub z: f64,
+}
+
+impl Default for Quaternion {
+  fn default() -> Self {
+    Quaternion {
+      w: 1.0,
+      x: 0.0,
+      y: 0.0,
+      z: 0.0,
+    }
+  }
+}
+
+/// An orientation as Z-Y-X Tait-Bryan angles, in degrees.
+#[derive(
+  Deserialize, Serialize, Clone, Copy, MaxSize, Debug, PartialEq, Default,
+)]
+pub struct EulerAngles {
+  /// Rotation about the body X axis.
+  pub roll: f64,
+  /// Rotation about the body Y axis.
+  pub pitch: f64,
+  /// Rotation about the vertical axis, from north.
+  pub yaw: f64,
+}
+
+/// The estimated attitude of the AHRS board.
+#[derive(
+  Deserialize, Serialize, Clone, Copy, MaxSize, Debug, PartialEq, Default,
+)]
+pub struct Attitude {
+  /// The orientation as a quaternion.
+  pub quaternion: Quaternion,
+  /// The same orientation as Euler angles.
+  pub euler: EulerAngles,
+  /// The angular rate about each body axis, in degrees/second.
+  pub angular_rate: Gyroscope,
+}
+
+/// The height of the AHRS board above the pad, from the barometer fused with
+/// the vertical acceleration measured by the IMU.
+#[derive(
+  Deserialize, Serialize, Clone, Copy, MaxSize, Debug, PartialEq, Default,
+)]
+pub struct Altitude {
+  /// Whether a pad reference has been captured. Until it has, every other
+  /// field is zero.
+  pub armed: bool,
+  /// The static pressure at the pad when the altimeter was armed.
+  pub reference_pressure: Bar,
+  /// The altitude from the barometer alone, unfiltered.
+  pub pressure_altitude: Meters,
+  /// The filtered altitude above the pad.
+  pub altitude: Meters,
+  /// The filtered vertical velocity, positive up.
+  pub vertical_velocity: MetersPerSecond,
 }
 
 /// Represents the state of AHRS as a whole
@@ -48,10 +158,22 @@ pub struct Barometer {
   Clone, Copy, MaxSize, Debug, Default, Deserialize, PartialEq, Serialize,
 )]
 pub struct Ahrs {
-  five_volt_rail: Rail,
-  imu: Imu,
-  magnetometer: Magnetometer,
-  barometer: Barometer,
+  /// The state of the 5V rail.
+  pub five_volt_rail: Rail,
+  /// The state of the IMU.
+  pub imu: Imu,
+  /// The bias correction the IMU applies to the gyroscope.
+  pub gyro_bias: Gyroscope,
+  /// How many reads of the IMU have succeeded or failed.
+  pub imu_health: ImuHealth,
+  /// The latest magnetic field, corrected for hard and soft iron.
+  pub magnetometer: Magnetometer,
+  /// The state of the barometer.
+  pub barometer: Barometer,
+  /// The attitude estimated from the IMU and magnetometer.
+  pub attitude: Attitude,
+  /// The altitude above the pad estimated from the barometer and IMU.
+  pub altitude: Altitude,
 }
 
 /// Represents the current state of a device on AHRS.
@@ -84,6 +206,31 @@ pub struct DataPoint {
 impl Ingestible for DataPoint {
   fn ingest(&self, vehicle_state: &mut VehicleState) {
     vehicle_state.ahrs = self.state;
+
+    // sequences read altitude like any other sensor, which reads as missing
+    // rather than zero until there is a pad reference to measure it from
+    let altitude = self.state.altitude;
+    let readings = &mut vehicle_state.sensor_readings;
+
+    if altitude.armed {
+      readings.insert(
+        ALTITUDE_SENSOR.to_owned(),
+        Measurement {
+          value: altitude.altitude,
+          unit: Unit::Meters,
+        },
+      );
+      readings.insert(
+        VERTICAL_VELOCITY_SENSOR.to_owned(),
+        Measurement {
+          value: altitude.vertical_velocity,
+          unit: Unit::MetersPerSecond,
+        },
+      );
+    } else {
+      readings.remove(ALTITUDE_SENSOR);
+      readings.remove(VERTICAL_VELOCITY_SENSOR);
+    }
   }
 }
 
@@ -92,12 +239,30 @@ impl Ingestible for DataPoint {
 pub enum Command {
   /// True if the camera should be enabled, False otherwise.
   CameraEnable(bool),
+
+  /// True if the current pressure should be captured as the pad reference
+  /// and altitude estimated from it, False to stop estimating altitude.
+  ArmAltimeter(bool),
+
+  /// Saves the IMU's current gyroscope bias correction to its flash, so it
+  /// is applied from power on.
+  PersistGyroBias,
+
+  /// True to start recording the magnetometer while the board is turned
+  /// through every orientation, False to stop and fit a new hard and soft
+  /// iron calibration to the recording.
+  CalibrateMagnetometer(bool),
 }
 
 impl fmt::Display for Command {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     match self {
       Self::CameraEnable(value) => write!(f, "Set CameraEnable to {}", value),
+      Self::ArmAltimeter(value) => write!(f, "Set ArmAltimeter to {}", value),
+      Self::PersistGyroBias => write!(f, "Persist the gyroscope bias"),
+      Self::CalibrateMagnetometer(value) => {
+        write!(f, "Set CalibrateMagnetometer to {}", value)
+      }
     }
   }
 }
@@ -29,8 +29,255 @@ pub struct Bms {
   pub charger: Current,
   pub e_stop: Voltage,
   pub rbf_tag: Voltage,
+  /// Whether the e-stop loop is open, debounced, or `None` if its sense line
+  /// isn't wired in the board definition or hasn't been read yet.
+  pub e_stop_tripped: Option<bool>,
+  /// Whether the remove-before-flight tag is inserted, debounced, or `None`
+  /// if its sense line isn't wired in the board definition or hasn't been
+  /// read yet.
+  pub rbf_tag_inserted: Option<bool>,
+  /// The state and progress of the charge controller.
+  pub charge: ChargeStatus,
+  /// The protections which have tripped since faults were last cleared.
+  pub faults: Faults,
+  /// The estimated state of charge of the battery, from 0 to 1.
+  pub state_of_charge: f64,
+  /// The charge drawn from the battery since it was last full, in Ah.
+  pub consumed_ah: f64,
+  /// The estimated time until the battery is empty at the recent average
+  /// current, in seconds, if it is discharging.
+  pub time_remaining: Option<f64>,
 }
 
+/// A condition that the BMS protects against on its own.
+#[derive(
+  Clone, Copy, Debug, Deserialize, Eq, Hash, MaxSize, PartialEq, Serialize,
+)]
+pub enum Protection {
+  /// The battery bus voltage falling below the threshold.
+  BatteryUndervoltage,
+  /// The battery bus current rising above the threshold.
+  BatteryOvercurrent,
+  /// The umbilical bus current rising above the threshold.
+  UmbilicalOvercurrent,
+  /// The SAM power bus current rising above the threshold.
+  SamOvercurrent,
+  /// The charger current rising above the threshold.
+  ChargerOvercurrent,
+}
+
+impl Protection {
+  /// Every protection the BMS implements.
+  pub const ALL: [Protection; 5] = [
+    Protection::BatteryUndervoltage,
+    Protection::BatteryOvercurrent,
+    Protection::UmbilicalOvercurrent,
+    Protection::SamOvercurrent,
+    Protection::ChargerOvercurrent,
+  ];
+
+  /// Gets the measurement this protection watches from the state of the BMS.
+  pub fn measure(&self, state: &Bms) -> f64 {
+    match self {
+      Self::BatteryUndervoltage => state.battery_bus.voltage,
+      Self::BatteryOvercurrent => state.battery_bus.current,
+      Self::UmbilicalOvercurrent => state.umbilical_bus.current,
+      Self::SamOvercurrent => state.sam_power_bus.current,
+      Self::ChargerOvercurrent => state.charger,
+    }
+  }
+
+  /// Determines if the given measurement violates the threshold. Measurements
+  /// which could not be read never violate a threshold.
+  pub fn violated_by(&self, value: f64, threshold: f64) -> bool {
+    match self {
+      Self::BatteryUndervoltage => value < threshold,
+      _ => value > threshold,
+    }
+  }
+}
+
+/// What the BMS does when a protection trips.
+///
+/// Every tripped protection is latched as a fault until the faults are
+/// cleared, regardless of its action.
+#[derive(
+  Clone, Copy, Debug, Deserialize, Eq, Hash, MaxSize, PartialEq, Serialize,
+)]
+pub enum ProtectionAction {
+  /// Open the battery load switch.
+  OpenBatteryLoadSwitch,
+  /// Open the SAM load switch.
+  OpenSamLoadSwitch,
+  /// Disable the charger.
+  DisableCharger,
+  /// Only latch the fault.
+  LatchFault,
+}
+
+/// A single on-board protection rule.
+#[derive(Clone, Copy, Debug, Deserialize, MaxSize, PartialEq, Serialize)]
+pub struct ProtectionLimit {
+  /// The condition this limit protects against.
+  pub protection: Protection,
+  /// Whether or not the limit is checked at all.
+  pub enabled: bool,
+  /// The voltage or current at which the limit is violated.
+  pub threshold: f64,
+  /// How long the limit must be continuously violated before tripping.
+  pub debounce_ms: u32,
+  /// What to do once the limit trips.
+  pub action: ProtectionAction,
+}
+
+/// The protections that have tripped since faults were last cleared.
+#[derive(
+  Clone, Copy, Debug, Default, Deserialize, MaxSize, PartialEq, Serialize,
+)]
+pub struct Faults {
+  /// Whether `Protection::BatteryUndervoltage` has tripped.
+  pub battery_undervoltage: bool,
+  /// Whether `Protection::BatteryOvercurrent` has tripped.
+  pub battery_overcurrent: bool,
+  /// Whether `Protection::UmbilicalOvercurrent` has tripped.
+  pub umbilical_overcurrent: bool,
+  /// Whether `Protection::SamOvercurrent` has tripped.
+  pub sam_overcurrent: bool,
+  /// Whether `Protection::ChargerOvercurrent` has tripped.
+  pub charger_overcurrent: bool,
+}
+
+impl Faults {
+  /// Determines if the given protection has tripped.
+  pub fn get(&self, protection: Protection) -> bool {
+    match protection {
+      Protection::BatteryUndervoltage => self.battery_undervoltage,
+      Protection::BatteryOvercurrent => self.battery_overcurrent,
+      Protection::UmbilicalOvercurrent => self.umbilical_overcurrent,
+      Protection::SamOvercurrent => self.sam_overcurrent,
+      Protection::ChargerOvercurrent => self.charger_overcurrent,
+    }
+  }
+
+  /// Marks the given protection as tripped or not.
+  pub fn set(&mut self, protection: Protection, tripped: bool) {
+    match protection {
+      Protection::BatteryUndervoltage => self.battery_undervoltage = tripped,
+      Protection::BatteryOvercurrent => self.battery_overcurrent = tripped,
+      Protection::UmbilicalOvercurrent => self.umbilical_overcurrent = tripped,
+      Protection::SamOvercurrent => self.sam_overcurrent = tripped,
+      Protection::ChargerOvercurrent => self.charger_overcurrent = tripped,
+    }
+  }
+
+  /// Determines if any protection has tripped.
+  pub fn any(&self) -> bool {
+    Protection::ALL.iter().any(|protection| self.get(*protection))
+  }
+}
+
+/// Why the charge controller stopped charging abnormally.
+#[derive(
+  Clone, Copy, Debug, Deserialize, Eq, Hash, MaxSize, PartialEq, Serialize,
+)]
+pub enum ChargeFault {
+  /// The battery was too deeply discharged to charge safely.
+  Undervoltage,
+  /// The battery voltage rose above its absolute maximum.
+  Overvoltage,
+  /// Precharge did not bring the battery up in time.
+  PrechargeTimeout,
+  /// The charge did not finish within the maximum charge time.
+  Timeout,
+  /// A protection on the charger tripped.
+  Protection,
+}
+
+/// The phase of charging the charge controller is in.
+///
+/// The charger regulates its own current and voltage, so the BMS only infers
+/// the phase from the battery voltage. It uses the phase to decide which
+/// timeout applies and when a tapering current means the charge is done.
+#[derive(
+  Clone, Copy, Debug, Default, Deserialize, Eq, MaxSize, PartialEq, Serialize,
+)]
+pub enum ChargerState {
+  /// Not charging.
+  #[default]
+  Idle,
+  /// Charging a deeply discharged battery at a reduced current.
+  Precharge,
+  /// Charging at the full charge current.
+  ConstantCurrent,
+  /// Holding the charge voltage while the current tapers off.
+  ConstantVoltage,
+  /// The charge terminated normally.
+  Done,
+  /// The charge was stopped because something went wrong.
+  Fault(ChargeFault),
+}
+
+/// The state and progress of the charge controller.
+#[derive(
+  Clone, Copy, Debug, Default, Deserialize, MaxSize, PartialEq, Serialize,
+)]
+pub struct ChargeStatus {
+  /// The phase of charging.
+  pub state: ChargerState,
+  /// Whether the charger is enabled, as read back from its enable line.
+  pub enabled: bool,
+  /// How long the current or most recent charge has run, in seconds.
+  pub elapsed: f64,
+  /// The charge delivered by the current or most recent charge, in Ah.
+  pub charged_ah: f64,
+}
+
+/// Which way the stand was e-stopped or reset.
+#[derive(
+  Clone, Copy, Debug, Deserialize, Eq, Hash, MaxSize, PartialEq, Serialize,
+)]
+pub enum EStopPath {
+  /// The e-stop loop, opened by a button and closed by the reset line.
+  Loop,
+  /// The remove-before-flight tag being inserted or removed.
+  RbfTag,
+  /// A reset commanded by the flight computer.
+  Command,
+}
+
+/// What changed in an e-stop event.
+#[derive(
+  Clone, Copy, Debug, Deserialize, Eq, Hash, MaxSize, PartialEq, Serialize,
+)]
+pub enum EStopEventKind {
+  /// The stand was e-stopped.
+  Tripped,
+  /// The stand was brought back out of e-stop.
+  Reset,
+  /// The remove-before-flight tag was inserted.
+  Inserted,
+  /// The remove-before-flight tag was removed.
+  Removed,
+}
+
+/// A single debounced change in e-stop state, sent to the flight computer as
+/// soon as it is seen and resent until the flight computer acknowledges it.
+#[derive(Clone, Copy, Debug, Deserialize, MaxSize, PartialEq, Serialize)]
+pub struct EStopEvent {
+  /// Counts up with every event since the BMS started, identifying the event
+  /// in its acknowledgement.
+  pub sequence: u32,
+  /// Whether the e-stop tripped or reset.
+  pub kind: EStopEventKind,
+  /// Which path caused the change.
+  pub path: EStopPath,
+  /// The raw e-stop sense voltage when the change was seen.
+  pub e_stop: Voltage,
+  /// The raw remove-before-flight sense voltage when the change was seen.
+  pub rbf_tag: Voltage,
+  /// The UNIX timestamp of when the change was seen.
+  pub timestamp: f64,
+}
 
 /// Represents the current state of a device on the BMS.
 /*#[derive(Deserialize, Serialize, Clone, MaxSize, Debug, PartialEq)]
@@ -84,6 +331,15 @@ pub enum Command {
   SamLoadSwitch(bool),
   /// If the Estop should be reset
   ResetEstop,
+  /// Run a self-test and send back a diagnostics report
+  SelfTest,
+  /// Replace the limit of one protection rule
+  SetProtectionLimit(ProtectionLimit),
+  /// Clear all latched faults
+  ClearFaults,
+  /// Acknowledge the e-stop event with this sequence number, so that it is
+  /// no longer resent
+  AcknowledgeEStop(u32),
 }
 
 impl fmt::Display for Command {
@@ -97,6 +353,14 @@ impl fmt::Display for Command {
         write!(f, "Set Sam Load Switch to {}", value)
       }
       Self::ResetEstop => write!(f, "Reset Estop"),
+      Self::SelfTest => write!(f, "Run Self-Test"),
+      Self::SetProtectionLimit(limit) => {
+        write!(f, "Set {:?} Limit to {:?}", limit.protection, limit)
+      }
+      Self::ClearFaults => write!(f, "Clear Faults"),
+      Self::AcknowledgeEStop(sequence) => {
+        write!(f, "Acknowledge E-Stop Event {sequence}")
+      }
     }
   }
 }
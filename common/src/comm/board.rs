@@ -0,0 +1,614 @@
+use super::ADCKind;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt, fs, io, ops::Range};
+
+/// Where the BeagleBone exposes the EEPROM of the first cape.
+pub const CAPE_EEPROM_PATH: &str = "/sys/bus/i2c/devices/2-0054/eeprom";
+
+/// Where the kernel exposes the hostname of the running board.
+const HOSTNAME_PATH: &str = "/proc/sys/kernel/hostname";
+
+// the cape EEPROM layout, as defined by the BeagleBone SRM
+const EEPROM_HEADER: [u8; 4] = [0xAA, 0x55, 0x33, 0xEE];
+const EEPROM_BOARD_NAME: Range<usize> = 6..38;
+const EEPROM_VERSION: Range<usize> = 38..42;
+
+/// Identifies a single GPIO by its controller and index on that controller.
+///
+/// The GPIO number used by the kernel is `controller * 32 + pin`.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct GpioPin {
+  /// The GPIO controller (bank) the pin belongs to.
+  pub controller: usize,
+
+  /// The index of the pin within its controller.
+  pub pin: usize,
+}
+
+/// Describes what a single input of an ADC measures and how its reading is
+/// scaled into that measurement.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct InputWiring {
+  /// The positive input (`AINx`) the measurement is taken on.
+  pub ain: u8,
+
+  /// What the input measures, named as the board software knows it, such as
+  /// `"battery_bus.voltage"`.
+  pub measurement: String,
+
+  /// What the reading is multiplied by once the offset is removed, such as
+  /// the ratio of a voltage divider.
+  #[serde(default = "unity")]
+  pub scale: f64,
+
+  /// What is subtracted from the reading before it is scaled, such as the
+  /// output of a current sense amplifier at zero current.
+  #[serde(default)]
+  pub offset: f64,
+}
+
+impl InputWiring {
+  /// Converts a reading of the input, in volts, into its measurement.
+  pub fn convert(&self, reading: f64) -> f64 {
+    (reading - self.offset) * self.scale
+  }
+}
+
+fn unity() -> f64 {
+  1.0
+}
+
+/// Describes how a single ADC is wired on a board.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct AdcWiring {
+  /// Which measurement the ADC is responsible for.
+  pub kind: ADCKind,
+
+  /// The active-low chip select of the ADC.
+  pub chip_select: GpioPin,
+
+  /// The active-low data ready line of the ADC, if it is wired.
+  pub data_ready: Option<GpioPin>,
+
+  /// How many input channels of the ADC are wired to something.
+  pub channels: u8,
+
+  /// What each input measures, in the order they are polled, for ADCs whose
+  /// inputs each measure something different. An input not listed here is
+  /// never muxed to.
+  #[serde(default)]
+  pub inputs: Vec<InputWiring>,
+}
+
+/// A description of everything on a single hardware revision of a board that
+/// software needs to know about.
+///
+/// Definitions are stored as JSON and loaded when the board starts up, so a
+/// new revision only requires a new definition rather than code changes.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct BoardDefinition {
+  /// The name of the hardware revision, such as `"sam-rev3"`.
+  pub revision: String,
+
+  /// Hostnames which identify this revision. A trailing `*` matches any
+  /// hostname with the preceding prefix.
+  #[serde(default)]
+  pub hostnames: Vec<String>,
+
+  /// Cape EEPROM IDs (board name and version) which identify this revision.
+  #[serde(default)]
+  pub eeprom_ids: Vec<String>,
+
+  /// Every ADC on the board, in the order they should be polled.
+  #[serde(default)]
+  pub adcs: Vec<AdcWiring>,
+
+  /// Chip selects which are not used but must be held inactive.
+  #[serde(default)]
+  pub unused_chip_selects: Vec<GpioPin>,
+
+  /// The driver of each valve, where index 0 is channel 1.
+  #[serde(default)]
+  pub valves: Vec<GpioPin>,
+
+  /// The sysfs brightness file of each LED, where index 0 is channel 0.
+  #[serde(default)]
+  pub leds: Vec<String>,
+
+  /// Any other named digital outputs, such as load switches.
+  #[serde(default)]
+  pub outputs: HashMap<String, GpioPin>,
+}
+
+/// An error encountered while loading or selecting a board definition.
+#[derive(Debug)]
+pub enum BoardDefinitionError {
+  /// The definitions file could not be read.
+  Io(io::Error),
+
+  /// The definitions could not be parsed.
+  Parse(serde_json::Error),
+
+  /// No definition matched the identity of this board.
+  UnknownRevision {
+    /// The hostname of the board, if it could be read.
+    hostname: Option<String>,
+
+    /// The cape EEPROM ID of the board, if it could be read.
+    eeprom_id: Option<String>,
+  },
+
+  /// The matching definition is missing something the software requires.
+  Incomplete {
+    /// The revision of the incomplete definition.
+    revision: String,
+
+    /// A description of what is missing.
+    missing: String,
+  },
+
+  /// The matching definition refers to a pin which does not exist.
+  InvalidPin {
+    /// The revision of the invalid definition.
+    revision: String,
+
+    /// The pin which does not exist.
+    pin: GpioPin,
+  },
+
+  /// The matching definition wires an ADC input which does not exist or is
+  /// already wired.
+  InvalidInput {
+    /// The revision of the invalid definition.
+    revision: String,
+
+    /// The ADC the input belongs to.
+    kind: ADCKind,
+
+    /// The input which is invalid.
+    ain: u8,
+  },
+
+  /// The matching definition names a measurement the software doesn't know.
+  UnknownMeasurement {
+    /// The revision of the invalid definition.
+    revision: String,
+
+    /// The measurement which isn't known.
+    measurement: String,
+  },
+}
+
+impl fmt::Display for BoardDefinitionError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Io(error) => write!(f, "could not read definitions: {error}"),
+      Self::Parse(error) => write!(f, "could not parse definitions: {error}"),
+      Self::UnknownRevision {
+        hostname,
+        eeprom_id,
+      } => write!(
+        f,
+        "no definition matches hostname {hostname:?} or EEPROM ID \
+         {eeprom_id:?}"
+      ),
+      Self::Incomplete { revision, missing } => {
+        write!(f, "definition '{revision}' is missing {missing}")
+      }
+      Self::InvalidPin { revision, pin } => write!(
+        f,
+        "definition '{revision}' refers to nonexistent pin {}.{}",
+        pin.controller, pin.pin
+      ),
+      Self::InvalidInput {
+        revision,
+        kind,
+        ain,
+      } => write!(
+        f,
+        "definition '{revision}' wires AIN{ain} of ADC {kind:?} more than \
+         once or beyond its channels"
+      ),
+      Self::UnknownMeasurement {
+        revision,
+        measurement,
+      } => write!(
+        f,
+        "definition '{revision}' names unknown measurement '{measurement}'"
+      ),
+    }
+  }
+}
+
+impl std::error::Error for BoardDefinitionError {}
+
+impl BoardDefinition {
+  /// Parses a JSON array of board definitions.
+  pub fn parse_all(
+    json: &str,
+  ) -> Result<Vec<BoardDefinition>, BoardDefinitionError> {
+    serde_json::from_str(json).map_err(BoardDefinitionError::Parse)
+  }
+
+  /// Loads the definitions at `path`, falling back to `default` (typically
+  /// embedded at compile time) if no such file exists, and selects the one
+  /// matching this board's EEPROM ID or hostname.
+  pub fn load(
+    path: &str,
+    default: &str,
+  ) -> Result<BoardDefinition, BoardDefinitionError> {
+    let definitions = match fs::read_to_string(path) {
+      Ok(json) => Self::parse_all(&json)?,
+      Err(error) if error.kind() == io::ErrorKind::NotFound => {
+        Self::parse_all(default)?
+      }
+      Err(error) => return Err(BoardDefinitionError::Io(error)),
+    };
+
+    Self::select(
+      definitions,
+      local_hostname().as_deref(),
+      read_eeprom_id(CAPE_EEPROM_PATH).as_deref(),
+    )
+  }
+
+  /// Picks the definition matching the given identity. The EEPROM ID takes
+  /// precedence over the hostname since it is burned into the hardware.
+  pub fn select(
+    definitions: Vec<BoardDefinition>,
+    hostname: Option<&str>,
+    eeprom_id: Option<&str>,
+  ) -> Result<BoardDefinition, BoardDefinitionError> {
+    let by_eeprom = eeprom_id.and_then(|id| {
+      definitions
+        .iter()
+        .position(|definition| definition.eeprom_ids.iter().any(|e| e == id))
+    });
+
+    let by_hostname = hostname.and_then(|hostname| {
+      definitions.iter().position(|definition| {
+        definition
+          .hostnames
+          .iter()
+          .any(|pattern| hostname_matches(pattern, hostname))
+      })
+    });
+
+    match by_eeprom.or(by_hostname) {
+      Some(index) => Ok(definitions.into_iter().nth(index).unwrap()),
+      None => Err(BoardDefinitionError::UnknownRevision {
+        hostname: hostname.map(str::to_owned),
+        eeprom_id: eeprom_id.map(str::to_owned),
+      }),
+    }
+  }
+
+  /// Ensures that every pin in the definition exists on a processor with the
+  /// given number of GPIO controllers, each with 32 pins.
+  pub fn validate(
+    &self,
+    controllers: usize,
+  ) -> Result<(), BoardDefinitionError> {
+    let pins = self
+      .chip_selects()
+      .chain(self.adcs.iter().filter_map(|adc| adc.data_ready))
+      .chain(self.valves.iter().copied())
+      .chain(self.outputs.values().copied());
+
+    for pin in pins {
+      if pin.controller >= controllers || pin.pin >= 32 {
+        return Err(BoardDefinitionError::InvalidPin {
+          revision: self.revision.clone(),
+          pin,
+        });
+      }
+    }
+
+    for adc in &self.adcs {
+      for (i, input) in adc.inputs.iter().enumerate() {
+        let repeated =
+          adc.inputs[..i].iter().any(|other| other.ain == input.ain);
+
+        if input.ain >= adc.channels || repeated {
+          return Err(BoardDefinitionError::InvalidInput {
+            revision: self.revision.clone(),
+            kind: adc.kind,
+            ain: input.ain,
+          });
+        }
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Ensures that every input names one of the given measurements, and that
+  /// each of the `required` measurements is wired.
+  pub fn require_measurements(
+    &self,
+    known: &[&str],
+    required: &[&str],
+  ) -> Result<(), BoardDefinitionError> {
+    let mut inputs = self.adcs.iter().flat_map(|adc| &adc.inputs);
+
+    if let Some(input) =
+      inputs.find(|input| !known.contains(&input.measurement.as_str()))
+    {
+      return Err(BoardDefinitionError::UnknownMeasurement {
+        revision: self.revision.clone(),
+        measurement: input.measurement.clone(),
+      });
+    }
+
+    for name in required {
+      if self.input(name).is_none() {
+        return Err(BoardDefinitionError::Incomplete {
+          revision: self.revision.clone(),
+          missing: format!("measurement '{name}'"),
+        });
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Ensures that every named output in `names` is defined.
+  pub fn require_outputs(
+    &self,
+    names: &[&str],
+  ) -> Result<(), BoardDefinitionError> {
+    for name in names {
+      if !self.outputs.contains_key(*name) {
+        return Err(BoardDefinitionError::Incomplete {
+          revision: self.revision.clone(),
+          missing: format!("output '{name}'"),
+        });
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Gets the wiring of the ADC of the given kind, if there is one.
+  pub fn adc(&self, kind: ADCKind) -> Option<&AdcWiring> {
+    self.adcs.iter().find(|adc| adc.kind == kind)
+  }
+
+  /// Gets the ADC input which takes the given measurement, if one does.
+  pub fn input(&self, measurement: &str) -> Option<&InputWiring> {
+    self
+      .adcs
+      .iter()
+      .flat_map(|adc| &adc.inputs)
+      .find(|input| input.measurement == measurement)
+  }
+
+  /// Gets the driver of a valve by its 1-indexed channel.
+  pub fn valve(&self, channel: u32) -> Option<GpioPin> {
+    let index = (channel as usize).checked_sub(1)?;
+    self.valves.get(index).copied()
+  }
+
+  /// Gets a named output.
+  pub fn output(&self, name: &str) -> Option<GpioPin> {
+    self.outputs.get(name).copied()
+  }
+
+  /// Every chip select on the board, used or not.
+  pub fn chip_selects(&self) -> impl Iterator<Item = GpioPin> + '_ {
+    self
+      .adcs
+      .iter()
+      .map(|adc| adc.chip_select)
+      .chain(self.unused_chip_selects.iter().copied())
+  }
+}
+
+fn hostname_matches(pattern: &str, hostname: &str) -> bool {
+  match pattern.strip_suffix('*') {
+    Some(prefix) => hostname.starts_with(prefix),
+    None => pattern == hostname,
+  }
+}
+
+/// Reads the hostname of the running board.
+pub fn local_hostname() -> Option<String> {
+  fs::read_to_string(HOSTNAME_PATH)
+    .ok()
+    .map(|hostname| hostname.trim().to_owned())
+    .filter(|hostname| !hostname.is_empty())
+}
+
+/// Reads the board name and version from a cape EEPROM, formatted as
+/// `"{name}-{version}"`. Returns `None` if there is no EEPROM or it has not
+/// been programmed.
+pub fn read_eeprom_id(path: &str) -> Option<String> {
+  let contents = fs::read(path).ok()?;
+
+  if contents.len() < EEPROM_VERSION.end || contents[..4] != EEPROM_HEADER {
+    return None;
+  }
+
+  let field = |range: Range<usize>| {
+    String::from_utf8_lossy(&contents[range])
+      .trim_matches(|c: char| c == '\0' || c.is_whitespace())
+      .to_owned()
+  };
+
+  Some(format!(
+    "{}-{}",
+    field(EEPROM_BOARD_NAME),
+    field(EEPROM_VERSION)
+  ))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::path::PathBuf;
+
+  const DEFINITIONS: &str = r#"[
+    {
+      "revision": "rev1",
+      "hostnames": ["bms-01"],
+      "eeprom_ids": ["BMS-0001"],
+      "adcs": [
+        {
+          "kind": "VBatUmbCharge",
+          "chip_select": { "controller": 0, "pin": 5 },
+          "data_ready": null,
+          "channels": 5,
+          "inputs": [
+            { "ain": 0, "measurement": "battery_bus.current", "scale": 2.0 },
+            { "ain": 4, "measurement": "charger", "offset": 0.25 }
+          ]
+        }
+      ]
+    },
+    {
+      "revision": "rev2",
+      "hostnames": ["bms-*"],
+      "eeprom_ids": ["BMS-0002"]
+    }
+  ]"#;
+
+  fn definitions() -> Vec<BoardDefinition> {
+    BoardDefinition::parse_all(DEFINITIONS).unwrap()
+  }
+
+  fn select(hostname: Option<&str>, eeprom_id: Option<&str>) -> String {
+    BoardDefinition::select(definitions(), hostname, eeprom_id)
+      .unwrap()
+      .revision
+  }
+
+  fn eeprom(name: &str, contents: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir()
+      .join(format!("board-eeprom-{}-{name}", std::process::id()));
+
+    fs::write(&path, contents).unwrap();
+    path
+  }
+
+  fn programmed(board_name: &[u8], version: &[u8]) -> Vec<u8> {
+    let mut contents = vec![0; 64];
+    contents[..4].copy_from_slice(&EEPROM_HEADER);
+    contents[EEPROM_BOARD_NAME][..board_name.len()].copy_from_slice(board_name);
+    contents[EEPROM_VERSION][..version.len()].copy_from_slice(version);
+    contents
+  }
+
+  #[test]
+  fn select_matches_exact_and_wildcard_hostnames() {
+    assert_eq!(select(Some("bms-01"), None), "rev1");
+    assert_eq!(select(Some("bms-07"), None), "rev2");
+  }
+
+  #[test]
+  fn select_prefers_eeprom_over_hostname() {
+    assert_eq!(select(Some("bms-01"), Some("BMS-0002")), "rev2");
+    assert_eq!(select(Some("bms-07"), Some("BMS-0001")), "rev1");
+  }
+
+  #[test]
+  fn select_falls_back_to_hostname_for_unknown_eeprom() {
+    assert_eq!(select(Some("bms-01"), Some("BMS-9999")), "rev1");
+  }
+
+  #[test]
+  fn select_rejects_unknown_identity() {
+    let result =
+      BoardDefinition::select(definitions(), Some("sam-01"), Some("SAM-0001"));
+
+    assert!(matches!(
+      result,
+      Err(BoardDefinitionError::UnknownRevision {
+        hostname: Some(_),
+        eeprom_id: Some(_),
+      })
+    ));
+
+    let result = BoardDefinition::select(definitions(), None, None);
+    assert!(matches!(
+      result,
+      Err(BoardDefinitionError::UnknownRevision { .. })
+    ));
+  }
+
+  #[test]
+  fn read_eeprom_id_trims_name_and_version() {
+    let path = eeprom("programmed", &programmed(b"BMS ", b"0001"));
+
+    assert_eq!(
+      read_eeprom_id(path.to_str().unwrap()).as_deref(),
+      Some("BMS-0001")
+    );
+
+    fs::remove_file(path).unwrap();
+  }
+
+  #[test]
+  fn read_eeprom_id_rejects_unprogrammed_eeproms() {
+    let mut blank = programmed(b"BMS", b"0001");
+    blank[..4].copy_from_slice(&[0xFF; 4]);
+    let path = eeprom("blank", &blank);
+    assert_eq!(read_eeprom_id(path.to_str().unwrap()), None);
+    fs::remove_file(path).unwrap();
+
+    let short = &programmed(b"BMS", b"0001")[..EEPROM_VERSION.end - 1];
+    let path = eeprom("short", short);
+    assert_eq!(read_eeprom_id(path.to_str().unwrap()), None);
+    fs::remove_file(path).unwrap();
+
+    assert_eq!(read_eeprom_id("/nonexistent/eeprom"), None);
+  }
+
+  #[test]
+  fn inputs_convert_with_scale_and_offset() {
+    let definitions = definitions();
+    let current = definitions[0].input("battery_bus.current").unwrap();
+    assert_eq!(current.convert(1.5), 3.0);
+
+    let charger = definitions[0].input("charger").unwrap();
+    assert_eq!(charger.scale, 1.0);
+    assert_eq!(charger.convert(1.25), 1.0);
+  }
+
+  #[test]
+  fn validate_rejects_invalid_inputs() {
+    let mut definition = definitions().remove(0);
+    assert!(definition.validate(4).is_ok());
+
+    definition.adcs[0].inputs[1].ain = 5;
+    assert!(matches!(
+      definition.validate(4),
+      Err(BoardDefinitionError::InvalidInput { ain: 5, .. })
+    ));
+
+    definition.adcs[0].inputs[1].ain = 0;
+    assert!(matches!(
+      definition.validate(4),
+      Err(BoardDefinitionError::InvalidInput { ain: 0, .. })
+    ));
+  }
+
+  #[test]
+  fn require_measurements_checks_names() {
+    let definition = definitions().remove(0);
+    let known = ["battery_bus.current", "charger"];
+
+    assert!(definition
+      .require_measurements(&known, &["charger"])
+      .is_ok());
+
+    assert!(matches!(
+      definition.require_measurements(&known[..1], &[]),
+      Err(BoardDefinitionError::UnknownMeasurement { .. })
+    ));
+
+    assert!(matches!(
+      definition.require_measurements(&known, &["battery_bus.voltage"]),
+      Err(BoardDefinitionError::Incomplete { .. })
+    ));
+  }
+}
@@ -0,0 +1,91 @@
+use serde::{Deserialize, Serialize};
+
+/// The health of a single ADC as observed during a self-test.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct AdcDiagnostics {
+  /// A human-readable name identifying which ADC this is on the board.
+  pub name: String,
+
+  /// The raw value of the ID register, or `None` if it could not be read.
+  pub id: Option<u8>,
+
+  /// The raw value of the STATUS register, or `None` if it could not be read.
+  pub status: Option<u8>,
+
+  /// Whether or not the DRDY line was pulled low within the expected time.
+  ///
+  /// This is `None` for ADCs which are not wired with a DRDY line.
+  pub data_ready: Option<bool>,
+
+  /// The number of SPI transfers to this ADC that have failed since startup.
+  pub spi_errors: u32,
+}
+
+/// A single voltage rail measured during a self-test.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct RailDiagnostics {
+  /// A human-readable name identifying the rail.
+  pub name: String,
+
+  /// The measured voltage of the rail, in volts.
+  pub voltage: f64,
+
+  /// The voltage the rail is expected to sit at, if known.
+  pub nominal: Option<f64>,
+
+  /// Whether or not the measured voltage falls within tolerance of nominal.
+  ///
+  /// Rails without a nominal voltage are always considered in range.
+  pub in_range: bool,
+}
+
+/// The result of reading back a digital output (such as a valve driver).
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct OutputDiagnostics {
+  /// A human-readable name identifying the output.
+  pub name: String,
+
+  /// The level that software last commanded the output to.
+  pub commanded: bool,
+
+  /// The level actually read back from the pin.
+  pub measured: bool,
+}
+
+/// A structured report produced by a board in response to a self-test.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct SelfTestReport {
+  /// The UNIX timestamp of when the self-test was performed.
+  pub timestamp: f64,
+
+  /// Diagnostics for every ADC on the board.
+  pub adcs: Vec<AdcDiagnostics>,
+
+  /// Every rail voltage measured by the board.
+  pub rails: Vec<RailDiagnostics>,
+
+  /// Read-back results for every digital output on the board.
+  pub outputs: Vec<OutputDiagnostics>,
+}
+
+impl AdcDiagnostics {
+  /// Determines if nothing about this ADC indicates a fault.
+  pub fn healthy(&self) -> bool {
+    self.id.is_some()
+      && self.status.is_some()
+      && self.data_ready != Some(false)
+      && self.spi_errors == 0
+  }
+}
+
+impl SelfTestReport {
+  /// Determines if every check in the report passed.
+  pub fn passed(&self) -> bool {
+    self.adcs.iter().all(AdcDiagnostics::healthy)
+      && self.rails.iter().all(|rail| rail.in_range)
+      && self
+        .outputs
+        .iter()
+        .all(|output| output.commanded == output.measured)
+  }
+}
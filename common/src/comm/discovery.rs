@@ -0,0 +1,318 @@
+use super::flight::{BoardKind, DataMessage};
+use jeflog::{fail, pass, warn};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+  env,
+  io,
+  net::{Ipv4Addr, SocketAddr, ToSocketAddrs, UdpSocket},
+  thread,
+  time::{Duration, Instant},
+};
+
+/// The port the flight computer broadcasts its announcements to.
+pub const DISCOVERY_PORT: u16 = 4574;
+
+/// How often the flight computer should broadcast its announcement.
+pub const ANNOUNCEMENT_PERIOD: Duration = Duration::from_millis(500);
+
+/// The environment variable holding the ID of the vehicle a computer belongs
+/// to.
+pub const VEHICLE_ID_VARIABLE: &str = "VEHICLE_ID";
+
+/// The vehicle ID used when none is configured.
+pub const DEFAULT_VEHICLE_ID: &str = "default";
+
+/// The environment variable which, if set, overrides discovery with a fixed
+/// flight computer hostname.
+pub const FLIGHT_HOSTNAME_VARIABLE: &str = "FLIGHT_HOSTNAME";
+
+/// The port data is exchanged with the flight computer on, on both ends.
+pub const FC_DATA_PORT: u16 = 4573;
+
+/// The port data boards receive commands from the flight computer on.
+pub const COMMAND_PORT: u16 = 8378;
+
+/// How long a data board goes without a flight computer heartbeat before it
+/// considers comms lost.
+pub const HEARTBEAT_TIME_LIMIT: Duration = Duration::from_millis(250);
+
+// how long to listen for a flight computer announcement before trying again
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(1);
+// how many times to send the identity before looking for the FC again
+const HANDSHAKE_ATTEMPTS: u32 = 5;
+// how long to give the FC to respond to each identity
+const HANDSHAKE_WAIT: Duration = Duration::from_millis(50);
+// the bounds of the exponential backoff between failed attempts
+const INITIAL_BACKOFF: Duration = Duration::from_millis(250);
+const MAX_BACKOFF: Duration = Duration::from_secs(8);
+
+/// Periodically broadcast by the flight computer so that data boards can
+/// locate it without knowing its hostname.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Announcement {
+  /// The vehicle the announcing flight computer belongs to. Boards ignore
+  /// announcements from other vehicles sharing the network.
+  pub vehicle_id: String,
+
+  /// The port on the flight computer that boards should send data to.
+  pub data_port: u16,
+}
+
+/// Gets the configured vehicle ID, falling back to the default.
+pub fn vehicle_id() -> String {
+  env::var(VEHICLE_ID_VARIABLE)
+    .ok()
+    .filter(|id| !id.is_empty())
+    .unwrap_or_else(|| DEFAULT_VEHICLE_ID.to_owned())
+}
+
+/// Gets the flight computer hostname override, if one is configured.
+pub fn hostname_override() -> Option<String> {
+  env::var(FLIGHT_HOSTNAME_VARIABLE)
+    .ok()
+    .filter(|hostname| !hostname.is_empty())
+}
+
+/// Broadcasts a single announcement on `socket`, which must have broadcasting
+/// enabled.
+pub fn announce(
+  socket: &UdpSocket,
+  announcement: &Announcement,
+) -> io::Result<()> {
+  let serialized = postcard::to_allocvec(announcement)
+    .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+
+  socket.send_to(&serialized, (Ipv4Addr::BROADCAST, DISCOVERY_PORT))?;
+  Ok(())
+}
+
+/// Listens for up to `timeout` for an announcement from the flight computer of
+/// the given vehicle, returning the address that data should be sent to.
+pub fn listen(
+  vehicle_id: &str,
+  timeout: Duration,
+) -> io::Result<Option<SocketAddr>> {
+  let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, DISCOVERY_PORT))?;
+  let deadline = Instant::now() + timeout;
+  let mut buffer = [0; 1024];
+
+  loop {
+    let remaining = deadline.saturating_duration_since(Instant::now());
+
+    if remaining.is_zero() {
+      return Ok(None);
+    }
+
+    socket.set_read_timeout(Some(remaining))?;
+
+    let (size, sender) = match socket.recv_from(&mut buffer) {
+      Ok(received) => received,
+      Err(error)
+        if matches!(
+          error.kind(),
+          io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+        ) =>
+      {
+        return Ok(None);
+      }
+      Err(error) => return Err(error),
+    };
+
+    // anything that isn't a well-formed announcement for this vehicle is
+    // someone else's business
+    match postcard::from_bytes::<Announcement>(&buffer[..size]) {
+      Ok(announcement) if announcement.vehicle_id == vehicle_id => {
+        return Ok(Some(SocketAddr::new(sender.ip(), announcement.data_port)));
+      }
+      _ => continue,
+    }
+  }
+}
+
+/// Locates the flight computer, either by resolving the configured hostname
+/// override or by listening for an announcement for the configured vehicle.
+pub fn locate_flight_computer(
+  data_port: u16,
+  timeout: Duration,
+) -> io::Result<Option<SocketAddr>> {
+  if let Some(hostname) = hostname_override() {
+    let address = format!("{hostname}:{data_port}")
+      .to_socket_addrs()?
+      .find(|address| address.is_ipv4());
+
+    return Ok(address);
+  }
+
+  listen(&vehicle_id(), timeout)
+}
+
+/// Connects a data board to the flight computer, blocking until it has
+/// handshaken with one. Returns the data socket, the command socket, and the
+/// address of the flight computer, in that order.
+///
+/// A flight computer which was `known` before comms were lost is tried first,
+/// before looking for it again.
+pub fn establish_flight_computer_connection(
+  board_id: &str,
+  kind: BoardKind,
+  known: Option<SocketAddr>,
+) -> (UdpSocket, UdpSocket, SocketAddr) {
+  let mut backoff = INITIAL_BACKOFF;
+
+  let (data_socket, command_socket) = loop {
+    match open_sockets() {
+      Ok(sockets) => break sockets,
+      Err(e) => {
+        warn!("Could not open sockets ({e}), retrying in {backoff:?}...");
+        backoff = wait(backoff);
+      }
+    }
+  };
+
+  // lets the flight computer know what kind of board this is and its ID
+  let identity = DataMessage::Identity(board_id.to_owned(), kind);
+  let packet = postcard::to_allocvec(&identity)
+    .expect("Could not create identity message send buffer");
+
+  if let Some(fc_address) = known {
+    if handshake(&data_socket, fc_address, &packet) {
+      pass!(
+        "Re-acquired flight computer at \x1b[1m{}\x1b[0m.",
+        fc_address.ip()
+      );
+      return (data_socket, command_socket, fc_address);
+    }
+
+    warn!("Flight computer did not respond, looking for it again...");
+  }
+
+  backoff = INITIAL_BACKOFF;
+
+  loop {
+    let fc_address =
+      match locate_flight_computer(FC_DATA_PORT, DISCOVERY_TIMEOUT) {
+        Ok(Some(address)) => address,
+        Ok(None) => {
+          warn!("Flight computer could not be located, retrying...");
+          continue;
+        }
+        Err(e) => {
+          warn!("Discovery failed ({e}), retrying in {backoff:?}...");
+          backoff = wait(backoff);
+          continue;
+        }
+      };
+
+    pass!(
+      "Flight computer located at \x1b[1m{}\x1b[0m.",
+      fc_address.ip()
+    );
+
+    if handshake(&data_socket, fc_address, &packet) {
+      return (data_socket, command_socket, fc_address);
+    }
+
+    warn!("Flight computer did not respond, retrying in {backoff:?}...");
+    backoff = wait(backoff);
+  }
+}
+
+// opens the data and command sockets, neither of which block so that the main
+// loop never waits on the flight computer
+fn open_sockets() -> io::Result<(UdpSocket, UdpSocket)> {
+  let data_socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, FC_DATA_PORT))?;
+  data_socket.set_nonblocking(true)?;
+
+  let command_socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, COMMAND_PORT))?;
+  command_socket.set_nonblocking(true)?;
+
+  Ok((data_socket, command_socket))
+}
+
+// sends the identity to the flight computer a few times, returning whether it
+// responded with its own
+fn handshake(socket: &UdpSocket, address: SocketAddr, packet: &[u8]) -> bool {
+  let mut buffer = [0; 1024];
+
+  for _ in 0..HANDSHAKE_ATTEMPTS {
+    if let Err(e) = socket.send_to(packet, address) {
+      warn!("Could not send identity message ({e}).");
+    }
+
+    thread::sleep(HANDSHAKE_WAIT);
+
+    let result = match socket.recv_from(&mut buffer) {
+      Ok((size, _)) => postcard::from_bytes::<DataMessage>(&buffer[..size]),
+      Err(_) => continue,
+    };
+
+    match result {
+      Ok(DataMessage::Identity(id, _)) => {
+        pass!("Connection established with flight computer {id}.");
+        return true;
+      }
+      Ok(DataMessage::FlightHeartbeat) => {
+        warn!("Recieved a heartbeat from the flight computer before identity.");
+      }
+      Ok(_) => {
+        warn!("Recieved nonsensical data from the flight computer.");
+      }
+      Err(e) => {
+        warn!("Could not deserialize recieved message ({e}).");
+      }
+    }
+  }
+
+  false
+}
+
+// sleeps for the given backoff, returning the next one
+fn wait(backoff: Duration) -> Duration {
+  thread::sleep(backoff);
+  (backoff * 2).min(MAX_BACKOFF)
+}
+
+/// Checks the data socket for a heartbeat from the flight computer. Returns
+/// when the last heartbeat was seen, to be passed in as `timer` next time, and
+/// whether comms have been lost.
+pub fn check_heartbeat(socket: &UdpSocket, timer: Instant) -> (Instant, bool) {
+  let mut buffer = [0; 256];
+
+  if Instant::now() - timer > HEARTBEAT_TIME_LIMIT {
+    return (timer, true);
+  }
+
+  let Ok((size, _)) = socket.recv_from(&mut buffer) else {
+    return (timer, false);
+  };
+
+  match postcard::from_bytes::<DataMessage>(&buffer[..size]) {
+    Ok(DataMessage::FlightHeartbeat) => (Instant::now(), false),
+    Ok(_) => {
+      warn!("Expected Flight Heartbeat was not detected.");
+      (timer, false)
+    }
+    Err(e) => {
+      warn!("Could not deserialize data from FC ({e}), continuing...");
+      (timer, false)
+    }
+  }
+}
+
+/// Receives a single command from the flight computer on the command socket,
+/// if one is waiting.
+pub fn receive_command<C: DeserializeOwned>(socket: &UdpSocket) -> Option<C> {
+  let mut buffer = [0; 1024];
+
+  let Ok((size, _)) = socket.recv_from(&mut buffer) else {
+    return None;
+  };
+
+  match postcard::from_bytes::<C>(&buffer[..size]) {
+    Ok(command) => Some(command),
+    Err(e) => {
+      fail!("Command was recieved but could not be deserialized ({e}).");
+      None
+    }
+  }
+}
@@ -1,17 +1,33 @@
-use super::{ahrs, bms, sam, VehicleState};
+use super::{ahrs, bms, diagnostics::SelfTestReport, sam, VehicleState};
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 
 /// String that represents the ID of a data board
 pub type BoardId = String;
 
+/// The kind of a board, which decides what it can be commanded to do.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum BoardKind {
+  /// A sensor and actuator module.
+  Sam,
+
+  /// The battery management system.
+  Bms,
+
+  /// The attitude and heading reference system.
+  Ahrs,
+
+  /// The flight computer itself.
+  Flight,
+}
+
 /// A generic data message that can originate from any subsystem to flight.
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub enum DataMessage<'a> {
   /// Represents the inital handshake between the FC and a data board.
   /// When FC recieves this from the data board, it'll reciprocate by
   /// sending one of its own.
-  Identity(BoardId),
+  Identity(BoardId, BoardKind),
 
   /// Flight computer will send this after no response from data board
   /// after extended period of time.
@@ -25,6 +41,28 @@ pub enum DataMessage<'a> {
 
   /// Data originating from Ahrs
   Ahrs(BoardId, Cow<'a, Vec<ahrs::DataPoint>>),
+
+  /// The result of a self-test requested by the flight computer.
+  Diagnostics(BoardId, Cow<'a, SelfTestReport>),
+
+  /// An e-stop trip or reset seen by the BMS.
+  EStop(BoardId, bms::EStopEvent),
+}
+
+impl DataMessage<'_> {
+  /// The ID of the board which sent the message, or `None` if it was sent by
+  /// the flight computer.
+  pub fn board_id(&self) -> Option<&BoardId> {
+    match self {
+      Self::Identity(board_id, _)
+      | Self::Sam(board_id, _)
+      | Self::Bms(board_id, _)
+      | Self::Ahrs(board_id, _)
+      | Self::Diagnostics(board_id, _)
+      | Self::EStop(board_id, _) => Some(board_id),
+      Self::FlightHeartbeat => None,
+    }
+  }
 }
 
 /// Defines how some data coming into the flight computer should be processed
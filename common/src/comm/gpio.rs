@@ -154,4 +154,17 @@ impl Pin {
       PinValue::Low
     }
   }
+
+  /// Reads back the level the pin is currently being driven to, as opposed to
+  /// the level measured on the pin by `digital_read`.
+  pub fn output_value(&self) -> PinValue {
+    let dataout = *self.gpio.dataout.lock().unwrap();
+    let dataout_bits = unsafe { read_volatile(dataout) };
+
+    if dataout_bits & (1 << self.index) != 0 {
+      PinValue::High
+    } else {
+      PinValue::Low
+    }
+  }
 }
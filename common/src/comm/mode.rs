@@ -0,0 +1,597 @@
+use super::{ValveState, VehicleState};
+use serde::{Deserialize, Serialize};
+use std::{fmt, fs, io};
+
+/// The name of the abort sequence, which every mode permits.
+const ABORT_SEQUENCE: &str = "abort";
+
+/// The operating mode of the vehicle, which limits which valves may be
+/// actuated and which sequences may be run.
+#[derive(
+  Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum VehicleMode {
+  /// Nothing on the vehicle should be actuated. The mode on startup.
+  #[default]
+  Safe,
+
+  /// The vehicle is being readied, but is not yet loaded.
+  Armed,
+
+  /// Propellants are being loaded.
+  Fill,
+
+  /// The vehicle is firing or about to.
+  Fire,
+
+  /// An abort has been triggered, by an operator or automatically.
+  Abort,
+}
+
+impl fmt::Display for VehicleMode {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "{}",
+      match self {
+        Self::Safe => "safe",
+        Self::Armed => "armed",
+        Self::Fill => "fill",
+        Self::Fire => "fire",
+        Self::Abort => "abort",
+      }
+    )
+  }
+}
+
+/// Which valves or sequences a mode permits, by name.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Permitted {
+  /// Every one is permitted.
+  All,
+
+  /// Only those named are permitted, so an empty list permits none.
+  Only(Vec<String>),
+}
+
+impl Permitted {
+  /// Whether the one with the given name is permitted.
+  pub fn permits(&self, name: &str) -> bool {
+    match self {
+      Self::All => true,
+      Self::Only(names) => names.iter().any(|permitted| permitted == name),
+    }
+  }
+}
+
+/// A condition on the vehicle which must hold for a transition to be made.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Guard {
+  /// The named sensor must have a reading within the given bounds, both of
+  /// which are inclusive and optional.
+  Sensor {
+    /// The name of the sensor, as in its mapping.
+    name: String,
+
+    /// The lowest permitted reading.
+    #[serde(default)]
+    min: Option<f64>,
+
+    /// The highest permitted reading.
+    #[serde(default)]
+    max: Option<f64>,
+  },
+
+  /// The named valve must actually be in the given state.
+  Valve {
+    /// The name of the valve, as in its mapping.
+    name: String,
+
+    /// The state the valve must be in.
+    state: ValveState,
+  },
+}
+
+impl Guard {
+  /// Checks the guard against the vehicle, describing why it doesn't hold if
+  /// it doesn't. A sensor without a reading never satisfies a guard.
+  pub fn check(&self, vehicle_state: &VehicleState) -> Result<(), String> {
+    match self {
+      Self::Sensor { name, min, max } => {
+        let Some(reading) = vehicle_state.sensor_readings.get(name) else {
+          return Err(format!("sensor '{name}' has no reading"));
+        };
+
+        let below = min.is_some_and(|min| reading.value < min);
+        let above = max.is_some_and(|max| reading.value > max);
+
+        // NaN compares false either way, so is rejected explicitly
+        if below || above || reading.value.is_nan() {
+          return Err(format!(
+            "sensor '{name}' reads {reading}, outside [{}, {}]",
+            bound(min),
+            bound(max)
+          ));
+        }
+      }
+      Self::Valve { name, state } => {
+        let actual = vehicle_state
+          .valve_states
+          .get(name)
+          .map_or(ValveState::Undetermined, |valve| valve.actual);
+
+        if actual != *state {
+          return Err(format!("valve '{name}' is {actual}, not {state}"));
+        }
+      }
+    }
+
+    Ok(())
+  }
+}
+
+fn bound(bound: &Option<f64>) -> String {
+  bound.map_or("-".to_owned(), |bound| bound.to_string())
+}
+
+/// A transition which may be requested out of a mode.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Transition {
+  /// The mode transitioned to.
+  pub to: VehicleMode,
+
+  /// Conditions which must all hold for the transition to be made.
+  #[serde(default)]
+  pub guards: Vec<Guard>,
+}
+
+/// What a single mode permits and which modes may be requested from it.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ModePolicy {
+  /// The mode described.
+  pub mode: VehicleMode,
+
+  /// The valves which may be actuated in this mode.
+  pub valves: Permitted,
+
+  /// The sequences which may be run in this mode. The abort sequence is
+  /// always permitted.
+  pub sequences: Permitted,
+
+  /// The transitions which an operator may request out of this mode. An
+  /// abort may happen from any mode, so needs no transition.
+  #[serde(default)]
+  pub transitions: Vec<Transition>,
+}
+
+/// The policies of every mode, which together form the mode state machine.
+///
+/// Tables are stored as JSON so that each test stand may define its own
+/// valves, sequences and guards without code changes.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ModeTable {
+  /// The policy of each mode, exactly one per mode.
+  pub modes: Vec<ModePolicy>,
+}
+
+/// An error encountered while loading a mode table.
+#[derive(Debug)]
+pub enum ModeTableError {
+  /// The table file could not be read.
+  Io(io::Error),
+
+  /// The table could not be parsed.
+  Parse(serde_json::Error),
+
+  /// The table has no policy for a mode.
+  Missing(VehicleMode),
+
+  /// The table has more than one policy for a mode.
+  Duplicate(VehicleMode),
+}
+
+impl fmt::Display for ModeTableError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Io(error) => write!(f, "could not read mode table: {error}"),
+      Self::Parse(error) => write!(f, "could not parse mode table: {error}"),
+      Self::Missing(mode) => write!(f, "mode table has no {mode} mode"),
+      Self::Duplicate(mode) => {
+        write!(f, "mode table defines {mode} mode more than once")
+      }
+    }
+  }
+}
+
+impl std::error::Error for ModeTableError {}
+
+/// Why a requested transition was refused.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TransitionError {
+  /// The current mode has no transition to the requested mode.
+  NotPermitted {
+    /// The current mode.
+    from: VehicleMode,
+
+    /// The requested mode.
+    to: VehicleMode,
+  },
+
+  /// A guard of the transition does not hold.
+  GuardFailed(String),
+
+  /// The abort mode was requested as a transition rather than by aborting.
+  AbortRequested,
+}
+
+impl fmt::Display for TransitionError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::NotPermitted { from, to } => {
+        write!(f, "{from} mode has no transition to {to} mode")
+      }
+      Self::GuardFailed(reason) => write!(f, "guard failed: {reason}"),
+      Self::AbortRequested => {
+        write!(f, "abort mode is only entered by aborting")
+      }
+    }
+  }
+}
+
+impl std::error::Error for TransitionError {}
+
+impl ModeTable {
+  /// Parses a mode table from JSON, ensuring every mode has one policy.
+  pub fn parse(json: &str) -> Result<ModeTable, ModeTableError> {
+    let table: ModeTable =
+      serde_json::from_str(json).map_err(ModeTableError::Parse)?;
+
+    let modes = [
+      VehicleMode::Safe,
+      VehicleMode::Armed,
+      VehicleMode::Fill,
+      VehicleMode::Fire,
+      VehicleMode::Abort,
+    ];
+
+    for mode in modes {
+      match table
+        .modes
+        .iter()
+        .filter(|policy| policy.mode == mode)
+        .count()
+      {
+        0 => return Err(ModeTableError::Missing(mode)),
+        1 => {}
+        _ => return Err(ModeTableError::Duplicate(mode)),
+      }
+    }
+
+    Ok(table)
+  }
+
+  /// Loads the table at `path`, falling back to `default` (typically embedded
+  /// at compile time) if no such file exists.
+  pub fn load(path: &str, default: &str) -> Result<ModeTable, ModeTableError> {
+    match fs::read_to_string(path) {
+      Ok(json) => Self::parse(&json),
+      Err(error) if error.kind() == io::ErrorKind::NotFound => {
+        Self::parse(default)
+      }
+      Err(error) => Err(ModeTableError::Io(error)),
+    }
+  }
+
+  /// Gets the policy of a mode.
+  pub fn policy(&self, mode: VehicleMode) -> &ModePolicy {
+    // every mode is checked for when the table is parsed
+    self
+      .modes
+      .iter()
+      .find(|policy| policy.mode == mode)
+      .expect("mode table is missing a mode")
+  }
+
+  /// Whether the valve may be actuated in the given mode.
+  pub fn permits_valve(&self, mode: VehicleMode, valve: &str) -> bool {
+    self.policy(mode).valves.permits(valve)
+  }
+
+  /// Whether the sequence may be run in the given mode.
+  pub fn permits_sequence(&self, mode: VehicleMode, sequence: &str) -> bool {
+    sequence == ABORT_SEQUENCE || self.policy(mode).sequences.permits(sequence)
+  }
+
+  /// Checks whether an operator may move the vehicle between modes in its
+  /// current state. Requesting the current mode is always permitted. The
+  /// abort mode is never a transition, as only an abort may enter it.
+  pub fn check_transition(
+    &self,
+    from: VehicleMode,
+    to: VehicleMode,
+    vehicle_state: &VehicleState,
+  ) -> Result<(), TransitionError> {
+    if from == to {
+      return Ok(());
+    }
+
+    if to == VehicleMode::Abort {
+      return Err(TransitionError::AbortRequested);
+    }
+
+    let transition = self
+      .policy(from)
+      .transitions
+      .iter()
+      .find(|transition| transition.to == to)
+      .ok_or(TransitionError::NotPermitted { from, to })?;
+
+    for guard in &transition.guards {
+      guard
+        .check(vehicle_state)
+        .map_err(TransitionError::GuardFailed)?;
+    }
+
+    Ok(())
+  }
+}
+
+/// What caused a change of mode.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModeChangeCause {
+  /// An operator requested the change from the control server.
+  Operator,
+
+  /// An abort forced the change, whoever or whatever triggered it.
+  Abort,
+}
+
+/// A record of a change of mode, or a refused request for one, which the
+/// flight computer reports to the control server to be audited.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ModeChange {
+  /// The mode before the change.
+  pub from: VehicleMode,
+
+  /// The mode changed to, or requested.
+  pub to: VehicleMode,
+
+  /// What caused the change.
+  pub cause: ModeChangeCause,
+
+  /// Who asked for the change, as reported by the control server, or `None`
+  /// if it was forced by an abort.
+  pub requested_by: Option<String>,
+
+  /// Why the change was refused, or `None` if it was made.
+  pub rejection: Option<String>,
+
+  /// The UNIX timestamp of the change on the flight computer.
+  pub timestamp: f64,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::comm::{sam::Unit, CompositeValveState, Measurement};
+
+  const TABLE: &str = r#"{
+    "modes": [
+      {
+        "mode": "safe",
+        "valves": { "only": [] },
+        "sequences": { "only": [] },
+        "transitions": [{ "to": "armed" }]
+      },
+      {
+        "mode": "armed",
+        "valves": { "only": ["vent"] },
+        "sequences": "all",
+        "transitions": [
+          { "to": "safe" },
+          {
+            "to": "fill",
+            "guards": [
+              { "sensor": { "name": "tank", "max": 50.0 } },
+              { "valve": { "name": "vent", "state": "closed" } }
+            ]
+          }
+        ]
+      },
+      {
+        "mode": "fill",
+        "valves": "all",
+        "sequences": "all",
+        "transitions": [{ "to": "armed" }]
+      },
+      {
+        "mode": "fire",
+        "valves": "all",
+        "sequences": "all"
+      },
+      {
+        "mode": "abort",
+        "valves": { "only": ["vent"] },
+        "sequences": { "only": [] },
+        "transitions": [{ "to": "safe" }]
+      }
+    ]
+  }"#;
+
+  fn with_sensor(value: f64) -> VehicleState {
+    let mut state = VehicleState::new();
+
+    state.sensor_readings.insert(
+      "tank".to_owned(),
+      Measurement {
+        value,
+        unit: Unit::Psi,
+      },
+    );
+
+    state
+  }
+
+  fn with_valve(mut state: VehicleState, actual: ValveState) -> VehicleState {
+    state.valve_states.insert(
+      "vent".to_owned(),
+      CompositeValveState {
+        commanded: actual,
+        actual,
+      },
+    );
+
+    state
+  }
+
+  fn tank(min: Option<f64>, max: Option<f64>) -> Guard {
+    Guard::Sensor {
+      name: "tank".to_owned(),
+      min,
+      max,
+    }
+  }
+
+  #[test]
+  fn parses_the_shipped_table() {
+    ModeTable::parse(include_str!("../../../flight/modes.json")).unwrap();
+  }
+
+  #[test]
+  fn rejects_missing_and_duplicate_modes() {
+    let mut table: serde_json::Value = serde_json::from_str(TABLE).unwrap();
+    let modes = table["modes"].as_array_mut().unwrap();
+
+    let fire = modes.remove(3);
+    let missing = serde_json::to_string(&table).unwrap();
+
+    assert!(matches!(
+      ModeTable::parse(&missing),
+      Err(ModeTableError::Missing(VehicleMode::Fire))
+    ));
+
+    let modes = table["modes"].as_array_mut().unwrap();
+    modes.push(fire.clone());
+    modes.push(fire);
+    let duplicate = serde_json::to_string(&table).unwrap();
+
+    assert!(matches!(
+      ModeTable::parse(&duplicate),
+      Err(ModeTableError::Duplicate(VehicleMode::Fire))
+    ));
+  }
+
+  #[test]
+  fn checks_sensor_bounds_inclusively() {
+    let guard = tank(Some(10.0), Some(50.0));
+
+    assert!(guard.check(&with_sensor(10.0)).is_ok());
+    assert!(guard.check(&with_sensor(50.0)).is_ok());
+    assert!(guard.check(&with_sensor(9.9)).is_err());
+    assert!(guard.check(&with_sensor(50.1)).is_err());
+
+    // an open bound permits anything on that side
+    assert!(tank(None, Some(50.0)).check(&with_sensor(-1e9)).is_ok());
+    assert!(tank(Some(10.0), None).check(&with_sensor(1e9)).is_ok());
+  }
+
+  #[test]
+  fn rejects_nan_and_missing_sensors() {
+    assert!(tank(None, None).check(&with_sensor(f64::NAN)).is_err());
+    assert!(tank(Some(0.0), None).check(&with_sensor(f64::NAN)).is_err());
+    assert!(tank(None, None).check(&VehicleState::new()).is_err());
+  }
+
+  #[test]
+  fn checks_the_actual_valve_state() {
+    let guard = Guard::Valve {
+      name: "vent".to_owned(),
+      state: ValveState::Closed,
+    };
+
+    let closed = with_valve(VehicleState::new(), ValveState::Closed);
+    assert!(guard.check(&closed).is_ok());
+
+    // commanded closed but still open isn't enough
+    let mut opening = with_valve(VehicleState::new(), ValveState::Open);
+    opening.valve_states.get_mut("vent").unwrap().commanded =
+      ValveState::Closed;
+    assert!(guard.check(&opening).is_err());
+
+    // an unmapped valve is undetermined
+    assert!(guard.check(&VehicleState::new()).is_err());
+  }
+
+  #[test]
+  fn permits_only_listed_transitions() {
+    let table = ModeTable::parse(TABLE).unwrap();
+    let state = VehicleState::new();
+
+    assert!(table
+      .check_transition(VehicleMode::Safe, VehicleMode::Armed, &state)
+      .is_ok());
+    assert!(table
+      .check_transition(VehicleMode::Safe, VehicleMode::Safe, &state)
+      .is_ok());
+    assert_eq!(
+      table.check_transition(VehicleMode::Safe, VehicleMode::Fire, &state),
+      Err(TransitionError::NotPermitted {
+        from: VehicleMode::Safe,
+        to: VehicleMode::Fire,
+      })
+    );
+  }
+
+  #[test]
+  fn requires_every_guard() {
+    let table = ModeTable::parse(TABLE).unwrap();
+    let (armed, fill) = (VehicleMode::Armed, VehicleMode::Fill);
+
+    let ready = with_valve(with_sensor(20.0), ValveState::Closed);
+    assert!(table.check_transition(armed, fill, &ready).is_ok());
+
+    let pressurized = with_valve(with_sensor(80.0), ValveState::Closed);
+    assert!(matches!(
+      table.check_transition(armed, fill, &pressurized),
+      Err(TransitionError::GuardFailed(_))
+    ));
+
+    let venting = with_valve(with_sensor(20.0), ValveState::Open);
+    assert!(matches!(
+      table.check_transition(armed, fill, &venting),
+      Err(TransitionError::GuardFailed(_))
+    ));
+  }
+
+  #[test]
+  fn never_transitions_into_abort() {
+    let table = ModeTable::parse(TABLE).unwrap();
+    let state = VehicleState::new();
+
+    for from in [VehicleMode::Safe, VehicleMode::Armed, VehicleMode::Fire] {
+      assert_eq!(
+        table.check_transition(from, VehicleMode::Abort, &state),
+        Err(TransitionError::AbortRequested)
+      );
+    }
+
+    // but leaving it is an ordinary transition
+    assert!(table
+      .check_transition(VehicleMode::Abort, VehicleMode::Safe, &state)
+      .is_ok());
+  }
+
+  #[test]
+  fn always_permits_the_abort_sequence() {
+    let table = ModeTable::parse(TABLE).unwrap();
+
+    assert!(table.permits_sequence(VehicleMode::Safe, ABORT_SEQUENCE));
+    assert!(!table.permits_sequence(VehicleMode::Safe, "fill"));
+    assert!(table.permits_valve(VehicleMode::Armed, "vent"));
+    assert!(!table.permits_valve(VehicleMode::Armed, "main"));
+  }
+}
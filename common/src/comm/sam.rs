@@ -37,6 +37,12 @@ pub enum Unit {
 
   /// Electric potential, in volts.
   Volts,
+
+  /// Distance, in meters.
+  Meters,
+
+  /// Speed, in meters per second.
+  MetersPerSecond,
 }
 
 /// Represents all possible channel types that may be used in a `NodeMapping`.
@@ -168,6 +174,10 @@ pub enum SamControlMessage {
     /// Set to `true` to turn off and `false` to turn off.
     on: bool,
   },
+
+  /// Instructs the board to run a self-test and send back a diagnostics
+  /// report.
+  SelfTest,
 }
 
 /// A single data point with a timestamp and channel, no units.
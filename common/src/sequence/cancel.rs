@@ -0,0 +1,208 @@
+use super::CancelledError;
+use pyo3::{ffi, PyErr, PyResult, PyTypeInfo, Python};
+use std::{
+  cell::RefCell,
+  os::raw::c_long,
+  ptr,
+  sync::{Arc, Condvar, Mutex},
+  thread::{self, ThreadId},
+  time::Duration,
+};
+
+thread_local! {
+  /// The cancellation of the sequence running on this thread, if any.
+  static CURRENT: RefCell<Option<Cancellation>> = const { RefCell::new(None) };
+}
+
+#[derive(Debug, Default)]
+struct Status {
+  cancelled: bool,
+  exited: bool,
+
+  /// The Rust and Python identifiers of the thread the sequence is running on,
+  /// while it is running.
+  thread: Option<(ThreadId, Option<c_long>)>,
+}
+
+/// Allows a running sequence to be cancelled from another thread, and that
+/// thread to wait until the sequence has actually exited.
+///
+/// Every blocking function and device access in the sequences library checks
+/// for cancellation, and a blocked sequence is woken immediately. A sequence
+/// busy executing Python has `CancelledError` raised into its thread instead.
+#[derive(Clone, Debug, Default)]
+pub struct Cancellation {
+  inner: Arc<(Mutex<Status>, Condvar)>,
+}
+
+impl Cancellation {
+  /// Constructs a new `Cancellation` for a sequence which hasn't started.
+  pub fn new() -> Self {
+    Cancellation::default()
+  }
+
+  /// Cancels the sequence, which has no effect if it has already exited.
+  pub fn cancel(&self) {
+    let (status, changed) = &*self.inner;
+
+    let mut locked = status.lock().unwrap();
+    locked.cancelled = true;
+    changed.notify_all();
+
+    let thread = locked.thread;
+    drop(locked);
+
+    // a sequence cancelling itself, as when it calls abort, raises once it
+    // next calls into the library instead
+    let Some((thread_id, Some(ident))) = thread else {
+      return;
+    };
+
+    if thread_id == thread::current().id() {
+      return;
+    }
+
+    Python::with_gil(|py| {
+      // checked again with the GIL held, which the sequence also holds when
+      // exiting, so that the exception can't be raised after it has exited
+      if status.lock().unwrap().thread.is_some() {
+        let exception = CancelledError::type_object_raw(py);
+
+        unsafe {
+          ffi::PyThreadState_SetAsyncExc(ident, exception.cast());
+        }
+      }
+    });
+  }
+
+  /// Whether the sequence has been cancelled.
+  pub fn is_cancelled(&self) -> bool {
+    self.inner.0.lock().unwrap().cancelled
+  }
+
+  /// Waits up to `timeout` for the sequence to exit, returning whether it has.
+  pub fn wait_for_exit(&self, timeout: Duration) -> bool {
+    let (status, changed) = &*self.inner;
+
+    // the sequence can't exit without the GIL, which the caller may be holding
+    // if it is itself a sequence
+    Python::with_gil(|py| {
+      py.allow_threads(|| {
+        let status = status.lock().unwrap();
+
+        let (status, _) = changed
+          .wait_timeout_while(status, timeout, |status| !status.exited)
+          .unwrap();
+
+        status.exited
+      })
+    })
+  }
+
+  /// Sleeps for `duration`, waking early and returning `true` if cancelled.
+  fn sleep(&self, duration: Duration) -> bool {
+    let (status, changed) = &*self.inner;
+    let status = status.lock().unwrap();
+
+    let (status, _) = changed
+      .wait_timeout_while(status, duration, |status| !status.cancelled)
+      .unwrap();
+
+    status.cancelled
+  }
+
+  /// Marks the sequence as running on the current thread until the returned
+  /// guard is dropped.
+  pub(crate) fn enter(&self) -> Running {
+    let ident = Python::with_gil(|py| {
+      py.import("threading")
+        .and_then(|threading| threading.call_method0("get_ident"))
+        .and_then(|ident| ident.extract::<c_long>())
+        .ok()
+    });
+
+    self.inner.0.lock().unwrap().thread = Some((thread::current().id(), ident));
+
+    // restored on exit, as the abort sequence may run within another sequence
+    let previous = CURRENT.with(|current| current.replace(Some(self.clone())));
+
+    Running {
+      cancellation: self.clone(),
+      previous,
+    }
+  }
+}
+
+/// Cancellations are equal only if they belong to the same sequence.
+impl PartialEq for Cancellation {
+  fn eq(&self, other: &Self) -> bool {
+    Arc::ptr_eq(&self.inner, &other.inner)
+  }
+}
+
+impl Eq for Cancellation {}
+
+/// Guard which marks a sequence as exited when dropped.
+pub(crate) struct Running {
+  cancellation: Cancellation,
+  previous: Option<Cancellation>,
+}
+
+impl Drop for Running {
+  fn drop(&mut self) {
+    CURRENT.with(|current| current.replace(self.previous.take()));
+
+    let (status, changed) = &*self.cancellation.inner;
+
+    Python::with_gil(|_| {
+      let mut status = status.lock().unwrap();
+
+      // clears an exception raised into the thread which arrived too late to
+      // interrupt the sequence, so that it doesn't interrupt whatever is next
+      if let Some((_, Some(ident))) = status.thread {
+        if status.cancelled {
+          unsafe {
+            ffi::PyThreadState_SetAsyncExc(ident, ptr::null_mut());
+          }
+        }
+      }
+
+      status.thread = None;
+      status.exited = true;
+      changed.notify_all();
+    });
+  }
+}
+
+fn cancelled() -> PyErr {
+  CancelledError::new_err("sequence was cancelled")
+}
+
+/// Fails with `CancelledError` if the sequence running on the current thread
+/// has been cancelled.
+pub(crate) fn check() -> PyResult<()> {
+  CURRENT.with(|current| match &*current.borrow() {
+    Some(cancellation) if cancellation.is_cancelled() => Err(cancelled()),
+    _ => Ok(()),
+  })
+}
+
+/// Sleeps the current thread without holding the GIL, waking early with
+/// `CancelledError` if the sequence running on it is cancelled.
+pub(crate) fn sleep(py: Python<'_>, duration: Duration) -> PyResult<()> {
+  let cancellation = CURRENT.with(|current| current.borrow().clone());
+
+  let was_cancelled = py.allow_threads(|| match cancellation {
+    Some(cancellation) => cancellation.sleep(duration),
+    None => {
+      thread::sleep(duration);
+      false
+    }
+  });
+
+  if was_cancelled {
+    return Err(cancelled());
+  }
+
+  Ok(())
+}
@@ -1,6 +1,5 @@
-use super::{DeviceAction, DEVICE_HANDLER};
+use super::{cancel, handle_device, DeviceAction};
 use crate::comm::ValveState;
-use jeflog::fail;
 use pyo3::{
   pyclass,
   pyclass::CompareOp,
@@ -32,17 +31,15 @@ impl Sensor {
 
   /// Reads the latest sensor measurements by indexing into the global vehicle
   /// state.
-  pub fn read(&self) -> PyObject {
-    let Some(device_handler) = &*DEVICE_HANDLER.lock().unwrap() else {
-      fail!("Device handler not set before accessing external device.");
-      return Python::with_gil(|py| PyNone::get(py).to_object(py));
-    };
+  pub fn read(&self, py: Python<'_>) -> PyResult<PyObject> {
+    cancel::check()?;
 
-    device_handler(&self.name, DeviceAction::ReadSensor)
+    let reading = handle_device(py, &self.name, DeviceAction::ReadSensor);
+    Ok(reading.unwrap_or_else(|| PyNone::get(py).to_object(py)))
   }
 
   fn __richcmp__(&self, other: &PyAny, op: CompareOp) -> PyResult<bool> {
-    other.rich_compare(self.read(), op)?.is_true()
+    other.rich_compare(self.read(other.py())?, op)?.is_true()
   }
 }
 
@@ -62,57 +59,53 @@ impl Valve {
   }
 
   /// Determines if the valve is open.
-  pub fn is_open(&self) -> Option<bool> {
-    let Some(device_handler) = &*DEVICE_HANDLER.lock().unwrap() else {
-      fail!("Device handler not set before accessing external device.");
-      return None;
-    };
+  pub fn is_open(&self, py: Python<'_>) -> PyResult<Option<bool>> {
+    cancel::check()?;
 
-    let state = device_handler(&self.name, DeviceAction::ReadValveState);
+    let Some(state) =
+      handle_device(py, &self.name, DeviceAction::ReadValveState)
+    else {
+      return Ok(None);
+    };
 
-    Python::with_gil(|py| {
-      let open: Py<PyAny> = "open".into_py(py);
-      state.into_ref(py).eq(open).ok()
-    })
+    let open: Py<PyAny> = "open".into_py(py);
+    Ok(state.into_ref(py).eq(open).ok())
   }
 
   /// Determines if the values is closed.
-  pub fn is_closed(&self) -> Option<bool> {
-    let Some(device_handler) = &*DEVICE_HANDLER.lock().unwrap() else {
-      fail!("Device handler not set before accessing external device.");
-      return None;
-    };
+  pub fn is_closed(&self, py: Python<'_>) -> PyResult<Option<bool>> {
+    cancel::check()?;
 
-    let state = device_handler(&self.name, DeviceAction::ReadValveState);
+    let Some(state) =
+      handle_device(py, &self.name, DeviceAction::ReadValveState)
+    else {
+      return Ok(None);
+    };
 
-    Python::with_gil(|py| {
-      let closed: Py<PyString> = "closed".into_py(py);
-      state.into_ref(py).eq(closed).ok()
-    })
+    let closed: Py<PyString> = "closed".into_py(py);
+    Ok(state.into_ref(py).eq(closed).ok())
   }
 
   /// Instructs the SAM board to open the valve.
-  pub fn open(&self) {
-    self.actuate(true);
+  pub fn open(&self, py: Python<'_>) -> PyResult<()> {
+    self.actuate(py, true)
   }
 
   /// Instructs the SAM board to close the valve.
-  pub fn close(&self) {
-    self.actuate(false);
+  pub fn close(&self, py: Python<'_>) -> PyResult<()> {
+    self.actuate(py, false)
   }
 
   /// Instructs the SAM board to actuate a valve.
-  pub fn actuate(&self, open: bool) {
-    let Some(device_handler) = &*DEVICE_HANDLER.lock().unwrap() else {
-      fail!("Device handler not set before accessing external device.");
-      return;
-    };
+  pub fn actuate(&self, py: Python<'_>, open: bool) -> PyResult<()> {
+    cancel::check()?;
 
     let state = if open {
       ValveState::Open
     } else {
       ValveState::Closed
     };
-    device_handler(&self.name, DeviceAction::ActuateValve { state });
+    handle_device(py, &self.name, DeviceAction::ActuateValve { state });
+    Ok(())
   }
 }
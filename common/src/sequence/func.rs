@@ -1,16 +1,22 @@
-use super::{DeviceAction, DEVICE_HANDLER};
+use super::{cancel, handle_device, DeviceAction};
 use crate::sequence::unit::Duration;
 
-use jeflog::fail;
-use pyo3::{pyclass, pyfunction, pymethods, PyAny, PyRef, PyRefMut, PyResult};
-use std::{thread, time::Instant};
+use pyo3::{
+  pyclass,
+  pyfunction,
+  pymethods,
+  PyAny,
+  PyRef,
+  PyRefMut,
+  PyResult,
+  Python,
+};
+use std::time::Instant;
 
 /// A Python-exposed function which waits the thread for the given duration.
 #[pyfunction]
-pub fn wait_for(duration: Duration) {
-  // TODO: considering using a different way to sleep, possibly sleeping only
-  // the GIL?
-  thread::sleep(duration.into());
+pub fn wait_for(py: Python<'_>, duration: Duration) -> PyResult<()> {
+  cancel::sleep(py, duration.into())
 }
 
 /// A Python-exposed function which waits until a condition function is true,
@@ -21,15 +27,19 @@ pub fn wait_until(
   timeout: Option<Duration>,
   poll_interval: Option<Duration>,
 ) -> PyResult<()> {
-  let timeout = timeout.map_or(std::time::Duration::MAX, Into::into);
-
   let interval =
     poll_interval.map_or(std::time::Duration::from_millis(10), Into::into);
 
-  let end_time = Instant::now() + timeout;
+  // without a timeout there is no end time, as none would be representable
+  let end_time =
+    timeout.and_then(|timeout| Instant::now().checked_add(timeout.into()));
+
+  cancel::check()?;
 
-  while !condition.call0()?.is_true()? && Instant::now() < end_time {
-    thread::sleep(interval);
+  while !condition.call0()?.is_true()?
+    && !end_time.is_some_and(|end_time| Instant::now() >= end_time)
+  {
+    cancel::sleep(condition.py(), interval)?;
   }
 
   Ok(())
@@ -37,13 +47,8 @@ pub fn wait_until(
 
 /// A Python-exposed function which immediately runs the abort sequence.
 #[pyfunction]
-pub fn abort() {
-  let Some(device_handler) = &*DEVICE_HANDLER.lock().unwrap() else {
-    fail!("Device handler not set before accessing external device.");
-    return;
-  };
-
-  device_handler("", DeviceAction::Abort);
+pub fn abort(py: Python<'_>) {
+  handle_device(py, "", DeviceAction::Abort);
 }
 
 /// Iterator which only yields the iteration after waiting for the given period.
@@ -62,13 +67,13 @@ impl IntervalIterator {
     _self
   }
 
-  fn __next__(mut _self: PyRefMut<'_, Self>) -> Option<i64> {
+  fn __next__(mut _self: PyRefMut<'_, Self>) -> PyResult<Option<i64>> {
     if _self.iteration >= _self.total {
-      return None;
+      return Ok(None);
     }
 
     let wait = _self.next_tick - Instant::now();
-    thread::sleep(wait);
+    cancel::sleep(_self.py(), wait)?;
 
     let iteration = _self.iteration;
     let next_tick = _self.next_tick + _self.period;
@@ -76,7 +81,7 @@ impl IntervalIterator {
     _self.next_tick = next_tick;
     _self.iteration += 1;
 
-    Some(iteration)
+    Ok(Some(iteration))
   }
 }
 
@@ -1,13 +1,24 @@
+mod cancel;
 mod device;
 mod func;
+mod output;
 mod unit;
 
+pub use cancel::Cancellation;
 pub use device::*;
 pub use exceptions::*;
 pub use func::*;
+pub use output::{set_output_handler, Output};
 pub use unit::*;
 
-use crate::comm::{NodeMapping, SensorType, Sequence, ValveState};
+use crate::comm::{
+  ahrs::{ALTITUDE_SENSOR, VERTICAL_VELOCITY_SENSOR},
+  NodeMapping,
+  SensorType,
+  Sequence,
+  SequenceOutcome,
+  ValveState,
+};
 use jeflog::{fail, warn};
 use std::sync::{Arc, Mutex, OnceLock};
 
@@ -32,16 +43,26 @@ mod exceptions {
   use pyo3::create_exception;
 
   create_exception!(sequences, AbortError, pyo3::exceptions::PyException);
+
+  // derived from BaseException, like KeyboardInterrupt, so that a sequence
+  // catching every Exception can't accidentally keep itself running
+  create_exception!(
+    sequences,
+    CancelledError,
+    pyo3::exceptions::PyBaseException
+  );
 }
 
 #[pymodule]
 fn sequences(py: Python<'_>, module: &PyModule) -> PyResult<()> {
   module.add_class::<Current>()?;
+  module.add_class::<Distance>()?;
   module.add_class::<Duration>()?;
   module.add_class::<ElectricPotential>()?;
   module.add_class::<Force>()?;
   module.add_class::<Pressure>()?;
   module.add_class::<Temperature>()?;
+  module.add_class::<Velocity>()?;
 
   module.add("A", Py::new(py, Current::new(1.0))?)?;
   module.add("mA", Py::new(py, Current::new(0.001))?)?;
@@ -53,11 +74,21 @@ fn sequences(py: Python<'_>, module: &PyModule) -> PyResult<()> {
   module.add("lbf", Py::new(py, Force::new(1.0))?)?;
   module.add("psi", Py::new(py, Pressure::new(1.0))?)?;
   module.add("K", Py::new(py, Temperature::new(1.0))?)?;
+  module.add("m", Py::new(py, Distance::new(1.0))?)?;
+  module.add("ft", Py::new(py, Distance::new(0.3048))?)?;
+  module.add("mps", Py::new(py, Velocity::new(1.0))?)?;
+  module.add("fps", Py::new(py, Velocity::new(0.3048))?)?;
 
   module.add_class::<Sensor>()?;
   module.add_class::<Valve>()?;
   module.add_class::<IntervalIterator>()?;
 
+  module.add("CancelledError", py.get_type::<CancelledError>())?;
+
+  // the module is imported before every sequence runs, so this is in place
+  // before any of them can print
+  py.import("sys")?.setattr("stdout", Py::new(py, Output)?)?;
+
   module.add_function(wrap_pyfunction!(wait_for, module)?)?;
   module.add_function(wrap_pyfunction!(wait_until, module)?)?;
   module.add_function(wrap_pyfunction!(abort, module)?)?;
@@ -66,7 +97,7 @@ fn sequences(py: Python<'_>, module: &PyModule) -> PyResult<()> {
   Ok(())
 }
 
-type DeviceHandler = dyn Fn(&str, DeviceAction) -> PyObject + Send;
+type DeviceHandler = dyn Fn(&str, DeviceAction) -> PyObject + Send + Sync;
 
 // let's break this one down:
 // Mutex<...> - required because this is a global variable, so needed to
@@ -75,15 +106,16 @@ type DeviceHandler = dyn Fn(&str, DeviceAction) -> PyObject + Send;
 // Option<...> - before initialization by set_device_handler, this will be None,
 //   so necessary for the compiler to be happy.
 //
-// Box<dyn ...> - wraps the enclosed dynamic type on the heap, because it's
-//   exact size and type are unknown at compile-time.
+// Arc<dyn ...> - wraps the enclosed dynamic type on the heap, because it's
+//   exact size and type are unknown at compile-time, and lets it be cloned out
+//   so that the mutex isn't held while the handler runs.
 //
 // Fn(&str, DeviceAction) -> Option<Measurement> - the trait bound of the type
 //   of the closure being stored, with its arguments and return value.
 //
-// + Send - requires that everything captured in the closure be safe to send
-//   across threads.
-pub(crate) static DEVICE_HANDLER: Mutex<Option<Box<DeviceHandler>>> =
+// + Send + Sync - requires that everything captured in the closure be safe to
+//   send across threads and to call from several at once.
+pub(crate) static DEVICE_HANDLER: Mutex<Option<Arc<DeviceHandler>>> =
   Mutex::new(None);
 
 pub(crate) static MAPPINGS: OnceLock<Arc<Mutex<Vec<NodeMapping>>>> =
@@ -128,35 +160,75 @@ pub enum DeviceAction {
 /// `Option<Measurement>` because in the event of a read, a measurement will
 /// need to be returned, but a valve actuation requires no return.
 pub fn set_device_handler(
-  handler: impl Fn(&str, DeviceAction) -> PyObject + Send + 'static,
+  handler: impl Fn(&str, DeviceAction) -> PyObject + Send + Sync + 'static,
 ) {
   let Ok(mut device_handler) = DEVICE_HANDLER.lock() else {
     fail!("Failed to lock global device handler: Mutex is poisoned.");
     return;
   };
 
-  *device_handler = Some(Box::new(handler));
+  *device_handler = Some(Arc::new(handler));
+}
+
+/// Hands an action to the device handler, returning `None` if none is set.
+///
+/// The GIL is released while the handler runs, so a handler which blocks
+/// doesn't stall every other sequence and trigger. The handler must acquire
+/// the GIL itself to construct its return value.
+pub(crate) fn handle_device(
+  py: Python<'_>,
+  device: &str,
+  action: DeviceAction,
+) -> Option<PyObject> {
+  // cloned out so that the lock isn't held while the handler runs, since
+  // aborting runs the abort sequence, which uses the handler itself
+  let handler = DEVICE_HANDLER.lock().unwrap().clone();
+
+  let Some(handler) = handler else {
+    fail!("Device handler not set before accessing external device.");
+    return None;
+  };
+
+  Some(py.allow_threads(|| handler(device, action)))
 }
 
-// TODO: change the run function to return an error in the event of one instead
-//of printing out the error.
+/// Runs a sequence, returning how it ended. The `initialize` function must be
+/// called before this.
+///
+/// Anything the sequence prints is given to the output handler. The sequence
+/// exits early if `cancellation` is cancelled, which is marked as exited once
+/// it has.
+pub fn run(sequence: Sequence, cancellation: Cancellation) -> SequenceOutcome {
+  let _running = cancellation.enter();
+  let _capturing = output::capture(&sequence.name);
 
-/// Runs a sequence. The `initialize` function must be called before this.
-pub fn run(sequence: Sequence) {
   let Some(mappings) = MAPPINGS.get() else {
-    fail!("Sequences library must be initialized before running a sequence.");
-    return;
+    return not_started(
+      "sequences library must be initialized before running a sequence",
+    );
   };
 
   let Ok(mappings) = mappings.lock() else {
-    fail!("Mappings could not be locked within common::sequence::run.");
-    return;
+    return not_started("mappings could not be locked");
   };
 
   Python::with_gil(|py| {
     if let Err(error) = py.run("from sequences import *", None, None) {
-      fail!("Failed to import sequences library: {error}");
-      return;
+      return not_started(format!(
+        "failed to import sequences library: {error}"
+      ));
+    }
+
+    // sensors derived on the vehicle rather than mapped to a board channel,
+    // defined first so that a mapping with the same name takes precedence
+    for name in [ALTITUDE_SENSOR, VERTICAL_VELOCITY_SENSOR] {
+      let definition = format!("{name} = Sensor('{name}')");
+
+      if let Err(error) = py.run(&definition, None, None) {
+        return not_started(format!(
+          "failed to define built-in sensor '{name}': {error}"
+        ));
+      }
     }
 
     for mapping in &*mappings {
@@ -166,19 +238,39 @@ pub fn run(sequence: Sequence) {
       };
 
       if let Err(error) = py.run(&definition, None, None) {
-        fail!(
-          "Failed to define '{}' as a mapping: {error}",
+        return not_started(format!(
+          "failed to define '{}' as a mapping: {error}",
           mapping.text_id
-        );
-        return;
+        ));
       }
     }
 
     // drop the lock before entering script to prevent deadlock
     drop(mappings);
 
-    if let Err(error) = py.run(&sequence.script, None, None) {
-      fail!("Failed to run sequence '{}': {error}", sequence.name);
+    // cancelled before it could start, so it is never raised into the thread
+    if cancellation.is_cancelled() {
+      return SequenceOutcome::Cancelled;
     }
-  });
+
+    match py.run(&sequence.script, None, None) {
+      Ok(()) => SequenceOutcome::Finished,
+      Err(error) if error.is_instance_of::<CancelledError>(py) => {
+        SequenceOutcome::Cancelled
+      }
+      Err(error) => SequenceOutcome::Raised {
+        error: error.to_string(),
+        traceback: error
+          .traceback(py)
+          .and_then(|traceback| traceback.format().ok()),
+      },
+    }
+  })
+}
+
+fn not_started(error: impl Into<String>) -> SequenceOutcome {
+  SequenceOutcome::Raised {
+    error: error.into(),
+    traceback: None,
+  }
 }
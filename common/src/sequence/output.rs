@@ -0,0 +1,129 @@
+use jeflog::fail;
+use pyo3::{pyclass, pymethods, Python};
+use std::{
+  cell::RefCell,
+  io::{self, Write},
+  mem,
+  sync::{Arc, Mutex},
+};
+
+thread_local! {
+  /// The output of the sequence running on this thread, if any.
+  static CAPTURE: RefCell<Option<Capture>> = const { RefCell::new(None) };
+}
+
+type OutputHandler = dyn Fn(&str, &str) + Send + Sync;
+
+static OUTPUT_HANDLER: Mutex<Option<Arc<OutputHandler>>> = Mutex::new(None);
+
+/// The output of a single sequence, collected until a full line is written.
+#[derive(Debug)]
+struct Capture {
+  name: String,
+  line: String,
+}
+
+/// Sets the output handler callback, which receives each line printed by a
+/// sequence.
+///
+/// The first argument of this callback is the name of the sequence and the
+/// second is the line printed, without its newline. Without a handler, lines
+/// are printed to standard output as usual.
+pub fn set_output_handler(
+  handler: impl Fn(&str, &str) + Send + Sync + 'static,
+) {
+  let Ok(mut output_handler) = OUTPUT_HANDLER.lock() else {
+    fail!("Failed to lock global output handler: Mutex is poisoned.");
+    return;
+  };
+
+  *output_handler = Some(Arc::new(handler));
+}
+
+/// Replaces `sys.stdout`, sending what each sequence prints to the output
+/// handler under its name. Anything printed outside of a sequence, such as by
+/// a trigger condition, still goes to standard output.
+#[pyclass]
+#[derive(Clone, Debug)]
+pub struct Output;
+
+#[pymethods]
+impl Output {
+  fn write(&self, py: Python<'_>, text: &str) -> usize {
+    let lines = CAPTURE.with(|capture| {
+      let mut capture = capture.borrow_mut();
+
+      let capture = capture.as_mut()?;
+
+      capture.line.push_str(text);
+
+      // only complete lines are handed over, the rest waiting for a newline
+      let Some(end) = capture.line.rfind('\n') else {
+        return Some((capture.name.clone(), Vec::new()));
+      };
+
+      let rest = capture.line.split_off(end + 1);
+      let complete = mem::replace(&mut capture.line, rest);
+
+      let lines = complete.lines().map(str::to_owned).collect::<Vec<_>>();
+      Some((capture.name.clone(), lines))
+    });
+
+    match lines {
+      Some((name, lines)) => {
+        py.allow_threads(|| lines.iter().for_each(|line| emit(&name, line)))
+      }
+      None => {
+        let mut stdout = io::stdout();
+        _ = stdout.write_all(text.as_bytes());
+        _ = stdout.flush();
+      }
+    }
+
+    text.len()
+  }
+
+  fn flush(&self) {}
+}
+
+/// Hands a line to the output handler, or prints it if there is none.
+fn emit(name: &str, line: &str) {
+  let handler = OUTPUT_HANDLER.lock().unwrap().clone();
+
+  match handler {
+    Some(handler) => handler(name, line),
+    None => println!("{line}"),
+  }
+}
+
+/// Captures the output of the sequence with the given name on the current
+/// thread until the returned guard is dropped.
+pub(crate) fn capture(name: &str) -> Capturing {
+  let capture = Capture {
+    name: name.to_owned(),
+    line: String::new(),
+  };
+
+  // restored on exit, as the abort sequence may run within another sequence
+  let previous = CAPTURE.with(|current| current.replace(Some(capture)));
+
+  Capturing { previous }
+}
+
+/// Guard which stops capturing output when dropped, handing over a final line
+/// which was never ended.
+pub(crate) struct Capturing {
+  previous: Option<Capture>,
+}
+
+impl Drop for Capturing {
+  fn drop(&mut self) {
+    let capture = CAPTURE.with(|current| current.replace(self.previous.take()));
+
+    if let Some(capture) = capture {
+      if !capture.line.is_empty() {
+        emit(&capture.name, &capture.line);
+      }
+    }
+  }
+}
@@ -76,11 +76,13 @@ macro_rules! create_unit {
 }
 
 create_unit!(Current, "A");
+create_unit!(Distance, "m");
 create_unit!(Duration, "s");
 create_unit!(ElectricPotential, "V");
 create_unit!(Force, "lbf");
 create_unit!(Pressure, "psi");
 create_unit!(Temperature, "K");
+create_unit!(Velocity, "m/s");
 
 impl From<Duration> for std::time::Duration {
   fn from(value: Duration) -> Self {
@@ -104,6 +106,8 @@ impl IntoPy<PyObject> for Measurement {
       Unit::Pounds => Force::new(self.value).into_py(py),
       Unit::Psi => Pressure::new(self.value).into_py(py),
       Unit::Volts => ElectricPotential::new(self.value).into_py(py),
+      Unit::Meters => Distance::new(self.value).into_py(py),
+      Unit::MetersPerSecond => Velocity::new(self.value).into_py(py),
     }
   }
 }
@@ -37,6 +37,17 @@ const FSCAL1_LOCATION: usize = 0x0F;
 const GPIODAT_LOCATION: usize = 0x10;
 const GPIOCON_LOCATION: usize = 0x11;
 
+/// The name of every register, indexed by its location.
+pub const REGISTER_NAMES: [&str; 18] = [
+  "ID", "STATUS", "INPMUX", "PGA", "DATARATE", "REF", "IDACMAG", "IDACMUX",
+  "VBIAS", "SYS", "RESERVED0", "OFCAL0", "OFCAL1", "RESERVED1", "FSCAL0",
+  "FSCAL1", "GPIODAT", "GPIOCON",
+];
+
+// bits 2-0 of the ID register identify the part
+const DEV_ID_MASK: u8 = 0b00000111;
+const ADS114S06_DEV_ID: u8 = 0b101;
+
 #[derive(Debug)]
 pub enum ADCError {
   InvalidPositiveInputMux,
@@ -54,6 +65,7 @@ pub enum ADCError {
   InvalidGpioNum,
   WritingToGpioInput,
   OutOfBoundsRegisterRead,
+  OutOfBoundsRegisterWrite,
   ForbiddenRegisterWrite,
   SPI(io::Error),
 }
@@ -70,6 +82,8 @@ pub struct ADC {
   pub cs_pin: Option<Pin>,
   pub kind: ADCKind,
   pub current_reg_vals: [u8; 18],
+  // how many SPI transfers have failed since the ADC was opened
+  pub spi_errors: u32,
 }
 
 impl ADC {
@@ -102,6 +116,7 @@ impl ADC {
       cs_pin,
       kind,
       current_reg_vals: [0; 18],
+      spi_errors: 0,
     };
 
     // possibly redundant based on how user handles drdy pin
@@ -111,6 +126,17 @@ impl ADC {
     Ok(adc)
   }
 
+  // performs a transfer, keeping count of any that fail
+  fn transfer(&mut self, transfer: &mut SpidevTransfer) -> io::Result<()> {
+    let result = self.spidev.transfer(transfer);
+
+    if result.is_err() {
+      self.spi_errors += 1;
+    }
+
+    result
+  }
+
   pub fn enable_chip_select(&mut self) {
     if let Some(ref mut pin) = self.cs_pin {
       pin.digital_write(Low); // active low
@@ -929,7 +955,7 @@ impl ADC {
     self.enable_chip_select();
     let tx_buf: [u8; 1] = [0x00];
     let mut transfer = SpidevTransfer::write(&tx_buf);
-    let result = self.spidev.transfer(&mut transfer);
+    let result = self.transfer(&mut transfer);
     self.disable_chip_select();
     match result {
       Ok(_) => Ok(()),
@@ -941,7 +967,7 @@ impl ADC {
     self.enable_chip_select();
     let tx_buf: [u8; 1] = [0x02];
     let mut transfer = SpidevTransfer::write(&tx_buf);
-    let result = self.spidev.transfer(&mut transfer);
+    let result = self.transfer(&mut transfer);
     self.disable_chip_select();
     match result {
       Ok(_) => Ok(()),
@@ -953,7 +979,7 @@ impl ADC {
     self.enable_chip_select();
     let tx_buf: [u8; 1] = [0x04];
     let mut transfer = SpidevTransfer::write(&tx_buf);
-    let result = self.spidev.transfer(&mut transfer);
+    let result = self.transfer(&mut transfer);
     self.disable_chip_select();
     match result {
       Ok(_) => Ok(()),
@@ -976,7 +1002,7 @@ impl ADC {
     self.enable_chip_select();
     let tx_buf: [u8; 1] = [0x06];
     let mut transfer = SpidevTransfer::write(&tx_buf);
-    let result = self.spidev.transfer(&mut transfer);
+    let result = self.transfer(&mut transfer);
     self.disable_chip_select();
     // wait 1 ms before any other commands
     thread::sleep(time::Duration::from_micros(1100));
@@ -994,7 +1020,7 @@ impl ADC {
     self.enable_chip_select();
     let tx_buf: [u8; 1] = [0x08];
     let mut transfer = SpidevTransfer::write(&tx_buf);
-    let result = self.spidev.transfer(&mut transfer);
+    let result = self.transfer(&mut transfer);
     self.disable_chip_select();
     thread::sleep(time::Duration::from_micros(1100));
     match result {
@@ -1007,7 +1033,7 @@ impl ADC {
     self.enable_chip_select();
     let tx_buf: [u8; 1] = [0x0A];
     let mut transfer = SpidevTransfer::write(&tx_buf);
-    let result = self.spidev.transfer(&mut transfer);
+    let result = self.transfer(&mut transfer);
     self.disable_chip_select();
     match result {
       Ok(_) => Ok(()),
@@ -1026,7 +1052,7 @@ impl ADC {
     let tx_buf: [u8; 3] = [0x12, 0x00, 0x00];
     let mut rx_buf: [u8; 3] = [0x00, 0x00, 0x00];
     let mut transfer = SpidevTransfer::read_write(&tx_buf, &mut rx_buf);
-    let result = self.spidev.transfer(&mut transfer);
+    let result = self.transfer(&mut transfer);
     self.disable_chip_select();
     match result {
       Ok(_) => Ok(((rx_buf[1] as i16) << 8) | (rx_buf[2] as i16)),
@@ -1043,7 +1069,7 @@ impl ADC {
     let tx_buf: [u8; 2] = [0x20 | (reg as u8), 0x00];
     let mut rx_buf: [u8; 2] = [0x00, 0x00];
     let mut transfer = SpidevTransfer::read_write(&tx_buf, &mut rx_buf);
-    let result = self.spidev.transfer(&mut transfer);
+    let result = self.transfer(&mut transfer);
     self.disable_chip_select();
     match result {
       Ok(_) => Ok(rx_buf[1]),
@@ -1063,7 +1089,7 @@ impl ADC {
     ];
     let mut rx_buf: [u8; 20] = [0; 20];
     let mut transfer = SpidevTransfer::read_write(&tx_buf, &mut rx_buf);
-    let result = self.spidev.transfer(&mut transfer);
+    let result = self.transfer(&mut transfer);
     self.disable_chip_select();
     match result {
       Ok(_) => {
@@ -1075,6 +1101,26 @@ impl ADC {
     }
   }
 
+  /*
+  Writes a whole register, keeping current_reg_vals in step. This is meant
+  for bring-up and debugging, the setters above are safer for everything else
+  */
+  pub fn write_reg(&mut self, reg: usize, data: u8) -> Result<(), ADCError> {
+    if reg > 17 {
+      return Err(ADCError::OutOfBoundsRegisterWrite);
+    }
+    self.spi_write_reg(reg, data)?;
+    self.current_reg_vals[reg] = data;
+    Ok(())
+  }
+
+  // checks that the ID register belongs to an ADS114S06
+  pub fn validate(&mut self) -> bool {
+    self
+      .spi_read_reg(ID_LOCATION)
+      .is_ok_and(|id| id & DEV_ID_MASK == ADS114S06_DEV_ID)
+  }
+
   fn spi_write_reg(&mut self, reg: usize, data: u8) -> Result<(), ADCError> {
     if reg == RESERVED0_LOCATION || reg == RESERVED1_LOCATION {
       return Err(ADCError::ForbiddenRegisterWrite);
@@ -1082,7 +1128,7 @@ impl ADC {
     self.enable_chip_select();
     let tx_buf: [u8; 3] = [0x40 | (reg as u8), 0x00, data];
     let mut transfer = SpidevTransfer::write(&tx_buf);
-    let result = self.spidev.transfer(&mut transfer);
+    let result = self.transfer(&mut transfer);
     self.disable_chip_select();
     match result {
       Ok(_) => Ok(()),
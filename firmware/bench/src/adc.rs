@@ -0,0 +1,169 @@
+use crate::{hold_idle_chip_selects, parse_number, pin, Stream};
+use ads114s06::{ADCError, ADC, REGISTER_NAMES};
+use anyhow::{anyhow, bail};
+use clap::ArgMatches;
+use common::comm::{board::GpioPin, gpio::PinValue::Low, ADCKind};
+use jeflog::{fail, pass};
+use std::time::{Duration, Instant};
+
+/// How long a conversion may take before a reading is skipped, which is
+/// generous for the 4000 SPS the ADC is streamed at.
+const DRDY_TIMEOUT: Duration = Duration::from_millis(10);
+
+// registers which change on their own, so can't be checked by reading back
+const STATUS_LOCATION: usize = 1;
+const GPIODAT_LOCATION: usize = 0x10;
+
+pub fn run(args: &ArgMatches) -> anyhow::Result<()> {
+  hold_idle_chip_selects(args);
+
+  let bus = args.get_one::<String>("bus").unwrap();
+  let cs = *args.get_one::<GpioPin>("cs").unwrap();
+  let drdy = *args.get_one::<GpioPin>("drdy").unwrap();
+
+  // the kind is only carried for the caller, so any will do on the bench
+  let mut adc = ADC::new(bus, pin(drdy), Some(pin(cs)), ADCKind::DiffSensors)
+    .map_err(adc_error)?;
+
+  match args.subcommand() {
+    Some(("dump", _)) => dump(&mut adc),
+    Some(("read", args)) => {
+      let location = register(args.get_one::<String>("register").unwrap())?;
+      let value = adc.spi_read_reg(location).map_err(adc_error)?;
+      println!("{}", format_register(location, value));
+      Ok(())
+    }
+    Some(("write", args)) => {
+      let location = register(args.get_one::<String>("register").unwrap())?;
+      let value = u8::try_from(*args.get_one::<u16>("value").unwrap())
+        .map_err(|_| anyhow!("ADC registers are only 8 bits wide."))?;
+
+      adc.write_reg(location, value).map_err(adc_error)?;
+
+      let value = adc.spi_read_reg(location).map_err(adc_error)?;
+      println!("{}", format_register(location, value));
+      Ok(())
+    }
+    Some(("stream", args)) => stream(&mut adc, args),
+    Some(("validate", _)) => validate(&mut adc),
+    _ => unreachable!("a subcommand is required"),
+  }
+}
+
+fn dump(adc: &mut ADC) -> anyhow::Result<()> {
+  let values = adc.spi_read_all_regs().map_err(adc_error)?;
+
+  for (location, value) in values.into_iter().enumerate() {
+    println!("{}", format_register(location, value));
+  }
+
+  Ok(())
+}
+
+/// Streams conversions of one channel against AINCOM, configured as the BMS
+/// does so that readings are comparable.
+fn stream(adc: &mut ADC, args: &ArgMatches) -> anyhow::Result<()> {
+  let channel = *args.get_one::<u8>("channel").unwrap();
+
+  adc
+    .set_negative_input_channel_to_aincom()
+    .map_err(adc_error)?;
+  adc.set_positive_input_channel(channel).map_err(adc_error)?;
+  adc.disable_pga().map_err(adc_error)?;
+  adc.enable_continious_conversion_mode().map_err(adc_error)?;
+  adc.enable_low_latency_filter().map_err(adc_error)?;
+  adc.set_data_rate(4000.0).map_err(adc_error)?;
+  adc.enable_positive_reference_buffer().map_err(adc_error)?;
+  adc.set_ref_input_internal_2v5_ref().map_err(adc_error)?;
+  adc
+    .enable_internal_voltage_reference_on_pwr_down()
+    .map_err(adc_error)?;
+  adc.disable_status_byte().map_err(adc_error)?;
+  adc.disable_crc_byte().map_err(adc_error)?;
+  adc.spi_start_conversion().map_err(adc_error)?;
+
+  let result = Stream::new(args)?.run("code,voltage", || {
+    let waiting = Instant::now();
+
+    while adc.check_drdy() != Low {
+      if waiting.elapsed() > DRDY_TIMEOUT {
+        bail!("data ready was not pulled low");
+      }
+    }
+
+    let code = adc.spi_read_data().map_err(adc_error)?;
+    let voltage = adc.calculate_differential_measurement(code);
+    Ok(format!("{code},{voltage:.6}"))
+  });
+
+  adc.spi_stop_conversion().map_err(adc_error)?;
+  result
+}
+
+fn validate(adc: &mut ADC) -> anyhow::Result<()> {
+  let mut passed = true;
+
+  if adc.validate() {
+    pass!("ID register identifies an ADS114S06.");
+  } else {
+    fail!("ID register does not identify an ADS114S06.");
+    passed = false;
+  }
+
+  // the registers were read on startup, so reading them again checks that
+  // the bus is passing data intact
+  let expected = adc.current_reg_vals;
+  let values = adc.spi_read_all_regs().map_err(adc_error)?;
+
+  for (location, (expected, value)) in
+    expected.into_iter().zip(values).enumerate()
+  {
+    if location == STATUS_LOCATION || location == GPIODAT_LOCATION {
+      continue;
+    }
+
+    if value != expected {
+      fail!(
+        "{} read back as {value:#04x} rather than {expected:#04x}.",
+        REGISTER_NAMES[location]
+      );
+      passed = false;
+    }
+  }
+
+  if !passed {
+    bail!("The ADC failed validation.");
+  }
+
+  pass!("Registers read back consistently.");
+  Ok(())
+}
+
+/// Finds a register by its name, ignoring case, or by its location.
+fn register(register: &str) -> anyhow::Result<usize> {
+  if let Some(location) = REGISTER_NAMES
+    .iter()
+    .position(|name| name.eq_ignore_ascii_case(register))
+  {
+    return Ok(location);
+  }
+
+  match parse_number(register) {
+    Ok(location) if (location as usize) < REGISTER_NAMES.len() => {
+      Ok(location as usize)
+    }
+    _ => bail!("There is no ADC register named or located at {register}."),
+  }
+}
+
+fn format_register(location: usize, value: u8) -> String {
+  format!(
+    "{location:#04x} {:<9} {value:#04x} {value:#010b}",
+    REGISTER_NAMES[location]
+  )
+}
+
+// ADCError only implements Debug
+fn adc_error(error: ADCError) -> anyhow::Error {
+  anyhow!("ADC error: {error:?}")
+}
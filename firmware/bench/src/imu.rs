@@ -0,0 +1,165 @@
+use crate::{hold_idle_chip_selects, parse_number, pin, Stream};
+use anyhow::{anyhow, bail};
+use clap::ArgMatches;
+use common::comm::{
+  board::GpioPin,
+  gpio::{
+    PinMode::{Input, Output},
+    PinValue::High,
+  },
+};
+use imu::{bit_mappings::ImuDriverError, AdisIMUDriver, Registers};
+use jeflog::{fail, pass};
+use spidev::Spidev;
+use std::{thread, time::Duration};
+
+/// How many bursts are read to check the health of the IMU.
+const VALIDATION_BURSTS: u32 = 200;
+
+/// The time between bursts while validating, slower than the fastest output
+/// rate so that every burst is a new sample.
+const VALIDATION_BURST_PERIOD: Duration = Duration::from_millis(1);
+
+pub fn run(args: &ArgMatches) -> anyhow::Result<()> {
+  hold_idle_chip_selects(args);
+
+  let bus = args.get_one::<String>("bus").unwrap();
+
+  let mut chip_select = pin(*args.get_one::<GpioPin>("cs").unwrap());
+  chip_select.mode(Output);
+  chip_select.digital_write(High); // active low
+
+  let mut data_ready = pin(*args.get_one::<GpioPin>("drdy").unwrap());
+  data_ready.mode(Input);
+
+  let mut nreset = pin(*args.get_one::<GpioPin>("nreset").unwrap());
+  nreset.mode(Output);
+
+  let mut driver = AdisIMUDriver::initialize(
+    Spidev::open(bus)?,
+    data_ready,
+    nreset,
+    chip_select,
+  )
+  .map_err(imu_error)?;
+
+  match args.subcommand() {
+    Some(("dump", _)) => {
+      for register in Registers::ALL {
+        match driver.read_register(register) {
+          Ok(value) => println!("{}", format_register(register, value)),
+          Err(error) => {
+            fail!("Failed to read {register}: {error}");
+          }
+        }
+      }
+
+      Ok(())
+    }
+    Some(("read", args)) => {
+      let register = register(args.get_one::<String>("register").unwrap())?;
+      let value = driver.read_register(register).map_err(imu_error)?;
+      println!("{}", format_register(register, value));
+      Ok(())
+    }
+    Some(("write", args)) => {
+      let register = register(args.get_one::<String>("register").unwrap())?;
+      let value = *args.get_one::<u16>("value").unwrap();
+
+      driver.write_register(register, value).map_err(imu_error)?;
+
+      let value = driver.read_register(register).map_err(imu_error)?;
+      println!("{}", format_register(register, value));
+      Ok(())
+    }
+    Some(("stream", args)) => Stream::new(args)?.run(
+      "data_counter,temp,gyro_x,gyro_y,gyro_z,accel_x,accel_y,accel_z",
+      || {
+        let (generic, data) = driver.burst_read_gyro_32().map_err(imu_error)?;
+
+        let [gyro_x, gyro_y, gyro_z] = data.get_gyro_float();
+        let [accel_x, accel_y, accel_z] = data.get_accel_float();
+
+        Ok(format!(
+          "{},{},{gyro_x:.6},{gyro_y:.6},{gyro_z:.6},\
+          {accel_x:.6},{accel_y:.6},{accel_z:.6}",
+          generic.data_counter, generic.temp,
+        ))
+      },
+    ),
+    Some(("validate", _)) => validate(&mut driver),
+    _ => unreachable!("a subcommand is required"),
+  }
+}
+
+fn validate(driver: &mut AdisIMUDriver) -> anyhow::Result<()> {
+  let mut passed = true;
+
+  if driver.validate() {
+    pass!("Product ID identifies an ADIS16500.");
+  } else {
+    fail!("Product ID does not identify an ADIS16500.");
+    passed = false;
+  }
+
+  match driver.read_config() {
+    Ok(config) => {
+      pass!("Configuration is valid: {config:?}");
+    }
+    Err(error) => {
+      fail!("Configuration is invalid: {error}");
+      passed = false;
+    }
+  }
+
+  driver.reset_health();
+
+  for _ in 0..VALIDATION_BURSTS {
+    // failures are counted by the driver
+    let _ = driver.burst_read_gyro_32();
+    thread::sleep(VALIDATION_BURST_PERIOD);
+  }
+
+  let health = driver.health();
+
+  if health.good_samples == VALIDATION_BURSTS {
+    pass!("All {VALIDATION_BURSTS} burst reads were valid.");
+  } else {
+    fail!("Not every burst read was valid: {health:?}");
+    passed = false;
+  }
+
+  if !passed {
+    bail!("The IMU failed validation.");
+  }
+
+  Ok(())
+}
+
+/// Finds a register by its name, ignoring case, or by its address.
+fn register(register: &str) -> anyhow::Result<Registers> {
+  let address = parse_number(register).ok();
+
+  Registers::ALL
+    .into_iter()
+    .find(|candidate| {
+      candidate.to_string().eq_ignore_ascii_case(register)
+        || address == Some(candidate.get_address()[0] as u16)
+    })
+    .ok_or_else(|| {
+      anyhow!("There is no IMU register named or located at {register}.")
+    })
+}
+
+fn format_register(register: Registers, value: u16) -> String {
+  format!(
+    "{:#04x} {:<12} {value:#06x} {value:#018b}",
+    register.get_address()[0],
+    register.to_string(),
+  )
+}
+
+// ImuDriverError doesn't implement std::error::Error
+fn imu_error(error: ImuDriverError) -> anyhow::Error {
+  anyhow!("{error}")
+}
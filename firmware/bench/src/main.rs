@@ -0,0 +1,271 @@
+mod adc;
+mod imu;
+
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use common::comm::{
+  board::GpioPin,
+  gpio::{Gpio, Pin, PinMode::Output, PinValue::High},
+};
+use jeflog::fail;
+use once_cell::sync::Lazy;
+use std::{
+  fs::File,
+  io::{self, LineWriter, Write},
+  path::PathBuf,
+  process,
+  thread,
+  time::{Duration, Instant},
+};
+
+/// The SPI bus which every board wires its ADCs and IMU to.
+const DEFAULT_BUS: &str = "/dev/spidev0.0";
+
+/// How many GPIO controllers the AM335x has.
+const GPIO_CONTROLLER_COUNT: usize = 4;
+
+/// How many pins each GPIO controller has.
+const PINS_PER_CONTROLLER: usize = 32;
+
+pub static GPIO_CONTROLLERS: Lazy<Vec<Gpio>> = Lazy::new(|| {
+  (0..GPIO_CONTROLLER_COUNT)
+    .map(Gpio::open_controller)
+    .collect()
+});
+
+fn main() -> anyhow::Result<()> {
+  let matches = Command::new("bench")
+    .about("Exercises the ADCs and IMU directly, for bringing up new boards.")
+    .subcommand_required(true)
+    .arg(
+      Arg::new("bus")
+        .long("bus")
+        .short('b')
+        .global(true)
+        .default_value(DEFAULT_BUS),
+    )
+    .arg(
+      Arg::new("idle")
+        .long("idle")
+        .help("Chip select of another device on the bus, held inactive")
+        .value_parser(parse_pin)
+        .action(ArgAction::Append)
+        .global(true),
+    )
+    .subcommand(
+      Command::new("adc")
+        .about("Talks to an ADS114S06 ADC.")
+        .subcommand_required(true)
+        .arg(
+          Arg::new("cs")
+            .long("cs")
+            .value_parser(parse_pin)
+            .required(true),
+        )
+        .arg(
+          Arg::new("drdy")
+            .long("drdy")
+            .value_parser(parse_pin)
+            .required(true),
+        )
+        .subcommands(device_commands(
+          stream_command().arg(
+            Arg::new("channel")
+              .long("channel")
+              .short('c')
+              .help("The positive input, measured against AINCOM")
+              .value_parser(clap::value_parser!(u8).range(0..=5))
+              .required(true),
+          ),
+        )),
+    )
+    .subcommand(
+      Command::new("imu")
+        .about("Talks to an ADIS IMU, by default wired as on the AHRS.")
+        .subcommand_required(true)
+        .arg(
+          Arg::new("cs")
+            .long("cs")
+            .value_parser(parse_pin)
+            .default_value("0.11"),
+        )
+        .arg(
+          Arg::new("drdy")
+            .long("drdy")
+            .value_parser(parse_pin)
+            .default_value("2.17"),
+        )
+        .arg(
+          Arg::new("nreset")
+            .long("nreset")
+            .value_parser(parse_pin)
+            .default_value("2.25"),
+        )
+        .subcommands(device_commands(stream_command())),
+    )
+    .get_matches();
+
+  match matches.subcommand() {
+    Some(("adc", args)) => adc::run(args),
+    Some(("imu", args)) => imu::run(args),
+    _ => {
+      fail!("Invalid command. Please check the command you entered.");
+      process::exit(1);
+    }
+  }
+}
+
+/// The subcommands which every device has.
+fn device_commands(stream: Command) -> [Command; 5] {
+  [
+    Command::new("dump").about("Prints the value of every register."),
+    Command::new("read")
+      .about("Prints a single register, by name or address.")
+      .arg(Arg::new("register").required(true)),
+    Command::new("write")
+      .about("Writes a single register, by name or address.")
+      .arg(Arg::new("register").required(true))
+      .arg(Arg::new("value").value_parser(parse_number).required(true)),
+    stream,
+    Command::new("validate").about("Runs the checks of the driver."),
+  ]
+}
+
+fn stream_command() -> Command {
+  Command::new("stream")
+    .about("Writes readings as CSV until interrupted.")
+    .arg(
+      Arg::new("rate")
+        .long("rate")
+        .short('r')
+        .help("Readings per second")
+        .value_parser(clap::value_parser!(f64))
+        .default_value("100"),
+    )
+    .arg(
+      Arg::new("samples")
+        .long("samples")
+        .short('n')
+        .help("Stop after this many readings")
+        .value_parser(clap::value_parser!(u64)),
+    )
+    .arg(
+      Arg::new("output")
+        .long("output")
+        .short('o')
+        .help("Write to a file rather than stdout")
+        .value_parser(clap::value_parser!(PathBuf)),
+    )
+}
+
+/// Opens a pin by its controller and index.
+pub fn pin(location: GpioPin) -> Pin {
+  GPIO_CONTROLLERS[location.controller].get_pin(location.pin)
+}
+
+/// Holds the chip select of every other device on the bus inactive, so that
+/// only the device being talked to answers.
+pub fn hold_idle_chip_selects(args: &ArgMatches) {
+  for location in args.get_many::<GpioPin>("idle").into_iter().flatten() {
+    let mut chip_select = pin(*location);
+    chip_select.mode(Output);
+    chip_select.digital_write(High); // active low
+  }
+}
+
+/// Parses a pin written as `<controller>.<pin>`, such as `2.17`.
+fn parse_pin(location: &str) -> Result<GpioPin, String> {
+  let (controller, pin) = location
+    .split_once('.')
+    .ok_or("expected <controller>.<pin>, such as 2.17")?;
+
+  let controller = controller.parse().map_err(|e| format!("{e}"))?;
+  let pin = pin.parse().map_err(|e| format!("{e}"))?;
+
+  if controller >= GPIO_CONTROLLER_COUNT {
+    return Err(format!(
+      "there are only {GPIO_CONTROLLER_COUNT} controllers"
+    ));
+  }
+
+  if pin >= PINS_PER_CONTROLLER {
+    return Err(format!("controllers only have {PINS_PER_CONTROLLER} pins"));
+  }
+
+  Ok(GpioPin { controller, pin })
+}
+
+/// Parses a decimal number, or a hexadecimal one prefixed with `0x`.
+pub fn parse_number(number: &str) -> Result<u16, String> {
+  match number.strip_prefix("0x") {
+    Some(hex) => u16::from_str_radix(hex, 16),
+    None => number.parse(),
+  }
+  .map_err(|e| format!("{e}"))
+}
+
+/// Writes rows of CSV at a steady rate, each prefixed with the seconds since
+/// streaming started.
+pub struct Stream {
+  output: Box<dyn Write>,
+  period: Duration,
+  samples: Option<u64>,
+}
+
+impl Stream {
+  pub fn new(args: &ArgMatches) -> anyhow::Result<Stream> {
+    let rate = *args.get_one::<f64>("rate").unwrap();
+
+    if !rate.is_finite() || rate <= 0.0 {
+      anyhow::bail!("The rate must be a positive number of readings.");
+    }
+
+    // flushed every line so that nothing is lost when interrupted
+    let output: Box<dyn Write> = match args.get_one::<PathBuf>("output") {
+      Some(path) => Box::new(LineWriter::new(File::create(path)?)),
+      None => Box::new(io::stdout()),
+    };
+
+    Ok(Stream {
+      output,
+      period: Duration::from_secs_f64(1.0 / rate),
+      samples: args.get_one::<u64>("samples").copied(),
+    })
+  }
+
+  /// Writes the header, then a row from `read` every period. A failed reading
+  /// is reported on stderr and skipped rather than ending the stream.
+  pub fn run(
+    mut self,
+    header: &str,
+    mut read: impl FnMut() -> anyhow::Result<String>,
+  ) -> anyhow::Result<()> {
+    writeln!(self.output, "time,{header}")?;
+
+    let start = Instant::now();
+    let mut next = start;
+    let mut written = 0;
+
+    while self.samples != Some(written) {
+      let now = Instant::now();
+
+      if let Some(wait) = next.checked_duration_since(now) {
+        thread::sleep(wait);
+      }
+
+      // a slow reading delays the ones after it rather than causing a burst
+      next = next.max(now) + self.period;
+
+      let time = start.elapsed().as_secs_f64();
+
+      match read() {
+        Ok(row) => {
+          writeln!(self.output, "{time:.6},{row}")?;
+          written += 1;
+        }
+        Err(error) => eprintln!("Skipped reading at {time:.6} s: {error}"),
+      }
+    }
+
+    Ok(())
+  }
+}
@@ -33,6 +33,17 @@ pub enum ImuDriverError {
   IOError(io::Error),
   /// Invalid Data received
   InvalidDataError(InvalidDataError),
+  /// The burst checksum didn't match its data, so the transfer was corrupted
+  ChecksumError {
+    /// The checksum sent by the IMU
+    expected: u16,
+    /// The checksum of the data as received
+    computed: u16,
+  },
+  /// The IMU dropped samples because they weren't read out in time
+  DataPathOverrun(DiagnosticStats),
+  /// A gyroscope or accelerometer failed its internal checks
+  SensorFailure(DiagnosticStats),
 }
 
 impl From<io::Error> for ImuDriverError {
@@ -42,8 +53,16 @@ impl From<io::Error> for ImuDriverError {
 }
 
 impl From<DiagnosticStats> for ImuDriverError {
+  /// Classifies a nonzero `DIAG_STAT`, with sensor failures taking precedence
+  /// as they make the data meaningless rather than late.
   fn from(stats: DiagnosticStats) -> Self {
-    ImuDriverError::ImuError(stats)
+    if stats.intersects(DiagnosticStats::SENSOR_FAILURES) {
+      ImuDriverError::SensorFailure(stats)
+    } else if stats.contains(DiagnosticStats::DATA_PATH_OVERRUN) {
+      ImuDriverError::DataPathOverrun(stats)
+    } else {
+      ImuDriverError::ImuError(stats)
+    }
   }
 }
 
@@ -66,6 +85,11 @@ bitflags! {
     const GYRO_1_FAILURE = 1 << 8;
     const GYRO_2_FAILURE = 1 << 9;
     const ACCELEROMETER_FAILURE = 1 << 10;
+
+    const SENSOR_FAILURES = Self::SENSOR_FAILURE.bits()
+      | Self::GYRO_1_FAILURE.bits()
+      | Self::GYRO_2_FAILURE.bits()
+      | Self::ACCELEROMETER_FAILURE.bits();
   }
 }
 
@@ -90,6 +114,20 @@ impl fmt::Display for ImuDriverError {
         write!(f, "Invalid Data Error - ")?;
         err.fmt(f)
       }
+      ImuDriverError::ChecksumError { expected, computed } => {
+        write!(
+          f,
+          "Checksum Error - expected {expected:#06x}, computed {computed:#06x}"
+        )
+      }
+      ImuDriverError::DataPathOverrun(stats) => {
+        write!(f, "Data Path Overrun - ")?;
+        stats.fmt(f)
+      }
+      ImuDriverError::SensorFailure(stats) => {
+        write!(f, "Sensor Failure - ")?;
+        stats.fmt(f)
+      }
     }
   }
 }
@@ -98,3 +136,154 @@ pub enum BurstSelectOptions {
   GyroBurst,
   DeltaBurst,
 }
+
+/// The source of the IMU's sample clock, from bits 4:2 of `MSC_CTRL`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncMode {
+  /// Sample on the internal 2000 SPS clock.
+  Internal,
+  /// Sample on each edge of the SYNC pin.
+  Direct,
+  /// Sample at the rate of the SYNC pin multiplied by `UP_SCALE`.
+  Scaled,
+  /// Sample on the internal clock and drive it out on the SYNC pin.
+  Output,
+}
+
+impl SyncMode {
+  fn bits(self) -> u16 {
+    match self {
+      SyncMode::Internal => 0b000,
+      SyncMode::Direct => 0b001,
+      SyncMode::Scaled => 0b010,
+      SyncMode::Output => 0b011,
+    }
+  }
+
+  fn from_bits(bits: u16) -> Option<SyncMode> {
+    match bits {
+      0b000 => Some(SyncMode::Internal),
+      0b001 => Some(SyncMode::Direct),
+      0b010 => Some(SyncMode::Scaled),
+      0b011 => Some(SyncMode::Output),
+      _ => None,
+    }
+  }
+}
+
+/// The largest setting of the Bartlett window filter bank, giving 64 taps.
+pub const MAX_FILTER_SIZE: u16 = 6;
+
+/// The largest decimation rate, giving 1 SPS from the 2000 SPS clock.
+pub const MAX_DEC_RATE: u16 = 1999;
+
+// bits of MSC_CTRL outside the sync mode
+const MSC_DR_POLARITY: u16 = 1 << 0;
+const MSC_SYNC_POLARITY: u16 = 1 << 1;
+const MSC_SYNC_MODE_SHIFT: u16 = 2;
+const MSC_SYNC_MODE_MASK: u16 = 0b111 << MSC_SYNC_MODE_SHIFT;
+const MSC_LINEAR_G_COMPENSATION: u16 = 1 << 6;
+const MSC_POINT_OF_PERCUSSION: u16 = 1 << 7;
+
+/// The user-settable configuration of the IMU, spread across `FILT_CTRL`,
+/// `MSC_CTRL`, `DEC_RATE` and `UP_SCALE`. Burst settings in `MSC_CTRL` are
+/// left to the driver.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ImuConfig {
+  /// The size of the Bartlett window filter, 2^N taps, from 0 to 6.
+  pub filter_size: u16,
+  /// Where the sample clock comes from.
+  pub sync_mode: SyncMode,
+  /// True if data ready is active high.
+  pub data_ready_active_high: bool,
+  /// True if samples are taken on the rising edge of SYNC.
+  pub sync_rising_edge: bool,
+  /// Whether the gyroscope compensates for linear acceleration.
+  pub linear_g_compensation: bool,
+  /// Whether the accelerometers are aligned to a common point of percussion.
+  pub point_of_percussion: bool,
+  /// Output one sample for every `decimation + 1` internal samples.
+  pub decimation: u16,
+  /// The multiplier of the SYNC rate in `SyncMode::Scaled`.
+  pub up_scale: u16,
+}
+
+impl Default for ImuConfig {
+  /// The factory defaults.
+  fn default() -> Self {
+    ImuConfig {
+      filter_size: 0,
+      sync_mode: SyncMode::Internal,
+      data_ready_active_high: true,
+      sync_rising_edge: false,
+      linear_g_compensation: true,
+      point_of_percussion: true,
+      decimation: 0,
+      up_scale: 0x07D0,
+    }
+  }
+}
+
+impl ImuConfig {
+  /// Decodes the configuration from the raw register values.
+  pub fn from_registers(
+    filt_ctrl: u16,
+    msc_ctrl: u16,
+    dec_rate: u16,
+    up_scale: u16,
+  ) -> Result<ImuConfig, InvalidDataError> {
+    let sync_mode = SyncMode::from_bits(
+      (msc_ctrl & MSC_SYNC_MODE_MASK) >> MSC_SYNC_MODE_SHIFT,
+    )
+    .ok_or(InvalidDataError::new("Reserved sync mode in MSC_CTRL"))?;
+
+    Ok(ImuConfig {
+      filter_size: filt_ctrl & 0b111,
+      sync_mode,
+      data_ready_active_high: msc_ctrl & MSC_DR_POLARITY != 0,
+      sync_rising_edge: msc_ctrl & MSC_SYNC_POLARITY != 0,
+      linear_g_compensation: msc_ctrl & MSC_LINEAR_G_COMPENSATION != 0,
+      point_of_percussion: msc_ctrl & MSC_POINT_OF_PERCUSSION != 0,
+      decimation: dec_rate,
+      up_scale,
+    })
+  }
+
+  /// Checks that every field is within the range its register accepts.
+  pub fn validate(&self) -> Result<(), InvalidDataError> {
+    if self.filter_size > MAX_FILTER_SIZE {
+      return Err(InvalidDataError::new("Filter size must be at most 6"));
+    }
+
+    if self.decimation > MAX_DEC_RATE {
+      return Err(InvalidDataError::new("Decimation rate must be at most 1999"));
+    }
+
+    Ok(())
+  }
+
+  /// The value of `MSC_CTRL`, keeping the burst bits of `current`.
+  pub fn msc_ctrl(&self, current: u16) -> u16 {
+    let mut value = current
+      & !(MSC_DR_POLARITY
+        | MSC_SYNC_POLARITY
+        | MSC_SYNC_MODE_MASK
+        | MSC_LINEAR_G_COMPENSATION
+        | MSC_POINT_OF_PERCUSSION);
+
+    value |= self.sync_mode.bits() << MSC_SYNC_MODE_SHIFT;
+
+    for (enabled, bit) in [
+      (self.data_ready_active_high, MSC_DR_POLARITY),
+      (self.sync_rising_edge, MSC_SYNC_POLARITY),
+      (self.linear_g_compensation, MSC_LINEAR_G_COMPENSATION),
+      (self.point_of_percussion, MSC_POINT_OF_PERCUSSION),
+    ] {
+      if enabled {
+        value |= bit;
+      }
+    }
+
+    value
+  }
+}
@@ -16,6 +16,22 @@ use crate::internals::*;
 const POWER_ON_START_UP_TIME: Duration = Duration::from_millis(310);
 /// From page 4 of documentation
 const RESET_DOWNTIME: Duration = Duration::from_millis(255);
+/// Time for a control register write to take effect, plus some leeway
+const REGISTER_WRITE_TIME: Duration = Duration::from_micros(200 + 100);
+/// Time for a flash memory update to complete, erring long
+const FLASH_UPDATE_TIME: Duration = Duration::from_millis(1200);
+
+/// GLOB_CMD bit which copies the user registers to flash
+const FLASH_MEMORY_UPDATE: u16 = 1 << 3;
+
+/// A gyroscope reading spread wider than this, in deg/s, means the IMU was
+/// not held still while its bias was estimated
+const MAX_STATIONARY_DEVIATION: f64 = 1.0;
+
+/// deg/s per LSB of the 32 bit gyroscope outputs and bias registers
+const GYRO_SCALE: f32 = 0.1 / 0x10000 as f32;
+/// m/s² per LSB of the 32 bit accelerometer outputs and bias registers
+const ACCEL_SCALE: f32 = 392.0 / 0x7D000000 as f32;
 
 #[derive(Clone, Debug)]
 pub struct DeltaReadData {
@@ -88,6 +104,36 @@ pub struct GenericData {
   pub data_counter: i16,
 }
 
+/// Counts of burst reads by outcome, so that an application can judge the
+/// health of the IMU rather than only see its latest error.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HealthCounters {
+  /// Reads that passed every check.
+  pub good_samples: u32,
+  /// Reads whose checksum didn't match, from a corrupted transfer.
+  pub checksum_failures: u32,
+  /// Reads flagging a data path overrun.
+  pub overruns: u32,
+  /// Reads flagging a gyroscope or accelerometer failure.
+  pub sensor_failures: u32,
+  /// Reads that failed in any other way, including on the SPI bus.
+  pub other_failures: u32,
+}
+
+impl HealthCounters {
+  fn record<T>(&mut self, result: &DriverResult<T>) {
+    let counter = match result {
+      Ok(_) => &mut self.good_samples,
+      Err(ImuDriverError::ChecksumError { .. }) => &mut self.checksum_failures,
+      Err(ImuDriverError::DataPathOverrun(_)) => &mut self.overruns,
+      Err(ImuDriverError::SensorFailure(_)) => &mut self.sensor_failures,
+      Err(_) => &mut self.other_failures,
+    };
+
+    *counter = counter.saturating_add(1);
+  }
+}
+
 #[derive(Clone, Debug)]
 pub struct GyroReadData {
   pub gyro: [i32; 3],
@@ -127,6 +173,48 @@ impl fmt::Display for GyroReadData {
   }
 }
 
+/// The user bias corrections, which the IMU adds to each gyroscope and
+/// accelerometer output. Stored in the same 32 bit format as the outputs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BiasOffsets {
+  pub gyro: [i32; 3],
+
+  pub accel: [i32; 3],
+}
+
+impl BiasOffsets {
+  /// Converts offsets in deg/s and m/s² to register values, rounding to the
+  /// nearest LSB.
+  pub fn from_float(gyro: [f32; 3], accel: [f32; 3]) -> BiasOffsets {
+    BiasOffsets {
+      gyro: gyro.map(|g| (g / GYRO_SCALE).round() as i32),
+      accel: accel.map(|a| (a / ACCEL_SCALE).round() as i32),
+    }
+  }
+
+  /// The gyroscope offsets in deg/s.
+  pub fn get_gyro_float(&self) -> [f32; 3] {
+    self.gyro.map(|g| g as f32 * GYRO_SCALE)
+  }
+
+  /// The accelerometer offsets in m/s².
+  pub fn get_accel_float(&self) -> [f32; 3] {
+    self.accel.map(|a| a as f32 * ACCEL_SCALE)
+  }
+}
+
+impl fmt::Display for BiasOffsets {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let gyro = self.get_gyro_float();
+    let accel = self.get_accel_float();
+    write!(
+      f,
+      "gyro : ({:08.4}, {:08.4}, {:08.4}) deg/s | accel : ({:08.4}, {:08.4}, {:08.4}) m/s²",
+      gyro[0], gyro[1], gyro[2], accel[0], accel[1], accel[2],
+    )
+  }
+}
+
 struct ConfigValues {
   msc_control_reg: u16,
   dec_rate_reg: u16,
@@ -140,19 +228,6 @@ impl ConfigValues {
     }
   }
 
-  fn get_last_burst_sel(&self) -> bool {
-    return (self.msc_control_reg & (1 << 8)) != 0;
-  }
-
-  /// DOES NOT WRITE TO REGISTER
-  fn set_burst_sel(&mut self, value: bool) {
-    if value {
-      self.msc_control_reg |= 0x0100;
-    } else {
-      self.msc_control_reg &= 0xFEFF;
-    }
-  }
-
   fn read_all_values(
     &mut self,
     driver: &mut AdisIMUDriver,
@@ -169,9 +244,76 @@ impl ConfigValues {
 
 const GLOB_CMD: [u8; 2] = [0x68, 0x69];
 
-#[derive(Copy, Clone)]
+/// MSC_CTRL bit which bursts delta angle and velocity instead of rate
+const MSC_BURST_SEL: u16 = 1 << 8;
+/// MSC_CTRL bit which bursts the LOW registers along with the OUT registers
+const MSC_BURST_32: u16 = 1 << 9;
+
+/// Bytes in a burst, counting the command word: DIAG_STAT, six 16 bit
+/// outputs, TEMP_OUT, DATA_CNTR and the checksum
+const BURST_16_LENGTH: usize = 22;
+/// As a 16 bit burst, but with each output preceded by its LOW register
+const BURST_32_LENGTH: usize = 34;
+
+/// Parses and validates a burst response of either size, returning the
+/// generic data and the first and second output triples (gyro and accel, or
+/// delta angle and delta velocity). The checksum is checked first, as a
+/// corrupted transfer makes the rest, including DIAG_STAT, meaningless.
+fn parse_burst(
+  rx_buf: &[u8],
+  wide: bool,
+) -> DriverResult<(GenericData, [i32; 3], [i32; 3])> {
+  // the SPI words arrive in the host's byte order
+  let word = |index: usize| {
+    u16::from_le_bytes([rx_buf[2 * index], rx_buf[2 * index + 1]])
+  };
+
+  let words = rx_buf.len() / 2;
+  let checksum_index = words - 1;
+
+  // the checksum covers every byte from DIAG_STAT through DATA_CNTR
+  let computed = rx_buf[2..2 * checksum_index]
+    .iter()
+    .fold(0u16, |sum, byte| sum.wrapping_add(*byte as u16));
+  let expected = word(checksum_index);
+
+  if computed != expected {
+    return Err(ImuDriverError::ChecksumError { expected, computed });
+  }
+
+  let diagnostic_stat: DiagnosticStats = word(1).into();
+
+  if !diagnostic_stat.is_empty() {
+    return Err(diagnostic_stat.into());
+  }
+
+  let output = |index: usize| {
+    if wide {
+      // each output is a LOW and OUT register pair
+      let low = word(2 + 2 * index) as u32;
+      let high = word(3 + 2 * index) as u32;
+      ((high << 16) | low) as i32
+    } else {
+      (word(2 + index) as i16 as i32) << 16
+    }
+  };
+
+  let generic = GenericData {
+    temp: word(checksum_index - 2) as i16,
+    data_counter: word(checksum_index - 1) as i16,
+  };
+
+  Ok((
+    generic,
+    [output(0), output(1), output(2)],
+    [output(3), output(4), output(5)],
+  ))
+}
+
+/// Every register of the IMU, named as in the datasheet.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[allow(non_camel_case_types)]
-enum Registers {
+pub enum Registers {
   DIAG_STAT,
   X_GYRO_LOW,
   X_GYRO_OUT,
@@ -220,6 +362,7 @@ enum Registers {
   MSC_CTRL,
   UP_SCALE,
   DEC_RATE,
+  GLOB_CMD,
 
   FIRM_REV,
   FIRM_DM,
@@ -234,7 +377,68 @@ enum Registers {
 }
 
 impl Registers {
-  fn get_address(&self) -> [u8; 2] {
+  /// Every register, in address order
+  pub const ALL: [Registers; 56] = [
+    Registers::DIAG_STAT,
+    Registers::X_GYRO_LOW,
+    Registers::X_GYRO_OUT,
+    Registers::Y_GYRO_LOW,
+    Registers::Y_GYRO_OUT,
+    Registers::Z_GYRO_LOW,
+    Registers::Z_GYRO_OUT,
+    Registers::X_ACCL_LOW,
+    Registers::X_ACCL_OUT,
+    Registers::Y_ACCL_LOW,
+    Registers::Y_ACCL_OUT,
+    Registers::Z_ACCL_LOW,
+    Registers::Z_ACCL_OUT,
+    Registers::TEMP_OUT,
+    Registers::TIME_STAMP,
+    Registers::DATA_CNTR,
+    Registers::X_DELTANG_LOW,
+    Registers::X_DELTANG_OUT,
+    Registers::Y_DELTANG_LOW,
+    Registers::Y_DELTANG_OUT,
+    Registers::Z_DELTANG_LOW,
+    Registers::Z_DELTANG_OUT,
+    Registers::X_DELTVEL_LOW,
+    Registers::X_DELTVEL_OUT,
+    Registers::Y_DELTVEL_LOW,
+    Registers::Y_DELTVEL_OUT,
+    Registers::Z_DELTVEL_LOW,
+    Registers::Z_DELTVEL_OUT,
+    Registers::XG_BIAS_LOW,
+    Registers::XG_BIAS_HIGH,
+    Registers::YG_BIAS_LOW,
+    Registers::YG_BIAS_HIGH,
+    Registers::ZG_BIAS_LOW,
+    Registers::ZG_BIAS_HIGH,
+    Registers::XA_BIAS_LOW,
+    Registers::XA_BIAS_HIGH,
+    Registers::YA_BIAS_LOW,
+    Registers::YA_BIAS_HIGH,
+    Registers::ZA_BIAS_LOW,
+    Registers::ZA_BIAS_HIGH,
+    Registers::FILT_CTRL,
+    Registers::RANG_MDL,
+    Registers::MSC_CTRL,
+    Registers::UP_SCALE,
+    Registers::DEC_RATE,
+    Registers::GLOB_CMD,
+    Registers::FIRM_REV,
+    Registers::FIRM_DM,
+    Registers::FIRM_Y,
+    Registers::PROD_ID,
+    Registers::SERIAL_NUM,
+    Registers::USER_SCR_1,
+    Registers::USER_SCR_2,
+    Registers::USER_SCR_3,
+    Registers::FLSHCNT_LOW,
+    Registers::FLSHCNT_HIGH,
+  ];
+
+  /// The addresses of the low and high bytes of the register
+  pub fn get_address(&self) -> [u8; 2] {
     match (self) {
       Registers::DIAG_STAT => [0x02, 0x03],
       Registers::X_GYRO_LOW => [0x04, 0x05],
@@ -284,6 +488,7 @@ impl Registers {
       Registers::MSC_CTRL => [0x60, 0x61],
       Registers::UP_SCALE => [0x62, 0x63],
       Registers::DEC_RATE => [0x64, 0x65],
+      Registers::GLOB_CMD => [0x68, 0x69],
 
       Registers::FIRM_REV => [0x6C, 0x6D],
       Registers::FIRM_DM => [0x6E, 0x6F],
@@ -298,7 +503,7 @@ impl Registers {
     }
   }
 
-  fn is_writeable(&self) -> bool {
+  pub fn is_writeable(&self) -> bool {
     match (self) {
       Registers::DIAG_STAT => false,
       Registers::X_GYRO_LOW => false,
@@ -348,6 +553,7 @@ impl Registers {
       Registers::MSC_CTRL => true,
       Registers::UP_SCALE => true,
       Registers::DEC_RATE => true,
+      Registers::GLOB_CMD => true,
 
       Registers::FIRM_REV => false,
       Registers::FIRM_DM => false,
@@ -405,7 +611,7 @@ impl fmt::Display for Registers {
         Registers::YG_BIAS_HIGH => "YG_BIAS_HIGH",
         Registers::ZG_BIAS_LOW => "ZG_BIAS_LOW",
         Registers::ZG_BIAS_HIGH => "ZG_BIAS_HIGH",
-        Registers::XA_BIAS_LOW => "XA_BIAS_LOW ",
+        Registers::XA_BIAS_LOW => "XA_BIAS_LOW",
         Registers::XA_BIAS_HIGH => "XA_BIAS_HIGH",
         Registers::YA_BIAS_LOW => "YA_BIAS_LOW",
         Registers::YA_BIAS_HIGH => "YA_BIAS_HIGH",
@@ -417,6 +623,7 @@ impl fmt::Display for Registers {
         Registers::MSC_CTRL => "MSC_CTRL",
         Registers::UP_SCALE => "UP_SCALE",
         Registers::DEC_RATE => "DEC_RATE",
+        Registers::GLOB_CMD => "GLOB_CMD",
 
         Registers::FIRM_REV => "FIRM_REV",
         Registers::FIRM_DM => "FIRM_DM",
@@ -450,6 +657,8 @@ pub struct AdisIMUDriver {
   internals: DriverInternals,
 
   config: ConfigValues,
+
+  health: HealthCounters,
 }
 
 impl AdisIMUDriver {
@@ -477,6 +686,7 @@ impl AdisIMUDriver {
         nchip_select,
       )?,
       config: ConfigValues::default(),
+      health: HealthCounters::default(),
     };
     // Wait until the time to power on has passed / the IMU just powered on
     sleep(POWER_ON_START_UP_TIME + Duration::from_millis(100));
@@ -590,6 +800,25 @@ impl AdisIMUDriver {
     Ok(())
   }
 
+  /// Reads any register by name, for inspecting the IMU rather than using it.
+  /// Beware that reading DIAG_STAT clears it.
+  pub fn read_register(&mut self, reg: Registers) -> DriverResult<u16> {
+    Ok(self.repeat_read_16_bit_redundant(reg, 3)? as u16)
+  }
+
+  /// Writes any writeable register by name, waiting for it to take effect.
+  /// The driver's cached configuration is not updated, so call `read_config`
+  /// afterwards if the driver will keep being used.
+  pub fn write_register(
+    &mut self,
+    reg: Registers,
+    value: u16,
+  ) -> DriverResult<()> {
+    self.write_to_reg(reg, value)?;
+    sleep(REGISTER_WRITE_TIME);
+    Ok(())
+  }
+
   pub fn write_dec_rate(&mut self, rate: u16) -> DriverResult<()> {
     self.write_to_reg(Registers::DEC_RATE, rate)?;
     sleep(Duration::from_micros(200 + 100));
@@ -614,338 +843,420 @@ impl AdisIMUDriver {
     }
   }
 
-  pub fn burst_read_gyro_16(
-    &mut self,
-  ) -> DriverResult<(GenericData, GyroReadData)> {
-    // TODO Configure burst mode + burst select
-    if self.config.get_last_burst_sel() {
-      self.config.set_burst_sel(false);
-      self.write_to_reg(Registers::MSC_CTRL, self.config.msc_control_reg)?;
-      sleep(Duration::from_micros(100)); // prob unneeded : TODO : CHECK
+  /// Reads every configuration register at once.
+  pub fn read_config(&mut self) -> DriverResult<ImuConfig> {
+    let filt_ctrl =
+      self.repeat_read_16_bit_redundant(Registers::FILT_CTRL, 3)? as u16;
+    let msc_ctrl =
+      self.repeat_read_16_bit_redundant(Registers::MSC_CTRL, 3)? as u16;
+    let dec_rate =
+      self.repeat_read_16_bit_redundant(Registers::DEC_RATE, 3)? as u16;
+    let up_scale =
+      self.repeat_read_16_bit_redundant(Registers::UP_SCALE, 3)? as u16;
+
+    self.config.msc_control_reg = msc_ctrl;
+    self.config.dec_rate_reg = dec_rate;
+
+    ImuConfig::from_registers(filt_ctrl, msc_ctrl, dec_rate, up_scale)
+      .map_err(Into::into)
+  }
+
+  /// Writes every configuration register at once. Takes effect immediately,
+  /// but is lost on reset unless followed by `flash_update`.
+  pub fn write_config(&mut self, config: &ImuConfig) -> DriverResult<()> {
+    config.validate()?;
+
+    self.write_filter_size(config.filter_size)?;
+    self.write_msc_ctrl(config.msc_ctrl(self.config.msc_control_reg))?;
+    self.write_dec_rate(config.decimation)?;
+    self.config.dec_rate_reg = config.decimation;
+    self.write_up_scale(config.up_scale)?;
+
+    Ok(())
+  }
+
+  /// Sets the Bartlett window filter bank to 2^`size` taps.
+  pub fn write_filter_size(&mut self, size: u16) -> DriverResult<()> {
+    if size > MAX_FILTER_SIZE {
+      return Err(InvalidDataError::new("Filter size must be at most 6").into());
     }
 
-    // Setup buffers
-    let mut tx_buf: [u8; 22] = [0; 22];
-    tx_buf[1] = GLOB_CMD[0];
+    self.write_to_reg(Registers::FILT_CTRL, size)?;
+    sleep(REGISTER_WRITE_TIME);
+    Ok(())
+  }
+  pub fn read_filter_size(&mut self) -> DriverResult<u16> {
+    Ok(self.read_16_bit(Registers::FILT_CTRL)? as u16 & 0b111)
+  }
 
-    let mut rx_buf: [u8; 22] = [0; 22];
+  /// Changes where the sample clock comes from, leaving the rest of
+  /// `MSC_CTRL` as last read or written.
+  pub fn write_sync_mode(&mut self, mode: SyncMode) -> DriverResult<()> {
+    let mut config = ImuConfig::from_registers(
+      0,
+      self.config.msc_control_reg,
+      self.config.dec_rate_reg,
+      0,
+    )?;
+    config.sync_mode = mode;
+
+    self.write_msc_ctrl(config.msc_ctrl(self.config.msc_control_reg))
+  }
 
-    self.internals.spi_transfer(&tx_buf, &mut rx_buf)?;
+  pub fn write_up_scale(&mut self, scale: u16) -> DriverResult<()> {
+    self.write_to_reg(Registers::UP_SCALE, scale)?;
+    sleep(REGISTER_WRITE_TIME);
+    Ok(())
+  }
+  pub fn read_up_scale(&mut self) -> DriverResult<u16> {
+    Ok(self.read_16_bit(Registers::UP_SCALE)? as u16)
+  }
 
-    // 16 bit data
-    let gyro = [
-      (i16::from_le_bytes(rx_buf[4..6].try_into().unwrap()) as i32) << 16,
-      (i16::from_le_bytes(rx_buf[6..8].try_into().unwrap()) as i32) << 16,
-      (i16::from_le_bytes(rx_buf[8..10].try_into().unwrap()) as i32) << 16,
-    ];
+  fn write_msc_ctrl(&mut self, value: u16) -> DriverResult<()> {
+    self.write_to_reg(Registers::MSC_CTRL, value)?;
+    self.config.msc_control_reg = value;
+    sleep(REGISTER_WRITE_TIME);
+    Ok(())
+  }
 
-    let accel = [
-      (i16::from_le_bytes(rx_buf[10..12].try_into().unwrap()) as i32) << 16,
-      (i16::from_le_bytes(rx_buf[12..14].try_into().unwrap()) as i32) << 16,
-      (i16::from_le_bytes(rx_buf[14..16].try_into().unwrap()) as i32) << 16,
+  fn read_32_bit(
+    &mut self,
+    low: Registers,
+    high: Registers,
+  ) -> DriverResult<i32> {
+    let low = self.repeat_read_16_bit_redundant(low, 3)? as u16 as u32;
+    let high = self.repeat_read_16_bit_redundant(high, 3)? as u16 as u32;
+    Ok(((high << 16) | low) as i32)
+  }
+
+  fn write_32_bit(
+    &mut self,
+    low: Registers,
+    high: Registers,
+    value: i32,
+  ) -> DriverResult<()> {
+    self.write_to_reg(low, value as u32 as u16)?;
+    self.write_to_reg(high, (value as u32 >> 16) as u16)?;
+    Ok(())
+  }
+
+  /// Reads the bias corrections currently applied by the IMU.
+  pub fn read_bias(&mut self) -> DriverResult<BiasOffsets> {
+    use Registers::*;
+
+    Ok(BiasOffsets {
+      gyro: [
+        self.read_32_bit(XG_BIAS_LOW, XG_BIAS_HIGH)?,
+        self.read_32_bit(YG_BIAS_LOW, YG_BIAS_HIGH)?,
+        self.read_32_bit(ZG_BIAS_LOW, ZG_BIAS_HIGH)?,
+      ],
+      accel: [
+        self.read_32_bit(XA_BIAS_LOW, XA_BIAS_HIGH)?,
+        self.read_32_bit(YA_BIAS_LOW, YA_BIAS_HIGH)?,
+        self.read_32_bit(ZA_BIAS_LOW, ZA_BIAS_HIGH)?,
+      ],
+    })
+  }
+
+  /// Replaces the bias corrections. They take effect immediately, but are
+  /// lost on reset unless followed by `flash_update`.
+  pub fn write_bias(&mut self, bias: &BiasOffsets) -> DriverResult<()> {
+    use Registers::*;
+
+    let registers = [
+      (XG_BIAS_LOW, XG_BIAS_HIGH, bias.gyro[0]),
+      (YG_BIAS_LOW, YG_BIAS_HIGH, bias.gyro[1]),
+      (ZG_BIAS_LOW, ZG_BIAS_HIGH, bias.gyro[2]),
+      (XA_BIAS_LOW, XA_BIAS_HIGH, bias.accel[0]),
+      (YA_BIAS_LOW, YA_BIAS_HIGH, bias.accel[1]),
+      (ZA_BIAS_LOW, ZA_BIAS_HIGH, bias.accel[2]),
     ];
 
-    // 16 bit data
+    for (low, high, value) in registers {
+      self.write_32_bit(low, high, value)?;
+    }
+
+    sleep(REGISTER_WRITE_TIME);
+    Ok(())
+  }
+
+  /// Saves all user registers, including the configuration and bias
+  /// corrections, to flash so they survive a reset or power cycle. Flash has
+  /// limited endurance, so this should only follow deliberate changes.
+  pub fn flash_update(&mut self) -> DriverResult<()> {
+    self.write_to_reg(Registers::GLOB_CMD, FLASH_MEMORY_UPDATE)?;
+    sleep(FLASH_UPDATE_TIME);
+
     let diagnostic_stat: DiagnosticStats =
-      u16::from_le_bytes(rx_buf[2..4].try_into().unwrap()).into();
-    let temp = i16::from_le_bytes(rx_buf[16..18].try_into().unwrap());
-    let data_counter = i16::from_le_bytes(rx_buf[18..20].try_into().unwrap());
+      (self.repeat_read_16_bit_redundant(Registers::DIAG_STAT, 3)? as u16)
+        .into();
 
-    let mut sum: u16 = 0;
-    for i in 2..20 {
-      sum += rx_buf[i] as u16;
-    }
-    if !diagnostic_stat.is_empty() {
+    if diagnostic_stat.contains(DiagnosticStats::FLASH_MEMORY_UPDATE_FAILURE) {
       return Err(diagnostic_stat.into());
     }
 
-    return if sum == u16::from_le_bytes(rx_buf[20..22].try_into().unwrap()) {
-      Ok((
-        GenericData { temp, data_counter },
-        GyroReadData { gyro, accel },
-      ))
-    } else {
-      Err(InvalidDataError::new("Checksum Failure").into())
-    };
+    Ok(())
   }
 
-  pub fn burst_read_delta_16(
+  /// Averages `samples` gyroscope readings, in deg/s, to estimate what the
+  /// IMU reads while stationary. Fails if the readings are spread too widely
+  /// for the IMU to have been still.
+  pub fn estimate_gyro_bias(
     &mut self,
-  ) -> DriverResult<(GenericData, DeltaReadData)> {
-    // TODO Configure burst mode + burst select
-    if !self.config.get_last_burst_sel() {
-      self.config.set_burst_sel(true);
-      self.write_to_reg(Registers::MSC_CTRL, self.config.msc_control_reg)?;
-      sleep(Duration::from_micros(100)); // prob unneeded : TODO : CHECK
-    }
-
-    // Setup buffers
-    let mut tx_buf: [u8; 22] = [0; 22];
-    tx_buf[1] = GLOB_CMD[0];
+    samples: usize,
+  ) -> DriverResult<[f32; 3]> {
+    let mut sum = [0.0f64; 3];
+    let mut sum_of_squares = [0.0f64; 3];
+    let mut last_counter = None;
+    let mut count = 0;
+
+    while count < samples {
+      let (generic, data) = self.burst_read_gyro_32()?;
+
+      // reading faster than the output rate returns the same sample again
+      if last_counter == Some(generic.data_counter) {
+        sleep(Duration::from_micros(100));
+        continue;
+      }
+      last_counter = Some(generic.data_counter);
+      count += 1;
 
-    let mut rx_buf: [u8; 22] = [0; 22];
+      for (axis, rate) in data.get_gyro_float().into_iter().enumerate() {
+        sum[axis] += rate as f64;
+        sum_of_squares[axis] += (rate as f64).powi(2);
+      }
+    }
 
-    self.internals.spi_transfer(&tx_buf, &mut rx_buf)?;
+    let count = count.max(1) as f64;
+    let mean = sum.map(|s| s / count);
 
-    // 16 bit data
-    let delta_angle = [
-      (i16::from_le_bytes(rx_buf[4..6].try_into().unwrap()) as i32) << 16,
-      (i16::from_le_bytes(rx_buf[6..8].try_into().unwrap()) as i32) << 16,
-      (i16::from_le_bytes(rx_buf[8..10].try_into().unwrap()) as i32) << 16,
-    ];
+    for (mean, sum_of_squares) in mean.iter().zip(sum_of_squares) {
+      let variance = sum_of_squares / count - mean * mean;
 
-    let delta_velocity = [
-      (i16::from_le_bytes(rx_buf[10..12].try_into().unwrap()) as i32) << 16,
-      (i16::from_le_bytes(rx_buf[12..14].try_into().unwrap()) as i32) << 16,
-      (i16::from_le_bytes(rx_buf[14..16].try_into().unwrap()) as i32) << 16,
-    ];
+      if variance > MAX_STATIONARY_DEVIATION.powi(2) {
+        return Err(
+          InvalidDataError::new("IMU moved while estimating gyro bias").into(),
+        );
+      }
+    }
 
-    // 16 bit data
-    let diagnostic_stat: DiagnosticStats =
-      u16::from_le_bytes(rx_buf[2..4].try_into().unwrap()).into();
-    let temp = i16::from_le_bytes(rx_buf[16..18].try_into().unwrap());
-    let data_counter = i16::from_le_bytes(rx_buf[18..20].try_into().unwrap());
+    Ok(mean.map(|m| m as f32))
+  }
 
-    let mut sum: u16 = 0;
-    for i in 2..20 {
-      sum += rx_buf[i] as u16;
-    }
-    if !diagnostic_stat.is_empty() {
-      return Err(diagnostic_stat.into());
+  /// Estimates the gyroscope bias from `samples` stationary readings and
+  /// corrects for it in the bias registers, returning the new offsets. The
+  /// correction is lost on reset unless followed by `flash_update`.
+  pub fn calibrate_gyro_bias(
+    &mut self,
+    samples: usize,
+  ) -> DriverResult<BiasOffsets> {
+    // the outputs already include the current correction, so the residual
+    // is removed from it rather than replacing it
+    let mut bias = self.read_bias()?;
+    let residual = self.estimate_gyro_bias(samples)?;
+
+    for (offset, residual) in bias.gyro.iter_mut().zip(residual) {
+      *offset -= (residual / GYRO_SCALE).round() as i32;
     }
 
-    //pub fn read_control_registers(&mut self) -> DriverResult<ConfigValues> {
+    self.write_bias(&bias)?;
+    Ok(bias)
+  }
 
-    //}
+  /// Reads gyroscope and accelerometer data in one 16 bit burst.
+  pub fn burst_read_gyro_16(
+    &mut self,
+  ) -> DriverResult<(GenericData, GyroReadData)> {
+    let (generic, gyro, accel) = self.burst_read(false, false)?;
+    Ok((generic, GyroReadData { gyro, accel }))
+  }
 
-    return if sum == u16::from_le_bytes(rx_buf[20..22].try_into().unwrap()) {
-      Ok((
-        GenericData { temp, data_counter },
-        DeltaReadData {
-          delta_angle,
-          delta_velocity,
-        },
-      ))
-    } else {
-      Err(InvalidDataError::new("Checksum Failure").into())
-    };
+  /// Reads delta angle and delta velocity data in one 16 bit burst.
+  pub fn burst_read_delta_16(
+    &mut self,
+  ) -> DriverResult<(GenericData, DeltaReadData)> {
+    let (generic, delta_angle, delta_velocity) =
+      self.burst_read(true, false)?;
+
+    Ok((
+      generic,
+      DeltaReadData {
+        delta_angle,
+        delta_velocity,
+      },
+    ))
   }
 
-  /// Does both gyro and delta burst reads
-  fn burst_read_gyro_and_delta(
+  /// Reads gyroscope and accelerometer data at full 32 bit resolution in one
+  /// burst.
+  pub fn burst_read_gyro_32(
     &mut self,
-  ) -> DriverResult<((GenericData, GyroReadData), (GenericData, DeltaReadData))>
-  {
-    const READ_START_OFFSET: usize = 16;
+  ) -> DriverResult<(GenericData, GyroReadData)> {
+    let (generic, gyro, accel) = self.burst_read(false, true)?;
+    Ok((generic, GyroReadData { gyro, accel }))
+  }
 
-    let mut tx_buf: [u8; 22 + READ_START_OFFSET] = [0; 22 + READ_START_OFFSET];
+  /// Reads delta angle and delta velocity data at full 32 bit resolution in
+  /// one burst.
+  pub fn burst_read_delta_32(
+    &mut self,
+  ) -> DriverResult<(GenericData, DeltaReadData)> {
+    let (generic, delta_angle, delta_velocity) = self.burst_read(true, true)?;
+
+    Ok((
+      generic,
+      DeltaReadData {
+        delta_angle,
+        delta_velocity,
+      },
+    ))
+  }
 
-    let mut tx_buf_b: [u8; 22 + READ_START_OFFSET] =
-      [0; 22 + READ_START_OFFSET];
+  /// The outcomes of every burst read since the driver was initialized or
+  /// the counters were last reset.
+  pub fn health(&self) -> HealthCounters {
+    self.health
+  }
 
-    // swap to gyro burst
-    let msc_reg_addr = Registers::MSC_CTRL.get_address();
-    self.config.msc_control_reg &= 0xFEFF;
-    {
-      let msc_write_bytes: [u8; 2] = self.config.msc_control_reg.to_be_bytes();
+  pub fn reset_health(&mut self) {
+    self.health = HealthCounters::default();
+  }
+
+  /// Switches the burst to the requested contents and size, if it isn't
+  /// already.
+  fn select_burst(&mut self, delta: bool, wide: bool) -> DriverResult<()> {
+    let mut msc_ctrl =
+      self.config.msc_control_reg & !(MSC_BURST_SEL | MSC_BURST_32);
 
-      tx_buf[1] = msc_reg_addr[0] | 0x80;
-      tx_buf[0] = msc_write_bytes[1];
-      tx_buf[3] = msc_reg_addr[1] | 0x80;
-      tx_buf[2] = msc_write_bytes[0];
+    if delta {
+      msc_ctrl |= MSC_BURST_SEL;
     }
-    tx_buf[READ_START_OFFSET + 1] = GLOB_CMD[0];
 
-    // swap to delta burst
-    self.config.msc_control_reg |= 0x0100;
-    {
-      let msc_write_bytes: [u8; 2] = self.config.msc_control_reg.to_be_bytes();
+    if wide {
+      msc_ctrl |= MSC_BURST_32;
+    }
 
-      tx_buf_b[1] = msc_reg_addr[0] | 0x80;
-      tx_buf_b[0] = msc_write_bytes[1];
-      tx_buf_b[3] = msc_reg_addr[1] | 0x80;
-      tx_buf_b[2] = msc_write_bytes[0];
+    if msc_ctrl != self.config.msc_control_reg {
+      self.write_msc_ctrl(msc_ctrl)?;
     }
-    tx_buf_b[READ_START_OFFSET + 1] = GLOB_CMD[0];
 
-    let mut rx_buf: [u8; 22 + READ_START_OFFSET] = [0; 22 + READ_START_OFFSET];
-    let mut rx_buf_b: [u8; 22 + READ_START_OFFSET] =
-      [0; 22 + READ_START_OFFSET];
+    Ok(())
+  }
+
+  /// Performs a burst read, counting its outcome.
+  fn burst_read(
+    &mut self,
+    delta: bool,
+    wide: bool,
+  ) -> DriverResult<(GenericData, [i32; 3], [i32; 3])> {
+    let result = self.select_burst(delta, wide).and_then(|()| {
+      let length = if wide { BURST_32_LENGTH } else { BURST_16_LENGTH };
 
-    // do spi stuff
-    self.internals.spi_transfer(&tx_buf, &mut rx_buf)?;
-    sleep(Duration::from_micros(30));
-    self.internals.spi_transfer(&tx_buf_b, &mut rx_buf_b)?;
+      let mut tx_buf = [0; BURST_32_LENGTH];
+      tx_buf[1] = GLOB_CMD[0];
 
-    let gyro_read = GyroReadData {
-      gyro: [
-        (i16::from_le_bytes(
-          rx_buf[READ_START_OFFSET + 4..READ_START_OFFSET + 6]
-            .try_into()
-            .unwrap(),
-        ) as i32)
-          << 16,
-        (i16::from_le_bytes(
-          rx_buf[READ_START_OFFSET + 6..READ_START_OFFSET + 8]
-            .try_into()
-            .unwrap(),
-        ) as i32)
-          << 16,
-        (i16::from_le_bytes(
-          rx_buf[READ_START_OFFSET + 8..READ_START_OFFSET + 10]
-            .try_into()
-            .unwrap(),
-        ) as i32)
-          << 16,
-      ],
+      let mut rx_buf = [0; BURST_32_LENGTH];
 
-      accel: [
-        (i16::from_le_bytes(
-          rx_buf[READ_START_OFFSET + 10..READ_START_OFFSET + 12]
-            .try_into()
-            .unwrap(),
-        ) as i32)
-          << 16,
-        (i16::from_le_bytes(
-          rx_buf[READ_START_OFFSET + 12..READ_START_OFFSET + 14]
-            .try_into()
-            .unwrap(),
-        ) as i32)
-          << 16,
-        (i16::from_le_bytes(
-          rx_buf[READ_START_OFFSET + 14..READ_START_OFFSET + 16]
-            .try_into()
-            .unwrap(),
-        ) as i32)
-          << 16,
-      ],
-    };
+      self
+        .internals
+        .spi_transfer(&tx_buf[..length], &mut rx_buf[..length])?;
 
-    // 16 bit data
-    {
-      let diagnostic_stat: DiagnosticStats = u16::from_le_bytes(
-        rx_buf[READ_START_OFFSET + 2..READ_START_OFFSET + 4]
-          .try_into()
-          .unwrap(),
-      )
-      .into();
-      if !diagnostic_stat.is_empty() {
-        return Err(diagnostic_stat.into());
-      }
-    }
-    let gyro_gen = GenericData {
-      temp: i16::from_le_bytes(
-        rx_buf[READ_START_OFFSET + 16..READ_START_OFFSET + 18]
-          .try_into()
-          .unwrap(),
-      ),
-      data_counter: i16::from_le_bytes(
-        rx_buf[READ_START_OFFSET + 18..READ_START_OFFSET + 20]
-          .try_into()
-          .unwrap(),
-      ),
-    };
+      parse_burst(&rx_buf[..length], wide)
+    });
 
-    {
-      let mut sum: u16 = 0;
-      for i in READ_START_OFFSET + 2..READ_START_OFFSET + 20 {
-        sum += rx_buf[i] as u16;
-      }
-      if sum
-        != u16::from_le_bytes(
-          rx_buf[READ_START_OFFSET + 20..READ_START_OFFSET + 22]
-            .try_into()
-            .unwrap(),
-        )
-      {
-        return Err(InvalidDataError::new("Gyro Checksum Failure").into());
-      }
+    self.health.record(&result);
+    result
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // builds a burst response from the words after the command word, appending
+  // the checksum over their bytes
+  fn burst(words: &[u16]) -> Vec<u8> {
+    let mut rx_buf = vec![0, 0];
+
+    for word in words {
+      rx_buf.extend(word.to_le_bytes());
     }
 
-    let delta_read = DeltaReadData {
-      delta_angle: [
-        (i16::from_le_bytes(
-          rx_buf_b[READ_START_OFFSET + 4..READ_START_OFFSET + 6]
-            .try_into()
-            .unwrap(),
-        ) as i32)
-          << 16,
-        (i16::from_le_bytes(
-          rx_buf_b[READ_START_OFFSET + 6..READ_START_OFFSET + 8]
-            .try_into()
-            .unwrap(),
-        ) as i32)
-          << 16,
-        (i16::from_le_bytes(
-          rx_buf_b[READ_START_OFFSET + 8..READ_START_OFFSET + 10]
-            .try_into()
-            .unwrap(),
-        ) as i32)
-          << 16,
-      ],
+    let checksum = rx_buf[2..]
+      .iter()
+      .fold(0u16, |sum, byte| sum.wrapping_add(*byte as u16));
+    rx_buf.extend(checksum.to_le_bytes());
 
-      delta_velocity: [
-        (i16::from_le_bytes(
-          rx_buf_b[READ_START_OFFSET + 10..READ_START_OFFSET + 12]
-            .try_into()
-            .unwrap(),
-        ) as i32)
-          << 16,
-        (i16::from_le_bytes(
-          rx_buf_b[READ_START_OFFSET + 12..READ_START_OFFSET + 14]
-            .try_into()
-            .unwrap(),
-        ) as i32)
-          << 16,
-        (i16::from_le_bytes(
-          rx_buf_b[READ_START_OFFSET + 14..READ_START_OFFSET + 16]
-            .try_into()
-            .unwrap(),
-        ) as i32)
-          << 16,
-      ],
-    };
+    rx_buf
+  }
 
-    // 16 bit data
-    {
-      let diagnostic_stat: DiagnosticStats = u16::from_le_bytes(
-        rx_buf_b[READ_START_OFFSET + 2..READ_START_OFFSET + 4]
-          .try_into()
-          .unwrap(),
-      )
-      .into();
-      if !diagnostic_stat.is_empty() {
-        return Err(diagnostic_stat.into());
+  #[test]
+  fn parses_a_16_bit_burst() {
+    let rx_buf = burst(&[0, 1, 2, 3, 0xFFFC, 0xFFFB, 0xFFFA, 25, 7]);
+    assert_eq!(rx_buf.len(), BURST_16_LENGTH);
+
+    let (generic, gyro, accel) = parse_burst(&rx_buf, false).unwrap();
+
+    assert_eq!(gyro, [1 << 16, 2 << 16, 3 << 16]);
+    assert_eq!(accel, [-4 << 16, -5 << 16, -6 << 16]);
+    assert_eq!(generic.temp, 25);
+    assert_eq!(generic.data_counter, 7);
+  }
+
+  #[test]
+  fn parses_a_32_bit_burst() {
+    let rx_buf = burst(&[
+      0, 0x5678, 0x1234, 0, 0x0001, 0xFFFF, 0xFFFF, 1, 0, 0, 0, 0xFFFE, 0xFFFF,
+      25, 7,
+    ]);
+    assert_eq!(rx_buf.len(), BURST_32_LENGTH);
+
+    let (generic, gyro, accel) = parse_burst(&rx_buf, true).unwrap();
+
+    assert_eq!(gyro, [0x1234_5678, 0x0001_0000, -1]);
+    assert_eq!(accel, [1, 0, -2]);
+    assert_eq!(generic.temp, 25);
+    assert_eq!(generic.data_counter, 7);
+  }
+
+  #[test]
+  fn rejects_a_checksum_mismatch() {
+    let mut rx_buf = burst(&[0, 1, 2, 3, 4, 5, 6, 25, 7]);
+
+    // flip a bit in the gyroscope, which the checksum no longer matches
+    rx_buf[4] ^= 0x10;
+
+    match parse_burst(&rx_buf, false) {
+      Err(ImuDriverError::ChecksumError { expected, computed }) => {
+        assert_eq!(computed, expected + 0x10);
       }
+      result => panic!("expected a checksum error, got {result:?}"),
     }
-    let delta_gen = GenericData {
-      temp: i16::from_le_bytes(
-        rx_buf_b[READ_START_OFFSET + 16..READ_START_OFFSET + 18]
-          .try_into()
-          .unwrap(),
-      ),
-      data_counter: i16::from_le_bytes(
-        rx_buf_b[READ_START_OFFSET + 18..READ_START_OFFSET + 20]
-          .try_into()
-          .unwrap(),
-      ),
-    };
-    {
-      let mut sum: u16 = 0;
-      for i in READ_START_OFFSET + 2..READ_START_OFFSET + 20 {
-        sum += rx_buf_b[i] as u16;
-      }
-      if sum
-        != u16::from_le_bytes(
-          rx_buf_b[READ_START_OFFSET + 20..READ_START_OFFSET + 22]
-            .try_into()
-            .unwrap(),
-        )
-      {
-        return Err(InvalidDataError::new("Delta Checksum Failure").into());
+  }
+
+  #[test]
+  fn reports_a_data_path_overrun() {
+    let overrun = DiagnosticStats::DATA_PATH_OVERRUN.bits();
+    let rx_buf = burst(&[overrun, 1, 2, 3, 4, 5, 6, 25, 7]);
+
+    assert!(matches!(
+      parse_burst(&rx_buf, false),
+      Err(ImuDriverError::DataPathOverrun(_))
+    ));
+  }
+
+  #[test]
+  fn reports_a_sensor_failure() {
+    // a failed sensor takes precedence over an overrun in the same burst
+    let stats = DiagnosticStats::GYRO_1_FAILURE.bits()
+      | DiagnosticStats::DATA_PATH_OVERRUN.bits();
+    let rx_buf = burst(&[stats, 1, 2, 3, 4, 5, 6, 25, 7]);
+
+    match parse_burst(&rx_buf, false) {
+      Err(ImuDriverError::SensorFailure(stats)) => {
+        assert!(stats.contains(DiagnosticStats::GYRO_1_FAILURE));
       }
+      result => panic!("expected a sensor failure, got {result:?}"),
     }
-
-    Ok(((gyro_gen, gyro_read), (delta_gen, delta_read)))
   }
 }
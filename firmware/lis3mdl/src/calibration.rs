@@ -0,0 +1,532 @@
+//! Hard and soft iron calibration by fitting an ellipsoid to recorded samples.
+//!
+//! Rotated through every orientation, an undistorted magnetometer traces a
+//! sphere centred on zero. Hard iron (fields fixed to the board) moves the
+//! centre and soft iron (material that bends the field) stretches the sphere
+//! into an ellipsoid. Fitting that ellipsoid gives the offset and matrix which
+//! map it back onto a sphere.
+
+use std::{array, fmt, str::FromStr};
+
+/// The fewest samples that can be fit, one per parameter of the ellipsoid.
+pub const MIN_SAMPLES: usize = 9;
+
+// pivots and eigenvalues smaller than this are treated as zero
+const EPSILON: f64 = 1e-12;
+
+/// A hard and soft iron correction, applied as `matrix * (raw - offset)`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Calibration {
+  /// The hard iron offset, in the units of the samples.
+  pub offset: [f64; 3],
+  /// The soft iron correction, symmetric and with a determinant of one so the
+  /// average strength of the field is preserved.
+  pub matrix: [[f64; 3]; 3],
+}
+
+impl Default for Calibration {
+  /// No correction.
+  fn default() -> Self {
+    Calibration {
+      offset: [0.0; 3],
+      matrix: IDENTITY,
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CalibrationError {
+  /// Fewer than `MIN_SAMPLES` samples were given.
+  TooFewSamples(usize),
+  /// The samples don't cover enough orientations to pin down an ellipsoid,
+  /// such as when the board was only turned about one axis.
+  Degenerate,
+  /// The best fitting surface isn't an ellipsoid, so the samples are likely
+  /// too noisy or were disturbed during recording.
+  NotAnEllipsoid,
+}
+
+impl fmt::Display for CalibrationError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      CalibrationError::TooFewSamples(count) => {
+        write!(f, "need at least {MIN_SAMPLES} samples, got {count}")
+      }
+      CalibrationError::Degenerate => {
+        write!(f, "samples do not cover enough orientations")
+      }
+      CalibrationError::NotAnEllipsoid => {
+        write!(f, "samples do not fit an ellipsoid")
+      }
+    }
+  }
+}
+
+impl Calibration {
+  /// Corrects a raw sample.
+  pub fn apply(&self, raw: [f64; 3]) -> [f64; 3] {
+    let centred = [
+      raw[0] - self.offset[0],
+      raw[1] - self.offset[1],
+      raw[2] - self.offset[2],
+    ];
+
+    multiply(&self.matrix, centred)
+  }
+
+  /// Fits a calibration to samples recorded while the board was turned
+  /// through as many orientations as possible.
+  pub fn fit(samples: &[[f64; 3]]) -> Result<Calibration, CalibrationError> {
+    if samples.len() < MIN_SAMPLES {
+      return Err(CalibrationError::TooFewSamples(samples.len()));
+    }
+
+    // the fit is done on samples centred and scaled to around one, so that
+    // the squared terms don't swamp the linear ones whatever the units
+    let count = samples.len() as f64;
+    let mut mean = [0.0; 3];
+
+    for sample in samples {
+      for (m, s) in mean.iter_mut().zip(sample) {
+        *m += s / count;
+      }
+    }
+
+    let scale = samples
+      .iter()
+      .flat_map(|sample| sample.iter().zip(&mean).map(|(s, m)| (s - m).abs()))
+      .fold(0.0, f64::max);
+
+    if scale < EPSILON {
+      return Err(CalibrationError::Degenerate);
+    }
+
+    let normalized = samples.iter().map(|sample| {
+      [
+        (sample[0] - mean[0]) / scale,
+        (sample[1] - mean[1]) / scale,
+        (sample[2] - mean[2]) / scale,
+      ]
+    });
+
+    // least squares fit of the quadric
+    //   Ax² + By² + Cz² + 2Dxy + 2Exz + 2Fyz + 2Gx + 2Hy + 2Iz = 1
+    // by its normal equations
+    let mut normal = [[0.0; 9]; 9];
+    let mut target = [0.0; 9];
+
+    for [x, y, z] in normalized {
+      let row = [
+        x * x,
+        y * y,
+        z * z,
+        2.0 * x * y,
+        2.0 * x * z,
+        2.0 * y * z,
+        2.0 * x,
+        2.0 * y,
+        2.0 * z,
+      ];
+
+      for i in 0..9 {
+        for j in 0..9 {
+          normal[i][j] += row[i] * row[j];
+        }
+
+        target[i] += row[i];
+      }
+    }
+
+    let [a, b, c, d, e, f, g, h, i] =
+      solve(normal, target).ok_or(CalibrationError::Degenerate)?;
+
+    let quadratic = [[a, d, e], [d, b, f], [e, f, c]];
+    let linear = [g, h, i];
+
+    // completing the square gives (x - c)ᵀ M (x - c) = 1 + cᵀ M c, where the
+    // centre c solves M c = -linear
+    let inverse = invert(&quadratic).ok_or(CalibrationError::Degenerate)?;
+    let centre = multiply(&inverse, linear).map(|v| -v);
+    let radius_squared = 1.0 + dot(centre, multiply(&quadratic, centre));
+
+    if radius_squared <= EPSILON {
+      return Err(CalibrationError::NotAnEllipsoid);
+    }
+
+    // M / (1 + cᵀ M c) maps the ellipsoid onto the unit sphere as a quadratic
+    // form, so its square root maps it onto the unit sphere as a vector
+    let shape = quadratic.map(|row| row.map(|v| v / radius_squared));
+    let (values, vectors) = eigen_symmetric(shape);
+
+    if values.iter().any(|v| *v <= EPSILON) {
+      return Err(CalibrationError::NotAnEllipsoid);
+    }
+
+    let mut root = [[0.0; 3]; 3];
+
+    for (k, value) in values.iter().enumerate() {
+      let root_value = value.sqrt();
+
+      for (row, root_row) in root.iter_mut().enumerate() {
+        for (col, element) in root_row.iter_mut().enumerate() {
+          *element += vectors[row][k] * root_value * vectors[col][k];
+        }
+      }
+    }
+
+    // undo the normalization, then scale from the unit sphere to one of the
+    // ellipsoid's geometric mean radius so the units are kept
+    let root = root.map(|row| row.map(|v| v / scale));
+    let mean_radius = determinant(&root).cbrt().recip();
+
+    Ok(Calibration {
+      offset: [
+        mean[0] + scale * centre[0],
+        mean[1] + scale * centre[1],
+        mean[2] + scale * centre[2],
+      ],
+      matrix: root.map(|row| row.map(|v| v * mean_radius)),
+    })
+  }
+}
+
+/// Written as the offset followed by the matrix in row order, separated by
+/// spaces, which `from_str` reads back.
+impl fmt::Display for Calibration {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let values = self.offset.iter().chain(self.matrix.iter().flatten());
+
+    for (index, value) in values.enumerate() {
+      if index > 0 {
+        write!(f, " ")?;
+      }
+
+      write!(f, "{value}")?;
+    }
+
+    Ok(())
+  }
+}
+
+impl FromStr for Calibration {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let values = s
+      .split_whitespace()
+      .map(str::parse::<f64>)
+      .collect::<Result<Vec<f64>, _>>()
+      .map_err(|error| error.to_string())?;
+
+    let Ok(values) = <[f64; 12]>::try_from(values.as_slice()) else {
+      return Err(format!("expected 12 values, found {}", values.len()));
+    };
+
+    if values.iter().any(|v| !v.is_finite()) {
+      return Err("calibration values must be finite".to_owned());
+    }
+
+    Ok(Calibration {
+      offset: [values[0], values[1], values[2]],
+      matrix: [
+        [values[3], values[4], values[5]],
+        [values[6], values[7], values[8]],
+        [values[9], values[10], values[11]],
+      ],
+    })
+  }
+}
+
+const IDENTITY: [[f64; 3]; 3] =
+  [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+fn multiply(matrix: &[[f64; 3]; 3], vector: [f64; 3]) -> [f64; 3] {
+  matrix.map(|row| dot(row, vector))
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+  a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn determinant(m: &[[f64; 3]; 3]) -> f64 {
+  m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+    - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+    + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+}
+
+fn invert(m: &[[f64; 3]; 3]) -> Option<[[f64; 3]; 3]> {
+  let det = determinant(m);
+
+  if det.abs() < EPSILON {
+    return None;
+  }
+
+  // the transposed matrix of cofactors over the determinant
+  let mut inverse = [[0.0; 3]; 3];
+
+  for (row, inverse_row) in inverse.iter_mut().enumerate() {
+    for (col, element) in inverse_row.iter_mut().enumerate() {
+      let (r1, r2) = ((col + 1) % 3, (col + 2) % 3);
+      let (c1, c2) = ((row + 1) % 3, (row + 2) % 3);
+
+      *element = (m[r1][c1] * m[r2][c2] - m[r1][c2] * m[r2][c1]) / det;
+    }
+  }
+
+  Some(inverse)
+}
+
+/// Solves `a x = b` by Gaussian elimination with partial pivoting, or None if
+/// `a` is singular.
+fn solve<const N: usize>(
+  mut a: [[f64; N]; N],
+  mut b: [f64; N],
+) -> Option<[f64; N]> {
+  for col in 0..N {
+    let pivot =
+      (col..N).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+
+    if a[pivot][col].abs() < EPSILON {
+      return None;
+    }
+
+    a.swap(col, pivot);
+    b.swap(col, pivot);
+
+    for row in col + 1..N {
+      let factor = a[row][col] / a[col][col];
+      let pivot_row = a[col];
+
+      for (element, pivot) in a[row][col..].iter_mut().zip(&pivot_row[col..]) {
+        *element -= factor * pivot;
+      }
+
+      b[row] -= factor * b[col];
+    }
+  }
+
+  let mut x = [0.0; N];
+
+  for row in (0..N).rev() {
+    let sum: f64 = (row + 1..N).map(|k| a[row][k] * x[k]).sum();
+    x[row] = (b[row] - sum) / a[row][row];
+  }
+
+  Some(x)
+}
+
+/// The eigenvalues and eigenvectors, as columns, of a symmetric matrix by
+/// Jacobi rotation.
+fn eigen_symmetric(mut a: [[f64; 3]; 3]) -> ([f64; 3], [[f64; 3]; 3]) {
+  let mut vectors = IDENTITY;
+
+  for _ in 0..50 {
+    let off_diagonal = a[0][1].powi(2) + a[0][2].powi(2) + a[1][2].powi(2);
+
+    if off_diagonal < EPSILON * EPSILON {
+      break;
+    }
+
+    for (p, q) in [(0, 1), (0, 2), (1, 2)] {
+      if a[p][q].abs() < EPSILON * EPSILON {
+        continue;
+      }
+
+      // the rotation which zeroes a[p][q]
+      let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+      let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+      let cos = (t * t + 1.0).sqrt().recip();
+      let sin = t * cos;
+
+      for row in a.iter_mut() {
+        let (akp, akq) = (row[p], row[q]);
+        row[p] = cos * akp - sin * akq;
+        row[q] = sin * akp + cos * akq;
+      }
+
+      let (row_p, row_q) = (a[p], a[q]);
+      a[p] = array::from_fn(|k| cos * row_p[k] - sin * row_q[k]);
+      a[q] = array::from_fn(|k| sin * row_p[k] + cos * row_q[k]);
+
+      for row in vectors.iter_mut() {
+        let (vp, vq) = (row[p], row[q]);
+        row[p] = cos * vp - sin * vq;
+        row[q] = sin * vp + cos * vq;
+      }
+    }
+  }
+
+  ([a[0][0], a[1][1], a[2][2]], vectors)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // roughly the strength of the earth's field, in gauss
+  const FIELD: f64 = 0.5;
+
+  // evenly spread directions on the unit sphere
+  fn directions(count: usize) -> Vec<[f64; 3]> {
+    let golden_angle = std::f64::consts::PI * (3.0 - 5.0f64.sqrt());
+
+    (0..count)
+      .map(|i| {
+        let z = 1.0 - 2.0 * (i as f64 + 0.5) / count as f64;
+        let radius = (1.0 - z * z).sqrt();
+        let angle = golden_angle * i as f64;
+
+        [radius * angle.cos(), radius * angle.sin(), z]
+      })
+      .collect()
+  }
+
+  // a small deterministic noise source, so the tests need no randomness
+  fn noise(state: &mut u64, amplitude: f64) -> f64 {
+    *state = state
+      .wrapping_mul(6_364_136_223_846_793_005)
+      .wrapping_add(1_442_695_040_888_963_407);
+
+    ((*state >> 11) as f64 / (1u64 << 53) as f64 - 0.5) * 2.0 * amplitude
+  }
+
+  // distorts true fields the way a board's hard and soft iron would
+  fn distort(
+    fields: &[[f64; 3]],
+    soft_iron: [[f64; 3]; 3],
+    hard_iron: [f64; 3],
+    amplitude: f64,
+  ) -> Vec<[f64; 3]> {
+    let mut state = 7;
+
+    fields
+      .iter()
+      .map(|field| {
+        let distorted = multiply(&soft_iron, *field);
+
+        [
+          distorted[0] + hard_iron[0] + noise(&mut state, amplitude),
+          distorted[1] + hard_iron[1] + noise(&mut state, amplitude),
+          distorted[2] + hard_iron[2] + noise(&mut state, amplitude),
+        ]
+      })
+      .collect()
+  }
+
+  fn magnitude(v: [f64; 3]) -> f64 {
+    dot(v, v).sqrt()
+  }
+
+  fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+    assert!(
+      (actual - expected).abs() <= tolerance,
+      "expected {expected} ± {tolerance}, got {actual}"
+    );
+  }
+
+  #[test]
+  fn sphere_needs_no_correction() {
+    let fields: Vec<_> = directions(200)
+      .iter()
+      .map(|d| d.map(|c| c * FIELD))
+      .collect();
+
+    let calibration = Calibration::fit(&fields).unwrap();
+
+    for (actual, expected) in calibration.offset.iter().zip([0.0; 3]) {
+      assert_close(*actual, expected, 1e-9);
+    }
+
+    for (row, identity_row) in calibration.matrix.iter().zip(IDENTITY) {
+      for (actual, expected) in row.iter().zip(identity_row) {
+        assert_close(*actual, expected, 1e-9);
+      }
+    }
+  }
+
+  #[test]
+  fn removes_hard_and_soft_iron() {
+    let fields: Vec<_> = directions(500)
+      .iter()
+      .map(|d| d.map(|c| c * FIELD))
+      .collect();
+
+    // symmetric and positive definite, with the determinant of one that the
+    // fit normalizes to
+    let soft_iron = [[1.2, 0.1, -0.05], [0.1, 0.9, 0.08], [-0.05, 0.08, 1.0]];
+    let det = determinant(&soft_iron).cbrt();
+    let soft_iron = soft_iron.map(|row| row.map(|v| v / det));
+    let hard_iron = [0.15, -0.3, 0.07];
+
+    let samples = distort(&fields, soft_iron, hard_iron, 0.002);
+    let calibration = Calibration::fit(&samples).unwrap();
+
+    for (actual, expected) in calibration.offset.iter().zip(hard_iron) {
+      assert_close(*actual, expected, 0.005);
+    }
+
+    for (sample, field) in samples.iter().zip(&fields) {
+      let corrected = calibration.apply(*sample);
+
+      assert_close(magnitude(corrected), FIELD, 0.01);
+
+      // a symmetric distortion is undone without rotating the field
+      let cosine = dot(corrected, *field) / (magnitude(corrected) * FIELD);
+      assert!(cosine > 1.0f64.to_radians().cos(), "cosine was {cosine}");
+    }
+  }
+
+  #[test]
+  fn keeps_units_of_the_samples() {
+    // raw counts of a magnetometer at 6842 LSB per gauss
+    let fields: Vec<_> = directions(300)
+      .iter()
+      .map(|d| d.map(|c| c * FIELD * 6842.0))
+      .collect();
+
+    let soft_iron = [[1.0, 0.0, 0.0], [0.0, 1.5, 0.0], [0.0, 0.0, 0.8]];
+    let samples = distort(&fields, soft_iron, [400.0, 250.0, -900.0], 0.0);
+
+    let calibration = Calibration::fit(&samples).unwrap();
+
+    // the mean radius of the ellipsoid, from the product of its semi-axes
+    let expected = FIELD * 6842.0 * (1.0f64 * 1.5 * 0.8).cbrt();
+
+    for sample in &samples {
+      assert_close(magnitude(calibration.apply(*sample)), expected, 1e-6);
+    }
+  }
+
+  #[test]
+  fn rejects_too_few_or_flat_samples() {
+    let fields = directions(8);
+    assert_eq!(
+      Calibration::fit(&fields),
+      Err(CalibrationError::TooFewSamples(8))
+    );
+
+    // only turned about the vertical axis, so the samples lie on a circle
+    let circle: Vec<_> = (0..100)
+      .map(|i| {
+        let angle = i as f64 * 0.1;
+        [FIELD * angle.cos(), FIELD * angle.sin(), 0.2]
+      })
+      .collect();
+
+    assert_eq!(Calibration::fit(&circle), Err(CalibrationError::Degenerate));
+  }
+
+  #[test]
+  fn round_trips_through_text() {
+    let calibration = Calibration {
+      offset: [0.1, -0.25, 3.0e-4],
+      matrix: [[1.1, 0.01, -0.02], [0.01, 0.95, 0.0], [-0.02, 0.0, 0.97]],
+    };
+
+    let text = calibration.to_string();
+    assert_eq!(text.parse::<Calibration>(), Ok(calibration));
+
+    assert!("1 2 3".parse::<Calibration>().is_err());
+    assert!("1 2 3 4 5 6 7 8 9 10 11 x".parse::<Calibration>().is_err());
+  }
+}
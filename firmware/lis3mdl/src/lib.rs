@@ -0,0 +1,276 @@
+//! Driver for the ST LIS3MDL three-axis magnetometer on the AHRS board, over
+//! SPI with a GPIO chip select.
+//!
+//! The magnetometer shares its SPI bus with the IMU, so rather than
+//! reconfiguring the bus for everyone, the word size and clock speed are set on
+//! each transfer.
+
+pub mod calibration;
+
+use common::comm::gpio::{
+  Pin,
+  PinMode::Output,
+  PinValue::{High, Low},
+};
+use spidev::{SpiModeFlags, Spidev, SpidevOptions, SpidevTransfer};
+use std::{fmt, io, thread, time::Duration};
+
+// register locations
+const WHO_AM_I: u8 = 0x0F;
+const CTRL_REG1: u8 = 0x20;
+const CTRL_REG2: u8 = 0x21;
+const CTRL_REG3: u8 = 0x22;
+const CTRL_REG4: u8 = 0x23;
+const CTRL_REG5: u8 = 0x24;
+const STATUS_REG: u8 = 0x27;
+const OUT_X_L: u8 = 0x28;
+const TEMP_OUT_L: u8 = 0x2E;
+
+// the value of WHO_AM_I for a LIS3MDL
+const DEVICE_ID: u8 = 0x3D;
+
+// address bits of the first byte of a transfer
+const READ: u8 = 0x80;
+const AUTO_INCREMENT: u8 = 0x40;
+
+// CTRL_REG1 bits
+const TEMP_EN: u8 = 1 << 7;
+const XY_ULTRA_HIGH_PERFORMANCE: u8 = 0b11 << 5;
+const DATA_RATE_SHIFT: u8 = 2;
+const FAST_ODR: u8 = 1 << 1;
+
+// CTRL_REG2 bits
+const FULL_SCALE_SHIFT: u8 = 5;
+const SOFT_RST: u8 = 1 << 2;
+
+// CTRL_REG3 bits, where zero selects continuous conversion
+const CONTINUOUS_CONVERSION: u8 = 0b00;
+
+// CTRL_REG4 bits
+const Z_ULTRA_HIGH_PERFORMANCE: u8 = 0b11 << 2;
+
+// CTRL_REG5 bits
+const BLOCK_DATA_UPDATE: u8 = 1 << 6;
+
+// STATUS_REG bits
+const ZYX_DATA_AVAILABLE: u8 = 1 << 3;
+
+// the datasheet allows 10 MHz, but the bus is shared with slower devices
+const SPI_SPEED_HZ: u32 = 1_000_000;
+
+// time for a soft reset to reload the registers, with some leeway
+const RESET_TIME: Duration = Duration::from_millis(10);
+
+#[derive(Debug)]
+pub enum MagnetometerError {
+  /// WHO_AM_I didn't match a LIS3MDL, so something else (or nothing) is on
+  /// the chip select.
+  InvalidDeviceId(u8),
+  SPI(io::Error),
+}
+
+impl From<io::Error> for MagnetometerError {
+  fn from(err: io::Error) -> MagnetometerError {
+    MagnetometerError::SPI(err)
+  }
+}
+
+impl fmt::Display for MagnetometerError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      MagnetometerError::InvalidDeviceId(id) => {
+        write!(f, "Expected device ID {DEVICE_ID:#04x}, found {id:#04x}")
+      }
+      MagnetometerError::SPI(err) => write!(f, "SPI ERROR - {err}"),
+    }
+  }
+}
+
+/// The range of field the magnetometer measures, in gauss.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FullScale {
+  Gauss4,
+  Gauss8,
+  Gauss12,
+  Gauss16,
+}
+
+impl FullScale {
+  fn bits(self) -> u8 {
+    match self {
+      FullScale::Gauss4 => 0b00,
+      FullScale::Gauss8 => 0b01,
+      FullScale::Gauss12 => 0b10,
+      FullScale::Gauss16 => 0b11,
+    }
+  }
+
+  /// The sensitivity, in LSB per gauss.
+  pub fn lsb_per_gauss(self) -> f64 {
+    match self {
+      FullScale::Gauss4 => 6842.0,
+      FullScale::Gauss8 => 3421.0,
+      FullScale::Gauss12 => 2281.0,
+      FullScale::Gauss16 => 1711.0,
+    }
+  }
+}
+
+/// How often the magnetometer produces a sample. `Fast` is 155 Hz in the
+/// ultra-high-performance mode the driver uses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DataRate {
+  Hz10,
+  Hz20,
+  Hz40,
+  Hz80,
+  Fast,
+}
+
+impl DataRate {
+  fn bits(self) -> u8 {
+    match self {
+      DataRate::Hz10 => 0b100 << DATA_RATE_SHIFT,
+      DataRate::Hz20 => 0b101 << DATA_RATE_SHIFT,
+      DataRate::Hz40 => 0b110 << DATA_RATE_SHIFT,
+      DataRate::Hz80 => 0b111 << DATA_RATE_SHIFT,
+      DataRate::Fast => FAST_ODR,
+    }
+  }
+}
+
+pub struct Magnetometer {
+  spidev: Spidev,
+  cs_pin: Pin,
+  full_scale: FullScale,
+}
+
+impl Magnetometer {
+  /// Opens the magnetometer, checks that it is a LIS3MDL and resets it to
+  /// continuous conversion at 80 Hz over ±4 gauss.
+  pub fn new(
+    bus: &str,
+    mut cs_pin: Pin,
+  ) -> Result<Magnetometer, MagnetometerError> {
+    cs_pin.mode(Output);
+    cs_pin.digital_write(High); // active low
+
+    let mut spidev = Spidev::open(bus)?;
+
+    // only the mode is shared with the IMU, which also uses mode 3
+    let options = SpidevOptions::new()
+      .mode(SpiModeFlags::SPI_MODE_3)
+      .lsb_first(false)
+      .build();
+
+    spidev.configure(&options)?;
+
+    let mut magnetometer = Magnetometer {
+      spidev,
+      cs_pin,
+      full_scale: FullScale::Gauss4,
+    };
+
+    let id = magnetometer.read_register(WHO_AM_I)?;
+
+    if id != DEVICE_ID {
+      return Err(MagnetometerError::InvalidDeviceId(id));
+    }
+
+    magnetometer.write_register(CTRL_REG2, SOFT_RST)?;
+    thread::sleep(RESET_TIME);
+
+    magnetometer.write_register(CTRL_REG4, Z_ULTRA_HIGH_PERFORMANCE)?;
+    magnetometer.write_register(CTRL_REG5, BLOCK_DATA_UPDATE)?;
+    magnetometer.set_data_rate(DataRate::Hz80)?;
+    magnetometer.set_full_scale(FullScale::Gauss4)?;
+    magnetometer.write_register(CTRL_REG3, CONTINUOUS_CONVERSION)?;
+
+    Ok(magnetometer)
+  }
+
+  pub fn set_data_rate(&mut self, rate: DataRate) -> io::Result<()> {
+    self.write_register(
+      CTRL_REG1,
+      TEMP_EN | XY_ULTRA_HIGH_PERFORMANCE | rate.bits(),
+    )
+  }
+
+  pub fn set_full_scale(&mut self, full_scale: FullScale) -> io::Result<()> {
+    self.write_register(CTRL_REG2, full_scale.bits() << FULL_SCALE_SHIFT)?;
+    self.full_scale = full_scale;
+    Ok(())
+  }
+
+  pub fn get_full_scale(&self) -> FullScale {
+    self.full_scale
+  }
+
+  /// Whether a new sample is available on all three axes.
+  pub fn data_ready(&mut self) -> io::Result<bool> {
+    Ok(self.read_register(STATUS_REG)? & ZYX_DATA_AVAILABLE != 0)
+  }
+
+  /// Reads the latest field on each axis, in gauss.
+  pub fn read_field(&mut self) -> io::Result<[f64; 3]> {
+    let mut raw = [0; 6];
+    self.read_registers(OUT_X_L, &mut raw)?;
+
+    let scale = self.full_scale.lsb_per_gauss();
+
+    Ok([
+      i16::from_le_bytes([raw[0], raw[1]]) as f64 / scale,
+      i16::from_le_bytes([raw[2], raw[3]]) as f64 / scale,
+      i16::from_le_bytes([raw[4], raw[5]]) as f64 / scale,
+    ])
+  }
+
+  /// Reads the die temperature, in degrees Celsius. It is only accurate
+  /// relative to itself, so is suited to compensation rather than display.
+  pub fn read_temperature(&mut self) -> io::Result<f64> {
+    let mut raw = [0; 2];
+    self.read_registers(TEMP_OUT_L, &mut raw)?;
+
+    // 8 LSB per degree, with zero at 25 degrees
+    Ok(25.0 + i16::from_le_bytes(raw) as f64 / 8.0)
+  }
+
+  fn read_register(&mut self, address: u8) -> io::Result<u8> {
+    let mut value = [0];
+    self.read_registers(address, &mut value)?;
+    Ok(value[0])
+  }
+
+  fn read_registers(
+    &mut self,
+    address: u8,
+    values: &mut [u8],
+  ) -> io::Result<()> {
+    let mut tx_buf = vec![0; values.len() + 1];
+    tx_buf[0] = READ | AUTO_INCREMENT | address;
+
+    let mut rx_buf = vec![0; values.len() + 1];
+    self.transfer(&tx_buf, &mut rx_buf)?;
+
+    values.copy_from_slice(&rx_buf[1..]);
+    Ok(())
+  }
+
+  fn write_register(&mut self, address: u8, value: u8) -> io::Result<()> {
+    let tx_buf = [address, value];
+    let mut rx_buf = [0; 2];
+    self.transfer(&tx_buf, &mut rx_buf)
+  }
+
+  fn transfer(&mut self, tx_buf: &[u8], rx_buf: &mut [u8]) -> io::Result<()> {
+    let mut transfer = SpidevTransfer::read_write(tx_buf, rx_buf);
+    transfer.bits_per_word = 8;
+    transfer.speed_hz = SPI_SPEED_HZ;
+
+    self.cs_pin.digital_write(Low);
+    let result = self.spidev.transfer(&mut transfer);
+    self.cs_pin.digital_write(High);
+
+    result
+  }
+}
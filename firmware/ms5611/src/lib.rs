@@ -0,0 +1,315 @@
+//! Driver for the TE MS5611 barometric pressure sensor on the AHRS board, over
+//! SPI with a GPIO chip select.
+//!
+//! A conversion takes milliseconds, so rather than blocking on each one, the
+//! driver starts a conversion and reads it back the next time it is polled,
+//! alternating between temperature and pressure.
+
+use common::comm::gpio::{
+  Pin,
+  PinMode::Output,
+  PinValue::{High, Low},
+};
+use spidev::{SpiModeFlags, Spidev, SpidevOptions, SpidevTransfer};
+use std::{
+  fmt,
+  io,
+  thread,
+  time::{Duration, Instant},
+};
+
+// commands
+const RESET: u8 = 0x1E;
+const CONVERT_D1: u8 = 0x40;
+const CONVERT_D2: u8 = 0x50;
+const ADC_READ: u8 = 0x00;
+const PROM_READ: u8 = 0xA0;
+
+// the oversampling ratio of 1024, added to either conversion command
+const OSR_1024: u8 = 0x04;
+
+// the longest a conversion takes at OSR 1024, with some leeway
+const CONVERSION_TIME: Duration = Duration::from_micros(2500);
+
+// time for a reset to reload the PROM, with some leeway
+const RESET_TIME: Duration = Duration::from_millis(3);
+
+// the datasheet allows 20 MHz, but the bus is shared with slower devices
+const SPI_SPEED_HZ: u32 = 1_000_000;
+
+#[derive(Debug)]
+pub enum BarometerError {
+  /// The calibration PROM failed its CRC, so something else (or nothing) is
+  /// on the chip select.
+  InvalidProm,
+  SPI(io::Error),
+}
+
+impl From<io::Error> for BarometerError {
+  fn from(err: io::Error) -> BarometerError {
+    BarometerError::SPI(err)
+  }
+}
+
+impl fmt::Display for BarometerError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      BarometerError::InvalidProm => {
+        write!(f, "Calibration PROM failed its CRC")
+      }
+      BarometerError::SPI(err) => write!(f, "SPI ERROR - {err}"),
+    }
+  }
+}
+
+/// A compensated reading of the barometer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Reading {
+  /// The temperature, in degrees Celsius.
+  pub temperature: f64,
+
+  /// The pressure, in bar.
+  pub pressure: f64,
+}
+
+// which conversion is running
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Conversion {
+  Temperature,
+  Pressure,
+}
+
+pub struct Barometer {
+  spidev: Spidev,
+  cs_pin: Pin,
+  // the factory calibration, C1 to C6 at indices 1 to 6
+  prom: [u16; 8],
+  running: Option<(Conversion, Instant)>,
+  // the raw temperature to compensate the next pressure with
+  d2: Option<u32>,
+}
+
+impl Barometer {
+  /// Opens the barometer, resets it and checks its calibration PROM.
+  pub fn new(bus: &str, mut cs_pin: Pin) -> Result<Barometer, BarometerError> {
+    cs_pin.mode(Output);
+    cs_pin.digital_write(High); // active low
+
+    let mut spidev = Spidev::open(bus)?;
+
+    // shares the bus with the IMU, which also uses mode 3
+    let options = SpidevOptions::new()
+      .mode(SpiModeFlags::SPI_MODE_3)
+      .lsb_first(false)
+      .build();
+
+    spidev.configure(&options)?;
+
+    let mut barometer = Barometer {
+      spidev,
+      cs_pin,
+      prom: [0; 8],
+      running: None,
+      d2: None,
+    };
+
+    barometer.transfer(&[RESET], &mut [0])?;
+    thread::sleep(RESET_TIME);
+
+    for i in 0..barometer.prom.len() {
+      let mut rx_buf = [0; 3];
+      barometer.transfer(&[PROM_READ + 2 * i as u8, 0, 0], &mut rx_buf)?;
+      barometer.prom[i] = u16::from_be_bytes([rx_buf[1], rx_buf[2]]);
+    }
+
+    if !prom_is_valid(&barometer.prom) {
+      return Err(BarometerError::InvalidProm);
+    }
+
+    Ok(barometer)
+  }
+
+  /// Reads back the running conversion if it has finished and starts the next
+  /// one. Returns a new reading each time a pressure conversion finishes.
+  pub fn poll(&mut self) -> io::Result<Option<Reading>> {
+    let conversion = match self.running {
+      Some((_, started)) if started.elapsed() < CONVERSION_TIME => {
+        return Ok(None);
+      }
+      Some((conversion, _)) => conversion,
+      None => {
+        self.start(Conversion::Temperature)?;
+        return Ok(None);
+      }
+    };
+
+    let raw = self.read_adc()?;
+
+    // a read before the conversion finished returns zero, so it is started
+    // over rather than trusted
+    if raw == 0 {
+      self.start(Conversion::Temperature)?;
+      return Ok(None);
+    }
+
+    match conversion {
+      Conversion::Temperature => {
+        self.d2 = Some(raw);
+        self.start(Conversion::Pressure)?;
+        Ok(None)
+      }
+      Conversion::Pressure => {
+        self.start(Conversion::Temperature)?;
+        Ok(self.d2.map(|d2| compensate(&self.prom, raw, d2)))
+      }
+    }
+  }
+
+  fn start(&mut self, conversion: Conversion) -> io::Result<()> {
+    let command = match conversion {
+      Conversion::Temperature => CONVERT_D2 | OSR_1024,
+      Conversion::Pressure => CONVERT_D1 | OSR_1024,
+    };
+
+    self.transfer(&[command], &mut [0])?;
+    self.running = Some((conversion, Instant::now()));
+    Ok(())
+  }
+
+  fn read_adc(&mut self) -> io::Result<u32> {
+    let mut rx_buf = [0; 4];
+    self.transfer(&[ADC_READ, 0, 0, 0], &mut rx_buf)?;
+    Ok(u32::from_be_bytes([0, rx_buf[1], rx_buf[2], rx_buf[3]]))
+  }
+
+  fn transfer(&mut self, tx_buf: &[u8], rx_buf: &mut [u8]) -> io::Result<()> {
+    let mut transfer = SpidevTransfer::read_write(tx_buf, rx_buf);
+    transfer.bits_per_word = 8;
+    transfer.speed_hz = SPI_SPEED_HZ;
+
+    self.cs_pin.digital_write(Low);
+    let result = self.spidev.transfer(&mut transfer);
+    self.cs_pin.digital_write(High);
+
+    result
+  }
+}
+
+/// Checks the 4-bit CRC in the last word of the PROM, as described in TE
+/// application note AN520. A PROM of all zeros or all ones is nothing
+/// answering on the bus, and is rejected despite passing.
+fn prom_is_valid(prom: &[u16; 8]) -> bool {
+  let blank = prom.iter().all(|word| *word == 0)
+    || prom.iter().all(|word| *word == 0xFFFF);
+
+  !blank && crc4(prom) == (prom[7] & 0x000F) as u8
+}
+
+fn crc4(prom: &[u16; 8]) -> u8 {
+  let mut words = *prom;
+  let mut remainder: u16 = 0;
+
+  // the CRC itself is not part of what it covers
+  words[7] &= 0xFF00;
+
+  for i in 0..16 {
+    remainder ^= if i % 2 == 1 {
+      words[i / 2] & 0x00FF
+    } else {
+      words[i / 2] >> 8
+    };
+
+    for _ in 0..8 {
+      remainder = if remainder & 0x8000 != 0 {
+        (remainder << 1) ^ 0x3000
+      } else {
+        remainder << 1
+      };
+    }
+  }
+
+  ((remainder >> 12) & 0x000F) as u8
+}
+
+/// Compensates a raw pressure (D1) and temperature (D2) with the factory
+/// calibration, including the second order correction below 20 degrees.
+fn compensate(prom: &[u16; 8], d1: u32, d2: u32) -> Reading {
+  let c = prom.map(i64::from);
+  let (d1, d2) = (i64::from(d1), i64::from(d2));
+
+  let dt = d2 - (c[5] << 8);
+  let mut temperature = 2000 + ((dt * c[6]) >> 23);
+  let mut offset = (c[2] << 16) + ((c[4] * dt) >> 7);
+  let mut sensitivity = (c[1] << 15) + ((c[3] * dt) >> 8);
+
+  if temperature < 2000 {
+    let cold = (temperature - 2000).pow(2);
+    let mut offset_2 = 5 * cold / 2;
+    let mut sensitivity_2 = 5 * cold / 4;
+
+    if temperature < -1500 {
+      let very_cold = (temperature + 1500).pow(2);
+      offset_2 += 7 * very_cold;
+      sensitivity_2 += 11 * very_cold / 2;
+    }
+
+    temperature -= dt.pow(2) >> 31;
+    offset -= offset_2;
+    sensitivity -= sensitivity_2;
+  }
+
+  // in hundredths of a millibar, which is pascals
+  let pressure = (((d1 * sensitivity) >> 21) - offset) >> 15;
+
+  Reading {
+    temperature: temperature as f64 / 100.0,
+    pressure: pressure as f64 / 100_000.0,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // the calibration from the worked example in the datasheet
+  const PROM: [u16; 8] = [0, 40127, 36924, 23317, 23282, 33464, 28312, 0];
+
+  #[test]
+  fn compensates_the_datasheet_example() {
+    let reading = compensate(&PROM, 9_085_466, 8_569_150);
+
+    assert_eq!(reading.temperature, 20.07);
+    assert_eq!(reading.pressure, 1.00009);
+  }
+
+  #[test]
+  fn corrects_below_twenty_degrees() {
+    // about 10 degrees colder than the datasheet example
+    let cold = compensate(&PROM, 9_085_466, 8_569_150 - 300_000);
+
+    assert!(cold.temperature < 10.0 && cold.temperature > 9.0);
+    assert!(cold.pressure.is_finite() && cold.pressure > 0.0);
+  }
+
+  #[test]
+  fn checks_the_application_note_crc() {
+    let prom = [
+      0x3132, 0x3334, 0x3536, 0x3738, 0x3940, 0x4142, 0x4344, 0x4546,
+    ];
+
+    assert_eq!(crc4(&prom), 0x0B);
+  }
+
+  #[test]
+  fn rejects_corrupt_and_blank_proms() {
+    let mut prom = PROM;
+    prom[7] = crc4(&prom) as u16;
+    assert!(prom_is_valid(&prom));
+
+    prom[3] ^= 1;
+    assert!(!prom_is_valid(&prom));
+
+    assert!(!prom_is_valid(&[0; 8]));
+    assert!(!prom_is_valid(&[0xFFFF; 8]));
+  }
+}
@@ -0,0 +1,232 @@
+use crate::{forwarder, state::SharedState};
+use common::comm::{
+  Configuration,
+  ConfigurationSource,
+  ConfigurationStatus,
+  FlightStatusMessage,
+};
+use jeflog::{fail, pass, warn};
+use serde::{Deserialize, Serialize};
+use std::{
+  fs::{self, File},
+  io::{self, Write},
+  path::Path,
+};
+
+/// Where the last configuration received from the server is saved, so that it
+/// survives the flight computer restarting.
+const CONFIGURATION_PATH: &str = "/var/lib/flight/configuration";
+
+/// A configuration as saved to disk.
+#[derive(Deserialize, Serialize)]
+struct Saved {
+  /// The UNIX timestamp at which the configuration was saved.
+  saved_at: f64,
+
+  configuration: Configuration,
+}
+
+/// Restores the configuration saved before the flight computer last stopped,
+/// if there is one. This should be called before anything reads the
+/// configuration.
+pub fn restore(shared: &SharedState) {
+  let saved = match load(Path::new(CONFIGURATION_PATH)) {
+    Ok(Some(saved)) => saved,
+    Ok(None) => {
+      warn!("No saved configuration to restore. Starting empty.");
+      return;
+    }
+    Err(error) => {
+      // likely saved by a different version of the flight software
+      fail!("Failed to restore saved configuration: {error}. Starting empty.");
+      return;
+    }
+  };
+
+  let Configuration {
+    mappings,
+    triggers,
+    abort_sequence,
+    comms_policies,
+    server_loss_policy,
+  } = saved.configuration;
+
+  pass!(
+    "Restored {} mappings, {} triggers, {} policies and {} abort sequence.",
+    mappings.len(),
+    triggers.len(),
+    comms_policies.len(),
+    if abort_sequence.is_some() { "an" } else { "no" }
+  );
+
+  *shared.mappings.lock().unwrap() = mappings;
+  *shared.triggers.lock().unwrap() = triggers;
+  *shared.abort_sequence.lock().unwrap() = abort_sequence;
+  *shared.comms_policies.lock().unwrap() = comms_policies;
+  *shared.server_loss_policy.lock().unwrap() = server_loss_policy;
+  *shared.configuration_source.lock().unwrap() =
+    ConfigurationSource::Restored {
+      saved_at: saved.saved_at,
+    };
+}
+
+/// Saves the current configuration after the server changes it, replacing
+/// what was saved before only once the new configuration is safely on disk.
+/// The server is then sent the digest of the new configuration and whether it
+/// was saved.
+pub fn save(shared: &SharedState) {
+  let saved = Saved {
+    saved_at: forwarder::timestamp(),
+    configuration: current(shared),
+  };
+
+  let persisted = match store(Path::new(CONFIGURATION_PATH), &saved) {
+    Ok(()) => {
+      *shared.configuration_source.lock().unwrap() = ConfigurationSource::Fresh;
+      true
+    }
+    Err(error) => {
+      fail!("Failed to save configuration: {error}");
+      false
+    }
+  };
+
+  *shared.configuration_persisted.lock().unwrap() = persisted;
+  report(shared, &saved.configuration);
+}
+
+/// Tells the server where the current configuration came from and its digest.
+pub fn report_status(shared: &SharedState) {
+  report(shared, &current(shared));
+}
+
+fn current(shared: &SharedState) -> Configuration {
+  Configuration {
+    mappings: shared.mappings.lock().unwrap().clone(),
+    triggers: shared.triggers.lock().unwrap().clone(),
+    abort_sequence: shared.abort_sequence.lock().unwrap().clone(),
+    comms_policies: shared.comms_policies.lock().unwrap().clone(),
+    server_loss_policy: shared.server_loss_policy.lock().unwrap().clone(),
+  }
+}
+
+fn report(shared: &SharedState, configuration: &Configuration) {
+  let status = ConfigurationStatus {
+    source: *shared.configuration_source.lock().unwrap(),
+    digest: configuration.digest(),
+    persisted: *shared.configuration_persisted.lock().unwrap(),
+  };
+
+  forwarder::report(shared, &FlightStatusMessage::Configuration(status));
+}
+
+fn load(path: &Path) -> io::Result<Option<Saved>> {
+  let bytes = match fs::read(path) {
+    Ok(bytes) => bytes,
+    Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+    Err(error) => return Err(error),
+  };
+
+  postcard::from_bytes(&bytes)
+    .map(Some)
+    .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+/// Writes to a temporary file beside `path` before renaming it over `path`,
+/// so that a power loss leaves either the old or the new file, never a part.
+fn store(path: &Path, saved: &Saved) -> io::Result<()> {
+  let bytes = postcard::to_allocvec(saved)
+    .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+
+  let directory = path.parent().unwrap_or(Path::new("."));
+  fs::create_dir_all(directory)?;
+
+  let temporary = path.with_extension("tmp");
+  let mut file = File::create(&temporary)?;
+  file.write_all(&bytes)?;
+  file.sync_all()?;
+
+  fs::rename(&temporary, path)?;
+
+  // the rename itself is only durable once the directory is synced
+  File::open(directory)?.sync_all()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use common::comm::Trigger;
+  use std::path::PathBuf;
+
+  /// A fresh directory for one test to save into.
+  fn directory(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+      "flight-configuration-{}-{name}",
+      std::process::id()
+    ));
+
+    let _ = fs::remove_dir_all(&path);
+    fs::create_dir_all(&path).unwrap();
+    path
+  }
+
+  fn saved(saved_at: f64) -> Saved {
+    Saved {
+      saved_at,
+      configuration: Configuration {
+        triggers: vec![Trigger {
+          name: "overpressure".to_owned(),
+          condition: "fuel_pt > 900 * psi".to_owned(),
+          script: "abort()".to_owned(),
+          active: true,
+          reset: None,
+          hold_ms: 50,
+          cooldown_ms: 0,
+          fire_if_holding: false,
+          poll_ms: None,
+        }],
+        ..Configuration::default()
+      },
+    }
+  }
+
+  #[test]
+  fn round_trips_through_disk() {
+    let path = directory("round-trip").join("configuration");
+    store(&path, &saved(1.5)).unwrap();
+
+    let loaded = load(&path).unwrap().unwrap();
+    assert_eq!(loaded.saved_at, 1.5);
+    assert_eq!(loaded.configuration, saved(1.5).configuration);
+    assert!(!path.with_extension("tmp").exists());
+  }
+
+  #[test]
+  fn missing_file_is_nothing_saved() {
+    let path = directory("missing").join("configuration");
+    assert!(load(&path).unwrap().is_none());
+  }
+
+  #[test]
+  fn corrupt_file_is_invalid_data() {
+    let path = directory("corrupt").join("configuration");
+    fs::write(&path, [0xff; 3]).unwrap();
+
+    let error = load(&path).err().unwrap();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+  }
+
+  #[test]
+  fn leftover_temporary_file_does_not_replace_the_saved_one() {
+    let path = directory("leftover").join("configuration");
+    store(&path, &saved(1.0)).unwrap();
+
+    // as if power were lost partway through writing the next save
+    fs::write(path.with_extension("tmp"), [0xff; 3]).unwrap();
+    assert_eq!(load(&path).unwrap().unwrap().saved_at, 1.0);
+
+    // and the next save goes through regardless
+    store(&path, &saved(2.0)).unwrap();
+    assert_eq!(load(&path).unwrap().unwrap().saved_at, 2.0);
+  }
+}
@@ -1,9 +1,50 @@
 use crate::state::SharedState;
+use common::comm::FlightStatusMessage;
 use jeflog::fail;
-use std::{net::UdpSocket, thread, time::Duration};
+use std::{
+  io::Write,
+  net::{SocketAddr, UdpSocket},
+  thread,
+  time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
+/// Sends a status message to the control server over the operator TCP stream,
+/// if one is currently connected.
+pub fn report(shared: &SharedState, message: &FlightStatusMessage) {
+  let mut server_stream = shared.server_stream.lock().unwrap();
+
+  let Some(stream) = server_stream.as_mut() else {
+    fail!("Cannot report status to server: no server is connected.");
+    return;
+  };
+
+  let serialized = match postcard::to_allocvec(message) {
+    Ok(serialized) => serialized,
+    Err(error) => {
+      fail!("Failed to serialize status message: {error}");
+      return;
+    }
+  };
+
+  if let Err(error) = stream.write_all(&serialized) {
+    fail!("Failed to send status message to server: {error}");
+  }
+}
+
+/// The current UNIX timestamp, with which status messages are stamped.
+pub fn timestamp() -> f64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|duration| duration.as_secs_f64())
+    .unwrap_or(0.0)
+}
+
+/// Constructs a closure which streams the vehicle state to the server and any
+/// standby it has, or to port 7201 of the server until the server says
+/// otherwise.
 pub fn forward_vehicle_state(shared: &SharedState) -> impl Fn() {
   let server_address = shared.server_address.clone();
+  let telemetry_targets = shared.telemetry_targets.clone();
   let vehicle_state = shared.vehicle_state.clone();
 
   let socket =
@@ -11,16 +52,24 @@ pub fn forward_vehicle_state(shared: &SharedState) -> impl Fn() {
 
   move || {
     loop {
-      if let Some(server_address) = *server_address.lock().unwrap() {
+      let mut targets = telemetry_targets.lock().unwrap().clone();
+
+      if targets.is_empty() {
+        if let Some(server_address) = *server_address.lock().unwrap() {
+          targets.push(SocketAddr::new(server_address, 7201));
+        }
+      }
+
+      if !targets.is_empty() {
         let vehicle_state = vehicle_state.lock().unwrap();
 
         // TODO: Change to something that doesn't allocate every iteration
         match postcard::to_allocvec(&*vehicle_state) {
           Ok(serialized) => {
-            let result = socket.send_to(&serialized, (server_address, 7201));
-
-            if result.is_err() {
-              fail!("Failed to send vehicle state to {server_address}:7201.");
+            for target in &targets {
+              if socket.send_to(&serialized, target).is_err() {
+                fail!("Failed to send vehicle state to {target}.");
+              }
             }
           }
           Err(error) => {
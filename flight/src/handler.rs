@@ -1,23 +1,42 @@
 use common::{
   comm::{
+    mode::{ModeChangeCause, VehicleMode},
     sam::SamControlMessage,
     CompositeValveState,
+    FlightStatusMessage,
     NodeMapping,
+    SensorType,
+    Sequence,
+    SequenceEvent,
+    SequenceEventKind,
+    SequenceOutcome,
     ValveState,
     VehicleState,
   },
-  sequence::{self, AbortError, DeviceAction},
+  sequence::{self, AbortError, Cancellation, DeviceAction},
 };
-use jeflog::{fail, warn};
+use jeflog::{fail, pass, warn};
 use pyo3::{types::PyNone, IntoPy, PyErr, PyObject, Python, ToPyObject};
-use std::{sync::Mutex, thread};
+use std::{
+  mem,
+  sync::Mutex,
+  thread,
+  time::{Duration, Instant},
+};
 
 use crate::{
-  state::SharedState,
+  forwarder,
+  mode,
+  server_loss,
+  state::{RunningSequence, SharedState},
   switchboard::commander::Command,
+  trigger,
   CommandSender,
 };
 
+/// How long a stopped or aborted sequence is given to exit.
+pub const EXIT_TIMEOUT: Duration = Duration::from_secs(1);
+
 pub fn create_device_handler(
   shared: SharedState,
   command_tx: CommandSender,
@@ -28,9 +47,14 @@ pub fn create_device_handler(
     let thread_id = thread::current().id();
     let sequences = shared.sequences.lock().unwrap();
 
-    if sequences.get_by_right(&thread_id).is_none() {
-      drop(sequences);
+    let running = sequences
+      .iter()
+      .find(|(_, running)| running.thread == thread_id)
+      .map(|(name, running)| (name.clone(), running.cancellation.clone()));
+
+    drop(sequences);
 
+    let Some((name, cancellation)) = running else {
       return Python::with_gil(|py| {
         AbortError::new_err("aborting sequence").restore(py);
         assert!(PyErr::occurred(py));
@@ -38,9 +62,19 @@ pub fn create_device_handler(
 
         PyNone::get(py).to_object(py)
       });
-    }
+    };
 
-    drop(sequences);
+    // held until the server returns, though an abort always goes ahead. a
+    // sequence cancelled while held exits once control returns to it.
+    // trigger scripts are never held, as they run on the thread checking every
+    // trigger, which would otherwise stop watching for whatever they guard
+    let holdable = name != "abort"
+      && !name.starts_with(trigger::SEQUENCE_PREFIX)
+      && !matches!(action, DeviceAction::Abort);
+
+    if holdable && !server_loss::wait_while_held(&shared, &cancellation) {
+      return Python::with_gil(|py| PyNone::get(py).to_object(py));
+    }
 
     match action {
       DeviceAction::ReadSensor => read_sensor(device, &shared.vehicle_state),
@@ -48,13 +82,21 @@ pub fn create_device_handler(
         read_valve_state(device, &shared.vehicle_state)
       }
       DeviceAction::ActuateValve { state } => {
-        actuate_valve(
-          device,
-          state,
-          &shared.mappings,
-          &shared.vehicle_state,
-          &tx,
-        );
+        if mode::permits_valve(&shared, device) {
+          actuate_valve(
+            device,
+            state,
+            &shared.mappings,
+            &shared.vehicle_state,
+            &tx,
+          );
+        } else {
+          fail!(
+            "Refused to actuate valve '{device}': not permitted in {} mode.",
+            mode::current(&shared)
+          );
+        }
+
         Python::with_gil(|py| PyNone::get(py).to_object(py))
       }
       DeviceAction::Abort => {
@@ -65,6 +107,17 @@ pub fn create_device_handler(
   }
 }
 
+/// Constructs the output handler, which prints each line a sequence prints and
+/// reports it to the server.
+pub fn create_output_handler(
+  shared: SharedState,
+) -> impl Fn(&str, &str) + Send + Sync {
+  move |name, line| {
+    println!("[{name}] {line}");
+    report_sequence(&shared, name, SequenceEventKind::Output(line.to_owned()));
+  }
+}
+
 fn read_sensor(name: &str, vehicle_state: &Mutex<VehicleState>) -> PyObject {
   let vehicle_state = vehicle_state.lock().unwrap();
 
@@ -120,6 +173,56 @@ fn actuate_valve(
   }
 
   drop(mappings);
+  set_commanded(name, state, vehicle_state);
+}
+
+/// Commands every valve on a board to its unpowered state, as when comms with
+/// the board are lost. This is permitted in every mode, like an abort.
+pub fn safe_board(
+  shared: &SharedState,
+  board_id: &str,
+  command_tx: &CommandSender,
+) {
+  // copied out, as the worker locks the vehicle state before the mappings
+  let valves = shared
+    .mappings
+    .lock()
+    .unwrap()
+    .iter()
+    .filter(|mapping| {
+      mapping.board_id == board_id && mapping.sensor_type == SensorType::Valve
+    })
+    .cloned()
+    .collect::<Vec<_>>();
+
+  for mapping in valves {
+    let message = SamControlMessage::ActuateValve {
+      channel: mapping.channel,
+      powered: false,
+    };
+
+    if let Err(error) =
+      command_tx.send((board_id.to_owned(), Command::Sam(message)))
+    {
+      fail!("Failed to send command: {error}");
+    }
+
+    let state = if mapping.normally_closed.unwrap_or(true) {
+      ValveState::Closed
+    } else {
+      ValveState::Open
+    };
+
+    set_commanded(&mapping.text_id, state, &shared.vehicle_state);
+  }
+}
+
+/// Records the state a valve was last commanded to.
+fn set_commanded(
+  name: &str,
+  state: ValveState,
+  vehicle_state: &Mutex<VehicleState>,
+) {
   let mut vehicle_state = vehicle_state.lock().unwrap();
 
   if let Some(existing) = vehicle_state.valve_states.get_mut(name) {
@@ -135,7 +238,149 @@ fn actuate_valve(
   }
 }
 
+/// Runs a sequence on the current thread, registered under its name so that
+/// it may access devices and be stopped. A sequence already running under the
+/// same name is stopped first.
+///
+/// The server is told when the sequence starts and how it ended, which is also
+/// returned.
+pub fn run_sequence(
+  shared: &SharedState,
+  sequence: Sequence,
+) -> SequenceOutcome {
+  let name = sequence.name.clone();
+  let cancellation = Cancellation::new();
+
+  let running = RunningSequence {
+    thread: thread::current().id(),
+    cancellation: cancellation.clone(),
+  };
+
+  let previous = shared
+    .sequences
+    .lock()
+    .unwrap()
+    .insert(name.clone(), running);
+
+  if let Some(previous) = previous {
+    wait_for_exit(&name, previous, Instant::now() + EXIT_TIMEOUT);
+  }
+
+  report_sequence(shared, &name, SequenceEventKind::Started);
+  let outcome = sequence::run(sequence, cancellation.clone());
+
+  // it may have been stopped, and another started under its name, meanwhile
+  let mut sequences = shared.sequences.lock().unwrap();
+
+  if sequences
+    .get(&name)
+    .is_some_and(|running| running.cancellation == cancellation)
+  {
+    sequences.remove(&name);
+  }
+
+  drop(sequences);
+
+  match &outcome {
+    SequenceOutcome::Finished => {
+      pass!("Sequence '{name}' finished.");
+    }
+    SequenceOutcome::Raised { error, traceback } => {
+      fail!("Sequence '{name}' raised an exception: {error}");
+
+      if let Some(traceback) = traceback {
+        fail!("{}", traceback.trim_end());
+      }
+    }
+    SequenceOutcome::Cancelled => {
+      warn!("Sequence '{name}' was cancelled.");
+    }
+  }
+
+  report_sequence(shared, &name, SequenceEventKind::Ended(outcome.clone()));
+  outcome
+}
+
+fn report_sequence(shared: &SharedState, name: &str, kind: SequenceEventKind) {
+  let event = SequenceEvent {
+    name: name.to_owned(),
+    kind,
+    timestamp: forwarder::timestamp(),
+  };
+
+  forwarder::report(shared, &FlightStatusMessage::Sequence(event));
+}
+
+/// Stops a running sequence, returning whether it exited within
+/// `EXIT_TIMEOUT`, or `None` if no sequence of that name was running.
+pub fn stop_sequence(shared: &SharedState, name: &str) -> Option<bool> {
+  let running = shared.sequences.lock().unwrap().remove(name)?;
+
+  running.cancellation.cancel();
+  Some(running.cancellation.wait_for_exit(EXIT_TIMEOUT))
+}
+
+/// Cancels a sequence and waits until `deadline` for it to exit, unless it is
+/// running on the current thread, in which case it exits once control returns
+/// to it.
+fn wait_for_exit(name: &str, running: RunningSequence, deadline: Instant) {
+  running.cancellation.cancel();
+  let remaining = deadline.saturating_duration_since(Instant::now());
+
+  if running.thread != thread::current().id()
+    && !running.cancellation.wait_for_exit(remaining)
+  {
+    fail!("Sequence '{name}' did not exit within {EXIT_TIMEOUT:?}.");
+  }
+}
+
+/// Stops every running sequence but the one named `kept`, returning whether
+/// that one is running.
+///
+/// Every sequence is cancelled before waiting on any so that they all exit
+/// together, and all have exited once this returns, so that none can actuate
+/// valves against whatever runs next.
+pub fn stop_all_except(shared: &SharedState, kept: &str) -> bool {
+  let mut sequences = shared.sequences.lock().unwrap();
+  let mut stopped = mem::take(&mut *sequences);
+
+  let running = stopped.remove(kept);
+  let is_running = running.is_some();
+
+  if let Some(running) = running {
+    sequences.insert(kept.to_owned(), running);
+  }
+
+  drop(sequences);
+
+  for running in stopped.values() {
+    running.cancellation.cancel();
+  }
+
+  // they exit together, so share one deadline rather than each getting the
+  // full timeout in turn
+  let deadline = Instant::now() + EXIT_TIMEOUT;
+
+  for (name, running) in stopped {
+    wait_for_exit(&name, running, deadline);
+  }
+
+  is_running
+}
+
 pub fn abort(shared: &SharedState) {
+  // entered even without an abort sequence, so that nothing else the abort
+  // mode doesn't permit can be actuated
+  mode::force(shared, VehicleMode::Abort, ModeChangeCause::Abort);
+
+  // an abort sequence which is already running is left to finish
+  let already_aborting = stop_all_except(shared, "abort");
+
+  if already_aborting {
+    warn!("Abort was called while the abort sequence is already running.");
+    return;
+  }
+
   let abort_sequence = shared.abort_sequence.lock().unwrap().clone();
 
   let Some(sequence) = abort_sequence else {
@@ -143,10 +388,5 @@ pub fn abort(shared: &SharedState) {
     return;
   };
 
-  let mut sequences = shared.sequences.lock().unwrap();
-  sequences.clear();
-  sequences.insert("abort".to_owned(), thread::current().id());
-  drop(sequences);
-
-  sequence::run(sequence);
+  run_sequence(shared, sequence);
 }
@@ -1,12 +1,16 @@
+mod configuration;
 mod forwarder;
 mod handler;
+mod mode;
+mod server_loss;
 mod state;
 mod switchboard;
+mod trigger;
 
-use std::{sync::mpsc::Sender, time::Duration};
+use std::{process, sync::mpsc::Sender, time::Duration};
 
 use common::comm::flight::BoardId;
-use jeflog::pass;
+use jeflog::{fail, pass};
 use state::ProgramState;
 
 const SERVO_PORT: u16 = 5025;
@@ -17,8 +21,11 @@ const SAM_PORT: u16 = 8378;
 
 /// How often heartbeats are sent
 const HEARTBEAT_PERIOD: Duration = Duration::from_millis(150);
-/// Milliseconds of inactivity before a board is declared dead
+/// Milliseconds of inactivity before a board without a comms policy is
+/// declared dead
 const TIME_TIL_DEATH: Duration = Duration::from_millis(100);
+/// How often boards are checked for having gone quiet, if none are heard from
+const COMMS_CHECK_PERIOD: Duration = Duration::from_millis(5);
 
 /// How large the buffer to send a command to a board should be (Can probably
 /// replace this with a sizeof(SamControlMessage)).
@@ -39,6 +46,17 @@ const FC_BOARD_ID: &str = "flight-01";
 type CommandSender = Sender<(BoardId, switchboard::commander::Command)>;
 
 fn main() {
+  // without a mode table nothing could be checked before being actuated
+  match mode::load() {
+    Ok(_) => {
+      pass!("Loaded vehicle mode table.");
+    }
+    Err(error) => {
+      fail!("Could not load the vehicle mode table: {error}. Exiting safely.");
+      process::exit(1);
+    }
+  }
+
   let mut state = ProgramState::Init;
 
   loop {
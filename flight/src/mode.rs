@@ -0,0 +1,127 @@
+use crate::{forwarder, state::SharedState};
+use common::comm::{
+  mode::{
+    ModeChange,
+    ModeChangeCause,
+    ModeTable,
+    ModeTableError,
+    VehicleMode,
+  },
+  FlightStatusMessage,
+};
+use jeflog::{pass, warn};
+use std::sync::OnceLock;
+
+/// Where the mode table of a test stand may be placed on the flight computer
+/// without rebuilding the software.
+const MODES_PATH: &str = "/etc/flight/modes.json";
+
+/// The mode table the flight software was built with.
+const DEFAULT_MODES: &str = include_str!("../modes.json");
+
+static MODES: OnceLock<ModeTable> = OnceLock::new();
+
+/// Loads the mode table. This must succeed before any valve is actuated.
+pub fn load() -> Result<&'static ModeTable, ModeTableError> {
+  if let Some(table) = MODES.get() {
+    return Ok(table);
+  }
+
+  let table = ModeTable::load(MODES_PATH, DEFAULT_MODES)?;
+  Ok(MODES.get_or_init(|| table))
+}
+
+/// Gets the mode table.
+///
+/// Panics if the table has not yet been loaded with `load`.
+pub fn table() -> &'static ModeTable {
+  MODES.get().expect("mode table was not loaded")
+}
+
+/// Gets the current mode of the vehicle.
+pub fn current(shared: &SharedState) -> VehicleMode {
+  shared.vehicle_state.lock().unwrap().mode
+}
+
+/// Whether the valve may be actuated in the current mode.
+pub fn permits_valve(shared: &SharedState, valve: &str) -> bool {
+  table().permits_valve(current(shared), valve)
+}
+
+/// Whether the sequence may be run in the current mode.
+pub fn permits_sequence(shared: &SharedState, sequence: &str) -> bool {
+  table().permits_sequence(current(shared), sequence)
+}
+
+/// Changes mode at the request of an operator, if the current mode has a
+/// transition to it and every guard of that transition holds. The outcome is
+/// reported to the server either way so that it can be audited, along with
+/// who requested it.
+pub fn request(shared: &SharedState, to: VehicleMode, requested_by: String) {
+  let mut vehicle_state = shared.vehicle_state.lock().unwrap();
+  let from = vehicle_state.mode;
+
+  // checked under the same lock as the change so that the guards can't be
+  // invalidated in between
+  let result = table().check_transition(from, to, &vehicle_state);
+
+  if result.is_ok() {
+    vehicle_state.mode = to;
+  }
+
+  drop(vehicle_state);
+
+  let rejection = match result {
+    Ok(()) => {
+      pass!(
+        "Changed mode from {from} to {to} at the request of {requested_by}."
+      );
+      None
+    }
+    Err(error) => {
+      warn!("Refused to change mode from {from} to {to}: {error}.");
+      Some(error.to_string())
+    }
+  };
+
+  report(
+    shared,
+    from,
+    to,
+    ModeChangeCause::Operator,
+    Some(requested_by),
+    rejection,
+  );
+}
+
+/// Changes mode without checking for a transition or its guards, as an abort
+/// must always be possible.
+pub fn force(shared: &SharedState, to: VehicleMode, cause: ModeChangeCause) {
+  let from =
+    std::mem::replace(&mut shared.vehicle_state.lock().unwrap().mode, to);
+
+  if from != to {
+    warn!("Forced mode from {from} to {to} by {cause:?}.");
+    report(shared, from, to, cause, None, None);
+  }
+}
+
+fn report(
+  shared: &SharedState,
+  from: VehicleMode,
+  to: VehicleMode,
+  cause: ModeChangeCause,
+  requested_by: Option<String>,
+  rejection: Option<String>,
+) {
+  let change = ModeChange {
+    from,
+    to,
+    cause,
+    requested_by,
+    rejection,
+    timestamp: forwarder::timestamp(),
+  };
+
+  forwarder::report(shared, &FlightStatusMessage::ModeChange(change));
+}
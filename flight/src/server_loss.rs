@@ -0,0 +1,230 @@
+use crate::{forwarder, handler, state::SharedState};
+use common::{
+  comm::{
+    FlightStatusMessage,
+    ServerLossAction,
+    ServerLossEvent,
+    ServerLossTimer,
+  },
+  sequence::Cancellation,
+};
+use jeflog::{fail, pass, warn};
+use std::{
+  thread,
+  time::{Duration, Instant},
+};
+
+/// How often the timer is updated in telemetry and checked for running out.
+const CHECK_PERIOD: Duration = Duration::from_millis(50);
+
+/// How often a held sequence checks whether it may go on.
+const HOLD_POLL_PERIOD: Duration = Duration::from_millis(10);
+
+/// A loss of the control server which hasn't yet ended.
+#[derive(Clone, Debug)]
+pub struct ServerLoss {
+  /// When the server was lost.
+  since: Instant,
+
+  /// The UNIX timestamp at which the server was lost.
+  lost_at: f64,
+
+  /// The action taken once the timer ran out, if it has.
+  acted: Option<ServerLossAction>,
+}
+
+/// Starts the timer on the loss of the control server, unless it is already
+/// running. The operator stream is dropped so that nothing more is sent on it.
+pub fn lost(shared: &SharedState) {
+  *shared.server_stream.lock().unwrap() = None;
+
+  let mut server_loss = shared.server_loss.lock().unwrap();
+
+  if server_loss.is_some() {
+    return;
+  }
+
+  *server_loss = Some(ServerLoss {
+    since: Instant::now(),
+    lost_at: forwarder::timestamp(),
+    acted: None,
+  });
+
+  drop(server_loss);
+
+  let policy = shared.server_loss_policy.lock().unwrap().clone();
+
+  match policy.action {
+    ServerLossAction::Continue => {
+      warn!("Lost the control server. Continuing without it.");
+    }
+    action => {
+      warn!(
+        "Lost the control server. Taking action {action:?} in {} ms.",
+        policy.timeout_ms
+      );
+    }
+  }
+}
+
+/// Stops the timer once the control server has reconnected, releasing any
+/// held sequences and reporting the loss to the server.
+pub fn regained(shared: &SharedState) {
+  let mut server_loss = shared.server_loss.lock().unwrap();
+
+  let Some(loss) = server_loss.take() else {
+    return;
+  };
+
+  // cleared with the loss still locked so that the timer can't put it back
+  shared.vehicle_state.lock().unwrap().server_loss = None;
+  drop(server_loss);
+
+  let elapsed = loss.since.elapsed();
+
+  let action = loss
+    .acted
+    .unwrap_or(shared.server_loss_policy.lock().unwrap().action);
+
+  if loss.acted == Some(ServerLossAction::Hold) {
+    pass!("Regained the control server after {elapsed:?}. Releasing hold.");
+  } else {
+    pass!("Regained the control server after {elapsed:?}.");
+  }
+
+  let event = ServerLossEvent {
+    lost_at: loss.lost_at,
+    regained_at: forwarder::timestamp(),
+    action,
+    acted: loss.acted.is_some(),
+  };
+
+  forwarder::report(shared, &FlightStatusMessage::ServerLoss(event));
+}
+
+/// Whether running sequences are being held until the server reconnects.
+pub fn held(shared: &SharedState) -> bool {
+  shared
+    .server_loss
+    .lock()
+    .unwrap()
+    .as_ref()
+    .is_some_and(|loss| loss.acted == Some(ServerLossAction::Hold))
+}
+
+/// Blocks a sequence while sequences are held, returning whether it may go on,
+/// or `false` if it was cancelled meanwhile.
+pub fn wait_while_held(
+  shared: &SharedState,
+  cancellation: &Cancellation,
+) -> bool {
+  loop {
+    if !held(shared) {
+      return true;
+    }
+
+    if cancellation.is_cancelled() {
+      return false;
+    }
+
+    thread::sleep(HOLD_POLL_PERIOD);
+  }
+}
+
+/// Constructs a closure which keeps the timer in telemetry up to date while
+/// the server is gone, taking the action of the server loss policy once the
+/// timer runs out.
+pub fn watch(shared: &SharedState) -> impl FnOnce() {
+  let shared = shared.clone();
+
+  move || loop {
+    thread::sleep(CHECK_PERIOD);
+
+    let policy = shared.server_loss_policy.lock().unwrap().clone();
+    let mut server_loss = shared.server_loss.lock().unwrap();
+
+    let Some(loss) = server_loss.as_mut() else {
+      continue;
+    };
+
+    let elapsed = loss.since.elapsed();
+    let timeout = Duration::from_millis(policy.timeout_ms.into());
+
+    let act = loss.acted.is_none()
+      && policy.action != ServerLossAction::Continue
+      && elapsed >= timeout;
+
+    if act {
+      loss.acted = Some(policy.action);
+    }
+
+    let action = loss.acted.unwrap_or(policy.action);
+
+    let remaining_s = match action {
+      ServerLossAction::Continue => None,
+      _ if loss.acted.is_some() => Some(0.0),
+      _ => Some(timeout.saturating_sub(elapsed).as_secs_f64()),
+    };
+
+    let timer = ServerLossTimer {
+      lost_at: loss.lost_at,
+      elapsed_s: elapsed.as_secs_f64(),
+      remaining_s,
+      action,
+      acted: loss.acted.is_some(),
+    };
+
+    shared.vehicle_state.lock().unwrap().server_loss = Some(timer);
+    drop(server_loss);
+
+    if act {
+      take(&shared, action);
+    }
+  }
+}
+
+/// Takes the action of the server loss policy.
+fn take(shared: &SharedState, action: ServerLossAction) {
+  match action {
+    ServerLossAction::Continue => {}
+    ServerLossAction::Hold => {
+      warn!("Control server is still gone. Holding running sequences.");
+    }
+    ServerLossAction::Abort => {
+      fail!("Control server is still gone. Aborting...");
+
+      // aborted on another thread so that the timer is still updated while
+      // the abort sequence runs
+      let shared = shared.clone();
+      thread::spawn(move || handler::abort(&shared));
+    }
+    ServerLossAction::Safe => {
+      fail!("Control server is still gone. Safing...");
+
+      let shared = shared.clone();
+      thread::spawn(move || safe(&shared));
+    }
+  }
+}
+
+/// Stops every running sequence besides an abort before running the safing
+/// sequence.
+fn safe(shared: &SharedState) {
+  let safing_sequence = shared
+    .server_loss_policy
+    .lock()
+    .unwrap()
+    .safing_sequence
+    .clone();
+
+  handler::stop_all_except(shared, "abort");
+
+  match safing_sequence {
+    Some(sequence) => {
+      handler::run_sequence(shared, sequence);
+    }
+    None => {
+      warn!("No safing sequence is set. Running sequences were stopped.");
+    }
+  }
+}
@@ -1,26 +1,40 @@
 use crate::{
+  configuration,
   forwarder,
-  handler::{self, create_device_handler},
+  handler::{self, create_device_handler, create_output_handler},
+  mode,
+  server_loss::{self, ServerLoss},
   switchboard::{self, commander::Command},
+  trigger,
   CommandSender,
   SERVO_PORT,
   SWITCHBOARD_ADDRESS,
 };
-use bimap::BiHashMap;
 use common::{
-  comm::{Computer, FlightControlMessage, NodeMapping, Sequence, VehicleState},
-  sequence,
+  comm::{
+    mode::VehicleMode,
+    CommsPolicy,
+    Computer,
+    Configuration,
+    ConfigurationSource,
+    FlightControlMessage,
+    NodeMapping,
+    Sequence,
+    ServerLossPolicy,
+    VehicleState,
+    SERVER_HEARTBEAT_TIMEOUT,
+  },
+  sequence::{self, Cancellation},
 };
 use jeflog::{fail, pass, task, warn};
 use postcard::experimental::max_size::MaxSize;
-use pyo3::Python;
 use std::{
+  collections::HashMap,
   fmt,
   io::{self, Read, Write},
-  net::{IpAddr, TcpStream, UdpSocket},
+  net::{IpAddr, SocketAddr, TcpStream, UdpSocket},
   sync::{Arc, Mutex, OnceLock},
   thread::{self, ThreadId},
-  time::Duration,
 };
 
 /// Holds all shared state that should be accessible concurrently in multiple
@@ -35,8 +49,82 @@ pub struct SharedState {
   pub mappings: Arc<Mutex<Vec<NodeMapping>>>,
   pub server_address: Arc<Mutex<Option<IpAddr>>>,
   pub triggers: Arc<Mutex<Vec<common::comm::Trigger>>>,
-  pub sequences: Arc<Mutex<BiHashMap<String, ThreadId>>>,
+  pub sequences: Arc<Mutex<HashMap<String, RunningSequence>>>,
   pub abort_sequence: Arc<Mutex<Option<Sequence>>>,
+  pub server_stream: Arc<Mutex<Option<TcpStream>>>,
+  pub configuration_source: Arc<Mutex<ConfigurationSource>>,
+  pub configuration_persisted: Arc<Mutex<bool>>,
+  pub comms_policies: Arc<Mutex<Vec<CommsPolicy>>>,
+  pub server_loss_policy: Arc<Mutex<ServerLossPolicy>>,
+  pub server_loss: Arc<Mutex<Option<ServerLoss>>>,
+  pub telemetry_targets: Arc<Mutex<Vec<SocketAddr>>>,
+}
+
+/// A sequence which is currently running, by which it may be stopped.
+#[derive(Clone, Debug)]
+pub struct RunningSequence {
+  /// The thread which the sequence is running on.
+  pub thread: ThreadId,
+
+  /// Cancels the sequence and waits for it to exit.
+  pub cancellation: Cancellation,
+}
+
+/// The connection to the control server, which holds onto whatever has been
+/// read of a message until the rest of it arrives.
+#[derive(Debug)]
+pub struct ServerSocket {
+  stream: TcpStream,
+  pending: Vec<u8>,
+}
+
+impl ServerSocket {
+  fn new(stream: TcpStream) -> Self {
+    ServerSocket {
+      stream,
+      pending: Vec::new(),
+    }
+  }
+
+  fn peer_addr(&self) -> io::Result<SocketAddr> {
+    self.stream.peer_addr()
+  }
+
+  /// Reads until a whole message has arrived, returning `None` once the server
+  /// has closed the connection.
+  ///
+  /// Messages are not framed, so several may arrive in one read or one may be
+  /// split across reads. Anything left over from the last read is decoded
+  /// before reading again.
+  fn receive(
+    &mut self,
+  ) -> io::Result<Option<postcard::Result<FlightControlMessage>>> {
+    let mut buffer = vec![0; 65_536];
+
+    loop {
+      match postcard::take_from_bytes::<FlightControlMessage>(&self.pending) {
+        Ok((message, remaining)) => {
+          let consumed = self.pending.len() - remaining.len();
+          self.pending.drain(..consumed);
+          return Ok(Some(Ok(message)));
+        }
+        Err(postcard::Error::DeserializeUnexpectedEnd) => {}
+        Err(error) => {
+          // there is no telling where the next message starts
+          self.pending.clear();
+          return Ok(Some(Err(error)));
+        }
+      }
+
+      let size = self.stream.read(&mut buffer)?;
+
+      if size == 0 {
+        return Ok(None);
+      }
+
+      self.pending.extend_from_slice(&buffer[..size]);
+    }
+  }
 }
 
 pub(crate) static COMMANDER_TX: OnceLock<CommandSender> =
@@ -58,7 +146,7 @@ pub enum ProgramState {
   /// State which waits for an operator command, such as setting mappings or
   /// running a sequence.
   WaitForOperator {
-    server_socket: TcpStream,
+    server_socket: ServerSocket,
 
     /// The shared flight state.
     shared: SharedState,
@@ -67,7 +155,7 @@ pub enum ProgramState {
   /// State which spawns a thread to run a sequence before returning to the
   /// `WaitForOperator` state.
   RunSequence {
-    server_socket: TcpStream,
+    server_socket: ServerSocket,
 
     /// A full description of the sequence to run.
     sequence: Sequence,
@@ -126,10 +214,21 @@ fn init() -> ProgramState {
     mappings: Arc::new(Mutex::new(Vec::new())),
     server_address: Arc::new(Mutex::new(None)),
     triggers: Arc::new(Mutex::new(Vec::new())),
-    sequences: Arc::new(Mutex::new(BiHashMap::new())),
+    sequences: Arc::new(Mutex::new(HashMap::new())),
     abort_sequence: Arc::new(Mutex::new(None)),
+    server_stream: Arc::new(Mutex::new(None)),
+    configuration_source: Arc::new(Mutex::new(ConfigurationSource::Empty)),
+    configuration_persisted: Arc::new(Mutex::new(true)),
+    comms_policies: Arc::new(Mutex::new(Vec::new())),
+    server_loss_policy: Arc::new(Mutex::new(ServerLossPolicy::default())),
+    server_loss: Arc::new(Mutex::new(None)),
+    telemetry_targets: Arc::new(Mutex::new(Vec::new())),
   };
 
+  // restored before anything can use the configuration, and before the server
+  // is found so that a restart mid-test leaves the vehicle configured
+  configuration::restore(&shared);
+
   let command_tx = match switchboard::start(shared.clone(), home_socket) {
     Ok(command_tx) => command_tx,
     Err(error) => {
@@ -143,12 +242,18 @@ fn init() -> ProgramState {
     shared.clone(),
     command_tx.clone(),
   ));
+  sequence::set_output_handler(create_output_handler(shared.clone()));
 
   COMMANDER_TX
     .set(command_tx)
     .expect("Could not set the channel for BMS and AHRS commands");
 
-  thread::spawn(check_triggers(&shared));
+  thread::spawn(trigger::check_triggers(&shared));
+  thread::spawn(server_loss::watch(&shared));
+
+  // spawned once here rather than on every connection, and sends nothing
+  // until a server is found
+  thread::spawn(forwarder::forward_vehicle_state(&shared));
 
   ProgramState::ServerDiscovery { shared }
 }
@@ -212,12 +317,26 @@ fn server_discovery(shared: SharedState) -> ProgramState {
       continue;
     }
 
+    // the server sends a heartbeat, so a read which times out means it has
+    // stopped without closing the connection
+    if let Err(error) = stream.set_read_timeout(Some(SERVER_HEARTBEAT_TIMEOUT))
+    {
+      fail!("Failed to set a read timeout on the server socket: {error}");
+      continue;
+    }
+
     *shared.server_address.lock().unwrap() =
       Some(stream.peer_addr().unwrap().ip());
-    thread::spawn(forwarder::forward_vehicle_state(&shared));
+    *shared.server_stream.lock().unwrap() = stream.try_clone().ok();
+
+    // kept while the server is gone so that its standby still receives the
+    // vehicle state, but replaced by whatever the new server sends
+    shared.telemetry_targets.lock().unwrap().clear();
+    server_loss::regained(&shared);
+    configuration::report_status(&shared);
 
     return ProgramState::WaitForOperator {
-      server_socket: stream,
+      server_socket: ServerSocket::new(stream),
       shared,
     };
   }
@@ -227,25 +346,53 @@ fn server_discovery(shared: SharedState) -> ProgramState {
 }
 
 fn wait_for_operator(
-  mut server_socket: TcpStream,
+  mut server_socket: ServerSocket,
   shared: SharedState,
 ) -> ProgramState {
-  let mut buffer = vec![0; 1_000_000];
-
-  match server_socket.read(&mut buffer) {
-    Ok(size) => {
-      // if the size is zero, a TCP shutdown packet was sent. the connection is
-      // closed.
-      if size == 0 {
-        return ProgramState::ServerDiscovery { shared };
-      }
-
-      match postcard::from_bytes::<FlightControlMessage>(&buffer) {
+  match server_socket.receive() {
+    // the server shut down the connection
+    Ok(None) => {
+      server_loss::lost(&shared);
+      ProgramState::ServerDiscovery { shared }
+    }
+    Ok(Some(received)) => {
+      match received {
         Ok(message) => {
           match message {
             FlightControlMessage::Mappings(mappings) => {
               pass!("Received mappings from server: {mappings:#?}");
               *shared.mappings.lock().unwrap() = mappings;
+              configuration::save(&shared);
+
+              ProgramState::WaitForOperator {
+                server_socket,
+                shared,
+              }
+            }
+            FlightControlMessage::Configuration(configuration) => {
+              let Configuration {
+                mappings,
+                triggers,
+                abort_sequence,
+                comms_policies,
+                server_loss_policy,
+              } = configuration;
+
+              pass!(
+                "Received {} mappings, {} triggers and {} comms policies.",
+                mappings.len(),
+                triggers.len(),
+                comms_policies.len()
+              );
+              pass!("Received server loss policy: {server_loss_policy:#?}");
+
+              *shared.mappings.lock().unwrap() = mappings;
+              *shared.triggers.lock().unwrap() = triggers;
+              *shared.abort_sequence.lock().unwrap() = abort_sequence;
+              *shared.comms_policies.lock().unwrap() = comms_policies;
+              *shared.server_loss_policy.lock().unwrap() = server_loss_policy;
+              configuration::save(&shared);
+
               ProgramState::WaitForOperator {
                 server_socket,
                 shared,
@@ -258,6 +405,21 @@ fn wait_for_operator(
               // set the shared abort sequence and return early
               if sequence.name == "abort" {
                 *shared.abort_sequence.lock().unwrap() = Some(sequence);
+                configuration::save(&shared);
+
+                return ProgramState::WaitForOperator {
+                  server_socket,
+                  shared,
+                };
+              }
+
+              if !mode::permits_sequence(&shared, &sequence.name) {
+                fail!(
+                  "Refused to run sequence '{}': not permitted in {} mode.",
+                  sequence.name,
+                  mode::current(&shared)
+                );
+
                 return ProgramState::WaitForOperator {
                   server_socket,
                   shared,
@@ -288,6 +450,44 @@ fn wait_for_operator(
 
               // necessary to allow passing 'shared' back to WaitForOperator
               drop(triggers);
+              configuration::save(&shared);
+
+              ProgramState::WaitForOperator {
+                server_socket,
+                shared,
+              }
+            }
+            FlightControlMessage::CommsPolicy(policy) => {
+              pass!("Received comms policy from server: {policy:#?}");
+
+              // replace the policy of the board if it already has one
+              let mut policies = shared.comms_policies.lock().unwrap();
+
+              let existing =
+                policies.iter().position(|p| p.board_id == policy.board_id);
+
+              if let Some(index) = existing {
+                policies[index] = policy;
+              } else {
+                policies.push(policy);
+              }
+
+              drop(policies);
+              configuration::save(&shared);
+
+              ProgramState::WaitForOperator {
+                server_socket,
+                shared,
+              }
+            }
+            // only there to show the server is still up
+            FlightControlMessage::Heartbeat => ProgramState::WaitForOperator {
+              server_socket,
+              shared,
+            },
+            FlightControlMessage::TelemetryTargets(targets) => {
+              pass!("Streaming vehicle state to {targets:?}.");
+              *shared.telemetry_targets.lock().unwrap() = targets;
 
               ProgramState::WaitForOperator {
                 server_socket,
@@ -296,13 +496,20 @@ fn wait_for_operator(
             }
             FlightControlMessage::StopSequence(name) => {
               pass!("Received instruction to stop sequence from server.");
-              let stopped =
-                shared.sequences.lock().unwrap().remove_by_left(&name);
 
-              if stopped.is_some() {
-                pass!("Stopped sequence '{name}'.");
-              } else {
-                warn!("Sequence '{name}' was not running.");
+              match handler::stop_sequence(&shared, &name) {
+                Some(true) => {
+                  pass!("Stopped sequence '{name}'.");
+                }
+                Some(false) => {
+                  fail!(
+                    "Sequence '{name}' did not exit within {:?} of being stopped.",
+                    handler::EXIT_TIMEOUT
+                  );
+                }
+                None => {
+                  warn!("Sequence '{name}' was not running.");
+                }
               }
 
               ProgramState::WaitForOperator {
@@ -349,6 +556,48 @@ fn wait_for_operator(
                 None => fail!("Could not obtain the BMS/AHRS command channel. Command couldn't be sent.")
               };
 
+              ProgramState::WaitForOperator {
+                server_socket,
+                shared,
+              }
+            }
+            FlightControlMessage::SetMode {
+              mode: requested,
+              requested_by,
+            } => {
+              pass!(
+                "Received request from {requested_by} for {requested} mode."
+              );
+
+              // the abort mode must come with everything else an abort does,
+              // so is never entered as an ordinary transition
+              if requested == VehicleMode::Abort {
+                handler::abort(&shared);
+              } else {
+                mode::request(&shared, requested, requested_by);
+              }
+
+              ProgramState::WaitForOperator {
+                server_socket,
+                shared,
+              }
+            }
+            FlightControlMessage::SelfTest(board_id) => {
+              pass!("Received self-test request for {board_id} from Servo.");
+
+              // the commander knows the kind of each board which has
+              // identified itself, and so how it runs a self-test
+              match COMMANDER_TX.get() {
+                Some(commander) => {
+                  if let Err(e) = commander.send((board_id, Command::SelfTest)) {
+                    fail!("Could not send self-test to commander in switchboard: {e}.")
+                  }
+                }
+                None => {
+                  fail!("Could not obtain the command channel. Self-test couldn't be sent.");
+                }
+              };
+
               ProgramState::WaitForOperator {
                 server_socket,
                 shared,
@@ -368,17 +617,26 @@ fn wait_for_operator(
         }
       }
     }
-    Err(ref error) if error.kind() == io::ErrorKind::WouldBlock => {
-      ProgramState::WaitForOperator {
-        server_socket,
-        shared,
-      }
+    // a read timeout shows up as either, depending on the platform
+    Err(ref error)
+      if matches!(
+        error.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+      ) =>
+    {
+      fail!(
+        "Heard nothing from the server for {SERVER_HEARTBEAT_TIMEOUT:?}. \
+        Dropping connection."
+      );
+      server_loss::lost(&shared);
+      ProgramState::ServerDiscovery { shared }
     }
     Err(error) => {
       fail!(
         "Failed to read from server socket: {}. Dropping connection.",
         error.to_string()
       );
+      server_loss::lost(&shared);
       ProgramState::ServerDiscovery { shared }
     }
   }
@@ -387,19 +645,14 @@ fn wait_for_operator(
 /// Spawns a thread which runs the specified sequence before returning to
 /// `WaitForOperator`.
 fn run_sequence(
-  server_socket: TcpStream,
+  server_socket: ServerSocket,
   sequence: Sequence,
   shared: SharedState,
 ) -> ProgramState {
-  let sequence_name = sequence.name.clone();
-
-  let thread_id = thread::spawn(|| sequence::run(sequence)).thread().id();
-
-  shared
-    .sequences
-    .lock()
-    .unwrap()
-    .insert(sequence_name, thread_id);
+  let runner = shared.clone();
+  thread::spawn(move || {
+    handler::run_sequence(&runner, sequence);
+  });
 
   ProgramState::WaitForOperator {
     server_socket,
@@ -407,51 +660,220 @@ fn run_sequence(
   }
 }
 
-/// Constructs a closure which continuously checks if any triggers have tripped,
-/// running the corresponding script inline if so.
-fn check_triggers(shared: &SharedState) -> impl FnOnce() {
-  let triggers = shared.triggers.clone();
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use common::comm::{
+    mode::VehicleMode,
+    sam::SamControlMessage,
+    SensorType,
+    Trigger,
+  };
+  use std::{
+    net::TcpListener,
+    sync::mpsc::{self, Receiver},
+    time::{Duration, Instant},
+  };
 
-  // return closure instead of using the function itself because of
-  // borrow-checking rules regarding moving the 'triggers' reference across
-  // closure bounds
-  move || {
-    loop {
-      let mut triggers = triggers.lock().unwrap();
-
-      for trigger in triggers.iter_mut() {
-        // perform check by running condition as Python script and getting
-        // truth value
-        let check = Python::with_gil(|py| {
-          py.eval(&trigger.condition, None, None)
-            .and_then(|condition| condition.extract::<bool>())
-        });
-
-        // checks if the condition evaluated true
-        if check.as_ref().is_ok_and(|c| *c) {
-          let sequence = Sequence {
-            name: format!("trigger_{}", trigger.name),
-            script: trigger.script.clone(),
-          };
-
-          // run sequence in the same thread so there is no rapid-fire
-          // sequence dispatches if a trigger is tripped
-          // note: this is intentionally blocking
-          common::sequence::run(sequence);
-        }
+  /// How long a trigger or sequence is given to act while another sequence is
+  /// sleeping, far shorter than that sleep so a held GIL would be caught.
+  const RESPONSE_TIMEOUT: Duration = Duration::from_secs(2);
 
-        if let Err(error) = check {
-          fail!(
-            "Trigger '{}' raised exception during execution: {error}",
-            trigger.name
-          );
-          trigger.active = false;
+  /// Waits for the valve to be commanded to the given power state.
+  fn wait_for_actuation(
+    commands: &Receiver<(String, Command)>,
+    powered: bool,
+  ) -> bool {
+    let deadline = Instant::now() + RESPONSE_TIMEOUT;
+
+    loop {
+      let remaining = deadline.saturating_duration_since(Instant::now());
+
+      let Ok((_, command)) = commands.recv_timeout(remaining) else {
+        return false;
+      };
+
+      if let Command::Sam(SamControlMessage::ActuateValve {
+        powered: actual,
+        ..
+      }) = command
+      {
+        if actual == powered {
+          return true;
         }
       }
+    }
+  }
 
-      // drop triggers before waiting so the lock isn't held over the wait
-      drop(triggers);
-      thread::sleep(Duration::from_millis(10));
+  fn shared_state(mappings: Vec<NodeMapping>) -> SharedState {
+    SharedState {
+      vehicle_state: Arc::new(Mutex::new(VehicleState::new())),
+      mappings: Arc::new(Mutex::new(mappings)),
+      server_address: Arc::new(Mutex::new(None)),
+      triggers: Arc::new(Mutex::new(Vec::new())),
+      sequences: Arc::new(Mutex::new(HashMap::new())),
+      abort_sequence: Arc::new(Mutex::new(None)),
+      server_stream: Arc::new(Mutex::new(None)),
+      configuration_source: Arc::new(Mutex::new(ConfigurationSource::Empty)),
+      configuration_persisted: Arc::new(Mutex::new(true)),
+      comms_policies: Arc::new(Mutex::new(Vec::new())),
+      server_loss_policy: Arc::new(Mutex::new(ServerLossPolicy::default())),
+      server_loss: Arc::new(Mutex::new(None)),
+      telemetry_targets: Arc::new(Mutex::new(Vec::new())),
     }
   }
+
+  #[test]
+  fn sleeping_sequence_does_not_block_triggers_or_sequences() {
+    let shared = shared_state(vec![NodeMapping {
+      text_id: "vent".to_owned(),
+      board_id: "sam-01".to_owned(),
+      sensor_type: SensorType::Valve,
+      channel: 1,
+      computer: Computer::Flight,
+      max: None,
+      min: None,
+      calibrated_offset: 0.0,
+      powered_threshold: None,
+      normally_closed: Some(true),
+    }]);
+
+    // the built in table permits the vent while armed
+    mode::load().expect("default mode table should be valid");
+    shared.vehicle_state.lock().unwrap().mode = VehicleMode::Armed;
+
+    let (command_tx, commands) = mpsc::channel();
+    sequence::initialize(shared.mappings.clone());
+    sequence::set_device_handler(create_device_handler(
+      shared.clone(),
+      command_tx,
+    ));
+
+    thread::spawn(trigger::check_triggers(&shared));
+
+    let sleeper = shared.clone();
+    thread::spawn(move || {
+      handler::run_sequence(
+        &sleeper,
+        Sequence {
+          name: "sleeper".to_owned(),
+          script: "wait_for(60 * s)".to_owned(),
+        },
+      )
+    });
+
+    thread::sleep(Duration::from_millis(200));
+    assert!(shared.sequences.lock().unwrap().contains_key("sleeper"));
+
+    shared.triggers.lock().unwrap().push(Trigger {
+      name: "open".to_owned(),
+      condition: "True".to_owned(),
+      script: "vent.open()".to_owned(),
+      active: true,
+      reset: None,
+      hold_ms: 0,
+      cooldown_ms: 0,
+      fire_if_holding: true,
+      poll_ms: None,
+    });
+
+    assert!(
+      wait_for_actuation(&commands, true),
+      "trigger did not fire while a sequence was sleeping"
+    );
+
+    shared.triggers.lock().unwrap().clear();
+
+    let second = shared.clone();
+    thread::spawn(move || {
+      handler::run_sequence(
+        &second,
+        Sequence {
+          name: "second".to_owned(),
+          script: "vent.close()".to_owned(),
+        },
+      )
+    });
+
+    assert!(
+      wait_for_actuation(&commands, false),
+      "second sequence did not run while a sequence was sleeping"
+    );
+
+    assert_eq!(handler::stop_sequence(&shared, "sleeper"), Some(true));
+  }
+
+  #[test]
+  fn server_socket_reassembles_unframed_messages() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut server =
+      TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let mut socket = ServerSocket::new(listener.accept().unwrap().0);
+
+    let stop = |name: &str| {
+      postcard::to_allocvec(&FlightControlMessage::StopSequence(name.into()))
+        .unwrap()
+    };
+
+    // two messages in one write, then one split across two writes
+    let mut both = stop("first");
+    both.extend(stop("second"));
+    server.write_all(&both).unwrap();
+
+    let split = stop("third");
+    server.write_all(&split[..3]).unwrap();
+    server.flush().unwrap();
+
+    let mut next = || match socket.receive().unwrap() {
+      Some(Ok(FlightControlMessage::StopSequence(name))) => name,
+      other => panic!("expected a stop sequence message, got {other:?}"),
+    };
+
+    assert_eq!(next(), "first");
+    assert_eq!(next(), "second");
+
+    server.write_all(&split[3..]).unwrap();
+    assert_eq!(next(), "third");
+
+    drop(server);
+    assert!(socket.receive().unwrap().is_none());
+  }
+
+  #[test]
+  fn silent_server_is_lost() {
+    let shared = shared_state(Vec::new());
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut server =
+      TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+
+    // far shorter than the real timeout, to keep the test quick
+    let stream = listener.accept().unwrap().0;
+    stream
+      .set_read_timeout(Some(Duration::from_millis(100)))
+      .unwrap();
+
+    let heartbeat =
+      postcard::to_allocvec(&FlightControlMessage::Heartbeat).unwrap();
+    server.write_all(&heartbeat).unwrap();
+
+    let ProgramState::WaitForOperator {
+      server_socket,
+      shared,
+    } = wait_for_operator(ServerSocket::new(stream), shared)
+    else {
+      panic!("a heartbeat should keep the connection");
+    };
+
+    assert!(shared.server_loss.lock().unwrap().is_none());
+
+    // the connection stays open, but nothing more is sent
+    let ProgramState::ServerDiscovery { shared } =
+      wait_for_operator(server_socket, shared)
+    else {
+      panic!("a missed heartbeat should drop the connection");
+    };
+
+    assert!(shared.server_loss.lock().unwrap().is_some());
+    drop(server);
+  }
 }
@@ -0,0 +1,46 @@
+use common::comm::discovery::{self, Announcement, ANNOUNCEMENT_PERIOD};
+use jeflog::{fail, pass, warn};
+use std::{net::UdpSocket, thread};
+
+/// Wakes every `ANNOUNCEMENT_PERIOD` to broadcast where the FC is, so that data
+/// boards can find it without knowing its hostname.
+pub fn announcer(data_port: u16) -> impl FnOnce() {
+  move || {
+    let socket = match UdpSocket::bind("0.0.0.0:0") {
+      Ok(socket) => socket,
+      Err(e) => {
+        fail!("Failed to bind announcement socket: {e}");
+        return;
+      }
+    };
+
+    if let Err(e) = socket.set_broadcast(true) {
+      fail!("Failed to enable broadcasting for announcements: {e}");
+      return;
+    }
+
+    let announcement = Announcement {
+      vehicle_id: discovery::vehicle_id(),
+      data_port,
+    };
+
+    pass!("Announcing as vehicle '{}'.", announcement.vehicle_id);
+
+    // only warn once per run of failures so a downed interface doesn't flood
+    // the log
+    let mut failing = false;
+
+    loop {
+      match discovery::announce(&socket, &announcement) {
+        Ok(()) => failing = false,
+        Err(e) if !failing => {
+          warn!("Failed to broadcast announcement: {e}");
+          failing = true;
+        }
+        Err(_) => {}
+      }
+
+      thread::sleep(ANNOUNCEMENT_PERIOD);
+    }
+  }
+}
@@ -5,8 +5,13 @@ use crate::{
   SAM_PORT,
 };
 
-use common::comm::{ahrs, bms, flight::BoardId, sam::SamControlMessage};
-use jeflog::{fail, pass};
+use common::comm::{
+  ahrs,
+  bms,
+  flight::{BoardId, BoardKind},
+  sam::SamControlMessage,
+};
+use jeflog::{fail, pass, warn};
 use std::{
   collections::HashMap,
   net::{SocketAddr, UdpSocket},
@@ -18,6 +23,9 @@ pub enum Command {
   Sam(SamControlMessage),
   Bms(bms::Command),
   Ahrs(ahrs::Command),
+
+  /// Has the board run a self-test, in whichever form its kind takes.
+  SelfTest,
 }
 
 /// "fast lane" for sending SamControlMessages. Only wakes up when there's a
@@ -26,7 +34,7 @@ pub fn commander(
   shared: SharedState,
   commands: Receiver<(BoardId, Command)>,
   sender: UdpSocket,
-  sockets: Arc<RwLock<HashMap<BoardId, SocketAddr>>>,
+  sockets: Arc<RwLock<HashMap<BoardId, (SocketAddr, BoardKind)>>>,
 ) -> impl FnOnce() {
   move || {
     let mut buffer = [0; COMMAND_MESSAGE_BUFFER_SIZE];
@@ -40,6 +48,27 @@ pub fn commander(
           postcard::to_slice::<ahrs::Command>(&c, &mut buffer)
         }
         Command::Bms(c) => postcard::to_slice::<bms::Command>(&c, &mut buffer),
+        Command::SelfTest => {
+          let sockets = sockets.read().unwrap();
+          let kind = sockets.get(&board_id).map(|(_, kind)| *kind);
+
+          match kind {
+            Some(BoardKind::Sam) => {
+              postcard::to_slice(&SamControlMessage::SelfTest, &mut buffer)
+            }
+            Some(BoardKind::Bms) => {
+              postcard::to_slice(&bms::Command::SelfTest, &mut buffer)
+            }
+            Some(kind) => {
+              warn!("Board {board_id} is {kind:?}, which has no self-test.");
+              continue;
+            }
+            None => {
+              fail!("Board {board_id} has not identified itself.");
+              continue;
+            }
+          }
+        }
       };
 
       let Ok(message) = output else {
@@ -49,7 +78,7 @@ pub fn commander(
       };
 
       let sockets = sockets.read().unwrap();
-      if let Some(socket) = sockets.get(&board_id) {
+      if let Some((socket, _)) = sockets.get(&board_id) {
         let socket = (socket.ip(), SAM_PORT);
 
         match sender.send_to(message, socket) {
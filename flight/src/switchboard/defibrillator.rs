@@ -1,7 +1,8 @@
 use crate::{handler, state::SharedState, HEARTBEAT_PERIOD};
 
-use common::comm::flight::{BoardId, DataMessage};
-use jeflog::fail;
+use super::lifetime::Link;
+use common::comm::flight::{BoardId, BoardKind, DataMessage};
+use jeflog::{fail, pass};
 use std::{
   collections::{HashMap, HashSet},
   net::{SocketAddr, UdpSocket},
@@ -11,11 +12,15 @@ use std::{
 
 /// Wakes every `HEARTBEAT_RATE` to send heartbeats to all the connected Sam
 /// boards to ensure that the FC isn't disconnected.
+///
+/// A heartbeat which fails to send is only logged, as a board which stops
+/// receiving them goes quiet and is handled by the lifetime as its comms
+/// policy says.
 pub fn defibrillator(
   shared: SharedState,
   sender: UdpSocket,
-  sockets: Arc<RwLock<HashMap<BoardId, SocketAddr>>>,
-  statuses: Arc<Mutex<HashSet<BoardId>>>,
+  sockets: Arc<RwLock<HashMap<BoardId, (SocketAddr, BoardKind)>>>,
+  statuses: Arc<Mutex<HashMap<BoardId, Link>>>,
 ) -> impl FnOnce() {
   move || {
     let mut buf = vec![0; crate::HEARTBEAT_BUFFER_SIZE];
@@ -30,27 +35,33 @@ pub fn defibrillator(
       }
     };
 
+    // boards which heartbeats are failing to be sent to, so that only the
+    // first of consecutive failures is logged
+    let mut failing = HashSet::new();
+
     loop {
       thread::sleep(HEARTBEAT_PERIOD);
 
       let sockets = sockets.read().unwrap();
       let statuses = statuses.lock().unwrap();
-      let mut abort = false;
-      for (board_id, address) in sockets.iter() {
-        if !statuses.contains(board_id) {
+      for (board_id, (address, _)) in sockets.iter() {
+        if statuses.get(board_id) != Some(&Link::Alive) {
           continue;
         }
 
-        if let Err(e) = sender.send_to(heartbeat, address) {
-          fail!("Couldn't send heartbeat to address {address:#?}: {e}");
-          abort = true;
+        match sender.send_to(heartbeat, address) {
+          Ok(_) => {
+            if failing.remove(board_id) {
+              pass!("Resumed sending heartbeats to {board_id}.");
+            }
+          }
+          Err(e) => {
+            if failing.insert(board_id.clone()) {
+              fail!("Couldn't send heartbeat to address {address:#?}: {e}");
+            }
+          }
         }
       }
-
-      if abort {
-        fail!("Aborting...");
-        handler::abort(&shared);
-      }
     }
   }
 }
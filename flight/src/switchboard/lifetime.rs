@@ -1,70 +1,473 @@
-use crate::{handler, state::SharedState, REFRESH_COUNT, TIME_TIL_DEATH};
-use common::comm::flight::BoardId;
-use jeflog::fail;
+use crate::{
+  forwarder,
+  handler,
+  state::SharedState,
+  CommandSender,
+  COMMS_CHECK_PERIOD,
+  REFRESH_COUNT,
+  TIME_TIL_DEATH,
+};
+use common::comm::{
+  flight::BoardId,
+  BoardCommsEvent,
+  BoardCommsEventKind,
+  CommsPolicy,
+  Criticality,
+  FlightStatusMessage,
+  Reconnect,
+};
+use jeflog::{fail, pass, warn};
 use std::{
-  collections::{HashMap, HashSet},
+  collections::HashMap,
   sync::{
-    mpsc::{Receiver, TryRecvError},
+    mpsc::{Receiver, RecvTimeoutError},
     Arc,
     Mutex,
   },
-  time::Instant,
+  thread,
+  time::{Duration, Instant},
 };
 
-/// Tracks the state of each board, detected if boards lose communications.
+/// The communications with a board, as judged by the lifetime.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Link {
+  /// The board is in service and being sent heartbeats.
+  Alive,
+
+  /// The board was lost, and is taken back into service once it identifies
+  /// itself again.
+  Lost,
+
+  /// The board was lost for good, so anything it sends is ignored.
+  Latched,
+}
+
+/// When a board in service was last heard from.
+struct Watched {
+  heard_at: Instant,
+
+  /// How many windows the board has missed since it was last heard from.
+  missed: u32,
+}
+
+/// Tracks the state of each board, handling any which lose communications as
+/// their comms policies say.
 pub fn lifetime(
   shared: SharedState,
   snooze: Receiver<BoardId>,
-  statuses: Arc<Mutex<HashSet<BoardId>>>,
+  statuses: Arc<Mutex<HashMap<BoardId, Link>>>,
+  command_tx: CommandSender,
 ) -> impl FnOnce() {
   move || {
-    let mut timers = HashMap::new();
+    let mut watched = HashMap::new();
 
     'main: loop {
-      let mut statuses = statuses.lock().unwrap();
+      let mut wait = COMMS_CHECK_PERIOD;
 
       for _ in 0..REFRESH_COUNT {
-        // get board to configure
-        let board_id = match snooze.try_recv() {
+        let board_id = match snooze.recv_timeout(wait) {
           Ok(board_id) => board_id,
-          Err(TryRecvError::Disconnected) => {
+          Err(RecvTimeoutError::Disconnected) => {
             break 'main;
           }
-          Err(TryRecvError::Empty) => {
+          Err(RecvTimeoutError::Timeout) => {
             break;
           }
         };
 
-        if !statuses.contains(&board_id) {
-          statuses.insert(board_id.clone());
-        }
+        // only the first board is waited for, so that boards going quiet are
+        // checked for at least every period
+        wait = Duration::ZERO;
 
-        // refresh timer
-        timers.insert(board_id, Instant::now());
+        hear(&shared, &statuses, &mut watched, board_id);
       }
 
-      let mut abort = false;
-      for board_id in timers.keys() {
-        if !statuses.contains(board_id) {
-          continue;
-        }
+      check(&shared, &statuses, &mut watched, &command_tx);
+    }
+
+    fail!("Switchboard unexpectedly dropped the snooze channel. Aborting.");
+    handler::abort(&shared);
+  }
+}
 
-        if Instant::now() - *timers.get(board_id).unwrap() > TIME_TIL_DEATH {
-          statuses.remove(board_id);
-          abort = true;
+/// Refreshes the timer of a board which was just heard from, taking it back
+/// into service if it was lost.
+fn hear(
+  shared: &SharedState,
+  statuses: &Mutex<HashMap<BoardId, Link>>,
+  watched: &mut HashMap<BoardId, Watched>,
+  board_id: BoardId,
+) {
+  let mut statuses = statuses.lock().unwrap();
 
-          fail!("Detected loss of comms from {board_id}");
-        }
+  if heard(&mut statuses, watched, &board_id, Instant::now()) {
+    pass!("Regained comms with {board_id}.");
+    report(shared, &board_id, BoardCommsEventKind::Recovered);
+  }
+}
+
+/// Refreshes the timer of a board heard from at `now`, returning whether it
+/// was taken back into service after being lost.
+fn heard(
+  statuses: &mut HashMap<BoardId, Link>,
+  watched: &mut HashMap<BoardId, Watched>,
+  board_id: &BoardId,
+  now: Instant,
+) -> bool {
+  let link = statuses.entry(board_id.clone()).or_insert(Link::Alive);
+
+  let regained = match link {
+    Link::Alive => false,
+    Link::Lost => {
+      *link = Link::Alive;
+      true
+    }
+    Link::Latched => return false,
+  };
+
+  let watch = Watched {
+    heard_at: now,
+    missed: 0,
+  };
+
+  watched.insert(board_id.clone(), watch);
+  regained
+}
+
+/// What a check found of a board in service.
+#[derive(Clone, Debug, PartialEq)]
+enum Verdict {
+  /// The board missed another window, but no more than its policy allows.
+  Missed {
+    board_id: BoardId,
+    missed: u32,
+    allowed: u32,
+  },
+
+  /// The board missed more windows than its policy allows, and is out of
+  /// service.
+  Lost {
+    board_id: BoardId,
+    criticality: Criticality,
+  },
+}
+
+/// Checks every board in service for having missed more windows than its
+/// policy allows, handling the loss of any which have.
+fn check(
+  shared: &SharedState,
+  statuses: &Mutex<HashMap<BoardId, Link>>,
+  watched: &mut HashMap<BoardId, Watched>,
+  command_tx: &CommandSender,
+) {
+  let policies = shared.comms_policies.lock().unwrap().clone();
+  let mut statuses = statuses.lock().unwrap();
+  let verdicts = judge(&mut statuses, watched, &policies, Instant::now());
+
+  // handled without the statuses locked, as safing a board sends commands
+  drop(statuses);
+
+  for verdict in verdicts {
+    match verdict {
+      Verdict::Missed {
+        board_id,
+        missed,
+        allowed,
+      } => {
+        warn!("Board {board_id} missed {missed} of {allowed} allowed windows.");
       }
+      Verdict::Lost {
+        board_id,
+        criticality,
+      } => {
+        lose(shared, &board_id, criticality, command_tx);
+      }
+    }
+  }
+}
 
-      drop(statuses);
-      if abort {
-        fail!("Aborting...");
-        handler::abort(&shared);
+/// Judges every board in service as of `now` against its policy, taking any
+/// which missed more windows than it allows out of service.
+fn judge(
+  statuses: &mut HashMap<BoardId, Link>,
+  watched: &mut HashMap<BoardId, Watched>,
+  policies: &[CommsPolicy],
+  now: Instant,
+) -> Vec<Verdict> {
+  let mut verdicts = Vec::new();
+
+  for (board_id, link) in statuses.iter_mut() {
+    let policy = policy_for(policies, board_id);
+
+    match link {
+      Link::Alive => {}
+      Link::Lost => continue,
+      Link::Latched => {
+        // the policy may have changed since the board was latched
+        if policy.reconnect == Reconnect::Resume {
+          *link = Link::Lost;
+        }
+
+        continue;
       }
     }
 
-    fail!("Switchboard unexpectedly dropped the snooze channel. Aborting.");
-    handler::abort(&shared);
+    let Some(watch) = watched.get_mut(board_id) else {
+      continue;
+    };
+
+    let silence = now.saturating_duration_since(watch.heard_at).as_millis();
+    let missed = silence / u128::from(policy.timeout_ms.max(1));
+    let missed = u32::try_from(missed).unwrap_or(u32::MAX);
+
+    if missed <= watch.missed {
+      continue;
+    }
+
+    watch.missed = missed;
+
+    if missed <= policy.grace_count {
+      verdicts.push(Verdict::Missed {
+        board_id: board_id.clone(),
+        missed,
+        allowed: policy.grace_count,
+      });
+
+      continue;
+    }
+
+    *link = match policy.reconnect {
+      Reconnect::Resume => Link::Lost,
+      Reconnect::Latch => Link::Latched,
+    };
+
+    watched.remove(board_id);
+
+    verdicts.push(Verdict::Lost {
+      board_id: board_id.clone(),
+      criticality: policy.criticality,
+    });
+  }
+
+  verdicts
+}
+
+/// Handles the loss of a board as its policy says.
+fn lose(
+  shared: &SharedState,
+  board_id: &str,
+  criticality: Criticality,
+  command_tx: &CommandSender,
+) {
+  fail!("Detected loss of comms from {board_id}.");
+  report(shared, board_id, BoardCommsEventKind::Lost(criticality));
+
+  match criticality {
+    Criticality::Abort => {
+      fail!("Aborting...");
+
+      // aborted on another thread so that other boards are still watched
+      // while the abort sequence runs
+      let shared = shared.clone();
+      thread::spawn(move || handler::abort(&shared));
+    }
+    Criticality::Safe => {
+      warn!("Safing the valves on {board_id}.");
+      handler::safe_board(shared, board_id, command_tx);
+    }
+    Criticality::Warn => {
+      warn!("Continuing without {board_id}.");
+    }
+  }
+}
+
+/// Gets the comms policy of a board, or the default if it has none, which
+/// aborts as soon as the board misses a single window.
+fn policy_for(policies: &[CommsPolicy], board_id: &str) -> CommsPolicy {
+  let policy = policies.iter().find(|policy| policy.board_id == board_id);
+
+  policy.cloned().unwrap_or_else(|| CommsPolicy {
+    board_id: board_id.to_owned(),
+    timeout_ms: TIME_TIL_DEATH.as_millis() as u32,
+    grace_count: 0,
+    criticality: Criticality::Abort,
+    reconnect: Reconnect::Resume,
+  })
+}
+
+fn report(shared: &SharedState, board_id: &str, kind: BoardCommsEventKind) {
+  let event = BoardCommsEvent {
+    board_id: board_id.to_owned(),
+    kind,
+    timestamp: forwarder::timestamp(),
+  };
+
+  forwarder::report(shared, &FlightStatusMessage::BoardComms(event));
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const BOARD_ID: &str = "sam-01";
+
+  fn policy(grace_count: u32, reconnect: Reconnect) -> CommsPolicy {
+    CommsPolicy {
+      board_id: BOARD_ID.to_owned(),
+      timeout_ms: 100,
+      grace_count,
+      criticality: Criticality::Safe,
+      reconnect,
+    }
+  }
+
+  /// The links and timers of the lifetime, with the board heard from once.
+  struct Boards {
+    statuses: HashMap<BoardId, Link>,
+    watched: HashMap<BoardId, Watched>,
+  }
+
+  impl Boards {
+    fn heard_at(now: Instant) -> Self {
+      let mut boards = Boards {
+        statuses: HashMap::new(),
+        watched: HashMap::new(),
+      };
+
+      assert!(!boards.hear(now));
+      boards
+    }
+
+    fn hear(&mut self, now: Instant) -> bool {
+      let board_id = BOARD_ID.to_owned();
+      heard(&mut self.statuses, &mut self.watched, &board_id, now)
+    }
+
+    fn judge(&mut self, policy: &CommsPolicy, now: Instant) -> Vec<Verdict> {
+      let policies = [policy.clone()];
+      judge(&mut self.statuses, &mut self.watched, &policies, now)
+    }
+
+    fn link(&self) -> Link {
+      self.statuses[BOARD_ID]
+    }
+  }
+
+  fn missed(missed: u32, allowed: u32) -> Vec<Verdict> {
+    vec![Verdict::Missed {
+      board_id: BOARD_ID.to_owned(),
+      missed,
+      allowed,
+    }]
+  }
+
+  fn lost() -> Vec<Verdict> {
+    vec![Verdict::Lost {
+      board_id: BOARD_ID.to_owned(),
+      criticality: Criticality::Safe,
+    }]
+  }
+
+  #[test]
+  fn loses_a_board_once_it_misses_more_than_its_grace_count() {
+    let start = Instant::now();
+    let at = |ms| start + Duration::from_millis(ms);
+    let policy = policy(2, Reconnect::Resume);
+    let mut boards = Boards::heard_at(start);
+
+    assert_eq!(boards.judge(&policy, at(99)), Vec::new());
+
+    // each window is warned about once, however often it is checked
+    assert_eq!(boards.judge(&policy, at(100)), missed(1, 2));
+    assert_eq!(boards.judge(&policy, at(150)), Vec::new());
+    assert_eq!(boards.judge(&policy, at(200)), missed(2, 2));
+
+    assert_eq!(boards.judge(&policy, at(300)), lost());
+    assert_eq!(boards.link(), Link::Lost);
+
+    // and the loss is handled once
+    assert_eq!(boards.judge(&policy, at(400)), Vec::new());
+  }
+
+  #[test]
+  fn hearing_a_board_restarts_its_windows() {
+    let start = Instant::now();
+    let at = |ms| start + Duration::from_millis(ms);
+    let policy = policy(1, Reconnect::Resume);
+    let mut boards = Boards::heard_at(start);
+
+    assert_eq!(boards.judge(&policy, at(100)), missed(1, 1));
+    assert!(!boards.hear(at(150)));
+
+    assert_eq!(boards.judge(&policy, at(200)), Vec::new());
+    assert_eq!(boards.judge(&policy, at(250)), missed(1, 1));
+    assert_eq!(boards.judge(&policy, at(350)), lost());
+  }
+
+  #[test]
+  fn a_resumed_board_is_regained_when_heard_from() {
+    let start = Instant::now();
+    let at = |ms| start + Duration::from_millis(ms);
+    let policy = policy(0, Reconnect::Resume);
+    let mut boards = Boards::heard_at(start);
+
+    assert_eq!(boards.judge(&policy, at(100)), lost());
+    assert!(boards.hear(at(150)));
+    assert_eq!(boards.link(), Link::Alive);
+
+    // and watched again from when it was heard
+    assert_eq!(boards.judge(&policy, at(249)), Vec::new());
+    assert_eq!(boards.judge(&policy, at(250)), lost());
+  }
+
+  #[test]
+  fn a_latched_board_is_ignored_when_heard_from() {
+    let start = Instant::now();
+    let at = |ms| start + Duration::from_millis(ms);
+    let policy = policy(0, Reconnect::Latch);
+    let mut boards = Boards::heard_at(start);
+
+    assert_eq!(boards.judge(&policy, at(100)), lost());
+    assert_eq!(boards.link(), Link::Latched);
+
+    assert!(!boards.hear(at(150)));
+    assert_eq!(boards.link(), Link::Latched);
+    assert_eq!(boards.judge(&policy, at(1000)), Vec::new());
+  }
+
+  #[test]
+  fn a_latched_board_is_rearmed_when_its_policy_changes_to_resume() {
+    let start = Instant::now();
+    let at = |ms| start + Duration::from_millis(ms);
+    let mut boards = Boards::heard_at(start);
+
+    let latch = policy(0, Reconnect::Latch);
+    assert_eq!(boards.judge(&latch, at(100)), lost());
+
+    let resume = policy(0, Reconnect::Resume);
+    assert_eq!(boards.judge(&resume, at(110)), Vec::new());
+    assert_eq!(boards.link(), Link::Lost);
+
+    assert!(boards.hear(at(120)));
+    assert_eq!(boards.link(), Link::Alive);
+  }
+
+  #[test]
+  fn boards_without_a_policy_abort_on_the_first_missed_window() {
+    let default = policy_for(&[policy(3, Reconnect::Latch)], "sam-02");
+
+    assert_eq!(
+      default,
+      CommsPolicy {
+        board_id: "sam-02".to_owned(),
+        timeout_ms: TIME_TIL_DEATH.as_millis() as u32,
+        grace_count: 0,
+        criticality: Criticality::Abort,
+        reconnect: Reconnect::Resume,
+      }
+    );
+
+    let own = policy_for(&[policy(3, Reconnect::Latch)], BOARD_ID);
+    assert_eq!(own, policy(3, Reconnect::Latch));
   }
 }
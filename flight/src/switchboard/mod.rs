@@ -1,16 +1,29 @@
+mod announcer;
 pub mod commander;
 mod defibrillator;
 mod lifetime;
 mod worker;
 
-use crate::{handler, state::SharedState, CommandSender, FC_BOARD_ID};
+use crate::{
+  forwarder,
+  handler,
+  state::SharedState,
+  CommandSender,
+  FC_BOARD_ID,
+  SAM_PORT,
+};
+use announcer::announcer;
 use commander::commander;
-use common::comm::flight::{BoardId, DataMessage};
+use common::comm::{
+  bms,
+  flight::{BoardId, BoardKind, DataMessage},
+  FlightStatusMessage,
+};
 use defibrillator::defibrillator;
 use jeflog::{fail, pass, warn};
-use lifetime::lifetime;
+use lifetime::{lifetime, Link};
 use std::{
-  collections::{HashMap, HashSet},
+  collections::HashMap,
   io,
   net::{SocketAddr, UdpSocket},
   sync::{
@@ -23,13 +36,12 @@ use std::{
 };
 use worker::{worker, Gig};
 
-// Concerns: might be a bit too abort happy?
-
 /// one-shot function that starts the switchboard.
 pub fn start(
   shared: SharedState,
   socket: UdpSocket,
 ) -> io::Result<CommandSender> {
+  let data_port = socket.local_addr()?.port();
   let reciever = socket.try_clone()?;
   let sender = socket.try_clone()?;
   let command_sender = socket.try_clone()?;
@@ -38,7 +50,7 @@ pub fn start(
   let (gig_tx, gig_rx) = mpsc::channel();
   let (command_tx, command_rx) = mpsc::channel();
 
-  let statuses = Arc::new(Mutex::new(HashSet::new()));
+  let statuses = Arc::new(Mutex::new(HashMap::new()));
   let sockets = Arc::new(RwLock::new(HashMap::new()));
 
   thread::spawn(switchboard(
@@ -48,8 +60,14 @@ pub fn start(
     socket,
     reciever,
     sockets.clone(),
+    statuses.clone(),
+  ));
+  thread::spawn(lifetime(
+    shared.clone(),
+    snooze_rx,
+    statuses.clone(),
+    command_tx.clone(),
   ));
-  thread::spawn(lifetime(shared.clone(), snooze_rx, statuses.clone()));
   thread::spawn(defibrillator(
     shared.clone(),
     sender,
@@ -57,6 +75,7 @@ pub fn start(
     statuses.clone(),
   ));
   thread::spawn(worker(shared.clone(), gig_rx));
+  thread::spawn(announcer(data_port));
   thread::spawn(commander(
     shared.clone(),
     command_rx,
@@ -75,11 +94,16 @@ pub fn switchboard(
   gig: Sender<(BoardId, Gig)>,
   handshake_sender: UdpSocket,
   reciever: UdpSocket,
-  sockets: Arc<RwLock<HashMap<BoardId, SocketAddr>>>,
+  sockets: Arc<RwLock<HashMap<BoardId, (SocketAddr, BoardKind)>>>,
+  statuses: Arc<Mutex<HashMap<BoardId, Link>>>,
 ) -> impl FnOnce() {
   move || {
     let mut buffer = [0; crate::DATA_MESSAGE_BUFFER_SIZE];
 
+    // the last e-stop event relayed from each board, as a board resends an
+    // event until it sees the acknowledgement
+    let mut estop_events = HashMap::new();
+
     loop {
       // Move the incoming UDP data into a buffer
       let (message_length, sender_address) =
@@ -92,7 +116,8 @@ pub fn switchboard(
           }
         };
 
-      let incoming_data = postcard::from_bytes(&buffer[..message_length]);
+      let incoming_data =
+        postcard::from_bytes::<DataMessage>(&buffer[..message_length]);
 
       // Interpret the data in the buffer
       let incoming_data = match incoming_data {
@@ -103,14 +128,34 @@ pub fn switchboard(
         }
       };
 
+      let link = incoming_data
+        .board_id()
+        .and_then(|board_id| statuses.lock().unwrap().get(board_id).copied());
+
+      let identity = matches!(incoming_data, DataMessage::Identity(..));
+
+      // a lost board must identify itself again before anything it sends is
+      // taken in, as it only starts watching for heartbeats once handshaken
+      // with, and a board lost for good is ignored entirely
+      match link {
+        Some(Link::Lost) if identity => {}
+        Some(Link::Lost | Link::Latched) => continue,
+        _ => {}
+      }
+
       let board_id = match incoming_data {
-        DataMessage::Identity(board_id) => {
+        DataMessage::Identity(board_id, kind) => {
           let mut sockets = sockets.write().unwrap();
-          sockets.insert(board_id.clone(), sender_address);
+          sockets.insert(board_id.clone(), (sender_address, kind));
 
-          pass!("Recieved identity message from board {board_id}");
+          if link == Some(Link::Lost) {
+            pass!("Re-acquired board {board_id} at {sender_address}.");
+          } else {
+            pass!("Recieved identity message from board {board_id}");
+          }
 
-          let identity = DataMessage::Identity(String::from(FC_BOARD_ID));
+          let identity =
+            DataMessage::Identity(String::from(FC_BOARD_ID), BoardKind::Flight);
 
           let handshake = match postcard::to_slice(&identity, &mut buffer) {
             Ok(identity) => identity,
@@ -161,6 +206,45 @@ pub fn switchboard(
 
           board_id
         }
+        DataMessage::Diagnostics(board_id, report) => {
+          pass!("Recieved self-test report from board {board_id}.");
+
+          let message = FlightStatusMessage::Diagnostics(
+            board_id.clone(),
+            report.into_owned(),
+          );
+          forwarder::report(&shared, &message);
+
+          board_id
+        }
+        DataMessage::EStop(board_id, event) => {
+          if estop_events.get(&board_id) != Some(&event) {
+            warn!(
+              "Board {board_id} reported e-stop {:?} by {:?}.",
+              event.kind, event.path
+            );
+
+            let message = FlightStatusMessage::EStop(board_id.clone(), event);
+            forwarder::report(&shared, &message);
+            estop_events.insert(board_id.clone(), event);
+          }
+
+          let ack = bms::Command::AcknowledgeEStop(event.sequence);
+          let command_address = (sender_address.ip(), SAM_PORT);
+
+          match postcard::to_allocvec(&ack) {
+            Ok(ack) => {
+              if let Err(e) = handshake_sender.send_to(&ack, command_address) {
+                fail!("Failed to acknowledge e-stop event of {board_id}: {e}");
+              }
+            }
+            Err(e) => {
+              fail!("Failed to serialize e-stop acknowledgement: {e}");
+            }
+          }
+
+          board_id
+        }
         DataMessage::FlightHeartbeat => {
           warn!("Recieved a FlightHeartbeat from {sender_address}.");
           continue;
@@ -175,3 +259,263 @@ pub fn switchboard(
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use common::comm::{
+    sam::{self, ChannelType},
+    BoardCommsEventKind,
+    CommsPolicy,
+    Computer,
+    ConfigurationSource,
+    Criticality,
+    NodeMapping,
+    Reconnect,
+    SensorType,
+    ServerLossPolicy,
+    VehicleState,
+  };
+  use std::{
+    borrow::Cow,
+    io::Read,
+    net::{TcpListener, TcpStream},
+    time::{Duration, Instant},
+  };
+
+  const BOARD_ID: &str = "sam-01";
+
+  /// How long the simulated board may go unheard before it is lost.
+  const TIMEOUT: Duration = Duration::from_millis(50);
+
+  /// How long anything expected of the flight computer is waited for.
+  const RESPONSE_TIMEOUT: Duration = Duration::from_secs(2);
+
+  /// A SAM board simulated over UDP on localhost.
+  struct Board {
+    socket: UdpSocket,
+    flight: SocketAddr,
+  }
+
+  impl Board {
+    /// Constructs a board on a port of its own, as a restarted board would
+    /// be.
+    fn new(flight: SocketAddr) -> Self {
+      let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+      socket
+        .set_read_timeout(Some(Duration::from_millis(10)))
+        .unwrap();
+
+      Board { socket, flight }
+    }
+
+    fn send(&self, message: &DataMessage) {
+      let bytes = postcard::to_allocvec(message).unwrap();
+      self.socket.send_to(&bytes, self.flight).unwrap();
+    }
+
+    /// Sends a reading of the pressure transducer on channel 1.
+    fn send_reading(&self, value: f64) {
+      let data_point = sam::DataPoint {
+        value,
+        timestamp: 0.0,
+        channel: 1,
+        channel_type: ChannelType::CurrentLoop,
+      };
+
+      let data = vec![data_point];
+      self.send(&DataMessage::Sam(BOARD_ID.to_owned(), Cow::Owned(data)));
+    }
+
+    /// Waits up to `timeout` for a message from the flight computer which
+    /// matches, sending the given reading meanwhile if there is one so that
+    /// the board isn't lost.
+    fn wait_for(
+      &self,
+      expected: impl Fn(&DataMessage) -> bool,
+      timeout: Duration,
+      reading: Option<f64>,
+    ) -> bool {
+      let mut buffer = [0; 1_024];
+      let deadline = Instant::now() + timeout;
+
+      while Instant::now() < deadline {
+        if let Some(value) = reading {
+          self.send_reading(value);
+        }
+
+        let Ok((size, _)) = self.socket.recv_from(&mut buffer) else {
+          continue;
+        };
+
+        let message = postcard::from_bytes::<DataMessage>(&buffer[..size]);
+
+        if message.is_ok_and(|message| expected(&message)) {
+          return true;
+        }
+      }
+
+      false
+    }
+
+    /// Sends the identity of the board until the flight computer responds
+    /// with its own.
+    fn identify(&self) -> bool {
+      let deadline = Instant::now() + RESPONSE_TIMEOUT;
+
+      while Instant::now() < deadline {
+        self.send(&DataMessage::Identity(BOARD_ID.to_owned(), BoardKind::Sam));
+
+        let identity =
+          |message: &DataMessage| matches!(message, DataMessage::Identity(..));
+
+        if self.wait_for(identity, Duration::from_millis(100), None) {
+          return true;
+        }
+      }
+
+      false
+    }
+
+    fn wait_for_heartbeat(&self, timeout: Duration, reading: f64) -> bool {
+      let heartbeat = |message: &DataMessage| {
+        matches!(message, DataMessage::FlightHeartbeat)
+      };
+
+      self.wait_for(heartbeat, timeout, Some(reading))
+    }
+  }
+
+  /// Sends a reading until the flight computer has ingested it.
+  fn wait_for_reading(shared: &SharedState, board: &Board, value: f64) -> bool {
+    let deadline = Instant::now() + RESPONSE_TIMEOUT;
+
+    while Instant::now() < deadline {
+      board.send_reading(value);
+      thread::sleep(Duration::from_millis(10));
+
+      if reading(shared) == Some(value) {
+        return true;
+      }
+    }
+
+    false
+  }
+
+  fn reading(shared: &SharedState) -> Option<f64> {
+    let vehicle_state = shared.vehicle_state.lock().unwrap();
+    vehicle_state.sensor_readings.get("pt").map(|pt| pt.value)
+  }
+
+  /// Waits for the flight computer to report a change in comms with a board
+  /// to the server.
+  fn wait_for_event(
+    server: &mut TcpStream,
+    pending: &mut Vec<u8>,
+  ) -> Option<BoardCommsEventKind> {
+    let mut buffer = [0; 1_024];
+
+    loop {
+      if let Ok((message, rest)) =
+        postcard::take_from_bytes::<FlightStatusMessage>(pending)
+      {
+        *pending = rest.to_vec();
+
+        if let FlightStatusMessage::BoardComms(event) = message {
+          return Some(event.kind);
+        }
+
+        continue;
+      }
+
+      let size = server.read(&mut buffer).ok().filter(|size| *size > 0)?;
+      pending.extend_from_slice(&buffer[..size]);
+    }
+  }
+
+  #[test]
+  fn lost_board_is_reacquired_after_identifying_again() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let (mut server, _) = listener.accept().unwrap();
+    server.set_read_timeout(Some(RESPONSE_TIMEOUT)).unwrap();
+
+    let shared = SharedState {
+      vehicle_state: Arc::new(Mutex::new(VehicleState::new())),
+      mappings: Arc::new(Mutex::new(vec![NodeMapping {
+        text_id: "pt".to_owned(),
+        board_id: BOARD_ID.to_owned(),
+        sensor_type: SensorType::Pt,
+        channel: 1,
+        computer: Computer::Flight,
+        max: None,
+        min: None,
+        calibrated_offset: 0.0,
+        powered_threshold: None,
+        normally_closed: None,
+      }])),
+      server_address: Arc::new(Mutex::new(None)),
+      triggers: Arc::new(Mutex::new(Vec::new())),
+      sequences: Arc::new(Mutex::new(HashMap::new())),
+      abort_sequence: Arc::new(Mutex::new(None)),
+      server_stream: Arc::new(Mutex::new(Some(client))),
+      configuration_source: Arc::new(Mutex::new(ConfigurationSource::Empty)),
+      configuration_persisted: Arc::new(Mutex::new(true)),
+      comms_policies: Arc::new(Mutex::new(vec![CommsPolicy {
+        board_id: BOARD_ID.to_owned(),
+        timeout_ms: TIMEOUT.as_millis() as u32,
+        grace_count: 0,
+        criticality: Criticality::Warn,
+        reconnect: Reconnect::Resume,
+      }])),
+      server_loss_policy: Arc::new(Mutex::new(ServerLossPolicy::default())),
+      server_loss: Arc::new(Mutex::new(None)),
+      telemetry_targets: Arc::new(Mutex::new(Vec::new())),
+    };
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let flight = socket.local_addr().unwrap();
+    start(shared.clone(), socket).unwrap();
+
+    let mut pending = Vec::new();
+
+    // the board is handshaken with, its data ingested and heartbeats sent
+    let board = Board::new(flight);
+    assert!(board.identify(), "flight did not respond to identity");
+    assert!(wait_for_reading(&shared, &board, 1.0));
+    assert!(board.wait_for_heartbeat(RESPONSE_TIMEOUT, 1.0));
+
+    // going quiet for longer than the policy allows loses the board
+    thread::sleep(TIMEOUT * 4);
+
+    assert_eq!(
+      wait_for_event(&mut server, &mut pending),
+      Some(BoardCommsEventKind::Lost(Criticality::Warn))
+    );
+
+    // a heartbeat sent before the loss may still be waiting
+    board.wait_for(|_| false, Duration::from_millis(50), None);
+
+    // until it identifies again, the board is sent no heartbeats and its data
+    // is ignored
+    assert!(
+      !board.wait_for_heartbeat(Duration::from_millis(400), 2.0),
+      "flight sent heartbeats to a lost board"
+    );
+    assert_eq!(reading(&shared), Some(1.0));
+
+    // the board restarts on a new port and identifies again, which restores
+    // where it is sent heartbeats and commands
+    drop(board);
+    let board = Board::new(flight);
+    assert!(board.identify(), "flight did not respond to identity");
+
+    assert_eq!(
+      wait_for_event(&mut server, &mut pending),
+      Some(BoardCommsEventKind::Recovered)
+    );
+
+    assert!(wait_for_reading(&shared, &board, 3.0));
+    assert!(board.wait_for_heartbeat(RESPONSE_TIMEOUT, 3.0));
+  }
+}
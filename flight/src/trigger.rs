@@ -0,0 +1,414 @@
+use crate::{forwarder, handler, state::SharedState};
+use common::comm::{
+  FlightStatusMessage,
+  Sequence,
+  Trigger,
+  TriggerEvent,
+  TriggerEventKind,
+};
+use jeflog::{fail, pass};
+use pyo3::{PyErr, PyObject, PyResult, Python};
+use std::{
+  mem,
+  thread,
+  time::{Duration, Instant},
+};
+
+/// How often the condition of a trigger is checked unless it sets its own
+/// rate, which is also the longest before a change to the triggers is noticed.
+const DEFAULT_POLL_PERIOD: Duration = Duration::from_millis(10);
+
+/// Prefixed to the name of a trigger to name the sequence its script runs as.
+pub const SEQUENCE_PREFIX: &str = "trigger_";
+
+/// The conditions of a trigger, compiled once when the trigger is set.
+struct Compiled {
+  condition: PyObject,
+  reset: Option<PyObject>,
+}
+
+/// Detects when a trigger should fire from successive evaluations of its
+/// conditions.
+#[derive(Debug)]
+struct Edge {
+  /// Whether the trigger has fired and not yet re-armed.
+  disarmed: bool,
+
+  /// When the condition started holding, while it holds.
+  holding_since: Option<Instant>,
+
+  /// When the trigger last fired.
+  fired_at: Option<Instant>,
+}
+
+impl Edge {
+  /// Starts disarmed, so that a trigger set while its condition already holds
+  /// doesn't fire until it re-arms, unless it opts in with `fire_if_holding`.
+  fn new(trigger: &Trigger) -> Self {
+    Edge {
+      disarmed: !trigger.fire_if_holding,
+      holding_since: None,
+      fired_at: None,
+    }
+  }
+
+  /// Updates the edge with whether the condition holds and, while disarmed,
+  /// whether the reset condition holds, returning whether the trigger fires.
+  fn update(
+    &mut self,
+    trigger: &Trigger,
+    now: Instant,
+    holds: bool,
+    reset: Option<bool>,
+  ) -> bool {
+    // without a reset condition, a trigger re-arms once its condition stops
+    // holding
+    if self.disarmed {
+      self.disarmed = !reset.unwrap_or(!holds);
+    }
+
+    if !holds {
+      self.holding_since = None;
+      return false;
+    }
+
+    let holding_since = *self.holding_since.get_or_insert(now);
+    let hold = Duration::from_millis(trigger.hold_ms.into());
+    let cooldown = Duration::from_millis(trigger.cooldown_ms.into());
+
+    let held = now.duration_since(holding_since) >= hold;
+    let cooled = self
+      .fired_at
+      .is_none_or(|fired_at| now.duration_since(fired_at) >= cooldown);
+
+    if self.disarmed || !held || !cooled {
+      return false;
+    }
+
+    self.disarmed = true;
+    self.fired_at = Some(now);
+    true
+  }
+}
+
+/// A trigger being checked, along with the state needed to check it.
+struct Watched {
+  trigger: Trigger,
+
+  /// The compiled conditions, or `None` if they failed to compile, in which
+  /// case the trigger never fires until it is replaced.
+  compiled: Option<Compiled>,
+
+  edge: Edge,
+  next_poll: Instant,
+
+  /// Whether the last evaluation failed, so that only the first of
+  /// consecutive failures is reported.
+  failing: bool,
+}
+
+impl Watched {
+  fn new(shared: &SharedState, trigger: Trigger) -> Self {
+    let compiled = Python::with_gil(|py| {
+      let condition = compile(py, &trigger.name, &trigger.condition)?;
+
+      let reset = trigger
+        .reset
+        .as_ref()
+        .map(|reset| compile(py, &trigger.name, reset))
+        .transpose()?;
+
+      Ok::<_, PyErr>(Compiled { condition, reset })
+    });
+
+    let failing = compiled.is_err();
+
+    if let Err(error) = &compiled {
+      fail!("Trigger '{}' failed to compile: {error}", trigger.name);
+
+      let kind = TriggerEventKind::Failed(error.to_string());
+      report(shared, &trigger.name, kind);
+    }
+
+    Watched {
+      edge: Edge::new(&trigger),
+      trigger,
+      compiled: compiled.ok(),
+      next_poll: Instant::now(),
+      failing,
+    }
+  }
+
+  fn poll_period(&self) -> Duration {
+    self.trigger.poll_ms.map_or(DEFAULT_POLL_PERIOD, |poll_ms| {
+      Duration::from_millis(poll_ms.max(1).into())
+    })
+  }
+
+  /// Evaluates the conditions of the trigger, returning whether it fires.
+  fn poll(&mut self, shared: &SharedState, now: Instant) -> bool {
+    let Some(compiled) = &self.compiled else {
+      return false;
+    };
+
+    let disarmed = self.edge.disarmed;
+
+    let result = Python::with_gil(|py| {
+      let holds = evaluate(py, &compiled.condition)?;
+
+      // the reset condition only matters until the trigger has re-armed
+      let reset = match &compiled.reset {
+        Some(reset) if disarmed => Some(evaluate(py, reset)?),
+        _ => None,
+      };
+
+      Ok::<_, PyErr>((holds, reset))
+    });
+
+    let name = &self.trigger.name;
+
+    match result {
+      Ok((holds, reset)) => {
+        if mem::take(&mut self.failing) {
+          pass!("Trigger '{name}' recovered.");
+          report(shared, name, TriggerEventKind::Recovered);
+        }
+
+        self.edge.update(&self.trigger, now, holds, reset)
+      }
+      Err(error) => {
+        if !mem::replace(&mut self.failing, true) {
+          fail!("Trigger '{name}' raised exception during execution: {error}");
+
+          let kind = TriggerEventKind::Failed(error.to_string());
+          report(shared, name, kind);
+        }
+
+        false
+      }
+    }
+  }
+}
+
+/// Compiles a condition of a trigger as a Python expression.
+fn compile(py: Python<'_>, name: &str, source: &str) -> PyResult<PyObject> {
+  let filename = format!("<trigger {name}>");
+
+  let code = py
+    .import("builtins")?
+    .getattr("compile")?
+    .call1((source, filename, "eval"))?;
+
+  Ok(code.into())
+}
+
+/// Evaluates a compiled condition in the same namespace sequences run in.
+fn evaluate(py: Python<'_>, code: &PyObject) -> PyResult<bool> {
+  let globals = py.import("__main__")?.dict();
+
+  py.import("builtins")?
+    .getattr("eval")?
+    .call1((code, globals))?
+    .extract::<bool>()
+}
+
+fn report(shared: &SharedState, name: &str, kind: TriggerEventKind) {
+  let event = TriggerEvent {
+    name: name.to_owned(),
+    kind,
+    timestamp: forwarder::timestamp(),
+  };
+
+  forwarder::report(shared, &FlightStatusMessage::Trigger(event));
+}
+
+/// Brings the watched triggers in line with those set by the server, keeping
+/// the state of any which are unchanged and compiling the rest.
+fn sync(shared: &SharedState, watched: &mut Vec<Watched>) {
+  let triggers = shared.triggers.lock().unwrap().clone();
+  let mut previous = mem::take(watched);
+
+  for trigger in triggers {
+    let existing = previous
+      .iter()
+      .position(|watched| watched.trigger == trigger);
+
+    watched.push(match existing {
+      Some(index) => previous.swap_remove(index),
+      None => Watched::new(shared, trigger),
+    });
+  }
+}
+
+/// Constructs a closure which continuously checks if any triggers have fired,
+/// running the corresponding script inline if so.
+pub fn check_triggers(shared: &SharedState) -> impl FnOnce() {
+  let shared = shared.clone();
+
+  move || {
+    let mut watched = Vec::new();
+
+    loop {
+      sync(&shared, &mut watched);
+
+      let now = Instant::now();
+
+      for watched in &mut watched {
+        if !watched.trigger.active || watched.next_poll > now {
+          continue;
+        }
+
+        watched.next_poll = now + watched.poll_period();
+
+        if !watched.poll(&shared, now) {
+          continue;
+        }
+
+        let name = &watched.trigger.name;
+        pass!("Trigger '{name}' fired.");
+        report(&shared, name, TriggerEventKind::Fired);
+
+        let sequence = Sequence {
+          name: format!("{SEQUENCE_PREFIX}{name}"),
+          script: watched.trigger.script.clone(),
+        };
+
+        // run sequence in the same thread so there is no rapid-fire
+        // sequence dispatches if a trigger is tripped
+        // note: this is intentionally blocking, so no trigger is polled, and
+        // no poll_ms honoured, until the script finishes
+        handler::run_sequence(&shared, sequence);
+      }
+
+      // woken at least every default period so that new triggers are noticed
+      let wake = watched
+        .iter()
+        .filter(|watched| watched.trigger.active)
+        .map(|watched| watched.next_poll)
+        .fold(Instant::now() + DEFAULT_POLL_PERIOD, Instant::min);
+
+      thread::sleep(wake.saturating_duration_since(Instant::now()));
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn trigger(hold_ms: u32, cooldown_ms: u32) -> Trigger {
+    Trigger {
+      name: "edge".to_owned(),
+      condition: "True".to_owned(),
+      script: "pass".to_owned(),
+      active: true,
+      reset: None,
+      hold_ms,
+      cooldown_ms,
+      fire_if_holding: false,
+      poll_ms: None,
+    }
+  }
+
+  fn ms(ms: u64) -> Duration {
+    Duration::from_millis(ms)
+  }
+
+  #[test]
+  fn waits_to_arm_when_set_while_already_true() {
+    let (trigger, start) = (trigger(0, 0), Instant::now());
+    let mut edge = Edge::new(&trigger);
+
+    // a trigger starts disarmed, as after a reboot, so a condition which
+    // already holds doesn't fire it until it first stops holding
+    assert!(!edge.update(&trigger, start, true, None));
+    assert!(!edge.update(&trigger, start + ms(10), true, None));
+    assert!(!edge.update(&trigger, start + ms(20), false, None));
+    assert!(edge.update(&trigger, start + ms(30), true, None));
+  }
+
+  #[test]
+  fn fires_when_set_while_already_true_if_opted_in() {
+    let (mut trigger, start) = (trigger(0, 0), Instant::now());
+    trigger.fire_if_holding = true;
+    let mut edge = Edge::new(&trigger);
+
+    assert!(edge.update(&trigger, start, true, None));
+    assert!(!edge.update(&trigger, start + ms(10), true, None));
+  }
+
+  #[test]
+  fn arms_on_the_reset_condition_when_set() {
+    let (trigger, start) = (trigger(0, 0), Instant::now());
+    let mut edge = Edge::new(&trigger);
+
+    assert!(!edge.update(&trigger, start, true, Some(false)));
+    assert!(!edge.update(&trigger, start + ms(10), false, Some(false)));
+    assert!(!edge.update(&trigger, start + ms(20), true, Some(false)));
+
+    assert!(!edge.update(&trigger, start + ms(30), false, Some(true)));
+    assert!(edge.update(&trigger, start + ms(40), true, None));
+  }
+
+  #[test]
+  fn rearms_once_the_condition_stops_holding() {
+    let (trigger, start) = (trigger(0, 0), Instant::now());
+    let mut edge = Edge::new(&trigger);
+
+    assert!(!edge.update(&trigger, start, false, None));
+    assert!(edge.update(&trigger, start + ms(10), true, None));
+    assert!(!edge.update(&trigger, start + ms(20), true, None));
+    assert!(!edge.update(&trigger, start + ms(30), false, None));
+    assert!(edge.update(&trigger, start + ms(40), true, None));
+  }
+
+  #[test]
+  fn rearms_only_once_the_reset_holds() {
+    let (trigger, start) = (trigger(0, 0), Instant::now());
+    let mut edge = Edge::new(&trigger);
+
+    assert!(!edge.update(&trigger, start, false, Some(true)));
+    assert!(edge.update(&trigger, start, true, None));
+
+    // the condition dropping isn't enough without the reset condition
+    assert!(!edge.update(&trigger, start + ms(10), false, Some(false)));
+    assert!(!edge.update(&trigger, start + ms(20), true, Some(false)));
+
+    // once the reset holds, the next time the condition does fires it
+    assert!(!edge.update(&trigger, start + ms(30), false, Some(true)));
+    assert!(edge.update(&trigger, start + ms(40), true, None));
+  }
+
+  #[test]
+  fn waits_for_the_condition_to_hold_continuously() {
+    let (trigger, start) = (trigger(100, 0), Instant::now());
+    let mut edge = Edge::new(&trigger);
+
+    assert!(!edge.update(&trigger, start, false, None));
+
+    assert!(!edge.update(&trigger, start, true, None));
+    assert!(!edge.update(&trigger, start + ms(90), true, None));
+
+    // dropping out restarts the hold
+    assert!(!edge.update(&trigger, start + ms(95), false, None));
+    assert!(!edge.update(&trigger, start + ms(100), true, None));
+    assert!(!edge.update(&trigger, start + ms(190), true, None));
+    assert!(edge.update(&trigger, start + ms(200), true, None));
+  }
+
+  #[test]
+  fn waits_out_the_cooldown_after_rearming() {
+    let (trigger, start) = (trigger(0, 100), Instant::now());
+    let mut edge = Edge::new(&trigger);
+
+    assert!(!edge.update(&trigger, start, false, None));
+
+    assert!(edge.update(&trigger, start, true, None));
+    assert!(!edge.update(&trigger, start + ms(10), false, None));
+
+    // re-armed, but still cooling down
+    assert!(!edge.update(&trigger, start + ms(20), true, None));
+    assert!(!edge.update(&trigger, start + ms(99), true, None));
+    assert!(edge.update(&trigger, start + ms(100), true, None));
+  }
+}
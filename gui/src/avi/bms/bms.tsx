@@ -4,7 +4,7 @@ import { GeneralTitleBar } from "../../general-components/TitleBar";
 import { listen } from "@tauri-apps/api/event";
 import { invoke } from "@tauri-apps/api/tauri";
 import { appWindow } from "@tauri-apps/api/window";
-import { Config, Sequence, State, runSequence, serverIp, StreamState, BMS as BMS_struct, Bus } from "../../comm";
+import { Config, Sequence, State, runSequence, serverIp, StreamState, BMS as BMS_struct, BMSFaults, Bus, ChargeStatus, ChargerState } from "../../comm";
 import { Valve } from "../../devices";
 import { enableCommand, disableCommand } from "../../commands";
 
@@ -18,9 +18,51 @@ const [bmsData, setBmsData] = createSignal({
   five_volt_rail: {voltage: 0, current: 0} as Bus,
   charger: 0,
   e_stop: 0,
-  rbf_tag: 0
+  rbf_tag: 0,
+  e_stop_tripped: null,
+  rbf_tag_inserted: null,
+  charge: {state: "Idle", enabled: false, elapsed: 0, charged_ah: 0} as ChargeStatus,
+  faults: {
+    battery_undervoltage: false,
+    battery_overcurrent: false,
+    umbilical_overcurrent: false,
+    sam_overcurrent: false,
+    charger_overcurrent: false
+  } as BMSFaults,
+  state_of_charge: null,
+  consumed_ah: 0,
+  time_remaining: null
 } as BMS_struct);
 
+// labels for each protection the BMS reports a fault for
+const faultLabels: [keyof BMSFaults, string][] = [
+  ["battery_undervoltage", "Battery Undervoltage"],
+  ["battery_overcurrent", "Battery Overcurrent"],
+  ["umbilical_overcurrent", "Umbilical Overcurrent"],
+  ["sam_overcurrent", "SAM Overcurrent"],
+  ["charger_overcurrent", "Charger Overcurrent"],
+];
+
+// formats a duration in seconds as hours and minutes, or a dash if unknown
+function formatDuration(seconds: number | null) {
+  if (seconds === null || seconds === undefined) {
+    return "-";
+  }
+  const minutes = Math.floor(seconds / 60);
+  return `${Math.floor(minutes / 60)}h ${minutes % 60}m`;
+}
+
+// formats the charger state for display, including the reason for any fault
+function formatChargerState(state: ChargerState | undefined) {
+  if (state === undefined) {
+    return "-";
+  }
+  if (typeof state === "object") {
+    return `FAULT (${state.Fault})`;
+  }
+  return state.replace(/([a-z])([A-Z])/g, "$1 $2").toUpperCase();
+}
+
 
 // listens to device updates and updates the values of BMS values accordingly for display
 listen('device_update', (event) => {
@@ -79,44 +121,52 @@ function BMS() {
                   <div class="adc-data-value"> {((bmsData() as BMS_struct).battery_bus as Bus).current} </div>
                 </div>
                 <div class="adc-data-row">
-                  <div class="adc-data-variable"> Variable 3 </div>
-                  <div class="adc-data-value"> Value 3 </div>
+                  <div class="adc-data-variable"> State of Charge </div>
+                  <div class="adc-data-value"> {(bmsData() as BMS_struct).state_of_charge == null ? "-" : `${((bmsData() as BMS_struct).state_of_charge! * 100).toFixed(1)}%`} </div>
+                </div>
+                <div class="adc-data-row">
+                  <div class="adc-data-variable"> Consumed </div>
+                  <div class="adc-data-value"> {(bmsData() as BMS_struct).consumed_ah?.toFixed(3)} Ah </div>
+                </div>
+                <div class="adc-data-row">
+                  <div class="adc-data-variable"> Time Remaining </div>
+                  <div class="adc-data-value"> {formatDuration((bmsData() as BMS_struct).time_remaining)} </div>
                 </div>
                 <div class="adc-data-row">
-                  <div class="adc-data-variable"> Variable 4 </div>
-                  <div class="adc-data-value"> Value 4 </div>
+                  <div class="adc-data-variable"> Charger </div>
+                  <div class="adc-data-value" style={{"color": typeof (bmsData() as BMS_struct).charge?.state === "object" ? '#C53434' : 'inherit'}}> {formatChargerState((bmsData() as BMS_struct).charge?.state)} ({(bmsData() as BMS_struct).charge?.enabled ? "ENABLED" : "DISABLED"}) </div>
                 </div>
                 <div class="adc-data-row">
-                  <div class="adc-data-variable"> Variable 5 </div>
-                  <div class="adc-data-value"> Value 5 </div>
+                  <div class="adc-data-variable"> Charge Progress </div>
+                  <div class="adc-data-value"> {(bmsData() as BMS_struct).charge?.charged_ah.toFixed(3)} Ah in {formatDuration((bmsData() as BMS_struct).charge?.elapsed ?? null)} </div>
+                </div>
+                <div class="adc-data-row">
+                  <div class="adc-data-variable"> E-Stop </div>
+                  <div class="adc-data-value" style={{"color": (bmsData() as BMS_struct).e_stop_tripped ? '#C53434' : 'inherit'}}> {(bmsData() as BMS_struct).e_stop_tripped == null ? "UNKNOWN" : (bmsData() as BMS_struct).e_stop_tripped ? "TRIPPED" : "OK"} ({(bmsData() as BMS_struct).e_stop?.toFixed(2)} V) </div>
+                </div>
+                <div class="adc-data-row">
+                  <div class="adc-data-variable"> RBF Tag </div>
+                  <div class="adc-data-value"> {(bmsData() as BMS_struct).rbf_tag_inserted == null ? "UNKNOWN" : (bmsData() as BMS_struct).rbf_tag_inserted ? "INSERTED" : "REMOVED"} ({(bmsData() as BMS_struct).rbf_tag?.toFixed(2)} V) </div>
                 </div>
               </div>
             </div>
             <div class="state-section">
-              <div class="section-title" style={{"text-decoration": 'underline'}}> States </div>
+              <div class="section-title" style={{"text-decoration": 'underline'}}> Faults </div>
               <div class="column-title-row">
-                <div class="column-title" style={{"font-size": "16px"}}> Smth? </div>
-                <div class="column-title" style={{"font-size": "16px"}}> States </div>
+                <div class="column-title" style={{"font-size": "16px"}}> Protection </div>
+                <div class="column-title" style={{"font-size": "16px"}}> State </div>
               </div>
-              {/* Change to iteratively display state variables and values once backend array is implemented */}
               <div class="state-row-container">
-                <div class="state-row">
-                  <div class="state-variable"> State 1 </div>
-                  <div class="state-value"> Value 1 </div>
-                </div>
-                <div class="state-row">
-                  <div class="state-variable"> State 2 </div>
-                  <div class="state-value"> Value 2 </div>
-                </div>
-                <div class="state-row">
-                  <div class="state-variable"> State 3 </div>
-                  <div class="state-value"> Value 3 </div>
-                </div>
-                <div class="state-row">
-                  <div class="state-variable"> State 4 </div>
-                  <div class="state-value"> Value 4 </div>
-                </div>
+                <For each={faultLabels}>{([key, label]) =>
+                  <div class="state-row">
+                    <div class="state-variable"> {label} </div>
+                    <div class="state-value" style={{"color": (bmsData() as BMS_struct).faults?.[key] ? '#C53434' : 'inherit'}}>
+                      {(bmsData() as BMS_struct).faults?.[key] ? "FAULT" : "OK"}
+                    </div>
+                  </div>
+                }</For>
               </div>
+              <button class="bms-button-en" onClick={() => enableCommand("bms", "faults")}> Clear Faults </button>
             </div>
             <div class="cell-voltages-section">
               <div class="section-title" style={{"text-decoration": 'underline'}}> Cell Voltages </div>
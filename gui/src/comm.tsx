@@ -82,6 +82,21 @@ export interface Trigger {
   script: string
 }
 
+// interface to represent the latest self-test report of a board
+export interface BoardDiagnostics {
+  board_id: string,
+  recorded_at: number,
+  passed: boolean,
+  report: SelfTestReport
+}
+
+export interface SelfTestReport {
+  timestamp: number,
+  adcs: Array<{name: string, id: number | null, status: number | null, data_ready: boolean | null, spi_errors: number}>,
+  rails: Array<{name: string, voltage: number, nominal: number | null, in_range: boolean}>,
+  outputs: Array<{name: string, commanded: boolean, measured: boolean}>
+}
+
 // interface representing the 'state' from the input stream
 export interface StreamState {
   valve_states: object,
@@ -89,7 +104,18 @@ export interface StreamState {
   update_times: object,
   sequences_running: Array<string>,
   bms: BMS,
-  ahrs: AHRS
+  ahrs: AHRS,
+  mode: string,
+  server_loss: ServerLossTimer | null
+}
+
+// interface to represent how long flight has been without the server
+export interface ServerLossTimer {
+  lost_at: number,
+  elapsed_s: number,
+  remaining_s: number | null,
+  action: string,
+  acted: boolean
 }
 
 // interface to represent a sensor from stream data
@@ -104,6 +130,26 @@ export interface Bus {
   current: number
 }
 
+// interface to represent the protections the BMS has tripped
+export interface BMSFaults {
+  battery_undervoltage: boolean,
+  battery_overcurrent: boolean,
+  umbilical_overcurrent: boolean,
+  sam_overcurrent: boolean,
+  charger_overcurrent: boolean
+}
+
+// state of the BMS charge controller; faults are reported as {Fault: reason}
+export type ChargerState = "Idle" | "Precharge" | "ConstantCurrent" | "ConstantVoltage" | "Done" | {Fault: string};
+
+// interface to represent the state and progress of the BMS charge controller
+export interface ChargeStatus {
+  state: ChargerState,
+  enabled: boolean,
+  elapsed: number,
+  charged_ah: number
+}
+
 // interface to represent BMS data
 export interface BMS {
   battery_bus: Bus,
@@ -112,7 +158,14 @@ export interface BMS {
   five_volt_rail: Bus,
   charger: number,
   e_stop: number,
-  rbf_tag: number
+  rbf_tag: number,
+  e_stop_tripped: boolean | null,
+  rbf_tag_inserted: boolean | null,
+  charge: ChargeStatus,
+  faults: BMSFaults,
+  state_of_charge: number | null,
+  consumed_ah: number,
+  time_remaining: number | null
 }
 
 // interface to represent AHRS data
@@ -387,6 +440,34 @@ export async function stopSequence(ip: string, name: string) {
   }
 }
 
+// function to get the latest self-test report of every board
+export async function getDiagnostics(ip: string) {
+  try {
+    const response = await fetch(`http://${ip}:${SERVER_PORT}/operator/diagnostics`, {
+      headers: new Headers({ 'Content-Type': 'application/json'}),
+    });
+    return await response.json();
+  } catch(e) {
+    return e;
+  }
+}
+
+// function to have a single board run a self-test
+export async function requestSelfTest(ip: string, boardId: string) {
+  try {
+    const response = await fetch(`http://${ip}:${SERVER_PORT}/operator/self-test`, {
+      headers: new Headers({ 'Content-Type': 'application/json'}),
+      method: 'POST',
+      body: JSON.stringify({
+        'board_id': boardId
+      }),
+    });
+    console.log('sent self-test request');
+    return response;
+  } catch(e) {
+    return e;
+  }
+}
 
 // function to open a stream to receive data on
 export async function openStream(ip: string) {
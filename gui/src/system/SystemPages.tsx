@@ -1,6 +1,6 @@
 import { Component, createSignal, For, Show } from "solid-js";
 import { invoke } from '@tauri-apps/api/tauri'
-import { setServerIp, connect, isConnected, setIsConnected, setActivity, serverIp, activity, selfIp, selfPort, sessionId, forwardingId, State, Config, sendActiveConfig, setSessionId, setForwardingId, setSelfIp, setSelfPort, Mapping, sendSequence, Sequence, getConfigs, sendConfig, getSequences, getTriggers, Trigger, sendTrigger } from "../comm";
+import { setServerIp, connect, isConnected, setIsConnected, setActivity, serverIp, activity, selfIp, selfPort, sessionId, forwardingId, State, Config, sendActiveConfig, setSessionId, setForwardingId, setSelfIp, setSelfPort, Mapping, sendSequence, Sequence, getConfigs, sendConfig, getSequences, getTriggers, Trigger, sendTrigger, BoardDiagnostics, getDiagnostics, requestSelfTest } from "../comm";
 import { turnOnLED, turnOffLED } from "../commands";
 import { emit, listen } from '@tauri-apps/api/event'
 import { appWindow } from "@tauri-apps/api/window";
@@ -49,6 +49,10 @@ const [channelTypes, setChannelTypes] = createSignal(["PT", "VALVE", "FLOW METER
 const [editableEntries, setEditableEntries] = createSignal([structuredClone(default_entry)]);
 const [configFocusIndex, setConfigFocusIndex] = createSignal(0);
 const [subConfigDisplay, setSubConfigDisplay] = createSignal('add');
+const [diagnostics, setDiagnostics] = createSignal([] as BoardDiagnostics[]);
+const [diagnosticsFocus, setDiagnosticsFocus] = createSignal('');
+const [selfTestBoard, setSelfTestBoard] = createSignal('');
+const [selfTestDisplay, setSelfTestDisplay] = createSignal("Self-Test");
 
 appWindow.onResized(({ payload: size }) => {
   setWindowHeight(window.innerHeight);
@@ -900,4 +904,113 @@ const Triggers: Component = (props) => {
 </div>
 }
 
-export {Connect, Feedsystem, ConfigView, Sequences, Triggers};
\ No newline at end of file
+async function refreshDiagnostics() {
+  setRefreshDisplay("Refreshing...");
+  var ip = serverIp() as string;
+  var reports = await getDiagnostics(ip);
+  if (reports instanceof Error) {
+    setRefreshDisplay('Error!');
+    await new Promise(r => setTimeout(r, 1000));
+    setRefreshDisplay('Refresh');
+    return;
+  }
+  setDiagnostics(reports as BoardDiagnostics[]);
+  setRefreshDisplay('Refreshed!');
+  await new Promise(r => setTimeout(r, 1000));
+  setRefreshDisplay('Refresh');
+}
+
+// the report is relayed back by the board a moment after it is requested, so
+// the reports are refreshed after a short wait
+async function runSelfTest(boardId: string) {
+  if (boardId.trim() == '') {
+    return;
+  }
+  setSelfTestDisplay("Requesting...");
+  var ip = serverIp() as string;
+  var response = await requestSelfTest(ip, boardId.trim());
+  if (response instanceof Error || !(response as Response).ok) {
+    setSelfTestDisplay('Error!');
+    await new Promise(r => setTimeout(r, 1000));
+    setSelfTestDisplay('Self-Test');
+    return;
+  }
+  setSelfTestDisplay('Requested!');
+  await new Promise(r => setTimeout(r, 1000));
+  setSelfTestDisplay('Self-Test');
+  setDiagnosticsFocus(boardId.trim());
+  await refreshDiagnostics();
+}
+
+const Diagnostics: Component = (props) => {
+  refreshDiagnostics();
+  const focused = () => diagnostics().find((board) => board.board_id == diagnosticsFocus());
+  return <div class="system-sequences-page">
+    <div style="text-align: center; font-size: 14px">DIAGNOSTICS</div>
+      <div class="sequences-list-view" style={{height: '200px'}}>
+        <div style={{display: "grid", "grid-template-columns": "100px 1fr 100px", width: '100%', "margin-bottom": '5px'}}>
+          <div></div>
+          <div style="text-align: center; font-size: 14px; font-family: 'Rubik'">Latest Self-Test per Board</div>
+          <button style={{"justify-content": "end"}} class="refresh-button" onClick={refreshDiagnostics}>{refreshDisplay()}</button>
+        </div>
+        <div class="horizontal-line"></div>
+        <div style={{"overflow-y": "auto", "max-height": '150px'}}>
+          <For each={diagnostics()}>{(board) =>
+              <div class="trigger-display-item" style={{"grid-template-columns": "1fr 200px 100px"}} onClick={() => setDiagnosticsFocus(board.board_id)}>
+                <div>{board.board_id}</div>
+                <div>{new Date(board.recorded_at * 1000).toLocaleString()}</div>
+                <div style={{color: board.passed? '#22873D' : '#C53434'}}>{board.passed? "PASSED" : "FAILED"}</div>
+              </div>
+            }
+          </For>
+        </div>
+      </div>
+      <div class="sequences-editor">
+        <div style={{display: "grid", "grid-template-columns": "220px 1fr", height: '50px'}}>
+          <input class="connect-textfield"
+            type="text"
+            name="self-test-board"
+            placeholder="Board ID"
+            value={selfTestBoard()}
+            onInput={(event) => setSelfTestBoard(event.currentTarget.value)}
+          style={{width: '200px'}}/>
+          <div style={{width: '100%'}}><button style={{float: "right"}} class="submit-sequence-button" onClick={() => runSelfTest(selfTestBoard() || diagnosticsFocus())}>{selfTestDisplay()}</button></div>
+        </div>
+        <Show when={focused()} fallback={<div style={{padding: '10px'}}>Select a board to view its report.</div>}>
+          <div style={{"overflow-y": "auto", padding: '10px'}}>
+            <div style={{"font-family": "Rubik", "margin-bottom": '5px'}}>{focused()!.board_id}</div>
+            <div style={{"margin-top": '10px'}}>ADCs</div>
+            <For each={focused()!.report.adcs}>{(adc) =>
+              <div style={{display: "grid", "grid-template-columns": "1fr 1fr 1fr 1fr 1fr"}}>
+                <div>{adc.name}</div>
+                <div>ID: {adc.id == null? "NONE" : "0x" + adc.id.toString(16)}</div>
+                <div>Status: {adc.status == null? "NONE" : "0x" + adc.status.toString(16)}</div>
+                <div>DRDY: {adc.data_ready == null? "N/A" : adc.data_ready? "OK" : "TIMEOUT"}</div>
+                <div>SPI errors: {adc.spi_errors}</div>
+              </div>
+            }</For>
+            <div style={{"margin-top": '10px'}}>Rails</div>
+            <For each={focused()!.report.rails}>{(rail) =>
+              <div style={{display: "grid", "grid-template-columns": "1fr 1fr 1fr 1fr"}}>
+                <div>{rail.name}</div>
+                <div>{rail.voltage.toFixed(2)} V</div>
+                <div>Nominal: {rail.nominal == null? "N/A" : rail.nominal.toFixed(2) + " V"}</div>
+                <div style={{color: rail.in_range? '#22873D' : '#C53434'}}>{rail.in_range? "IN RANGE" : "OUT OF RANGE"}</div>
+              </div>
+            }</For>
+            <div style={{"margin-top": '10px'}}>Outputs</div>
+            <For each={focused()!.report.outputs}>{(output) =>
+              <div style={{display: "grid", "grid-template-columns": "1fr 1fr 1fr 1fr"}}>
+                <div>{output.name}</div>
+                <div>Commanded: {output.commanded? "HIGH" : "LOW"}</div>
+                <div>Measured: {output.measured? "HIGH" : "LOW"}</div>
+                <div style={{color: output.commanded == output.measured? '#22873D' : '#C53434'}}>{output.commanded == output.measured? "OK" : "MISMATCH"}</div>
+              </div>
+            }</For>
+          </div>
+        </Show>
+    </div>
+</div>
+}
+
+export {Connect, Feedsystem, ConfigView, Sequences, Triggers, Diagnostics};
\ No newline at end of file
@@ -1,3 +1,4 @@
+use common::comm::{board::BoardDefinition, ADCKind};
 use jeflog::fail;
 use spidev::spidevioctl::SpidevTransfer;
 use spidev::Spidev;
@@ -8,6 +9,7 @@ use std::collections::HashMap;
 use std::rc::Rc;
 
 use crate::gpio::{
+  board_pin,
   Gpio,
   Pin,
   PinMode::{Input, Output},
@@ -28,9 +30,29 @@ pub enum Measurement {
   Rtd,
 }
 
+impl Measurement {
+  // Gets the measurement an ADC is responsible for from its board definition
+  pub fn from_kind(kind: ADCKind) -> Option<Measurement> {
+    match kind {
+      ADCKind::CurrentLoopPt => Some(Measurement::CurrentLoopPt),
+      ADCKind::VValve => Some(Measurement::VValve),
+      ADCKind::IValve => Some(Measurement::IValve),
+      ADCKind::VPower => Some(Measurement::VPower),
+      ADCKind::IPower => Some(Measurement::IPower),
+      ADCKind::Tc1 => Some(Measurement::Tc1),
+      ADCKind::Tc2 => Some(Measurement::Tc2),
+      ADCKind::DiffSensors => Some(Measurement::DiffSensors),
+      ADCKind::Rtd => Some(Measurement::Rtd),
+      ADCKind::VBatUmbCharge | ADCKind::SamAnd5V => None,
+    }
+  }
+}
+
 pub struct ADC {
   pub measurement: Measurement,
+  pub channels: u64,
   pub spidev: Rc<Spidev>,
+  pub spi_errors: u32,
   ambient_temp: f64,
   gpio_mappings: Rc<HashMap<Measurement, Pin>>,
   drdy_mappings: Rc<HashMap<Measurement, Pin>>,
@@ -40,34 +62,22 @@ impl ADC {
   // Constructs a new instance of an Analog-to-Digital Converter
   pub fn new(
     measurement: Measurement,
+    channels: u64,
     spidev: Rc<Spidev>,
     gpio_mappings: Rc<HashMap<Measurement, Pin>>,
     drdy_mappings: Rc<HashMap<Measurement, Pin>>,
   ) -> ADC {
     ADC {
       measurement,
+      channels,
       spidev,
+      spi_errors: 0,
       ambient_temp: 0.0,
       gpio_mappings,
       drdy_mappings,
     }
   }
 
-  pub fn cs_mappings() -> HashMap<Measurement, usize> {
-    let mut cs_gpios: HashMap<Measurement, usize> = HashMap::new();
-    cs_gpios.insert(Measurement::CurrentLoopPt, 30);
-    cs_gpios.insert(Measurement::IValve, 4);
-    cs_gpios.insert(Measurement::VValve, 26);
-    cs_gpios.insert(Measurement::VPower, 13);
-    cs_gpios.insert(Measurement::IPower, 15);
-    cs_gpios.insert(Measurement::Tc1, 10);
-    cs_gpios.insert(Measurement::Tc2, 20);
-    cs_gpios.insert(Measurement::DiffSensors, 16);
-    cs_gpios.insert(Measurement::Rtd, 11);
-
-    cs_gpios
-  }
-
   pub fn init_gpio(&mut self, prev_adc: Option<Measurement>) {
     // pull old adc HIGH
     if let Some(old_adc) = prev_adc {
@@ -94,6 +104,31 @@ impl ADC {
     }
   }
 
+  // Polls the data ready pin until it is pulled low or the timeout elapses,
+  // returning None if this ADC has no data ready pin
+  pub fn wait_for_data_ready(
+    &mut self,
+    timeout: time::Duration,
+  ) -> Option<bool> {
+    let drdy_pin = self.drdy_mappings.get(&self.measurement)?;
+    let start = time::Instant::now();
+
+    while start.elapsed() < timeout {
+      if drdy_pin.digital_read() == Low {
+        return Some(true);
+      }
+    }
+
+    Some(false)
+  }
+
+  // Performs a transfer, keeping count of any that fail
+  fn transfer(&mut self, transfer: &mut SpidevTransfer) {
+    if self.spidev.transfer(transfer).is_err() {
+      self.spi_errors += 1;
+    }
+  }
+
   pub fn init_regs(&mut self) {
     // Read initial registers
     self.read_regs(0, 17);
@@ -142,7 +177,7 @@ impl ADC {
   pub fn reset_status(&mut self) {
     let tx_buf_reset = [0x06];
     let mut transfer = SpidevTransfer::write(&tx_buf_reset);
-    let _status = self.spidev.transfer(&mut transfer);
+    self.transfer(&mut transfer);
   }
 
   pub fn start_conversion(&mut self) {
@@ -150,7 +185,7 @@ impl ADC {
     let mut rx_buf_rdata = [0x00];
     let mut transfer =
       SpidevTransfer::read_write(&tx_buf_rdata, &mut rx_buf_rdata);
-    let _status = self.spidev.transfer(&mut transfer);
+    self.transfer(&mut transfer);
     thread::sleep(time::Duration::from_millis(1));
   }
 
@@ -159,7 +194,7 @@ impl ADC {
     let mut rx_buf_rdata = [0x00];
     let mut transfer =
       SpidevTransfer::read_write(&tx_buf_rdata, &mut rx_buf_rdata);
-    let _status = self.spidev.transfer(&mut transfer);
+    self.transfer(&mut transfer);
     thread::sleep(time::Duration::from_millis(1000));
   }
 
@@ -170,7 +205,7 @@ impl ADC {
     tx_buf_readreg[1] = num_regs;
     let mut transfer =
       SpidevTransfer::read_write(&tx_buf_readreg, &mut rx_buf_readreg);
-    let _status = self.spidev.transfer(&mut transfer);
+    self.transfer(&mut transfer);
 
     println!("{:?} regs: {:?}", self.measurement, rx_buf_readreg);
     if rx_buf_readreg.iter().all(|&byte| byte == 0) {
@@ -178,12 +213,26 @@ impl ADC {
     }
   }
 
+  pub fn read_reg(&mut self, reg: u8) -> Option<u8> {
+    let tx_buf_readreg = [0x20 | reg, 0x00, 0x00];
+    let mut rx_buf_readreg = [0x00; 3];
+    let mut transfer =
+      SpidevTransfer::read_write(&tx_buf_readreg, &mut rx_buf_readreg);
+
+    if self.spidev.transfer(&mut transfer).is_err() {
+      self.spi_errors += 1;
+      return None;
+    }
+
+    Some(rx_buf_readreg[2])
+  }
+
   pub fn write_reg(&mut self, reg: u8, data: u8) {
     let tx_buf_writereg = [0x40 | reg, 0x00, data];
     let mut rx_buf_writereg = [0x40, 0x00, 0x00];
     let mut transfer =
       SpidevTransfer::read_write(&tx_buf_writereg, &mut rx_buf_writereg);
-    let _status = self.spidev.transfer(&mut transfer);
+    self.transfer(&mut transfer);
   }
 
   pub fn get_adc_reading(&mut self, iteration: u64) -> (f64, f64) {
@@ -328,7 +377,7 @@ impl ADC {
     let mut rx_buf_rdata = [0x00, 0x00, 0x00];
     let mut transfer =
       SpidevTransfer::read_write(&tx_buf_rdata, &mut rx_buf_rdata);
-    let _status = self.spidev.transfer(&mut transfer);
+    self.transfer(&mut transfer);
     let value: i16 = ((rx_buf_rdata[1] as i16) << 8) | (rx_buf_rdata[2] as i16);
 
     let mut reading;
@@ -385,96 +434,47 @@ pub fn open_controllers() -> Vec<Arc<Gpio>> {
 
 pub fn gpio_controller_mappings(
   controllers: &[Arc<Gpio>],
+  board: &BoardDefinition,
 ) -> HashMap<Measurement, Pin> {
-  let cl_pin = controllers[0].get_pin(30);
-  cl_pin.mode(Output);
-
-  let i_valve_pin = controllers[2].get_pin(4);
-  i_valve_pin.mode(Output);
-
-  let v_valve_pin = controllers[0].get_pin(26);
-  v_valve_pin.mode(Output);
-
-  let v_power_pin = controllers[2].get_pin(13);
-  v_power_pin.mode(Output);
-
-  let i_power_pin = controllers[2].get_pin(15);
-  i_power_pin.mode(Output);
-
-  let tc_1_pin = controllers[0].get_pin(10);
-  tc_1_pin.mode(Output);
-
-  let tc_2_pin = controllers[0].get_pin(20);
-  tc_2_pin.mode(Output);
+  let mut mappings = HashMap::new();
 
-  let diff_pin = controllers[3].get_pin(16);
-  diff_pin.mode(Output);
+  for adc in &board.adcs {
+    let Some(measurement) = Measurement::from_kind(adc.kind) else {
+      continue;
+    };
 
-  let rtd_pin = controllers[2].get_pin(11);
-  rtd_pin.mode(Output);
+    let pin = board_pin(controllers, adc.chip_select);
+    pin.mode(Output);
+    mappings.insert(measurement, pin);
+  }
 
-  HashMap::from([
-    (Measurement::CurrentLoopPt, cl_pin),
-    (Measurement::IValve, i_valve_pin),
-    (Measurement::VValve, v_valve_pin),
-    (Measurement::VPower, v_power_pin),
-    (Measurement::IPower, i_power_pin),
-    (Measurement::Tc1, tc_1_pin),
-    (Measurement::Tc2, tc_2_pin),
-    (Measurement::DiffSensors, diff_pin),
-    (Measurement::Rtd, rtd_pin),
-  ])
+  mappings
 }
 
 pub fn data_ready_mappings(
   controllers: &[Arc<Gpio>],
+  board: &BoardDefinition,
 ) -> HashMap<Measurement, Pin> {
-  let cl_pin = controllers[1].get_pin(28);
-  cl_pin.mode(Input);
-
-  let i_valve_pin = controllers[2].get_pin(3);
-  i_valve_pin.mode(Input);
-
-  let v_valve_pin = controllers[1].get_pin(12);
-  v_valve_pin.mode(Input);
-
-  let v_power_pin = controllers[2].get_pin(12);
-  v_power_pin.mode(Input);
-
-  let i_power_pin = controllers[2].get_pin(14);
-  i_power_pin.mode(Input);
-
-  let diff_pin = controllers[3].get_pin(15);
-  diff_pin.mode(Input);
+  let mut mappings = HashMap::new();
+
+  for adc in &board.adcs {
+    let (Some(measurement), Some(data_ready)) =
+      (Measurement::from_kind(adc.kind), adc.data_ready)
+    else {
+      continue;
+    };
+
+    let pin = board_pin(controllers, data_ready);
+    pin.mode(Input);
+    mappings.insert(measurement, pin);
+  }
 
-  HashMap::from([
-    (Measurement::CurrentLoopPt, cl_pin),
-    (Measurement::IValve, i_valve_pin),
-    (Measurement::VValve, v_valve_pin),
-    (Measurement::VPower, v_power_pin),
-    (Measurement::IPower, i_power_pin),
-    (Measurement::DiffSensors, diff_pin),
-  ])
+  mappings
 }
 
-pub fn pull_gpios_high(controllers: &[Arc<Gpio>]) {
-  let pins = vec![
-    controllers[0].get_pin(30),
-    controllers[2].get_pin(4),
-    controllers[0].get_pin(26),
-    controllers[2].get_pin(13),
-    controllers[2].get_pin(15),
-    controllers[0].get_pin(10),
-    controllers[0].get_pin(20),
-    controllers[3].get_pin(16),
-    controllers[2].get_pin(11),
-    controllers[0].get_pin(5),
-    controllers[0].get_pin(13),
-    controllers[0].get_pin(23),
-    controllers[2].get_pin(23),
-  ];
-
-  for pin in pins.iter() {
+pub fn pull_gpios_high(controllers: &[Arc<Gpio>], board: &BoardDefinition) {
+  for chip_select in board.chip_selects() {
+    let pin = board_pin(controllers, chip_select);
     pin.mode(Output);
     pin.digital_write(High);
   }
@@ -0,0 +1,30 @@
+use crate::adc::Measurement;
+use common::comm::board::{BoardDefinition, BoardDefinitionError};
+
+/// Where an updated set of board definitions may be placed on the SAM without
+/// rebuilding the software.
+const DEFINITIONS_PATH: &str = "/etc/sam/boards.json";
+
+/// The board definitions the SAM software was built with.
+const DEFAULT_DEFINITIONS: &str = include_str!("../boards.json");
+
+/// How many GPIO controllers the AM335x has.
+const GPIO_CONTROLLER_COUNT: usize = 4;
+
+/// Loads the definition of the board this is running on, making sure that
+/// everything in it is something the SAM software knows how to drive.
+pub fn load() -> Result<BoardDefinition, BoardDefinitionError> {
+  let board = BoardDefinition::load(DEFINITIONS_PATH, DEFAULT_DEFINITIONS)?;
+  board.validate(GPIO_CONTROLLER_COUNT)?;
+
+  for adc in &board.adcs {
+    if Measurement::from_kind(adc.kind).is_none() {
+      return Err(BoardDefinitionError::Incomplete {
+        revision: board.revision.clone(),
+        missing: format!("a SAM measurement for ADC kind {:?}", adc.kind),
+      });
+    }
+  }
+
+  Ok(board)
+}
@@ -1,8 +1,9 @@
-use common::comm::sam::SamControlMessage;
+use common::comm::{board::BoardDefinition, sam::SamControlMessage};
 use jeflog::fail;
-use std::fs::File;
+use std::sync::mpsc::Sender;
 
 use crate::gpio::{
+  board_pin,
   Gpio,
   PinMode::Output,
   PinValue::{High, Low},
@@ -11,7 +12,11 @@ use std::io::Write;
 use std::net::UdpSocket;
 use std::sync::Arc;
 
-pub fn begin(gpio_controllers: Vec<Arc<Gpio>>) {
+pub fn begin(
+  gpio_controllers: Vec<Arc<Gpio>>,
+  board: Arc<BoardDefinition>,
+  self_test: Sender<()>,
+) {
   // data: 4573
   let socket = UdpSocket::bind("0.0.0.0:8378").expect("Cannot bind to socket");
   let mut buf = [0; 65536];
@@ -24,155 +29,57 @@ pub fn begin(gpio_controllers: Vec<Arc<Gpio>>) {
     println!("{:#?}", deserialized_result);
     match deserialized_result {
       Ok(message) => {
-        execute(message, gpio_controllers.clone());
+        execute(message, gpio_controllers.clone(), &board, &self_test);
       }
       Err(_error) => fail!("Bad command message from flight computer"),
     };
   }
 }
 
-fn execute(command: SamControlMessage, gpio_controllers: Vec<Arc<Gpio>>) {
+fn execute(
+  command: SamControlMessage,
+  gpio_controllers: Vec<Arc<Gpio>>,
+  board: &BoardDefinition,
+  self_test: &Sender<()>,
+) {
   match command {
-    SamControlMessage::SetLed { channel, on } => match on {
-      true => match channel {
-        0 => {
-          let mut file: File = std::fs::OpenOptions::new()
-            .write(true)
-            .truncate(true)
-            .open("/sys/class/leds/beaglebone:green:usr0/brightness")
-            .unwrap();
-          file.write_all(b"1").expect("Failed to write");
-        }
-        1 => {
-          let mut file: File = std::fs::OpenOptions::new()
-            .write(true)
-            .truncate(true)
-            .open("/sys/class/leds/beaglebone:green:usr1/brightness")
-            .unwrap();
-          file.write_all(b"1").expect("Failed to write");
-        }
-        2 => {
-          let mut file: File = std::fs::OpenOptions::new()
-            .write(true)
-            .truncate(true)
-            .open("/sys/class/leds/beaglebone:green:usr2/brightness")
-            .unwrap();
-          file.write_all(b"1").expect("Failed to write");
-        }
-        3 => {
-          let mut file: File = std::fs::OpenOptions::new()
-            .write(true)
-            .truncate(true)
-            .open("/sys/class/leds/beaglebone:green:usr3/brightness")
-            .unwrap();
-          file.write_all(b"1").expect("Failed to write");
-        }
-        _ => println!("Error"),
-      },
-      false => match channel {
-        0 => {
-          let mut file: File = std::fs::OpenOptions::new()
-            .write(true)
-            .truncate(true)
-            .open("/sys/class/leds/beaglebone:green:usr0/brightness")
-            .unwrap();
-          file.write_all(b"0").expect("Failed to write");
-        }
-        1 => {
-          let mut file: File = std::fs::OpenOptions::new()
-            .write(true)
-            .truncate(true)
-            .open("/sys/class/leds/beaglebone:green:usr1/brightness")
-            .unwrap();
-          file.write_all(b"0").expect("Failed to write");
-        }
-        2 => {
-          let mut file: File = std::fs::OpenOptions::new()
-            .write(true)
-            .truncate(true)
-            .open("/sys/class/leds/beaglebone:green:usr2/brightness")
-            .unwrap();
-          file.write_all(b"0").expect("Failed to write");
-        }
-        3 => {
-          let mut file: File = std::fs::OpenOptions::new()
-            .write(true)
-            .truncate(true)
-            .open("/sys/class/leds/beaglebone:green:usr3/brightness")
-            .unwrap();
-          file.write_all(b"0").expect("Failed to write");
-        }
-        _ => println!("Error"),
-      },
-    },
+    // the ADCs belong to the state thread, so it has to run the test
+    SamControlMessage::SelfTest => {
+      if self_test.send(()).is_err() {
+        fail!("State thread is no longer listening for self-tests");
+      }
+    }
 
-    SamControlMessage::ActuateValve { channel, powered } => match powered {
-      true => match channel {
-        1 => {
-          let pin = gpio_controllers[0].get_pin(8);
-          pin.mode(Output);
-          pin.digital_write(High);
-        }
-        2 => {
-          let pin = gpio_controllers[2].get_pin(16);
-          pin.mode(Output);
-          pin.digital_write(High);
-        }
-        3 => {
-          let pin = gpio_controllers[2].get_pin(17);
-          pin.mode(Output);
-          pin.digital_write(High);
-        }
-        4 => {
-          let pin = gpio_controllers[2].get_pin(25);
-          pin.mode(Output);
-          pin.digital_write(High);
-        }
-        5 => {
-          let pin = gpio_controllers[2].get_pin(1);
-          pin.mode(Output);
-          pin.digital_write(High);
-        }
-        6 => {
-          let pin = gpio_controllers[1].get_pin(14);
-          pin.mode(Output);
-          pin.digital_write(High);
-        }
-        _ => fail!("Invalid channel number, could not open valve"),
-      },
-      false => match channel {
-        1 => {
-          let pin = gpio_controllers[0].get_pin(8);
-          pin.mode(Output);
-          pin.digital_write(Low);
-        }
-        2 => {
-          let pin = gpio_controllers[2].get_pin(16);
-          pin.mode(Output);
-          pin.digital_write(Low);
-        }
-        3 => {
-          let pin = gpio_controllers[2].get_pin(17);
-          pin.mode(Output);
-          pin.digital_write(Low);
-        }
-        4 => {
-          let pin = gpio_controllers[2].get_pin(25);
-          pin.mode(Output);
-          pin.digital_write(Low);
-        }
-        5 => {
-          let pin = gpio_controllers[2].get_pin(1);
-          pin.mode(Output);
-          pin.digital_write(Low);
-        }
-        6 => {
-          let pin = gpio_controllers[1].get_pin(14);
-          pin.mode(Output);
-          pin.digital_write(Low);
+    SamControlMessage::SetLed { channel, on } => {
+      let Some(path) = board.leds.get(channel as usize) else {
+        fail!("Invalid LED channel {channel}");
+        return;
+      };
+
+      let result = std::fs::OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(if on { b"1" } else { b"0" }));
+
+      if let Err(error) = result {
+        fail!("Failed to set LED {channel}: {error}");
+      }
+    }
+
+    SamControlMessage::ActuateValve { channel, powered } => {
+      let Some(valve) = board.valve(channel) else {
+        match powered {
+          true => fail!("Invalid channel number, could not open valve"),
+          false => fail!("Invalid channel number, could not close valve"),
         }
-        _ => fail!("Invalid channel number, could not close valve"),
-      },
-    },
+
+        return;
+      };
+
+      let pin = board_pin(&gpio_controllers, valve);
+      pin.mode(Output);
+      pin.digital_write(if powered { High } else { Low });
+    }
   }
 }
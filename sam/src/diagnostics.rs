@@ -0,0 +1,87 @@
+use crate::{
+  adc::{Measurement, ADC},
+  gpio::{board_pin, Gpio, PinValue::High},
+};
+use common::comm::{
+  board::BoardDefinition,
+  diagnostics::{
+    AdcDiagnostics,
+    OutputDiagnostics,
+    RailDiagnostics,
+    SelfTestReport,
+  },
+  sam::{ChannelType, DataPoint},
+};
+use std::{
+  sync::Arc,
+  time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+// register addresses on the ADS114S06
+const ID_REGISTER: u8 = 0x00;
+const STATUS_REGISTER: u8 = 0x01;
+
+// conversions run at 4000 SPS, so DRDY should never take this long
+const DRDY_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// Reads the ID and status registers, DRDY health and SPI error count of every
+/// ADC, reports the latest rail measurements, and reads back every valve
+/// driver without changing its state.
+pub fn self_test(
+  adcs: &mut [ADC],
+  curr_measurement: &mut Option<Measurement>,
+  latest: &[DataPoint],
+  gpio_controllers: &[Arc<Gpio>],
+  board: &BoardDefinition,
+) -> SelfTestReport {
+  let mut report = SelfTestReport {
+    timestamp: SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|duration| duration.as_secs_f64())
+      .unwrap_or(0.0),
+    adcs: Vec::with_capacity(adcs.len()),
+    rails: Vec::new(),
+    outputs: Vec::with_capacity(board.valves.len()),
+  };
+
+  for adc in adcs.iter_mut() {
+    adc.init_gpio(*curr_measurement);
+    *curr_measurement = Some(adc.measurement);
+
+    let data_ready = adc.wait_for_data_ready(DRDY_TIMEOUT);
+    let id = adc.read_reg(ID_REGISTER);
+    let status = adc.read_reg(STATUS_REGISTER);
+
+    report.adcs.push(AdcDiagnostics {
+      name: format!("{:?}", adc.measurement),
+      // an ID of all zeros or all ones means nothing answered on the bus
+      id: id.filter(|id| *id != 0x00 && *id != 0xFF),
+      status,
+      data_ready,
+      spi_errors: adc.spi_errors,
+    });
+  }
+
+  for data_point in latest {
+    if data_point.channel_type == ChannelType::RailVoltage {
+      report.rails.push(RailDiagnostics {
+        name: format!("rail_{}", data_point.channel),
+        voltage: data_point.value,
+        nominal: None,
+        in_range: data_point.value.is_finite(),
+      });
+    }
+  }
+
+  for (channel, valve) in board.valves.iter().enumerate() {
+    let pin = board_pin(gpio_controllers, *valve);
+
+    report.outputs.push(OutputDiagnostics {
+      name: format!("valve_{}", channel + 1),
+      commanded: pin.output_value() == High,
+      measured: pin.digital_read() == High,
+    });
+  }
+
+  report
+}
@@ -14,6 +14,7 @@
 // Table 12 and 13 were used to determine the P[8/9]_pin_number on expansion
 // header -> gpio controller value in chip
 
+use common::comm::board::GpioPin;
 use libc::{c_int, c_void, off_t, size_t};
 use std::{
   ffi::CString,
@@ -124,6 +125,11 @@ impl Gpio {
   }
 }
 
+/// Gets a pin as described by the board definition.
+pub fn board_pin(controllers: &[Arc<Gpio>], pin: GpioPin) -> Pin {
+  controllers[pin.controller].get_pin(pin.pin)
+}
+
 impl Pin {
   pub fn mode(&self, mode: PinMode) {
     let oe = self.gpio.oe.lock().unwrap();
@@ -159,4 +165,17 @@ impl Pin {
       PinValue::Low
     }
   }
+
+  /// Reads back the level the pin is currently being driven to, as opposed to
+  /// the level measured on the pin by `digital_read`.
+  pub fn output_value(&self) -> PinValue {
+    let dataout = self.gpio.dataout.lock().unwrap();
+    let bits = unsafe { std::ptr::read_volatile(*dataout) };
+
+    if bits & (1 << self.index) != 0 {
+      PinValue::High
+    } else {
+      PinValue::Low
+    }
+  }
 }
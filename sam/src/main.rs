@@ -1,6 +1,8 @@
 pub mod adc;
+pub mod board;
 pub mod command;
 pub mod data;
+pub mod diagnostics;
 pub mod discovery;
 pub mod gpio;
 pub mod state;
@@ -8,19 +10,42 @@ pub mod tc;
 
 use adc::open_controllers;
 use command::begin;
+use common::comm::board::BoardDefinition;
 use gpio::Gpio;
-use std::{sync::Arc, thread};
+use jeflog::{fail, pass};
+use std::{
+  process,
+  sync::{
+    mpsc::{self, Receiver},
+    Arc,
+  },
+  thread,
+};
 fn main() {
+  // nothing may be driven until we know which revision we are running on, so
+  // an unknown board exits before touching any GPIO
+  let board = match board::load() {
+    Ok(board) => Arc::new(board),
+    Err(error) => {
+      fail!("Could not identify this board: {error}. Exiting safely.");
+      process::exit(1);
+    }
+  };
+
+  pass!("Loaded board definition for {}.", board.revision);
+
   let controllers = open_controllers();
   let controllers1 = controllers.clone();
   let controllers2 = controllers.clone();
+  let board1 = board.clone();
+  let (self_test_tx, self_test_rx) = mpsc::channel();
 
   let state_thread = thread::spawn(move || {
-    init_state(controllers1);
+    init_state(controllers1, board1, self_test_rx);
   });
 
   let command_thread = thread::spawn(move || {
-    begin(controllers2.clone());
+    begin(controllers2.clone(), board, self_test_tx);
   });
 
   state_thread.join().expect("Could not join state thread");
@@ -29,9 +54,13 @@ fn main() {
     .expect("Could not join command thread");
 }
 
-fn init_state(controllers: Vec<Arc<Gpio>>) {
+fn init_state(
+  controllers: Vec<Arc<Gpio>>,
+  board: Arc<BoardDefinition>,
+  self_test: Receiver<()>,
+) {
   let mut sam_state = state::State::Init;
-  let mut data = state::Data::new(controllers);
+  let mut data = state::Data::new(controllers, board, self_test);
   loop {
     sam_state = sam_state.next(&mut data);
   }
@@ -8,25 +8,38 @@ use crate::{
     ADC,
   },
   data::{generate_data_point, serialize_data},
-  gpio::Gpio,
+  diagnostics,
+  gpio::{board_pin, Gpio},
+};
+use common::comm::{
+  board::BoardDefinition,
+  discovery,
+  flight::{BoardKind, DataMessage},
+  sam::DataPoint,
 };
-use common::comm::{flight::DataMessage, sam::DataPoint};
 use hostname;
 use jeflog::{fail, pass, task, warn};
 use spidev::{SpiModeFlags, Spidev, SpidevOptions};
-use std::net::ToSocketAddrs;
 use std::rc::Rc;
 use std::{
+  borrow::Cow,
   net::{SocketAddr, UdpSocket},
-  sync::Arc,
-  thread,
-  time::Instant,
+  sync::{mpsc::Receiver, Arc},
+  thread::{self, JoinHandle},
+  time::{Duration, Instant},
 };
 
-const FC_ADDR: &str = "server-01";
+// where the flight computer listens for data
+const FC_DATA_PORT: u16 = 4573;
+
+// how long to listen for an announcement before trying again
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(1);
 
 const FC_HEARTBEAT_TIMEOUT: u128 = 500;
 
+// how long to give the flight computer to respond to each identity
+const IDENTITY_WAIT: Duration = Duration::from_millis(50);
+
 pub struct Data {
   pub data_socket: UdpSocket,
   flight_computer: Option<SocketAddr>,
@@ -36,10 +49,19 @@ pub struct Data {
   data_points: Vec<DataPoint>,
   board_id: Option<String>,
   gpio_controllers: Vec<Arc<Gpio>>,
+  board: Arc<BoardDefinition>,
+  self_test: Receiver<()>,
+  /// The thread monitoring heartbeats from the flight computer, which exits
+  /// once they stop.
+  heartbeat: Option<JoinHandle<()>>,
 }
 
 impl Data {
-  pub fn new(gpio_controllers: Vec<Arc<Gpio>>) -> Data {
+  pub fn new(
+    gpio_controllers: Vec<Arc<Gpio>>,
+    board: Arc<BoardDefinition>,
+    self_test: Receiver<()>,
+  ) -> Data {
     Data {
       data_socket: UdpSocket::bind(("0.0.0.0", 4573))
         .expect("Could not bind client socket"),
@@ -50,6 +72,9 @@ impl Data {
       data_points: Vec::with_capacity(60),
       board_id: None,
       gpio_controllers,
+      board,
+      self_test,
+      heartbeat: None,
     }
   }
 }
@@ -85,79 +110,32 @@ impl State {
         spidev.configure(&options).unwrap();
 
         let ref_spidev: Rc<_> = Rc::new(spidev);
-        let ref_controllers =
-          Rc::new(gpio_controller_mappings(&data.gpio_controllers));
-        let ref_drdy = Rc::new(data_ready_mappings(&data.gpio_controllers));
-
-        // Instantiate all measurement types
-        let ds = ADC::new(
-          adc::Measurement::DiffSensors,
-          ref_spidev.clone(),
-          ref_controllers.clone(),
-          ref_drdy.clone(),
-        );
-        let cl = ADC::new(
-          adc::Measurement::CurrentLoopPt,
-          ref_spidev.clone(),
-          ref_controllers.clone(),
-          ref_drdy.clone(),
-        );
-        let board_power = ADC::new(
-          adc::Measurement::VPower,
-          ref_spidev.clone(),
-          ref_controllers.clone(),
-          ref_drdy.clone(),
-        );
-        let board_current = ADC::new(
-          adc::Measurement::IPower,
-          ref_spidev.clone(),
-          ref_controllers.clone(),
-          ref_drdy.clone(),
-        );
-        let vvalve = ADC::new(
-          adc::Measurement::VValve,
-          ref_spidev.clone(),
-          ref_controllers.clone(),
-          ref_drdy.clone(),
-        );
-        let ivalve = ADC::new(
-          adc::Measurement::IValve,
-          ref_spidev.clone(),
-          ref_controllers.clone(),
-          ref_drdy.clone(),
-        );
-        let rtd = ADC::new(
-          adc::Measurement::Rtd,
-          ref_spidev.clone(),
-          ref_controllers.clone(),
-          ref_drdy.clone(),
-        );
-        let tc1 = ADC::new(
-          adc::Measurement::Tc1,
-          ref_spidev.clone(),
-          ref_controllers.clone(),
-          ref_drdy.clone(),
-        );
-        let tc2 = ADC::new(
-          adc::Measurement::Tc2,
-          ref_spidev.clone(),
-          ref_controllers.clone(),
-          ref_drdy.clone(),
-        );
-
-        pull_gpios_high(&data.gpio_controllers);
-
-        data.adcs = Some(vec![
-          ds,
-          cl,
-          board_power,
-          board_current,
-          vvalve,
-          ivalve,
-          //rtd,
-          tc1,
-          tc2,
-        ]);
+        let ref_controllers = Rc::new(gpio_controller_mappings(
+          &data.gpio_controllers,
+          &data.board,
+        ));
+        let ref_drdy =
+          Rc::new(data_ready_mappings(&data.gpio_controllers, &data.board));
+
+        // Instantiate every ADC wired on this revision of the board
+        let adcs = data
+          .board
+          .adcs
+          .iter()
+          .filter_map(|wiring| {
+            Some(ADC::new(
+              adc::Measurement::from_kind(wiring.kind)?,
+              wiring.channels as u64,
+              ref_spidev.clone(),
+              ref_controllers.clone(),
+              ref_drdy.clone(),
+            ))
+          })
+          .collect();
+
+        pull_gpios_high(&data.gpio_controllers, &data.board);
+
+        data.adcs = Some(adcs);
 
         data
           .data_socket
@@ -170,21 +148,25 @@ impl State {
       State::DeviceDiscovery => {
         task!("Locating the flight computer.");
 
-        let address = format!("{}.local:4573", FC_ADDR)
-          .to_socket_addrs()
-          .ok()
-          .and_then(|mut addrs| addrs.find(|addr| addr.is_ipv4()));
+        let address = discovery::locate_flight_computer(
+          FC_DATA_PORT,
+          DISCOVERY_TIMEOUT,
+        );
 
-        let Some(address) = address else {
-          fail!("Target \x1b[1m{}\x1b[0m could not be located.", FC_ADDR);
-          return State::DeviceDiscovery;
+        let address = match address {
+          Ok(Some(address)) => address,
+          Ok(None) => {
+            fail!("Flight computer could not be located.");
+            return State::DeviceDiscovery;
+          }
+          Err(error) => {
+            fail!("Failed to locate flight computer: {error}");
+            thread::sleep(DISCOVERY_TIMEOUT);
+            return State::DeviceDiscovery;
+          }
         };
 
-        pass!(
-          "Target \x1b[1m{}\x1b[0m located at \x1b[1m{}\x1b[0m.",
-          FC_ADDR,
-          address.ip()
-        );
+        pass!("Flight computer located at \x1b[1m{}\x1b[0m.", address);
         data.flight_computer = Some(address);
 
         State::InitAdcs
@@ -194,7 +176,7 @@ impl State {
         let mut buf = [0; 65536];
 
         if let Some(board_id) = data.board_id.clone() {
-          let identity = DataMessage::Identity(board_id);
+          let identity = DataMessage::Identity(board_id, BoardKind::Sam);
           let data_serialized = postcard::to_allocvec(&identity);
 
           if let Some(socket_addr) = data.flight_computer {
@@ -209,6 +191,8 @@ impl State {
           fail!("Could not send Identity message, invalid board information.");
         }
 
+        thread::sleep(IDENTITY_WAIT);
+
         if let Ok((num_bytes, _)) = data.data_socket.recv_from(&mut buf) {
           let deserialized_result =
             postcard::from_bytes::<DataMessage>(&buf[..num_bytes]);
@@ -217,16 +201,21 @@ impl State {
             Ok(message) => {
               match message {
                 // FC sends identity back
-                DataMessage::Identity(_) => {
+                DataMessage::Identity(..) => {
                   pass!("Received Identity message from the flight computer, monitoring heartbeat");
 
                   let socket_copy = data.data_socket.try_clone();
                   let controllers = data.gpio_controllers.clone();
+                  let board = data.board.clone();
 
                   // Spawn heartbeat thread
-                  thread::spawn(move || {
-                    monitor_heartbeat(socket_copy.ok().unwrap(), &controllers);
-                  });
+                  data.heartbeat = Some(thread::spawn(move || {
+                    monitor_heartbeat(
+                      socket_copy.ok().unwrap(),
+                      &controllers,
+                      &board,
+                    );
+                  }));
 
                   return State::PollAdcs;
                 }
@@ -263,19 +252,24 @@ impl State {
       }
 
       State::PollAdcs => {
+        // once heartbeats stop, the valves have been made safe and the flight
+        // computer ignores the board until it identifies itself again
+        if data.heartbeat.as_ref().is_some_and(JoinHandle::is_finished) {
+          data.heartbeat = None;
+          warn!("Lost the flight computer, identifying to it again.");
+
+          return State::Identity;
+        }
+
         data.data_points.clear();
 
-        for i in 0..6 {
-          for adc in data.adcs.as_mut().unwrap() {
-            if (i > 2 && adc.measurement == adc::Measurement::DiffSensors)
-              || (i > 4 && adc.measurement == adc::Measurement::VPower)
-              || (i > 1
-                && (adc.measurement == adc::Measurement::IPower
-                  || adc.measurement == adc::Measurement::Rtd))
-              || (i > 3
-                && (adc.measurement == adc::Measurement::Tc1
-                  || adc.measurement == adc::Measurement::Tc2))
-            {
+        let adcs = data.adcs.as_mut().unwrap();
+        let max_channels = adcs.iter().map(|adc| adc.channels).max();
+
+        for i in 0..max_channels.unwrap_or(0) {
+          for adc in adcs.iter_mut() {
+            // skip channels which aren't wired on this ADC
+            if i >= adc.channels {
               continue;
             }
 
@@ -317,13 +311,60 @@ impl State {
               .expect("couldn't send data to flight computer");
           }
         }
+
+        if data.self_test.try_recv().is_ok() {
+          run_self_test(data);
+        }
+
         State::PollAdcs
       }
     }
   }
 }
 
-fn monitor_heartbeat(socket: UdpSocket, gpio_controllers: &[Arc<Gpio>]) {
+fn run_self_test(data: &mut Data) {
+  task!("Running self-test.");
+
+  let report = diagnostics::self_test(
+    data.adcs.as_mut().unwrap(),
+    &mut data.curr_measurement,
+    &data.data_points,
+    &data.gpio_controllers,
+    &data.board,
+  );
+
+  if report.passed() {
+    pass!("Self-test passed.");
+  } else {
+    warn!("Self-test found faults: {report:#?}");
+  }
+
+  let (Some(board_id), Some(socket_addr)) =
+    (data.board_id.clone(), data.flight_computer)
+  else {
+    fail!("Could not send self-test report, flight computer unknown.");
+    return;
+  };
+
+  let message = DataMessage::Diagnostics(board_id, Cow::Owned(report));
+
+  match postcard::to_allocvec(&message) {
+    Ok(serialized) => {
+      if let Err(error) = data.data_socket.send_to(&serialized, socket_addr) {
+        fail!("Could not send self-test report: {error}");
+      }
+    }
+    Err(error) => {
+      fail!("Could not serialize self-test report: {error}");
+    }
+  }
+}
+
+fn monitor_heartbeat(
+  socket: UdpSocket,
+  gpio_controllers: &[Arc<Gpio>],
+  board: &BoardDefinition,
+) {
   let mut buf = [0; 65536];
   let mut last_heartbeat = Instant::now();
 
@@ -350,23 +391,14 @@ fn monitor_heartbeat(socket: UdpSocket, gpio_controllers: &[Arc<Gpio>]) {
     }
   }
 
-  abort(gpio_controllers);
+  abort(gpio_controllers, board);
 }
 
-fn abort(controllers: &[Arc<Gpio>]) {
+fn abort(controllers: &[Arc<Gpio>], board: &BoardDefinition) {
   fail!("Aborting the SAM Board.");
-  warn!("You must manually restart SAM software.");
-
-  let pins = [
-    controllers[0].get_pin(8),  // valve 1
-    controllers[2].get_pin(16), // valve 2
-    controllers[2].get_pin(17), // valve 3
-    controllers[2].get_pin(25), // valve 4
-    controllers[2].get_pin(1),  // valve 5
-    controllers[1].get_pin(14), // valve 6
-  ];
-
-  for pin in pins.iter() {
+
+  for valve in &board.valves {
+    let pin = board_pin(controllers, *valve);
     pin.mode(Output);
     pin.digital_write(Low);
   }
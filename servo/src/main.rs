@@ -115,6 +115,33 @@ fn main() -> anyhow::Result<()> {
             .long("quiet")
             .short('q')
             .action(ArgAction::SetTrue),
+        )
+        .arg(
+          Arg::new("port")
+            .long("port")
+            .default_value("7200")
+            .value_parser(clap::value_parser!(u16)),
+        )
+        .arg(
+          Arg::new("telemetry_port")
+            .long("telemetry-port")
+            .default_value("7201")
+            .value_parser(clap::value_parser!(u16)),
+        )
+        .arg(
+          Arg::new("standby")
+            .long("standby")
+            .value_name("PRIMARY")
+            .help("Mirrors the servo at PRIMARY, such as server-01.local:7200, until promoted.")
+            .required(false),
+        )
+        .arg(
+          Arg::new("peer")
+            .long("peer")
+            .value_name("PEER")
+            .help("Starts as the standby of the servo at PEER, such as server-02.local:7200, if it was promoted in place of this one.")
+            .conflicts_with("standby")
+            .required(false),
         ),
     )
     .subcommand(
@@ -0,0 +1,3 @@
+DROP INDEX sequence_events_by_name;
+DROP TRIGGER sequence_event_retention;
+DROP TABLE SequenceEvents;
@@ -0,0 +1,18 @@
+CREATE TABLE SequenceEvents (
+	name TEXT NOT NULL,
+	kind TEXT NOT NULL,
+	detail TEXT,
+	traceback TEXT,
+	timestamp REAL NOT NULL,
+	recorded_at REAL NOT NULL DEFAULT (unixepoch('now', 'subsec'))
+);
+
+-- keeps only the latest 100000 sequence events, as a chatty sequence would
+-- otherwise grow the table without bound
+CREATE TRIGGER sequence_event_retention
+AFTER INSERT ON SequenceEvents
+BEGIN
+	DELETE FROM SequenceEvents WHERE rowid <= new.rowid - 100000;
+END;
+
+CREATE INDEX sequence_events_by_name ON SequenceEvents (name, timestamp);
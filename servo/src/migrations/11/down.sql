@@ -0,0 +1,6 @@
+ALTER TABLE Triggers DROP reset;
+ALTER TABLE Triggers DROP hold_ms;
+ALTER TABLE Triggers DROP cooldown_ms;
+ALTER TABLE Triggers DROP fire_if_holding;
+ALTER TABLE Triggers DROP poll_ms;
+DROP TABLE TriggerEvents;
@@ -0,0 +1,12 @@
+ALTER TABLE Triggers ADD reset TEXT;
+ALTER TABLE Triggers ADD hold_ms INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE Triggers ADD cooldown_ms INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE Triggers ADD fire_if_holding BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE Triggers ADD poll_ms INTEGER;
+CREATE TABLE TriggerEvents (
+	name TEXT NOT NULL,
+	kind TEXT NOT NULL,
+	detail TEXT,
+	timestamp REAL NOT NULL,
+	recorded_at REAL NOT NULL DEFAULT (unixepoch('now', 'subsec'))
+);
@@ -0,0 +1,2 @@
+DROP TABLE CommsPolicies;
+DROP TABLE BoardCommsEvents;
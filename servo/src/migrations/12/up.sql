@@ -0,0 +1,14 @@
+CREATE TABLE CommsPolicies (
+	board_id TEXT NOT NULL PRIMARY KEY,
+	timeout_ms INTEGER NOT NULL,
+	grace_count INTEGER NOT NULL,
+	criticality TEXT NOT NULL,
+	reconnect TEXT NOT NULL
+);
+CREATE TABLE BoardCommsEvents (
+	board_id TEXT NOT NULL,
+	kind TEXT NOT NULL,
+	criticality TEXT,
+	timestamp REAL NOT NULL,
+	recorded_at REAL NOT NULL DEFAULT (unixepoch('now', 'subsec'))
+);
@@ -0,0 +1,2 @@
+DROP TABLE ServerLossPolicy;
+DROP TABLE ServerLosses;
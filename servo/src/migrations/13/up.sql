@@ -0,0 +1,13 @@
+CREATE TABLE ServerLossPolicy (
+	id INTEGER NOT NULL PRIMARY KEY CHECK(id = 0),
+	action TEXT NOT NULL,
+	timeout_ms INTEGER NOT NULL,
+	safing_sequence TEXT
+);
+CREATE TABLE ServerLosses (
+	action TEXT NOT NULL,
+	acted BOOLEAN NOT NULL,
+	lost_at REAL NOT NULL,
+	regained_at REAL NOT NULL,
+	recorded_at REAL NOT NULL DEFAULT (unixepoch('now', 'subsec'))
+);
@@ -0,0 +1,6 @@
+CREATE TABLE BoardDiagnostics (
+	board_id TEXT NOT NULL,
+	report TEXT NOT NULL,
+	passed BOOLEAN NOT NULL,
+	recorded_at REAL NOT NULL DEFAULT (unixepoch('now', 'subsec'))
+);
@@ -0,0 +1,9 @@
+CREATE TABLE EStopEvents (
+	board_id TEXT NOT NULL,
+	kind TEXT NOT NULL,
+	path TEXT NOT NULL,
+	e_stop_voltage REAL NOT NULL,
+	rbf_tag_voltage REAL NOT NULL,
+	timestamp REAL NOT NULL,
+	recorded_at REAL NOT NULL DEFAULT (unixepoch('now', 'subsec'))
+);
@@ -0,0 +1,9 @@
+CREATE TABLE ModeChanges (
+	from_mode TEXT NOT NULL,
+	to_mode TEXT NOT NULL,
+	cause TEXT NOT NULL,
+	rejection TEXT,
+	requested_by TEXT,
+	timestamp REAL NOT NULL,
+	recorded_at REAL NOT NULL DEFAULT (unixepoch('now', 'subsec'))
+);
@@ -5,7 +5,7 @@ use rusqlite::Connection as SqlConnection;
 use std::{cmp::Ordering, future::Future, path::Path, sync::Arc};
 use tokio::sync::Mutex;
 
-use super::Shared;
+use super::{standby, Shared};
 
 // include_dir is a separate library which evidently accesses files relative to
 // the project root, while include_str is a standard library macro which
@@ -115,11 +115,16 @@ impl Database {
 
   /// Continuously logs the vehicle state each time a new one arrives into the
   /// database.
+  ///
+  /// A standby logs nothing until promoted, as it mirrors the snapshots of the
+  /// primary instead.
   pub fn log_vehicle_state(&self, shared: &Shared) -> impl Future<Output = ()> {
+    let shared = shared.clone();
     let vehicle_state = shared.vehicle.clone();
     let connection = self.connection.clone();
 
     async move {
+      standby::wait_for_primary(&shared).await;
       let mut buffer = [0_u8; 10_000];
 
       loop {
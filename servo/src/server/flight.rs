@@ -1,22 +1,47 @@
-use super::{Database, Shared};
+use super::{standby, Database, Shared};
 
-use jeflog::warn;
+use jeflog::{fail, pass, task, warn};
 use postcard::experimental::max_size::MaxSize;
-use std::{future::Future, net::IpAddr};
+use rusqlite::{Connection as SqlConnection, OptionalExtension};
+use std::{
+  future::Future,
+  net::{IpAddr, SocketAddr},
+  sync::Arc,
+};
 use tokio::time::Instant;
 
 use common::comm::{
+  flight::BoardId,
+  mode::VehicleMode,
+  BoardCommsEventKind,
+  CommsPolicy,
   Computer,
+  Configuration,
+  ConfigurationSource,
+  ConfigurationStatus,
   FlightControlMessage,
+  FlightStatusMessage,
   NodeMapping,
   Sequence,
+  SequenceEventKind,
+  SequenceOutcome,
+  ServerLossPolicy,
   Trigger,
+  TriggerEventKind,
   VehicleState,
+  SERVER_HEARTBEAT_PERIOD,
 };
 
 use tokio::{
   io::{self, AsyncReadExt, AsyncWriteExt},
-  net::{TcpListener, TcpStream, UdpSocket},
+  net::{
+    tcp::{OwnedReadHalf, OwnedWriteHalf},
+    TcpListener,
+    TcpStream,
+    UdpSocket,
+  },
+  sync::Mutex,
+  task::JoinHandle,
 };
 
 /// Struct capable of performing thread-safe operations on a flight computer
@@ -24,10 +49,49 @@ use tokio::{
 #[derive(Debug)]
 pub struct FlightComputer {
   database: Database,
-  stream: TcpStream,
+  stream: OwnedWriteHalf,
+  receiver: JoinHandle<()>,
+  configuration: Arc<Mutex<FlightConfiguration>>,
+}
+
+/// What is known of the configuration held by the flight computer.
+#[derive(Debug, Default)]
+struct FlightConfiguration {
+  /// The status last reported by the flight computer.
+  status: Option<ConfigurationStatus>,
+
+  /// The digest of the configuration last sent to the flight computer, until
+  /// it reports holding it.
+  pushed: Option<u64>,
+}
+
+impl Drop for FlightComputer {
+  fn drop(&mut self) {
+    self.receiver.abort();
+  }
 }
 
 impl FlightComputer {
+  /// Wraps a newly-accepted flight connection, spawning a task which receives
+  /// status messages sent back by the flight computer.
+  pub fn new(stream: TcpStream, shared: &Shared) -> Self {
+    let (reader, writer) = stream.into_split();
+    let configuration = Arc::new(Mutex::new(FlightConfiguration::default()));
+
+    let receiver = tokio::spawn(receive_status(
+      reader,
+      shared.clone(),
+      configuration.clone(),
+    ));
+
+    FlightComputer {
+      database: shared.database.clone(),
+      stream: writer,
+      receiver,
+      configuration,
+    }
+  }
+
   /// Send a slice of bytes along the TCP connection to the flight computer.
   pub async fn send_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
     self.stream.write_all(bytes).await
@@ -45,42 +109,7 @@ impl FlightComputer {
 
   /// Sends the given set of mappings to the flight computer.
   pub async fn send_mappings(&mut self) -> anyhow::Result<()> {
-    let mappings = self
-      .database
-      .connection
-      .lock()
-      .await
-      .prepare(
-        "
-				SELECT
-					text_id,
-					board_id,
-					sensor_type,
-					channel,
-					computer,
-					max,
-					min,
-					calibrated_offset,
-					powered_threshold,
-					normally_closed
-				FROM NodeMappings WHERE active = TRUE
-			",
-      )?
-      .query_and_then([], |row| {
-        Ok(NodeMapping {
-          text_id: row.get(0)?,
-          board_id: row.get(1)?,
-          sensor_type: row.get(2)?,
-          channel: row.get(3)?,
-          computer: row.get(4)?,
-          max: row.get(5)?,
-          min: row.get(6)?,
-          calibrated_offset: row.get(7)?,
-          powered_threshold: row.get(8)?,
-          normally_closed: row.get(9)?,
-        })
-      })?
-      .collect::<Result<Vec<NodeMapping>, rusqlite::Error>>()?;
+    let mappings = load_mappings(&*self.database.connection.lock().await)?;
 
     let message = FlightControlMessage::Mappings(mappings);
     let serialized = postcard::to_allocvec(&message)?;
@@ -130,39 +159,256 @@ impl FlightComputer {
     Ok(())
   }
 
+  /// Sends the comms policy of a board to the flight computer, replacing any
+  /// the board had before.
+  pub async fn send_comms_policy(
+    &mut self,
+    policy: CommsPolicy,
+  ) -> anyhow::Result<()> {
+    let message = FlightControlMessage::CommsPolicy(policy);
+    let serialized = postcard::to_allocvec(&message)?;
+
+    self.send_bytes(&serialized).await?;
+    Ok(())
+  }
+
+  /// Tells the flight computer to stream the vehicle state to this servo, on
+  /// its telemetry port, and to the standby if there is one.
+  pub async fn send_telemetry_targets(
+    &mut self,
+    telemetry_port: u16,
+    standby: Option<SocketAddr>,
+  ) -> anyhow::Result<()> {
+    // the address the flight computer reached this servo at
+    let local = self.stream.local_addr()?.ip();
+
+    let mut targets = vec![SocketAddr::new(local, telemetry_port)];
+    targets.extend(standby);
+
+    let message = FlightControlMessage::TelemetryTargets(targets);
+    let serialized = postcard::to_allocvec(&message)?;
+
+    self.send_bytes(&serialized).await?;
+    Ok(())
+  }
+
+  /// Instructs the flight computer to run a self-test on the given board.
+  pub async fn self_test(&mut self, board_id: BoardId) -> anyhow::Result<()> {
+    let message = FlightControlMessage::SelfTest(board_id);
+    let serialized = postcard::to_allocvec(&message)?;
+
+    self.send_bytes(&serialized).await?;
+    Ok(())
+  }
+
+  /// Requests that the flight computer change to the given mode on behalf of
+  /// `requested_by`. Whether the change was made is reported back separately
+  /// and recorded.
+  pub async fn set_mode(
+    &mut self,
+    mode: VehicleMode,
+    requested_by: String,
+  ) -> anyhow::Result<()> {
+    let message = FlightControlMessage::SetMode { mode, requested_by };
+    let serialized = postcard::to_allocvec(&message)?;
+
+    self.send_bytes(&serialized).await?;
+    Ok(())
+  }
+
+  /// Gets the configuration status last reported by the flight computer, or
+  /// `None` if it has not yet reported one.
+  pub async fn configuration(&self) -> Option<ConfigurationStatus> {
+    self.configuration.lock().await.status
+  }
+
   /// Checks if the underlying TCP stream has been closed.
   pub fn check_closed(&self) -> bool {
-    let mut buffer = [0; 1];
-
-    // if the flight stream reads zero bytes, it's closed.
-    // this indicates that the current flight computer should not be there.
-    self
-      .stream
-      .try_read(&mut buffer)
-      .is_ok_and(|size| size == 0)
+    // the receiver only exits once the flight stream reads zero bytes or
+    // errors, which indicates that this flight computer should not be there.
+    self.receiver.is_finished()
   }
 
-  /// Sends a comprehensive update of mappings, triggers, and abort sequence to
-  /// flight.
+  /// Sends a comprehensive update of mappings, triggers, abort sequence, comms
+  /// policies and server loss policy to flight, replacing whatever it held
+  /// before. Flight acknowledges with the digest of its new configuration,
+  /// which is checked against the database.
   pub async fn update(&mut self) -> anyhow::Result<()> {
-    self.send_mappings().await?;
+    let configuration = load_configuration(&self.database).await?;
 
-    // TODO: send triggers and abort sequence automatically
+    // noted before sending so that the acknowledgement can't arrive first
+    self.configuration.lock().await.pushed = Some(configuration.digest());
+
+    let message = FlightControlMessage::Configuration(configuration);
+    let serialized = postcard::to_allocvec(&message)?;
 
+    self.send_bytes(&serialized).await?;
     Ok(())
   }
 }
 
+/// Loads the active mappings from the database.
+fn load_mappings(
+  connection: &SqlConnection,
+) -> rusqlite::Result<Vec<NodeMapping>> {
+  connection
+    .prepare(
+      "
+			SELECT
+				text_id,
+				board_id,
+				sensor_type,
+				channel,
+				computer,
+				max,
+				min,
+				calibrated_offset,
+				powered_threshold,
+				normally_closed
+			FROM NodeMappings WHERE active = TRUE
+		",
+    )?
+    .query_and_then([], |row| {
+      Ok(NodeMapping {
+        text_id: row.get(0)?,
+        board_id: row.get(1)?,
+        sensor_type: row.get(2)?,
+        channel: row.get(3)?,
+        computer: row.get(4)?,
+        max: row.get(5)?,
+        min: row.get(6)?,
+        calibrated_offset: row.get(7)?,
+        powered_threshold: row.get(8)?,
+        normally_closed: row.get(9)?,
+      })
+    })?
+    .collect()
+}
+
+/// Loads the configuration which the flight computer should hold, being the
+/// active mappings, the active triggers, the abort sequence, the comms
+/// policies and the server loss policy with its safing sequence.
+pub async fn load_configuration(
+  database: &Database,
+) -> anyhow::Result<Configuration> {
+  let connection = database.connection.lock().await;
+  let mappings = load_mappings(&connection)?;
+
+  let triggers = connection
+    .prepare(
+      "
+			SELECT
+				name,
+				condition,
+				script,
+				active,
+				reset,
+				hold_ms,
+				cooldown_ms,
+				fire_if_holding,
+				poll_ms
+			FROM Triggers WHERE active = TRUE
+		",
+    )?
+    .query_and_then([], |row| {
+      Ok(Trigger {
+        name: row.get(0)?,
+        condition: row.get(1)?,
+        script: row.get(2)?,
+        active: row.get(3)?,
+        reset: row.get(4)?,
+        hold_ms: row.get(5)?,
+        cooldown_ms: row.get(6)?,
+        fire_if_holding: row.get(7)?,
+        poll_ms: row.get(8)?,
+      })
+    })?
+    .collect::<Result<Vec<Trigger>, rusqlite::Error>>()?;
+
+  let abort_sequence = connection
+    .query_row(
+      "SELECT script FROM Sequences WHERE name = 'abort'",
+      [],
+      |row| {
+        Ok(Sequence {
+          name: "abort".to_owned(),
+          script: row.get(0)?,
+        })
+      },
+    )
+    .optional()?;
+
+  let comms_policies = connection
+    .prepare(
+      "
+			SELECT
+				board_id,
+				timeout_ms,
+				grace_count,
+				criticality,
+				reconnect
+			FROM CommsPolicies
+		",
+    )?
+    .query_and_then([], |row| {
+      Ok(CommsPolicy {
+        board_id: row.get(0)?,
+        timeout_ms: row.get(1)?,
+        grace_count: row.get(2)?,
+        criticality: row.get(3)?,
+        reconnect: row.get(4)?,
+      })
+    })?
+    .collect::<Result<Vec<CommsPolicy>, rusqlite::Error>>()?;
+
+  let server_loss_policy = connection
+    .query_row(
+      "
+			SELECT action, timeout_ms, safing_sequence, script
+			FROM ServerLossPolicy
+			LEFT JOIN Sequences ON Sequences.name = safing_sequence
+		",
+      [],
+      |row| {
+        let name = row.get::<_, Option<String>>(2)?;
+        let script = row.get::<_, Option<String>>(3)?;
+
+        Ok(ServerLossPolicy {
+          action: row.get(0)?,
+          timeout_ms: row.get(1)?,
+          safing_sequence: name
+            .zip(script)
+            .map(|(name, script)| Sequence { name, script }),
+        })
+      },
+    )
+    .optional()?
+    .unwrap_or_default();
+
+  Ok(Configuration {
+    mappings,
+    triggers,
+    abort_sequence,
+    comms_policies,
+    server_loss_policy,
+  })
+}
+
 /// A listener function which auto-connects to the flight computer.
 ///
 /// The flight computer is expected to fetch the IP address of the
 /// ground computer by hostname resolution, outside the scope of servo.
+///
+/// A standby only starts listening once promoted, so that the flight computer
+/// never connects to it while the primary is there.
 pub fn auto_connect(server: &Shared) -> impl Future<Output = io::Result<()>> {
-  let database = server.database.clone();
+  let shared = server.clone();
   let flight = server.flight.clone();
   let ground = server.ground.clone();
 
   async move {
+    standby::wait_for_primary(&shared).await;
+
     let listener = TcpListener::bind("0.0.0.0:5025").await?;
     let mut buffer = [0; Computer::POSTCARD_MAX_SIZE];
 
@@ -203,16 +449,22 @@ pub fn auto_connect(server: &Shared) -> impl Future<Output = io::Result<()>> {
           // one there already. otherwise, this defaults to gracefully closing
           // the new connection on drop.
           if flight.is_none() {
-            let mut new_flight = FlightComputer {
-              stream,
-              database: database.clone(),
-            };
+            let mut new_flight = FlightComputer::new(stream, &shared);
 
             if let Err(error) = new_flight.update().await {
               warn!("Failed to send update to new flight: {error}");
               continue;
             }
 
+            let standby = *shared.standby.lock().await;
+            let telemetry_port = shared.ports.telemetry;
+
+            if let Err(error) =
+              new_flight.send_telemetry_targets(telemetry_port, standby).await
+            {
+              warn!("Failed to send telemetry targets to new flight: {error}");
+            }
+
             *flight = Some(new_flight);
           }
         }
@@ -220,30 +472,28 @@ pub fn auto_connect(server: &Shared) -> impl Future<Output = io::Result<()>> {
           let mut ground = ground.0.lock().await;
 
           if let Some(existing) = &*ground {
-            let mut buffer = [0; 1];
-
-            // if the flight stream reads zero bytes, it's closed. this
-            // indicates that the current flight computer should not be there.
-            if existing
-              .stream
-              .try_read(&mut buffer)
-              .is_ok_and(|size| size == 0)
-            {
+            if existing.check_closed() {
               *ground = None;
             }
           }
 
           if ground.is_none() {
-            let mut new_ground = FlightComputer {
-              stream,
-              database: database.clone(),
-            };
+            let mut new_ground = FlightComputer::new(stream, &shared);
 
             if let Err(error) = new_ground.update().await {
               warn!("Failed to send update to new flight: {error}");
               continue;
             }
 
+            let standby = *shared.standby.lock().await;
+            let telemetry_port = shared.ports.telemetry;
+
+            if let Err(error) =
+              new_ground.send_telemetry_targets(telemetry_port, standby).await
+            {
+              warn!("Failed to send telemetry targets to new ground: {error}");
+            }
+
             *ground = Some(new_ground);
           }
         }
@@ -252,6 +502,301 @@ pub fn auto_connect(server: &Shared) -> impl Future<Output = io::Result<()>> {
   }
 }
 
+/// Sends a heartbeat to the flight and ground computers every
+/// `SERVER_HEARTBEAT_PERIOD` once this servo is primary, so that they notice if
+/// it stops without closing the connection.
+pub fn send_heartbeats(server: &Shared) -> impl Future<Output = ()> {
+  let shared = server.clone();
+
+  async move {
+    standby::wait_for_primary(&shared).await;
+
+    let heartbeat = postcard::to_allocvec(&FlightControlMessage::Heartbeat)
+      .expect("failed to serialize heartbeat");
+
+    let mut interval = tokio::time::interval(SERVER_HEARTBEAT_PERIOD);
+
+    loop {
+      interval.tick().await;
+
+      for computer in [&shared.flight, &shared.ground] {
+        let mut computer = computer.0.lock().await;
+
+        // one which has closed is left alone until it reconnects and replaces
+        // itself, rather than failing to send every period
+        let Some(computer) = computer.as_mut().filter(|c| !c.check_closed())
+        else {
+          continue;
+        };
+
+        if let Err(error) = computer.send_bytes(&heartbeat).await {
+          warn!("Failed to send heartbeat: {error}");
+        }
+      }
+    }
+  }
+}
+
+/// Receives status messages sent back over the flight TCP stream until the
+/// stream is closed.
+async fn receive_status(
+  mut reader: OwnedReadHalf,
+  shared: Shared,
+  configuration: Arc<Mutex<FlightConfiguration>>,
+) {
+  let mut pending = Vec::new();
+  let mut buffer = vec![0; 65_536];
+
+  loop {
+    match reader.read(&mut buffer).await {
+      // a read of zero bytes means the flight computer closed the stream
+      Ok(0) => break,
+      Ok(size) => pending.extend_from_slice(&buffer[..size]),
+      Err(error) => {
+        warn!("Failed to read from flight socket: {error}");
+        break;
+      }
+    }
+
+    // messages are not framed, so several may have arrived at once or one may
+    // be split across reads
+    loop {
+      match postcard::take_from_bytes::<FlightStatusMessage>(&pending) {
+        Ok((message, remaining)) => {
+          let consumed = pending.len() - remaining.len();
+          pending.drain(..consumed);
+
+          let result = process_status(&shared, &configuration, message).await;
+
+          if let Err(error) = result {
+            warn!("Failed to process status message from flight: {error}");
+          }
+        }
+        Err(postcard::Error::DeserializeUnexpectedEnd) => break,
+        Err(error) => {
+          warn!("Failed to deserialize status message from flight: {error}");
+          pending.clear();
+          break;
+        }
+      }
+    }
+  }
+}
+
+/// Records a single status message from the flight computer.
+async fn process_status(
+  shared: &Shared,
+  configuration: &Mutex<FlightConfiguration>,
+  message: FlightStatusMessage,
+) -> anyhow::Result<()> {
+  let database = &shared.database;
+
+  match message {
+    FlightStatusMessage::Diagnostics(board_id, report) => {
+      database.connection.lock().await.execute(
+        "INSERT INTO BoardDiagnostics (board_id, report, passed)
+          VALUES (?1, ?2, ?3)",
+        rusqlite::params![
+          board_id,
+          serde_json::to_string(&report)?,
+          report.passed()
+        ],
+      )?;
+    }
+    FlightStatusMessage::EStop(board_id, event) => {
+      warn!(
+        "Board {board_id} reported e-stop {:?} by {:?}.",
+        event.kind, event.path
+      );
+
+      database.connection.lock().await.execute(
+        "INSERT INTO EStopEvents
+          (board_id, kind, path, e_stop_voltage, rbf_tag_voltage, timestamp)
+          VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        rusqlite::params![
+          board_id,
+          format!("{:?}", event.kind),
+          format!("{:?}", event.path),
+          event.e_stop,
+          event.rbf_tag,
+          event.timestamp
+        ],
+      )?;
+    }
+    FlightStatusMessage::ModeChange(change) => {
+      match &change.rejection {
+        Some(rejection) => {
+          warn!(
+            "Flight refused to change from {} to {} mode: {rejection}.",
+            change.from, change.to
+          );
+        }
+        None => {
+          pass!(
+            "Flight changed from {} to {} mode ({:?}).",
+            change.from,
+            change.to,
+            change.cause
+          );
+        }
+      }
+
+      database.connection.lock().await.execute(
+        "INSERT INTO ModeChanges
+          (from_mode, to_mode, cause, requested_by, rejection, timestamp)
+          VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        rusqlite::params![
+          change.from.to_string(),
+          change.to.to_string(),
+          format!("{:?}", change.cause),
+          change.requested_by,
+          change.rejection,
+          change.timestamp
+        ],
+      )?;
+    }
+    FlightStatusMessage::Sequence(event) => {
+      let (kind, detail, traceback) = match &event.kind {
+        SequenceEventKind::Started => ("started", None, None),
+        SequenceEventKind::Output(line) => ("output", Some(line), None),
+        SequenceEventKind::Ended(SequenceOutcome::Finished) => {
+          pass!("Sequence '{}' finished on flight.", event.name);
+          ("finished", None, None)
+        }
+        SequenceEventKind::Ended(SequenceOutcome::Raised {
+          error,
+          traceback,
+        }) => {
+          warn!("Sequence '{}' raised on flight: {error}", event.name);
+          ("raised", Some(error), traceback.as_ref())
+        }
+        SequenceEventKind::Ended(SequenceOutcome::Cancelled) => {
+          warn!("Sequence '{}' was cancelled on flight.", event.name);
+          ("cancelled", None, None)
+        }
+      };
+
+      database.connection.lock().await.execute(
+        "INSERT INTO SequenceEvents
+          (name, kind, detail, traceback, timestamp)
+          VALUES (?1, ?2, ?3, ?4, ?5)",
+        rusqlite::params![event.name, kind, detail, traceback, event.timestamp],
+      )?;
+
+      // only fails if nobody is listening, in which case the event is dropped
+      _ = shared.sequence_events.send(event);
+    }
+    FlightStatusMessage::Trigger(event) => {
+      let (kind, detail) = match &event.kind {
+        TriggerEventKind::Fired => {
+          pass!("Trigger '{}' fired on flight.", event.name);
+          ("fired", None)
+        }
+        TriggerEventKind::Failed(error) => {
+          warn!("Trigger '{}' failed on flight: {error}", event.name);
+          ("failed", Some(error))
+        }
+        TriggerEventKind::Recovered => ("recovered", None),
+      };
+
+      database.connection.lock().await.execute(
+        "INSERT INTO TriggerEvents (name, kind, detail, timestamp)
+          VALUES (?1, ?2, ?3, ?4)",
+        rusqlite::params![event.name, kind, detail, event.timestamp],
+      )?;
+    }
+    FlightStatusMessage::BoardComms(event) => {
+      let board_id = &event.board_id;
+
+      let (kind, criticality) = match &event.kind {
+        BoardCommsEventKind::Lost(criticality) => {
+          warn!("Flight lost comms with {board_id} ({criticality:?}).");
+          ("lost", Some(criticality))
+        }
+        BoardCommsEventKind::Recovered => {
+          pass!("Flight regained comms with {board_id}.");
+          ("recovered", None)
+        }
+      };
+
+      database.connection.lock().await.execute(
+        "INSERT INTO BoardCommsEvents (board_id, kind, criticality, timestamp)
+          VALUES (?1, ?2, ?3, ?4)",
+        rusqlite::params![board_id, kind, criticality, event.timestamp],
+      )?;
+    }
+    FlightStatusMessage::ServerLoss(event) => {
+      let duration = event.regained_at - event.lost_at;
+
+      if event.acted {
+        warn!(
+          "Flight was without servo for {duration:.1} s and took action {:?}.",
+          event.action
+        );
+      } else {
+        warn!("Flight was without servo for {duration:.1} s.");
+      }
+
+      database.connection.lock().await.execute(
+        "INSERT INTO ServerLosses (action, acted, lost_at, regained_at)
+          VALUES (?1, ?2, ?3, ?4)",
+        rusqlite::params![
+          event.action,
+          event.acted,
+          event.lost_at,
+          event.regained_at
+        ],
+      )?;
+    }
+    FlightStatusMessage::Configuration(status) => {
+      match status.source {
+        ConfigurationSource::Empty => {
+          warn!("Flight is running without a configuration.");
+        }
+        ConfigurationSource::Restored { saved_at } => {
+          warn!("Flight restored its configuration, saved at {saved_at}.");
+        }
+        ConfigurationSource::Fresh => {
+          pass!("Flight is running with a fresh configuration.");
+        }
+      }
+
+      if !status.persisted {
+        fail!("Flight failed to save its configuration to disk.");
+      }
+
+      let mut configuration = configuration.lock().await;
+      configuration.status = Some(status);
+
+      match configuration.pushed {
+        // flight reports what it holds as soon as it connects, which is sent
+        // before the configuration pushed on connection is received, so it
+        // isn't compared until that is acknowledged
+        Some(pushed) if status.digest != pushed => {
+          task!("Waiting for flight to acknowledge its configuration.");
+          return Ok(());
+        }
+        Some(_) => configuration.pushed = None,
+        None => {}
+      }
+
+      drop(configuration);
+      let expected = load_configuration(database).await?.digest();
+
+      if status.digest == expected {
+        pass!("Flight holds the configuration in the database.");
+      } else {
+        warn!(
+          "Flight configuration differs from database ({:016x} vs {:016x}).",
+          status.digest, expected
+        );
+      }
+    }
+  }
+
+  Ok(())
+}
+
 /// Repeatedly receives vehicle state information from the flight computer.
 pub fn receive_vehicle_state(
   shared: &Shared,
@@ -261,9 +806,10 @@ pub fn receive_vehicle_state(
   let last_state = shared.last_vehicle_state.clone();
 
   let last_vehicle_state = shared.last_vehicle_state.clone();
+  let port = shared.ports.telemetry;
 
   async move {
-    let socket = UdpSocket::bind("0.0.0.0:7201").await.unwrap();
+    let socket = UdpSocket::bind(("0.0.0.0", port)).await.unwrap();
     let mut frame_buffer = vec![0; 20_000];
 
     loop {
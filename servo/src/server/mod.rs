@@ -10,21 +10,50 @@ pub mod flight;
 /// All server API route functions.
 pub mod routes;
 
+/// Hot-standby components, by which a second servo mirrors the primary and
+/// may be promoted to replace it.
+pub mod standby;
+
 use axum::Router;
-use common::comm::VehicleState;
+use common::comm::{SequenceEvent, VehicleState};
 pub use database::Database;
 pub use error::{ServerError as Error, ServerResult as Result};
 pub use flight::FlightComputer;
+pub use standby::Role;
 use tokio::time::Instant;
 use tower_http::cors::{self, CorsLayer};
 
 use std::{io, net::SocketAddr, path::Path, sync::Arc};
 use tokio::{
   net::TcpListener,
-  sync::{Mutex, Notify},
+  sync::{broadcast, Mutex, Notify},
   task::JoinHandle,
 };
 
+/// How many sequence events are kept for a subscriber which has fallen behind
+/// before the oldest are dropped.
+const SEQUENCE_EVENT_CAPACITY: usize = 1024;
+
+/// The ports servo listens on, which must differ between two instances on the
+/// same machine. The flight computer always connects on port 5025.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ports {
+  /// The port of the HTTP server.
+  pub http: u16,
+
+  /// The port on which the vehicle state is received.
+  pub telemetry: u16,
+}
+
+impl Default for Ports {
+  fn default() -> Self {
+    Ports {
+      http: 7200,
+      telemetry: 7201,
+    }
+  }
+}
+
 /// Contains all of Servo's shared server state.
 #[derive(Clone, Debug)]
 pub struct Shared {
@@ -46,6 +75,20 @@ pub struct Shared {
 
   // keep track of the update rate / rolling duration of the vehicle state
   pub rolling_duration: Arc<(Mutex<Option<f64>>, Notify)>,
+
+  /// Sequence events as they are reported by the flight computer, so that
+  /// they may be streamed to operators live.
+  pub sequence_events: broadcast::Sender<SequenceEvent>,
+
+  /// The ports this servo listens on.
+  pub ports: Ports,
+
+  /// Whether this servo is the primary or a standby, notified on promotion.
+  pub role: Arc<(Mutex<Role>, Notify)>,
+
+  /// Where the standby mirroring this servo receives the vehicle state, once
+  /// one has mirrored it.
+  pub standby: Arc<Mutex<Option<SocketAddr>>>,
 }
 
 /// The server, constructed with all route functions ready.
@@ -61,7 +104,11 @@ async fn wait_for_display_end(shutdown_future: JoinHandle<io::Result<()>>) {
 
 impl Server {
   /// Constructs a new `Server` and opens a `Database` based on the path given.
-  pub fn new(database_path: Option<&Path>) -> anyhow::Result<Self> {
+  pub fn new(
+    database_path: Option<&Path>,
+    ports: Ports,
+    role: Role,
+  ) -> anyhow::Result<Self> {
     let database;
 
     if let Some(path) = database_path {
@@ -77,6 +124,10 @@ impl Server {
       vehicle: Arc::new((Mutex::new(VehicleState::new()), Notify::new())),
       last_vehicle_state: Arc::new((Mutex::new(None), Notify::new())),
       rolling_duration: Arc::new((Mutex::new(None), Notify::new())),
+      sequence_events: broadcast::channel(SEQUENCE_EVENT_CAPACITY).0,
+      ports,
+      role: Arc::new((Mutex::new(role), Notify::new())),
+      standby: Arc::new(Mutex::new(None)),
     };
 
     Ok(Server { shared })
@@ -92,6 +143,19 @@ impl Server {
   pub async fn serve(
     &self,
     shutdown_future: JoinHandle<io::Result<()>>,
+  ) -> io::Result<()> {
+    let address = ("0.0.0.0", self.shared.ports.http);
+    let listener = TcpListener::bind(address).await?;
+
+    self.serve_on(listener, shutdown_future).await
+  }
+
+  /// Serves the route functions as `serve` does, on a listener which is
+  /// already bound, such as one bound to whichever port was free.
+  pub async fn serve_on(
+    &self,
+    listener: TcpListener,
+    shutdown_future: JoinHandle<io::Result<()>>,
   ) -> io::Result<()> {
     use axum::routing::{delete, get, post, put};
 
@@ -102,9 +166,20 @@ impl Server {
 
     let router = Router::new()
       .route("/data/forward", get(routes::forward_data))
+      .route(
+        "/data/sequence-events",
+        get(routes::forward_sequence_events),
+      )
       .route("/data/export", post(routes::export))
       .route("/admin/sql", post(routes::execute_sql))
+      .route("/admin/role", get(routes::get_role))
+      .route("/admin/promote", post(routes::promote))
+      .route("/standby/mirror", get(routes::mirror))
       .route("/operator/command", post(routes::dispatch_operator_command))
+      .route(
+        "/operator/bms/protection",
+        post(routes::set_bms_protection_limit),
+      )
       .route("/operator/mappings", get(routes::get_mappings))
       .route("/operator/mappings", post(routes::post_mappings))
       .route("/operator/mappings", put(routes::put_mappings))
@@ -117,21 +192,50 @@ impl Server {
         "/operator/active-configuration",
         post(routes::activate_configuration),
       )
+      .route(
+        "/operator/flight-configuration",
+        get(routes::get_flight_configuration),
+      )
       .route("/operator/calibrate", post(routes::calibrate))
       .route("/operator/sequence", get(routes::retrieve_sequences))
       .route("/operator/sequence", put(routes::save_sequence))
       .route("/operator/sequence", delete(routes::delete_sequence))
       .route("/operator/run-sequence", post(routes::run_sequence))
       .route("/operator/stop-sequence", post(routes::stop_sequence))
+      .route(
+        "/operator/sequence-events",
+        get(routes::get_sequence_events),
+      )
       .route("/operator/abort", post(routes::abort))
       .route("/operator/trigger", get(routes::get_triggers))
       .route("/operator/trigger", put(routes::set_trigger))
       .route("/operator/trigger", delete(routes::delete_trigger))
+      .route("/operator/trigger-events", get(routes::get_trigger_events))
+      .route("/operator/comms-policy", get(routes::get_comms_policies))
+      .route("/operator/comms-policy", put(routes::set_comms_policy))
+      .route("/operator/comms-policy", delete(routes::delete_comms_policy))
+      .route(
+        "/operator/board-comms-events",
+        get(routes::get_board_comms_events),
+      )
+      .route(
+        "/operator/server-loss-policy",
+        get(routes::get_server_loss_policy),
+      )
+      .route(
+        "/operator/server-loss-policy",
+        put(routes::set_server_loss_policy),
+      )
+      .route("/operator/server-losses", get(routes::get_server_losses))
+      .route("/operator/self-test", post(routes::request_self_test))
+      .route("/operator/diagnostics", get(routes::get_diagnostics))
+      .route("/operator/estop-events", get(routes::get_estop_events))
+      .route("/operator/mode", post(routes::set_mode))
+      .route("/operator/mode-changes", get(routes::get_mode_changes))
       .layer(cors)
       .with_state(self.shared.clone())
       .into_make_service_with_connect_info::<SocketAddr>();
 
-    let listener = TcpListener::bind("0.0.0.0:7200").await?;
     axum::serve(listener, router)
       .with_graceful_shutdown(wait_for_display_end(shutdown_future))
       .await?;
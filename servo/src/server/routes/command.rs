@@ -45,13 +45,15 @@ pub async fn dispatch_operator_command(
         // Inefficient code but this doesnt need to be any better
         if request.target.as_deref().unwrap_or_default() == "estop" {
           FlightControlMessage::BmsCommand(bms::Command::ResetEstop)
+        } else if request.target.as_deref().unwrap_or_default() == "faults" {
+          FlightControlMessage::BmsCommand(bms::Command::ClearFaults)
         } else {
           let state = match request.state.as_deref() {
             Some("enabled") => true,
             Some("disabled") => false,
-            None => {
-              Err(bad_request("state is a required field for all but estop"))?
-            }
+            None => Err(bad_request(
+              "state is a required field for all but estop and faults",
+            ))?,
             _ => Err(bad_request("unrecognized state identifier"))?,
           };
 
@@ -65,18 +67,26 @@ pub async fn dispatch_operator_command(
         }
       }
       "ahrs" => {
-        let state = match request.state.as_deref() {
-          Some("enabled") => true,
-          Some("disabled") => false,
-          None => Err(bad_request("state is a required field"))?,
-          _ => Err(bad_request("unrecognized state identifier"))?,
-        };
+        if request.target.as_deref().unwrap_or_default() == "gyro_bias" {
+          FlightControlMessage::AhrsCommand(ahrs::Command::PersistGyroBias)
+        } else {
+          let state = match request.state.as_deref() {
+            Some("enabled") => true,
+            Some("disabled") => false,
+            None => Err(bad_request(
+              "state is a required field for all but gyro_bias",
+            ))?,
+            _ => Err(bad_request("unrecognized state identifier"))?,
+          };
 
-        FlightControlMessage::AhrsCommand(match request.target.as_deref() {
-          Some("camera") => ahrs::Command::CameraEnable(state),
-          None => Err(bad_request("must supply target name"))?,
-          _ => Err(bad_request("unrecognized ahrs target"))?,
-        })
+          FlightControlMessage::AhrsCommand(match request.target.as_deref() {
+            Some("camera") => ahrs::Command::CameraEnable(state),
+            Some("altimeter") => ahrs::Command::ArmAltimeter(state),
+            Some("magnetometer") => ahrs::Command::CalibrateMagnetometer(state),
+            None => Err(bad_request("must supply target name"))?,
+            _ => Err(bad_request("unrecognized ahrs target"))?,
+          })
+        }
       }
       _ => return Err(bad_request("unrecognized command identifier")),
     };
@@ -90,3 +100,23 @@ pub async fn dispatch_operator_command(
 
   Ok(())
 }
+
+/// Route handler to replace one of the limits the BMS protects itself with.
+pub async fn set_bms_protection_limit(
+  State(shared): State<Shared>,
+  Json(limit): Json<bms::ProtectionLimit>,
+) -> server::Result<()> {
+  let mut flight = shared.flight.0.lock().await;
+
+  let Some(flight) = flight.as_mut() else {
+    return Err(internal("flight computer not connected"));
+  };
+
+  let command =
+    FlightControlMessage::BmsCommand(bms::Command::SetProtectionLimit(limit));
+  let serialized = postcard::to_allocvec(&command).map_err(internal)?;
+
+  flight.send_bytes(&serialized).await.map_err(internal)?;
+
+  Ok(())
+}
@@ -0,0 +1,174 @@
+use axum::{extract::State, Json};
+use common::comm::{flight::BoardId, CommsPolicy};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+use crate::server::{
+  self,
+  error::{bad_request, internal},
+  Shared,
+};
+
+/// Route function which returns the comms policy of every board which has
+/// one. Boards without one use the default of the flight computer.
+pub async fn get_comms_policies(
+  State(shared): State<Shared>,
+) -> server::Result<Json<Vec<CommsPolicy>>> {
+  let database = shared.database.connection.lock().await;
+
+  let policies = database
+    .prepare(
+      "
+			SELECT
+				board_id,
+				timeout_ms,
+				grace_count,
+				criticality,
+				reconnect
+			FROM CommsPolicies
+		",
+    )
+    .map_err(internal)?
+    .query_and_then([], |row| {
+      Ok(CommsPolicy {
+        board_id: row.get(0)?,
+        timeout_ms: row.get(1)?,
+        grace_count: row.get(2)?,
+        criticality: row.get(3)?,
+        reconnect: row.get(4)?,
+      })
+    })
+    .map_err(internal)?
+    .collect::<rusqlite::Result<Vec<CommsPolicy>>>()
+    .map_err(internal)?;
+
+  Ok(Json(policies))
+}
+
+/// Route function which creates or updates the comms policy of a board in the
+/// database and on the flight computer.
+pub async fn set_comms_policy(
+  State(shared): State<Shared>,
+  Json(request): Json<CommsPolicy>,
+) -> server::Result<()> {
+  if request.timeout_ms == 0 {
+    return Err(bad_request("timeout_ms must be greater than zero"));
+  }
+
+  let database = shared.database.connection.lock().await;
+
+  database
+    .execute(
+      "
+			INSERT INTO CommsPolicies (
+				board_id,
+				timeout_ms,
+				grace_count,
+				criticality,
+				reconnect
+			)
+			VALUES (?1, ?2, ?3, ?4, ?5)
+			ON CONFLICT (board_id) DO UPDATE SET
+				timeout_ms = excluded.timeout_ms,
+				grace_count = excluded.grace_count,
+				criticality = excluded.criticality,
+				reconnect = excluded.reconnect
+		",
+      params![
+        request.board_id,
+        request.timeout_ms,
+        request.grace_count,
+        request.criticality,
+        request.reconnect
+      ],
+    )
+    .map_err(internal)?;
+
+  drop(database);
+
+  if let Some(flight) = shared.flight.0.lock().await.as_mut() {
+    flight.send_comms_policy(request).await.map_err(internal)?;
+  }
+
+  Ok(())
+}
+
+/// Request struct used to delete the comms policy of a board, returning it to
+/// the default.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DeleteCommsPolicyRequest {
+  /// The ID of the board whose policy is deleted.
+  pub board_id: BoardId,
+}
+
+/// Route function which deletes the comms policy of a board from the database,
+/// resending the whole configuration so that the flight computer drops it too.
+pub async fn delete_comms_policy(
+  State(shared): State<Shared>,
+  Json(request): Json<DeleteCommsPolicyRequest>,
+) -> server::Result<()> {
+  let database = shared.database.connection.lock().await;
+
+  database
+    .execute(
+      "DELETE FROM CommsPolicies WHERE board_id = ?1",
+      params![request.board_id],
+    )
+    .map_err(internal)?;
+
+  drop(database);
+
+  if let Some(flight) = shared.flight.0.lock().await.as_mut() {
+    flight.update().await.map_err(internal)?;
+  }
+
+  Ok(())
+}
+
+/// A single loss or recovery of comms with a board, as recorded from the
+/// flight computer.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BoardCommsEventRecord {
+  /// The ID of the board.
+  pub board_id: BoardId,
+
+  /// What happened, either "lost" or "recovered".
+  pub kind: String,
+
+  /// How the loss was handled, one of "abort", "safe" or "warn", for a loss.
+  pub criticality: Option<String>,
+
+  /// The UNIX timestamp of the event on the flight computer.
+  pub timestamp: f64,
+}
+
+/// Route function which returns every recorded loss and recovery of comms
+/// with a board, oldest first.
+pub async fn get_board_comms_events(
+  State(shared): State<Shared>,
+) -> server::Result<Json<Vec<BoardCommsEventRecord>>> {
+  let database = shared.database.connection.lock().await;
+
+  let events = database
+    .prepare(
+      "
+			SELECT board_id, kind, criticality, timestamp
+			FROM BoardCommsEvents
+			ORDER BY timestamp
+		",
+    )
+    .map_err(internal)?
+    .query_map([], |row| {
+      Ok(BoardCommsEventRecord {
+        board_id: row.get(0)?,
+        kind: row.get(1)?,
+        criticality: row.get(2)?,
+        timestamp: row.get(3)?,
+      })
+    })
+    .map_err(internal)?
+    .collect::<Result<Vec<BoardCommsEventRecord>, rusqlite::Error>>()
+    .map_err(internal)?;
+
+  Ok(Json(events))
+}
@@ -0,0 +1,63 @@
+use axum::{extract::State, Json};
+use common::comm::ConfigurationSource;
+use serde::{Deserialize, Serialize};
+
+use crate::server::{
+  self,
+  error::internal,
+  flight,
+  Shared,
+};
+
+/// Response struct describing the configuration of the flight computer.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct FlightConfigurationResponse {
+  /// Where the configuration came from, or `None` if the flight computer has
+  /// not yet said.
+  pub source: Option<ConfigurationSource>,
+
+  /// The digest of the configuration held by the flight computer, in
+  /// hexadecimal, or `None` if it has not yet reported one.
+  pub digest: Option<String>,
+
+  /// Whether the flight computer saved its configuration to disk, so that it
+  /// restarts with it, or `None` if it has not yet said.
+  pub persisted: Option<bool>,
+
+  /// The digest of the configuration in the database, in hexadecimal.
+  pub expected_digest: String,
+
+  /// Whether the flight computer holds exactly the configuration in the
+  /// database.
+  pub synchronized: bool,
+}
+
+/// Route function which returns whether the flight computer is running with a
+/// configuration received since it started or one restored from disk, and
+/// whether that configuration matches the database.
+pub async fn get_flight_configuration(
+  State(shared): State<Shared>,
+) -> server::Result<Json<FlightConfigurationResponse>> {
+  let status = shared
+    .flight
+    .0
+    .lock()
+    .await
+    .as_ref()
+    .ok_or(internal("flight computer not connected"))?
+    .configuration()
+    .await;
+
+  let expected = flight::load_configuration(&shared.database)
+    .await
+    .map_err(internal)?
+    .digest();
+
+  Ok(Json(FlightConfigurationResponse {
+    source: status.map(|status| status.source),
+    digest: status.map(|status| format!("{:016x}", status.digest)),
+    persisted: status.map(|status| status.persisted),
+    expected_digest: format!("{expected:016x}"),
+    synchronized: status.is_some_and(|status| status.digest == expected),
+  }))
+}
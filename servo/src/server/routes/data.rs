@@ -424,6 +424,7 @@ mod tests {
   use common::comm::{
     ahrs::Ahrs,
     bms::Bms,
+    mode::VehicleMode,
     sam::Unit,
     CompositeValveState,
     Measurement,
@@ -489,6 +490,8 @@ mod tests {
           bms: Bms::default(),
           ahrs: Ahrs::default(),
           sensor_readings: HashMap::new(),
+          mode: VehicleMode::Safe,
+          server_loss: None,
         };
 
         for i in 0..4 {
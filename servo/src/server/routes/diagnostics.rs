@@ -0,0 +1,139 @@
+use axum::{extract::State, Json};
+use common::comm::{diagnostics::SelfTestReport, flight::BoardId};
+use serde::{Deserialize, Serialize};
+
+use crate::server::{
+  self,
+  error::internal,
+  Shared,
+};
+
+/// Request struct used to request a self-test of a single board.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SelfTestRequest {
+  /// The ID of the board which should run the self-test.
+  pub board_id: BoardId,
+}
+
+/// The most recent diagnostics reported by a single board.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BoardDiagnostics {
+  /// The ID of the board which produced the report.
+  pub board_id: BoardId,
+
+  /// The UNIX timestamp of when the report was received by the server.
+  pub recorded_at: f64,
+
+  /// Whether or not every check in the report passed.
+  pub passed: bool,
+
+  /// The full report sent back by the board.
+  pub report: SelfTestReport,
+}
+
+/// Route function which instructs the flight computer to run a self-test on
+/// the given board. The report is stored once it is relayed back.
+pub async fn request_self_test(
+  State(shared): State<Shared>,
+  Json(request): Json<SelfTestRequest>,
+) -> server::Result<()> {
+  let mut flight = shared.flight.0.lock().await;
+
+  let flight = flight
+    .as_mut()
+    .ok_or(internal("flight computer not connected"))?;
+
+  flight.self_test(request.board_id).await.map_err(internal)
+}
+
+/// Route function which returns the latest self-test report of every board.
+pub async fn get_diagnostics(
+  State(shared): State<Shared>,
+) -> server::Result<Json<Vec<BoardDiagnostics>>> {
+  let database = shared.database.connection.lock().await;
+
+  let diagnostics = database
+    .prepare(
+      "
+			SELECT board_id, MAX(recorded_at), passed, report
+			FROM BoardDiagnostics
+			GROUP BY board_id
+			ORDER BY board_id
+		",
+    )
+    .map_err(internal)?
+    .query_and_then([], |row| {
+      let report: String = row.get(3)?;
+
+      Ok(BoardDiagnostics {
+        board_id: row.get(0)?,
+        recorded_at: row.get(1)?,
+        passed: row.get(2)?,
+        report: serde_json::from_str(&report).map_err(internal)?,
+      })
+    })
+    .map_err(internal)?
+    .collect::<server::Result<Vec<BoardDiagnostics>>>()?;
+
+  Ok(Json(diagnostics))
+}
+
+/// A single e-stop trip or reset reported by a BMS.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct EStopRecord {
+  /// The ID of the BMS which saw the event.
+  pub board_id: BoardId,
+
+  /// Whether the e-stop tripped or reset.
+  pub kind: String,
+
+  /// Which path caused the event.
+  pub path: String,
+
+  /// The raw e-stop sense voltage when the event was seen.
+  pub e_stop_voltage: f64,
+
+  /// The raw remove-before-flight sense voltage when the event was seen.
+  pub rbf_tag_voltage: f64,
+
+  /// The UNIX timestamp of when the BMS saw the event.
+  pub timestamp: f64,
+}
+
+/// Route function which returns every recorded e-stop event, oldest first.
+pub async fn get_estop_events(
+  State(shared): State<Shared>,
+) -> server::Result<Json<Vec<EStopRecord>>> {
+  let database = shared.database.connection.lock().await;
+
+  let events = database
+    .prepare(
+      "
+			SELECT
+				board_id,
+				kind,
+				path,
+				e_stop_voltage,
+				rbf_tag_voltage,
+				timestamp
+			FROM EStopEvents
+			ORDER BY timestamp
+		",
+    )
+    .map_err(internal)?
+    .query_map([], |row| {
+      Ok(EStopRecord {
+        board_id: row.get(0)?,
+        kind: row.get(1)?,
+        path: row.get(2)?,
+        e_stop_voltage: row.get(3)?,
+        rbf_tag_voltage: row.get(4)?,
+        timestamp: row.get(5)?,
+      })
+    })
+    .map_err(internal)?
+    .collect::<Result<Vec<EStopRecord>, rusqlite::Error>>()
+    .map_err(internal)?;
+
+  Ok(Json(events))
+}
@@ -4,22 +4,52 @@ pub mod admin;
 /// Route functions related to operator commands.
 pub mod command;
 
+/// Route functions for setting the loss-of-comms policies of boards and
+/// auditing losses.
+pub mod comms;
+
+/// Route functions for checking the configuration held by the flight computer.
+pub mod configuration;
+
 /// Route functions for fetching and manipulating data about the flight
 /// computer.
 pub mod data;
 
+/// Route functions for requesting and viewing board self-tests and e-stop
+/// events.
+pub mod diagnostics;
+
 /// Route functions for getting and setting node mappings.
 pub mod mappings;
 
-/// Route functions for setting and sending sequences.
+/// Route functions for changing the mode of the vehicle and auditing changes.
+pub mod mode;
+
+/// Route functions for setting and sending sequences, and following them as
+/// they run.
 pub mod sequence;
 
-/// Route functions for setting and deleting triggers.
+/// Route functions for setting what the flight computer does without servo and
+/// auditing the times it was without it.
+pub mod server_loss;
+
+/// Route functions for mirroring this servo to a standby, and for promoting a
+/// standby to the primary.
+pub mod standby;
+
+/// Route functions for setting and deleting triggers, and auditing when they
+/// fire.
 pub mod trigger;
 
 pub use admin::*;
 pub use command::*;
+pub use comms::*;
+pub use configuration::*;
 pub use data::*;
+pub use diagnostics::*;
 pub use mappings::*;
+pub use mode::*;
 pub use sequence::*;
+pub use server_loss::*;
+pub use standby::*;
 pub use trigger::*;
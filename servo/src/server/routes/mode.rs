@@ -0,0 +1,106 @@
+use axum::{
+  extract::{ConnectInfo, State},
+  Json,
+};
+use common::comm::mode::VehicleMode;
+use jeflog::{pass, warn};
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+
+use crate::server::{
+  self,
+  error::{bad_request, internal},
+  Shared,
+};
+
+/// Request struct used to request a change of vehicle mode.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SetModeRequest {
+  /// The mode which the vehicle should change to.
+  pub mode: VehicleMode,
+}
+
+/// A single change of mode made or refused by the flight computer.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ModeChangeRecord {
+  /// The mode before the change.
+  pub from: String,
+
+  /// The mode changed to, or requested.
+  pub to: String,
+
+  /// What caused the change.
+  pub cause: String,
+
+  /// The address of whoever requested the change, or `None` if it was forced
+  /// by an abort.
+  pub requested_by: Option<String>,
+
+  /// Why the change was refused, or `None` if it was made.
+  pub rejection: Option<String>,
+
+  /// The UNIX timestamp of the change on the flight computer.
+  pub timestamp: f64,
+}
+
+/// Route function which requests that the flight computer change mode. The
+/// flight computer checks the transition itself, and its decision is recorded
+/// once it is reported back, along with the address of whoever requested it.
+///
+/// The abort mode can't be requested here, as entering it must come with the
+/// abort sequence; `/operator/abort` is used instead.
+pub async fn set_mode(
+  State(shared): State<Shared>,
+  ConnectInfo(address): ConnectInfo<SocketAddr>,
+  Json(request): Json<SetModeRequest>,
+) -> server::Result<()> {
+  if request.mode == VehicleMode::Abort {
+    warn!("{address} requested abort mode, which must go through an abort.");
+    return Err(bad_request("use /operator/abort to enter abort mode"));
+  }
+
+  pass!("{address} requested a change to {} mode.", request.mode);
+
+  let mut flight = shared.flight.0.lock().await;
+
+  let flight = flight
+    .as_mut()
+    .ok_or(internal("flight computer not connected"))?;
+
+  flight
+    .set_mode(request.mode, address.to_string())
+    .await
+    .map_err(internal)
+}
+
+/// Route function which returns every recorded change of mode, oldest first.
+pub async fn get_mode_changes(
+  State(shared): State<Shared>,
+) -> server::Result<Json<Vec<ModeChangeRecord>>> {
+  let database = shared.database.connection.lock().await;
+
+  let changes = database
+    .prepare(
+      "
+			SELECT from_mode, to_mode, cause, requested_by, rejection, timestamp
+			FROM ModeChanges
+			ORDER BY timestamp
+		",
+    )
+    .map_err(internal)?
+    .query_map([], |row| {
+      Ok(ModeChangeRecord {
+        from: row.get(0)?,
+        to: row.get(1)?,
+        cause: row.get(2)?,
+        requested_by: row.get(3)?,
+        rejection: row.get(4)?,
+        timestamp: row.get(5)?,
+      })
+    })
+    .map_err(internal)?
+    .collect::<Result<Vec<ModeChangeRecord>, rusqlite::Error>>()
+    .map_err(internal)?;
+
+  Ok(Json(changes))
+}
@@ -1,7 +1,15 @@
-use axum::{extract::State, Json};
-use common::comm::Sequence;
+use axum::{
+  extract::{ws, Query, State, WebSocketUpgrade},
+  response::Response,
+  Json,
+};
+use common::comm::{Sequence, SequenceEventKind, SequenceOutcome};
+use futures_util::{SinkExt, StreamExt};
+use jeflog::warn;
 use rusqlite::params;
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::{sync::broadcast::error::RecvError, time};
 
 use crate::server::{
   self,
@@ -99,6 +107,29 @@ pub async fn save_sequence(
 
       flight.send_sequence(sequence).await.map_err(internal)?;
     }
+
+    return Ok(());
+  }
+
+  // the safing sequence is held by flight, so it must be resent when changed
+  let is_safing_sequence = shared
+    .database
+    .connection
+    .lock()
+    .await
+    .query_row(
+      "SELECT EXISTS (
+        SELECT 1 FROM ServerLossPolicy WHERE safing_sequence = ?1
+      )",
+      [&request.name],
+      |row| row.get::<_, bool>(0),
+    )
+    .map_err(internal)?;
+
+  if is_safing_sequence {
+    if let Some(flight) = shared.flight.0.lock().await.as_mut() {
+      flight.update().await.map_err(internal)?;
+    }
   }
 
   Ok(())
@@ -189,11 +220,28 @@ pub struct StopSequenceRequest {
   pub name: String,
 }
 
-/// Route function which instructs the flight computer to stop a sequence.
+/// How long to wait for a stopped sequence to be reported as ended, which is
+/// longer than the flight computer gives it to exit.
+const STOP_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Response struct for stopping a sequence.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct StopSequenceResponse {
+  /// How the sequence ended, which is usually cancelled but may be another
+  /// outcome if it ended on its own first. `None` if it wasn't running, or
+  /// wasn't reported as ended within `STOP_TIMEOUT`.
+  pub outcome: Option<SequenceOutcome>,
+}
+
+/// Route function which instructs the flight computer to stop a sequence and
+/// waits for it to be reported as ended.
 pub async fn stop_sequence(
   State(shared): State<Shared>,
   Json(request): Json<StopSequenceRequest>,
-) -> server::Result<()> {
+) -> server::Result<Json<StopSequenceResponse>> {
+  // subscribed before sending so that the end can't be missed
+  let mut events = shared.sequence_events.subscribe();
+
   shared
     .flight
     .0
@@ -201,11 +249,35 @@ pub async fn stop_sequence(
     .await
     .as_mut()
     .ok_or(internal("flight computer not connected"))?
-    .stop_sequence(request.name)
+    .stop_sequence(request.name.clone())
     .await
     .map_err(internal)?;
 
-  Ok(())
+  let ended = async {
+    loop {
+      match events.recv().await {
+        Ok(event) if event.name == request.name => {
+          if let SequenceEventKind::Ended(outcome) = event.kind {
+            return Some(outcome);
+          }
+        }
+        Ok(_) | Err(RecvError::Lagged(_)) => continue,
+        Err(RecvError::Closed) => return None,
+      }
+    }
+  };
+
+  let outcome = time::timeout(STOP_TIMEOUT, ended).await.ok().flatten();
+
+  if outcome.is_none() {
+    warn!(
+      "Sequence '{}' was not reported as ended within {STOP_TIMEOUT:?} of \
+       being stopped.",
+      request.name
+    );
+  }
+
+  Ok(Json(StopSequenceResponse { outcome }))
 }
 
 /// Route function which instructs the flight computer to abort.
@@ -223,3 +295,129 @@ pub async fn abort(State(shared): State<Shared>) -> server::Result<()> {
 
   Ok(())
 }
+
+/// A single event in a run of a sequence, as recorded from the flight
+/// computer.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SequenceEventRecord {
+  /// The name of the sequence.
+  pub name: String,
+
+  /// What happened, one of "started", "output", "finished", "raised" or
+  /// "cancelled".
+  pub kind: String,
+
+  /// The line printed for output, or the exception for a sequence which
+  /// raised one.
+  pub detail: Option<String>,
+
+  /// The traceback of the exception raised, if it has one.
+  pub traceback: Option<String>,
+
+  /// The UNIX timestamp of the event on the flight computer.
+  pub timestamp: f64,
+}
+
+/// The most sequence events returned at once, and the number returned when no
+/// limit is given.
+const MAX_SEQUENCE_EVENTS: u32 = 1000;
+
+/// Query parameters for narrowing down the recorded sequence events.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct SequenceEventQuery {
+  /// Only events of the sequence with this name.
+  pub name: Option<String>,
+
+  /// Only events after this UNIX timestamp, as of the flight computer.
+  pub since: Option<f64>,
+
+  /// The most events to return, at most `MAX_SEQUENCE_EVENTS`.
+  pub limit: Option<u32>,
+}
+
+/// Route function which returns the recorded sequence events matching the
+/// query, oldest first. Only the oldest `limit` are returned, so a client
+/// catches up on more by passing the timestamp of the last as `since`.
+pub async fn get_sequence_events(
+  State(shared): State<Shared>,
+  Query(query): Query<SequenceEventQuery>,
+) -> server::Result<Json<Vec<SequenceEventRecord>>> {
+  let limit = query
+    .limit
+    .unwrap_or(MAX_SEQUENCE_EVENTS)
+    .min(MAX_SEQUENCE_EVENTS);
+
+  let database = shared.database.connection.lock().await;
+
+  let events = database
+    .prepare(
+      "
+			SELECT name, kind, detail, traceback, timestamp
+			FROM SequenceEvents
+			WHERE (?1 IS NULL OR name = ?1) AND (?2 IS NULL OR timestamp > ?2)
+			ORDER BY timestamp
+			LIMIT ?3
+		",
+    )
+    .map_err(internal)?
+    .query_map(params![query.name, query.since, limit], |row| {
+      Ok(SequenceEventRecord {
+        name: row.get(0)?,
+        kind: row.get(1)?,
+        detail: row.get(2)?,
+        traceback: row.get(3)?,
+        timestamp: row.get(4)?,
+      })
+    })
+    .map_err(internal)?
+    .collect::<Result<Vec<SequenceEventRecord>, rusqlite::Error>>()
+    .map_err(internal)?;
+
+  Ok(Json(events))
+}
+
+/// Route function which streams sequence events over a WebSocket as JSON as
+/// they are reported by the flight computer, so that operators can follow the
+/// output of a sequence while it runs.
+pub async fn forward_sequence_events(
+  ws: WebSocketUpgrade,
+  State(shared): State<Shared>,
+) -> Response {
+  ws.on_upgrade(move |socket| async move {
+    let mut events = shared.sequence_events.subscribe();
+    let (mut writer, mut reader) = socket.split();
+
+    let forwarding_handle = tokio::spawn(async move {
+      loop {
+        let event = match events.recv().await {
+          Ok(event) => event,
+          Err(RecvError::Lagged(skipped)) => {
+            warn!("Sequence event stream fell behind, skipping {skipped}.");
+            continue;
+          }
+          Err(RecvError::Closed) => break,
+        };
+
+        let json = match serde_json::to_string(&event) {
+          Ok(json) => json,
+          Err(error) => {
+            warn!("Failed to serialize sequence event into JSON: {error}");
+            continue;
+          }
+        };
+
+        if writer.send(ws::Message::Text(json)).await.is_err() {
+          _ = writer.close().await;
+          break;
+        }
+      }
+    });
+
+    // the stream is only written to, so wait until the peer closes it
+    while !matches!(reader.next().await, Some(Ok(ws::Message::Close(_))) | None)
+    {
+    }
+
+    forwarding_handle.abort();
+  })
+}
@@ -0,0 +1,156 @@
+use axum::{extract::State, Json};
+use common::comm::ServerLossAction;
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::server::{
+  self,
+  error::{bad_request, internal},
+  Shared,
+};
+
+/// The server loss policy as stored in the database, naming its safing
+/// sequence rather than holding its script.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ServerLossPolicyRecord {
+  /// What the flight computer does once servo has been gone for `timeout_ms`.
+  pub action: ServerLossAction,
+
+  /// How long, in milliseconds, servo may be gone before the action is taken.
+  pub timeout_ms: u32,
+
+  /// The name of the saved sequence run by the `safe` action.
+  pub safing_sequence: Option<String>,
+}
+
+/// Route function which returns the server loss policy, or the default of
+/// continuing if none has been set.
+pub async fn get_server_loss_policy(
+  State(shared): State<Shared>,
+) -> server::Result<Json<ServerLossPolicyRecord>> {
+  let database = shared.database.connection.lock().await;
+
+  let policy = database
+    .query_row(
+      "SELECT action, timeout_ms, safing_sequence FROM ServerLossPolicy",
+      [],
+      |row| {
+        Ok(ServerLossPolicyRecord {
+          action: row.get(0)?,
+          timeout_ms: row.get(1)?,
+          safing_sequence: row.get(2)?,
+        })
+      },
+    )
+    .optional()
+    .map_err(internal)?
+    .unwrap_or_default();
+
+  Ok(Json(policy))
+}
+
+/// Route function which sets the server loss policy in the database and on
+/// the flight computer.
+///
+/// The safing sequence must already be saved, and is sent to the flight
+/// computer with the policy so that it can be run without servo.
+pub async fn set_server_loss_policy(
+  State(shared): State<Shared>,
+  Json(request): Json<ServerLossPolicyRecord>,
+) -> server::Result<()> {
+  if request.action == ServerLossAction::Safe
+    && request.safing_sequence.is_none()
+  {
+    return Err(bad_request("the safe action requires a safing_sequence"));
+  }
+
+  if request.safing_sequence.as_deref() == Some("abort") {
+    return Err(bad_request("the abort sequence is run by the abort action"));
+  }
+
+  let database = shared.database.connection.lock().await;
+
+  if let Some(name) = &request.safing_sequence {
+    let saved = database
+      .query_row(
+        "SELECT EXISTS (SELECT 1 FROM Sequences WHERE name = ?1)",
+        [name],
+        |row| row.get::<_, bool>(0),
+      )
+      .map_err(internal)?;
+
+    if !saved {
+      return Err(bad_request(format!("sequence '{name}' is not saved")));
+    }
+  }
+
+  database
+    .execute(
+      "
+			INSERT INTO ServerLossPolicy (id, action, timeout_ms, safing_sequence)
+			VALUES (0, ?1, ?2, ?3)
+			ON CONFLICT (id) DO UPDATE SET
+				action = excluded.action,
+				timeout_ms = excluded.timeout_ms,
+				safing_sequence = excluded.safing_sequence
+		",
+      params![request.action, request.timeout_ms, request.safing_sequence],
+    )
+    .map_err(internal)?;
+
+  drop(database);
+
+  if let Some(flight) = shared.flight.0.lock().await.as_mut() {
+    flight.update().await.map_err(internal)?;
+  }
+
+  Ok(())
+}
+
+/// A single loss of servo by the flight computer, as recorded once it
+/// reconnected.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ServerLossRecord {
+  /// What the policy was to do once the timer ran out.
+  pub action: ServerLossAction,
+
+  /// Whether the action was taken before servo reconnected.
+  pub acted: bool,
+
+  /// The UNIX timestamp at which the flight computer lost servo.
+  pub lost_at: f64,
+
+  /// The UNIX timestamp at which servo reconnected.
+  pub regained_at: f64,
+}
+
+/// Route function which returns every recorded loss of servo by the flight
+/// computer, oldest first.
+pub async fn get_server_losses(
+  State(shared): State<Shared>,
+) -> server::Result<Json<Vec<ServerLossRecord>>> {
+  let database = shared.database.connection.lock().await;
+
+  let losses = database
+    .prepare(
+      "
+			SELECT action, acted, lost_at, regained_at
+			FROM ServerLosses
+			ORDER BY lost_at
+		",
+    )
+    .map_err(internal)?
+    .query_map([], |row| {
+      Ok(ServerLossRecord {
+        action: row.get(0)?,
+        acted: row.get(1)?,
+        lost_at: row.get(2)?,
+        regained_at: row.get(3)?,
+      })
+    })
+    .map_err(internal)?
+    .collect::<Result<Vec<ServerLossRecord>, rusqlite::Error>>()
+    .map_err(internal)?;
+
+  Ok(Json(losses))
+}
@@ -0,0 +1,96 @@
+use axum::{
+  extract::{ConnectInfo, Query, State},
+  Json,
+};
+use jeflog::{pass, warn};
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+
+use crate::server::{
+  self,
+  error::{bad_request, internal},
+  standby::{self, Mirror, Role},
+  Shared,
+};
+
+/// Query of a standby requesting a mirror of the primary.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct MirrorRequest {
+  /// The ID of the latest vehicle snapshot the standby already holds.
+  pub after: i64,
+
+  /// The port on which the standby receives the vehicle state.
+  pub telemetry_port: u16,
+}
+
+/// Route function which gives a standby everything it needs to catch up with
+/// this servo, registering it so that the flight computer streams the vehicle
+/// state to it too.
+pub async fn mirror(
+  State(shared): State<Shared>,
+  ConnectInfo(address): ConnectInfo<SocketAddr>,
+  Query(request): Query<MirrorRequest>,
+) -> server::Result<Json<Mirror>> {
+  if *shared.role.0.lock().await != Role::Primary {
+    return Err(bad_request("only the primary may be mirrored"));
+  }
+
+  let telemetry = SocketAddr::new(address.ip(), request.telemetry_port);
+  let previous = shared.standby.lock().await.replace(telemetry);
+
+  if previous != Some(telemetry) {
+    pass!("Standby at {telemetry} is mirroring this servo.");
+    standby::send_telemetry_targets(&shared).await;
+  }
+
+  let database = shared.database.connection.lock().await;
+  let mirror = standby::collect(&database, request.after).map_err(internal)?;
+
+  Ok(Json(mirror))
+}
+
+/// Response struct giving the role of this servo.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RoleResponse {
+  /// Whether this servo is the primary or a standby, and which primary it
+  /// mirrors if a standby.
+  #[serde(flatten)]
+  pub role: Role,
+
+  /// Where the standby mirroring this servo receives the vehicle state, if
+  /// one has.
+  pub standby: Option<SocketAddr>,
+}
+
+/// Route function which returns the role of this servo, by which operators
+/// can check that a standby is keeping up before they need it.
+pub async fn get_role(
+  State(shared): State<Shared>,
+) -> server::Result<Json<RoleResponse>> {
+  let role = shared.role.0.lock().await.clone();
+  let standby = *shared.standby.lock().await;
+
+  Ok(Json(RoleResponse { role, standby }))
+}
+
+/// Route function which promotes a standby to the primary, after which it
+/// stops mirroring and accepts the flight computer when it next looks for a
+/// server.
+///
+/// This should only be done once the primary is gone, as the flight computer
+/// port can't be taken while the primary holds it.
+pub async fn promote(State(shared): State<Shared>) -> server::Result<()> {
+  let mut role = shared.role.0.lock().await;
+
+  if *role == Role::Primary {
+    return Err(bad_request("this servo is already the primary"));
+  }
+
+  *role = Role::Primary;
+  drop(role);
+
+  shared.role.1.notify_waiters();
+  warn!("Promoted to primary. Accepting the flight computer.");
+
+  Ok(())
+}
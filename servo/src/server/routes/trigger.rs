@@ -12,7 +12,21 @@ pub async fn get_triggers(
   let database = shared.database.connection.lock().await;
 
   let triggers = database
-    .prepare("SELECT name, condition, script, active FROM Triggers")
+    .prepare(
+      "
+			SELECT
+				name,
+				condition,
+				script,
+				active,
+				reset,
+				hold_ms,
+				cooldown_ms,
+				fire_if_holding,
+				poll_ms
+			FROM Triggers
+		",
+    )
     .map_err(internal)?
     .query_and_then([], |row| {
       Ok(Trigger {
@@ -20,6 +34,11 @@ pub async fn get_triggers(
         condition: row.get(1)?,
         script: row.get(2)?,
         active: row.get(3)?,
+        reset: row.get(4)?,
+        hold_ms: row.get(5)?,
+        cooldown_ms: row.get(6)?,
+        fire_if_holding: row.get(7)?,
+        poll_ms: row.get(8)?,
       })
     })
     .map_err(internal)?
@@ -40,18 +59,38 @@ pub async fn set_trigger(
   database
     .execute(
       "
-			INSERT INTO Triggers (name, condition, script, active)
-			VALUES (?1, ?2, ?3, ?4)
+			INSERT INTO Triggers (
+				name,
+				condition,
+				script,
+				active,
+				reset,
+				hold_ms,
+				cooldown_ms,
+				fire_if_holding,
+				poll_ms
+			)
+			VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
 			ON CONFLICT (name) DO UPDATE SET
 				condition = excluded.condition,
 				script = excluded.script,
-				active = excluded.active
+				active = excluded.active,
+				reset = excluded.reset,
+				hold_ms = excluded.hold_ms,
+				cooldown_ms = excluded.cooldown_ms,
+				fire_if_holding = excluded.fire_if_holding,
+				poll_ms = excluded.poll_ms
 		",
       params![
         request.name,
         request.condition,
         request.script,
-        request.active
+        request.active,
+        request.reset,
+        request.hold_ms,
+        request.cooldown_ms,
+        request.fire_if_holding,
+        request.poll_ms
       ],
     )
     .map_err(internal)?;
@@ -97,6 +136,11 @@ pub async fn delete_trigger(
         condition: "False".to_owned(),
         script: "".to_owned(),
         active: false,
+        reset: None,
+        hold_ms: 0,
+        cooldown_ms: 0,
+        fire_if_holding: false,
+        poll_ms: None,
       })
       .await
       .map_err(internal)?;
@@ -104,3 +148,49 @@ pub async fn delete_trigger(
 
   Ok(())
 }
+
+/// A single event of a trigger, as recorded from the flight computer.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TriggerEventRecord {
+  /// The name of the trigger.
+  pub name: String,
+
+  /// What happened, one of "fired", "failed" or "recovered".
+  pub kind: String,
+
+  /// Why the trigger failed, for a failure.
+  pub detail: Option<String>,
+
+  /// The UNIX timestamp of the event on the flight computer.
+  pub timestamp: f64,
+}
+
+/// Route function which returns every recorded trigger event, oldest first.
+pub async fn get_trigger_events(
+  State(shared): State<Shared>,
+) -> server::Result<Json<Vec<TriggerEventRecord>>> {
+  let database = shared.database.connection.lock().await;
+
+  let events = database
+    .prepare(
+      "
+			SELECT name, kind, detail, timestamp
+			FROM TriggerEvents
+			ORDER BY timestamp
+		",
+    )
+    .map_err(internal)?
+    .query_map([], |row| {
+      Ok(TriggerEventRecord {
+        name: row.get(0)?,
+        kind: row.get(1)?,
+        detail: row.get(2)?,
+        timestamp: row.get(3)?,
+      })
+    })
+    .map_err(internal)?
+    .collect::<Result<Vec<TriggerEventRecord>, rusqlite::Error>>()
+    .map_err(internal)?;
+
+  Ok(Json(events))
+}
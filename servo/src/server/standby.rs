@@ -0,0 +1,706 @@
+use super::{Database, Shared};
+
+use jeflog::{pass, warn};
+use rusqlite::{
+  types::{Value, ValueRef},
+  Connection as SqlConnection,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+  future::Future,
+  time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Every table mirrored whole to a standby, being everything the flight
+/// computer is configured from and the sequences operators may run.
+pub const MIRRORED_TABLES: [&str; 5] = [
+  "NodeMappings",
+  "Sequences",
+  "Triggers",
+  "CommsPolicies",
+  "ServerLossPolicy",
+];
+
+/// The most vehicle snapshots sent to a standby at once, so that a standby
+/// which has fallen far behind catches up over several requests.
+pub const SNAPSHOT_BATCH_SIZE: usize = 2_000;
+
+/// How long a standby which has caught up waits before mirroring again.
+const MIRROR_PERIOD: Duration = Duration::from_secs(1);
+
+/// How long a servo starting up waits for its peer to give its role.
+const PEER_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Whether this servo is the one the flight computer connects to, or a
+/// standby mirroring it.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "role")]
+pub enum Role {
+  /// Accepts the flight computer and records the vehicle state itself.
+  Primary,
+
+  /// Mirrors the database of the primary and receives the vehicle state
+  /// alongside it, without accepting the flight computer until promoted.
+  Standby {
+    /// The address of the HTTP server of the primary, such as
+    /// `server-01.local:7200`.
+    primary: String,
+
+    /// The UNIX timestamp at which the primary was last mirrored.
+    mirrored_at: Option<f64>,
+  },
+}
+
+/// A whole table as mirrored to a standby.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct MirroredTable {
+  /// The name of the table, which must be one of `MIRRORED_TABLES`.
+  pub name: String,
+
+  /// The names of the columns, in the order of the values of each row.
+  pub columns: Vec<String>,
+
+  /// Every row of the table. Blobs are arrays of bytes, as from the SQL route.
+  pub rows: Vec<Vec<serde_json::Value>>,
+}
+
+/// A single vehicle snapshot as mirrored to a standby.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct MirroredSnapshot {
+  /// The ID of the snapshot on the primary, which it keeps on the standby.
+  pub snapshot_id: i64,
+
+  /// The Postcard-serialized vehicle state, encoded with Base64.
+  pub vehicle_state: String,
+
+  /// The UNIX timestamp at which the primary recorded the snapshot.
+  pub recorded_at: f64,
+}
+
+/// Everything a standby needs from the primary to catch up with it.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Mirror {
+  /// Every table in `MIRRORED_TABLES`.
+  pub tables: Vec<MirroredTable>,
+
+  /// The vehicle snapshots after those the standby already has, oldest first
+  /// and at most `SNAPSHOT_BATCH_SIZE` of them.
+  pub snapshots: Vec<MirroredSnapshot>,
+}
+
+/// Collects the mirrored tables and the vehicle snapshots after `after`.
+pub fn collect(
+  connection: &SqlConnection,
+  after: i64,
+) -> rusqlite::Result<Mirror> {
+  let mut tables = Vec::new();
+
+  for name in MIRRORED_TABLES {
+    let mut statement = connection.prepare(&format!("SELECT * FROM {name}"))?;
+
+    let columns = statement
+      .column_names()
+      .iter()
+      .map(|column| column.to_string())
+      .collect::<Vec<_>>();
+
+    let rows = statement
+      .query_map([], |row| {
+        (0..columns.len())
+          .map(|column| row.get_ref(column).map(to_json))
+          .collect()
+      })?
+      .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    tables.push(MirroredTable {
+      name: name.to_owned(),
+      columns,
+      rows,
+    });
+  }
+
+  let snapshots = connection
+    .prepare(
+      "
+			SELECT snapshot_id, vehicle_state, recorded_at
+			FROM VehicleSnapshots
+			WHERE snapshot_id > ?1
+			ORDER BY snapshot_id
+			LIMIT ?2
+		",
+    )?
+    .query_map(rusqlite::params![after, SNAPSHOT_BATCH_SIZE], |row| {
+      Ok(MirroredSnapshot {
+        snapshot_id: row.get(0)?,
+        vehicle_state: base64::encode(row.get::<_, Vec<u8>>(1)?),
+        recorded_at: row.get(2)?,
+      })
+    })?
+    .collect::<rusqlite::Result<Vec<_>>>()?;
+
+  Ok(Mirror { tables, snapshots })
+}
+
+/// Applies a mirror of the primary, replacing the mirrored tables whole and
+/// adding the new snapshots, all in one transaction so that the standby never
+/// holds half of a mirror.
+pub fn apply(
+  connection: &mut SqlConnection,
+  mirror: &Mirror,
+) -> anyhow::Result<()> {
+  let transaction = connection.transaction()?;
+
+  for table in &mirror.tables {
+    let Some(name) = MIRRORED_TABLES.iter().find(|name| **name == table.name)
+    else {
+      anyhow::bail!("table {} is not mirrored", table.name);
+    };
+
+    // the names are checked against the schema of the standby before being
+    // put in a query, as they come from over the network
+    let known = transaction
+      .prepare(&format!("SELECT * FROM {name} LIMIT 0"))?
+      .column_names()
+      .iter()
+      .map(|column| column.to_string())
+      .collect::<Vec<_>>();
+
+    if let Some(column) = table.columns.iter().find(|c| !known.contains(c)) {
+      anyhow::bail!("{name} on the primary has unknown column {column}");
+    }
+
+    let columns = table.columns.join(", ");
+    let placeholders = vec!["?"; table.columns.len()].join(", ");
+
+    transaction.execute(&format!("DELETE FROM {name}"), [])?;
+
+    let mut insert = transaction.prepare(&format!(
+      "INSERT INTO {name} ({columns}) VALUES ({placeholders})"
+    ))?;
+
+    for row in &table.rows {
+      let values = row.iter().map(from_json).collect::<Vec<_>>();
+      insert.execute(rusqlite::params_from_iter(values))?;
+    }
+  }
+
+  for snapshot in &mirror.snapshots {
+    transaction.execute(
+      "INSERT OR IGNORE INTO VehicleSnapshots
+        (snapshot_id, vehicle_state, recorded_at)
+        VALUES (?1, ?2, ?3)",
+      rusqlite::params![
+        snapshot.snapshot_id,
+        base64::decode(&snapshot.vehicle_state)?,
+        snapshot.recorded_at
+      ],
+    )?;
+  }
+
+  transaction.commit()?;
+  Ok(())
+}
+
+/// The ID of the latest vehicle snapshot held, or zero if there are none.
+pub fn latest_snapshot(connection: &SqlConnection) -> rusqlite::Result<i64> {
+  connection.query_row(
+    "SELECT COALESCE(MAX(snapshot_id), 0) FROM VehicleSnapshots",
+    [],
+    |row| row.get(0),
+  )
+}
+
+/// Requests a mirror from the primary and applies it, returning how many
+/// snapshots were added.
+pub async fn mirror_once(
+  client: &reqwest::Client,
+  primary: &str,
+  database: &Database,
+  telemetry_port: u16,
+) -> anyhow::Result<usize> {
+  let after = latest_snapshot(&*database.connection.lock().await)?;
+
+  let mirror = client
+    .get(format!("http://{primary}/standby/mirror"))
+    .query(&[("after", after), ("telemetry_port", telemetry_port.into())])
+    .send()
+    .await?
+    .error_for_status()?
+    .json::<Mirror>()
+    .await?;
+
+  apply(&mut *database.connection.lock().await, &mirror)?;
+  Ok(mirror.snapshots.len())
+}
+
+/// Mirrors the primary for as long as this servo is a standby, as fast as the
+/// primary gives snapshots while behind and every `MIRROR_PERIOD` once caught
+/// up.
+pub fn mirror(shared: &Shared) -> impl Future<Output = ()> {
+  let shared = shared.clone();
+
+  async move {
+    let client = reqwest::Client::new();
+
+    // only the first of consecutive failures is logged
+    let mut failing = false;
+
+    loop {
+      let Role::Standby { primary, .. } = shared.role.0.lock().await.clone()
+      else {
+        break;
+      };
+
+      let result = mirror_once(
+        &client,
+        &primary,
+        &shared.database,
+        shared.ports.telemetry,
+      )
+      .await;
+
+      let caught_up = match result {
+        Ok(added) => {
+          if failing {
+            pass!("Resumed mirroring the primary at {primary}.");
+            failing = false;
+          }
+
+          if let Role::Standby { mirrored_at, .. } =
+            &mut *shared.role.0.lock().await
+          {
+            *mirrored_at = Some(timestamp());
+          }
+
+          added < SNAPSHOT_BATCH_SIZE
+        }
+        Err(error) => {
+          if !failing {
+            warn!("Failed to mirror the primary at {primary}: {error}");
+            failing = true;
+          }
+
+          true
+        }
+      };
+
+      if caught_up {
+        tokio::time::sleep(MIRROR_PERIOD).await;
+      }
+    }
+
+    pass!("Stopped mirroring, as this servo was promoted.");
+  }
+}
+
+/// Decides the role of a servo which would start as the primary, given the
+/// HTTP address of its peer, which may have been promoted in its place while
+/// it was down.
+///
+/// The flight computer tries `server-01` first when looking for a server, so
+/// a primary which restarted after its standby was promoted would otherwise
+/// take the flight computer back with whatever it held before. If the peer is
+/// the primary, this servo becomes its standby instead. A peer which can't be
+/// reached is taken to be gone.
+pub async fn startup_role(client: &reqwest::Client, peer: &str) -> Role {
+  let response = client
+    .get(format!("http://{peer}/admin/role"))
+    .timeout(PEER_TIMEOUT)
+    .send()
+    .await
+    .and_then(|response| response.error_for_status());
+
+  let peer_role = match response {
+    Ok(response) => response.json::<Role>().await,
+    Err(error) => Err(error),
+  };
+
+  match peer_role {
+    Ok(Role::Primary) => {
+      warn!("The peer at {peer} is the primary. Starting as its standby.");
+
+      Role::Standby {
+        primary: peer.to_owned(),
+        mirrored_at: None,
+      }
+    }
+    Ok(Role::Standby { .. }) => Role::Primary,
+    Err(error) => {
+      warn!("Failed to get the role of the peer at {peer}: {error}");
+      Role::Primary
+    }
+  }
+}
+
+/// Waits until this servo is the primary, returning immediately if it already
+/// is.
+pub async fn wait_for_primary(shared: &Shared) {
+  loop {
+    // created before checking so that a promotion in between isn't missed
+    let promoted = shared.role.1.notified();
+
+    if *shared.role.0.lock().await == Role::Primary {
+      return;
+    }
+
+    promoted.await;
+  }
+}
+
+/// Tells the flight and ground computers, whichever are connected, to stream
+/// the vehicle state to this servo and its standby.
+pub async fn send_telemetry_targets(shared: &Shared) {
+  let standby = *shared.standby.lock().await;
+
+  for computer in [&shared.flight, &shared.ground] {
+    if let Some(computer) = computer.0.lock().await.as_mut() {
+      let result = computer
+        .send_telemetry_targets(shared.ports.telemetry, standby)
+        .await;
+
+      if let Err(error) = result {
+        warn!("Failed to send telemetry targets: {error}");
+      }
+    }
+  }
+}
+
+fn to_json(value: ValueRef<'_>) -> serde_json::Value {
+  match value {
+    ValueRef::Null => serde_json::Value::Null,
+    ValueRef::Integer(value) => value.into(),
+    ValueRef::Real(value) => serde_json::Number::from_f64(value)
+      .map_or(serde_json::Value::Null, serde_json::Value::Number),
+    ValueRef::Text(value) => String::from_utf8_lossy(value).into(),
+    ValueRef::Blob(value) => value.to_vec().into(),
+  }
+}
+
+fn from_json(value: &serde_json::Value) -> Value {
+  match value {
+    serde_json::Value::Null => Value::Null,
+    serde_json::Value::Bool(value) => Value::Integer((*value).into()),
+    serde_json::Value::Number(number) => match number.as_i64() {
+      Some(integer) => Value::Integer(integer),
+      None => Value::Real(number.as_f64().unwrap_or_default()),
+    },
+    serde_json::Value::String(text) => Value::Text(text.clone()),
+    serde_json::Value::Array(bytes) => Value::Blob(
+      bytes
+        .iter()
+        .filter_map(|byte| byte.as_u64().and_then(|b| u8::try_from(b).ok()))
+        .collect(),
+    ),
+    serde_json::Value::Object(_) => Value::Text(value.to_string()),
+  }
+}
+
+fn timestamp() -> f64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|duration| duration.as_secs_f64())
+    .unwrap_or(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::server::{flight::load_configuration, Ports, Server};
+  use std::{
+    io,
+    net::{SocketAddr, TcpListener, UdpSocket},
+    time::Instant,
+  };
+
+  /// How long the standby is given to catch up with the primary.
+  const CATCH_UP_TIMEOUT: Duration = Duration::from_secs(5);
+
+  /// The sockets holding the ports of a servo started in this process until
+  /// it is served.
+  struct Sockets {
+    http: TcpListener,
+
+    // never read, but held so that nothing else takes the port meanwhile
+    _telemetry: UdpSocket,
+  }
+
+  /// Starts a servo on ports which are free on localhost, as two can't share
+  /// them. The ports stay bound from here on, so nothing else can take them
+  /// before the servo is served.
+  ///
+  /// Two servos in one process are as good as two processes here, as they
+  /// share nothing but the runtime: each has its own `Shared`, database and
+  /// ports, and they only reach each other over HTTP as separate processes
+  /// would. The tasks spawned are those `servo serve` spawns, less the ones
+  /// which need the flight computer.
+  fn start(role: Role) -> (Server, Sockets) {
+    let http = TcpListener::bind("127.0.0.1:0").unwrap();
+    let telemetry = UdpSocket::bind("127.0.0.1:0").unwrap();
+    http.set_nonblocking(true).unwrap();
+
+    let ports = Ports {
+      http: http.local_addr().unwrap().port(),
+      telemetry: telemetry.local_addr().unwrap().port(),
+    };
+
+    let server = Server::new(None, ports, role).unwrap();
+    server.shared.database.migrate().unwrap();
+
+    let sockets = Sockets {
+      http,
+      _telemetry: telemetry,
+    };
+
+    (server, sockets)
+  }
+
+  /// Serves a started servo on the runtime of the caller.
+  fn serve(server: &Server, sockets: Sockets) {
+    let listener = tokio::net::TcpListener::from_std(sockets.http).unwrap();
+    let server = server.clone();
+
+    tokio::spawn(async move {
+      let hang = tokio::spawn(std::future::pending::<io::Result<()>>());
+      server.serve_on(listener, hang).await
+    });
+  }
+
+  fn sequences(database: &Database) -> Vec<(String, String)> {
+    database
+      .connection
+      .blocking_lock()
+      .prepare("SELECT name, script FROM Sequences ORDER BY name")
+      .unwrap()
+      .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+      .unwrap()
+      .collect::<rusqlite::Result<_>>()
+      .unwrap()
+  }
+
+  fn snapshots(database: &Database) -> Vec<(i64, Vec<u8>, f64)> {
+    database
+      .connection
+      .blocking_lock()
+      .prepare(
+        "SELECT snapshot_id, vehicle_state, recorded_at FROM VehicleSnapshots",
+      )
+      .unwrap()
+      .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+      .unwrap()
+      .collect::<rusqlite::Result<_>>()
+      .unwrap()
+  }
+
+  /// Waits for the standby to hold every snapshot the primary does.
+  async fn wait_for_catch_up(primary: &Database, standby: &Database) -> bool {
+    let deadline = Instant::now() + CATCH_UP_TIMEOUT;
+
+    while Instant::now() < deadline {
+      let target = latest_snapshot(&*primary.connection.lock().await).unwrap();
+      let held = latest_snapshot(&*standby.connection.lock().await).unwrap();
+
+      if held == target {
+        return true;
+      }
+
+      tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    false
+  }
+
+  #[test]
+  fn standby_mirrors_primary_until_promoted() {
+    let (primary, primary_sockets) = start(Role::Primary);
+    let primary_address = format!("127.0.0.1:{}", primary.shared.ports.http);
+
+    let (standby, standby_sockets) = start(Role::Standby {
+      primary: primary_address.clone(),
+      mirrored_at: None,
+    });
+
+    primary
+      .shared
+      .database
+      .connection
+      .blocking_lock()
+      .execute_batch(
+        "
+        INSERT INTO NodeMappings (
+          text_id, configuration_id, channel, board_id, sensor_type,
+          computer, active, max, min, calibrated_offset, powered_threshold,
+          normally_closed
+        )
+        VALUES
+          ('fuel_pt', 'hotfire', 1, 'sam-01', 'pt', 'flight', TRUE,
+            1000.0, 0.0, 1.5, NULL, NULL),
+          ('fuel_valve', 'hotfire', 2, 'sam-01', 'valve', 'flight', TRUE,
+            NULL, NULL, 0.0, 0.25, TRUE);
+        INSERT INTO Sequences (name, configuration_id, script)
+        VALUES ('abort', NULL, 'fuel_valve.close()'),
+          ('vent', 'hotfire', 'fuel_valve.open()');
+        INSERT INTO Triggers (name, condition, script, active, hold_ms)
+        VALUES ('overpressure', 'fuel_pt > 900 * psi', 'abort()', TRUE, 50);
+        INSERT INTO CommsPolicies
+        VALUES ('sam-01', 250, 2, 'safe', 'resume');
+        INSERT INTO ServerLossPolicy
+        VALUES (0, 'safe', 5000, 'vent');
+        INSERT INTO VehicleSnapshots (vehicle_state, recorded_at)
+        VALUES (x'0102', 1.0), (x'0304', 2.0), (x'05', 3.0);
+        ",
+      )
+      .unwrap();
+
+    // left over from before, which the mirror must replace
+    standby
+      .shared
+      .database
+      .connection
+      .blocking_lock()
+      .execute(
+        "INSERT INTO Sequences (name, script) VALUES ('stale', 'pass')",
+        [],
+      )
+      .unwrap();
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+      .enable_all()
+      .build()
+      .unwrap();
+
+    runtime.block_on(async {
+      serve(&primary, primary_sockets);
+      serve(&standby, standby_sockets);
+
+      tokio::spawn(mirror(&standby.shared));
+
+      assert!(
+        wait_for_catch_up(&primary.shared.database, &standby.shared.database)
+          .await,
+        "standby did not catch up with the primary"
+      );
+
+      // the standby registered where it receives the vehicle state
+      let telemetry = standby.shared.ports.telemetry;
+      assert_eq!(
+        *primary.shared.standby.lock().await,
+        Some(SocketAddr::from(([127, 0, 0, 1], telemetry)))
+      );
+
+      let expected = load_configuration(&primary.shared.database).await;
+      let mirrored = load_configuration(&standby.shared.database).await;
+      assert_eq!(mirrored.unwrap(), expected.unwrap());
+
+      // new snapshots are mirrored as they are recorded
+      primary
+        .shared
+        .database
+        .connection
+        .lock()
+        .await
+        .execute(
+          "INSERT INTO VehicleSnapshots (vehicle_state) VALUES (x'06')",
+          [],
+        )
+        .unwrap();
+
+      assert!(
+        wait_for_catch_up(&primary.shared.database, &standby.shared.database)
+          .await,
+        "standby did not mirror a new snapshot"
+      );
+    });
+
+    assert_eq!(
+      sequences(&standby.shared.database),
+      sequences(&primary.shared.database)
+    );
+    assert_eq!(
+      snapshots(&standby.shared.database),
+      snapshots(&primary.shared.database)
+    );
+
+    runtime.block_on(async {
+      let client = reqwest::Client::new();
+      let standby_url =
+        format!("http://127.0.0.1:{}", standby.shared.ports.http);
+
+      let role = client
+        .get(format!("{standby_url}/admin/role"))
+        .send()
+        .await
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+
+      assert_eq!(role["role"], "standby");
+      assert_eq!(role["primary"], primary_address.as_str());
+      assert!(role["mirrored_at"].is_f64());
+
+      // a standby can't itself be mirrored
+      let status = client
+        .get(format!("{standby_url}/standby/mirror"))
+        .query(&[("after", 0), ("telemetry_port", 1)])
+        .send()
+        .await
+        .unwrap()
+        .status();
+
+      assert_eq!(status, reqwest::StatusCode::BAD_REQUEST);
+
+      let promoted = client
+        .post(format!("{standby_url}/admin/promote"))
+        .send()
+        .await
+        .unwrap();
+
+      assert!(promoted.status().is_success());
+
+      tokio::time::timeout(CATCH_UP_TIMEOUT, wait_for_primary(&standby.shared))
+        .await
+        .expect("promotion did not wake anything waiting for it");
+
+      let again = client
+        .post(format!("{standby_url}/admin/promote"))
+        .send()
+        .await
+        .unwrap();
+
+      assert_eq!(again.status(), reqwest::StatusCode::BAD_REQUEST);
+    });
+  }
+
+  #[test]
+  fn restarted_primary_defers_to_promoted_peer() {
+    let (peer, peer_sockets) = start(Role::Primary);
+    let peer_address = format!("127.0.0.1:{}", peer.shared.ports.http);
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+      .enable_all()
+      .build()
+      .unwrap();
+
+    runtime.block_on(async {
+      serve(&peer, peer_sockets);
+      let client = reqwest::Client::new();
+
+      // the peer was promoted while this servo was down
+      assert_eq!(
+        startup_role(&client, &peer_address).await,
+        Role::Standby {
+          primary: peer_address.clone(),
+          mirrored_at: None,
+        }
+      );
+
+      // the peer is still the standby of this servo
+      *peer.shared.role.0.lock().await = Role::Standby {
+        primary: "127.0.0.1:1".to_owned(),
+        mirrored_at: None,
+      };
+
+      assert_eq!(startup_role(&client, &peer_address).await, Role::Primary);
+    });
+  }
+}
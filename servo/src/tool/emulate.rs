@@ -1,6 +1,6 @@
 use clap::ArgMatches;
 use common::comm::{
-  flight::DataMessage,
+  flight::{BoardKind, DataMessage},
   sam::{ChannelType, DataPoint, Unit},
   CompositeValveState,
   Measurement,
@@ -184,7 +184,7 @@ pub fn emulate_sam(flight: SocketAddr) -> anyhow::Result<()> {
 
   let board_id = "sam-01";
 
-  let identity = DataMessage::Identity(board_id.to_owned());
+  let identity = DataMessage::Identity(board_id.to_owned(), BoardKind::Sam);
   let handshake = postcard::to_slice(&identity, &mut buffer)?;
   socket.send(handshake)?;
 
@@ -1,6 +1,6 @@
 use crate::{
   interface,
-  server::{flight, Server},
+  server::{flight, standby, Ports, Role, Server},
 };
 use clap::ArgMatches;
 use std::io;
@@ -28,33 +28,55 @@ pub fn serve(servo_dir: &Path, args: &ArgMatches) -> anyhow::Result<()> {
 
   let quiet = args.get_one::<bool>("quiet").copied().unwrap_or(false);
 
-  let database_path = servo_dir.join("database.sqlite");
-  let server = Server::new((!volatile).then_some(&database_path))?;
-
-  server.shared.database.migrate()?;
+  let ports = Ports {
+    http: args.get_one::<u16>("port").copied().unwrap_or(7200),
+    telemetry: args.get_one::<u16>("telemetry_port").copied().unwrap_or(7201),
+  };
 
-  tokio::runtime::Builder::new_multi_thread()
+  let runtime = tokio::runtime::Builder::new_multi_thread()
     .worker_threads(10)
     .enable_all()
     .build()
-    .unwrap()
-    .block_on(async move {
-      tokio::spawn(flight::auto_connect(&server.shared));
-      tokio::spawn(flight::receive_vehicle_state(&server.shared));
-      tokio::spawn(server.shared.database.log_vehicle_state(&server.shared));
-
-      // The task that, once finished, will signal the server to terminate.
-      // Set to the TUI if it is launched, otherwise set to an infinitely
-      // hanging await that should(?) consume no resources.
-      // let shutdown_task: tokio::task::JoinHandle<io::Result<()>>;
-      let shutdown_task = if !quiet {
-        tokio::spawn(interface::display(server.shared.clone()))
-      } else {
-        tokio::spawn(infinite_hang())
-      };
-
-      server.serve(shutdown_task).await
-    })?;
+    .unwrap();
+
+  let primary = args.get_one::<String>("standby");
+  let peer = args.get_one::<String>("peer");
+
+  let role = match (primary, peer) {
+    (Some(primary), _) => Role::Standby {
+      primary: primary.clone(),
+      mirrored_at: None,
+    },
+    (None, Some(peer)) => {
+      runtime.block_on(standby::startup_role(&reqwest::Client::new(), peer))
+    }
+    (None, None) => Role::Primary,
+  };
+
+  let database_path = servo_dir.join("database.sqlite");
+  let server = Server::new((!volatile).then_some(&database_path), ports, role)?;
+
+  server.shared.database.migrate()?;
+
+  runtime.block_on(async move {
+    tokio::spawn(standby::mirror(&server.shared));
+    tokio::spawn(flight::auto_connect(&server.shared));
+    tokio::spawn(flight::send_heartbeats(&server.shared));
+    tokio::spawn(flight::receive_vehicle_state(&server.shared));
+    tokio::spawn(server.shared.database.log_vehicle_state(&server.shared));
+
+    // The task that, once finished, will signal the server to terminate.
+    // Set to the TUI if it is launched, otherwise set to an infinitely
+    // hanging await that should(?) consume no resources.
+    // let shutdown_task: tokio::task::JoinHandle<io::Result<()>>;
+    let shutdown_task = if !quiet {
+      tokio::spawn(interface::display(server.shared.clone()))
+    } else {
+      tokio::spawn(infinite_hang())
+    };
+
+    server.serve(shutdown_task).await
+  })?;
 
   Ok(())
 }